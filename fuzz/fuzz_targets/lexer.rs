@@ -0,0 +1,11 @@
+//! Lex arbitrary sources. The lexer must report the malformed code as diagnostics instead of panicking.
+
+#![no_main]
+
+use fluid_lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let _ = Lexer::new(source, "<fuzz>").tokenize();
+    let _ = Lexer::new(source, "<fuzz>").set_preserve_comments(true).tokenize();
+});
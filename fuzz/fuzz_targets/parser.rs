@@ -0,0 +1,16 @@
+//! Lex and parse arbitrary sources. The tokens are parsed even if the source has lexer errors, so that the parser
+//! also sees the unknown characters.
+
+#![no_main]
+
+use fluid_lexer::Lexer;
+use fluid_parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let mut lexer = Lexer::new(source, "<fuzz>");
+    let (tokens, _) = lexer.tokenize();
+
+    let _ = Parser::new(&tokens, lexer.file()).run();
+    let _ = Parser::new(&tokens, lexer.file()).run_expression();
+});
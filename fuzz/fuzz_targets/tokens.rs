@@ -0,0 +1,24 @@
+//! Parse arbitrary token streams, including the ones that no source can be lexed into.
+
+#![no_main]
+
+use std::sync::Arc;
+
+use fluid_error::SourceFile;
+use fluid_lexer::{Token, TokenPosition, TokenType};
+use fluid_parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|kinds: Vec<TokenType>| {
+    // The tokens of the lexer always end with `EOF`. They all point at the start of the empty file, since the
+    // diagnostics are not rendered.
+    let tokens = kinds
+        .into_iter()
+        .chain(Some(TokenType::EOF))
+        .map(|kind| Token::new(kind, TokenPosition::new(0, 0, 1)))
+        .collect::<Vec<_>>();
+    let file = Arc::new(SourceFile::new("<fuzz>", ""));
+
+    let _ = Parser::new(&tokens, file.clone()).run();
+    let _ = Parser::new(&tokens, file).run_expression();
+});
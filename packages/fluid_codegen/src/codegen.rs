@@ -1,26 +1,22 @@
 use std::{
-    ffi::CString,
-    fs,
-    mem::{self, MaybeUninit},
-    panic,
+    any::Any,
+    env, fmt, fs, mem,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
     path::Path,
-    process, ptr,
+    process,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use backtrace::Backtrace;
+use fluid_mangle::Mangling;
+use llvm::target_machine::{LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMRelocMode};
 
-use fluid_parser::{Expression, Parser, Statement};
+use fluid_error::{codes, AnnotationType, Diagnostic, DiagnosticBuilder, DiagnosticHandler, Severity, Slice, SourceAnnotation, SourceFile};
+use fluid_parser::{Ast, StatementKind, Type};
 
-use llvm::{
-    core::*,
-    execution_engine::*,
-    prelude::*,
-    target_machine::*,
-    transforms::{scalar::*, util::*},
-    *,
-};
-
-use crate::{cstring, symbol::SymbolTable};
+use crate::{crash::CrashHandler, llvm_safe::*, symbol::SymbolTable};
 
 #[cfg(debug_assertions)]
 const DEBUG: bool = true;
@@ -31,217 +27,664 @@ const DEBUG: bool = false;
 /// Type of codegen to do.
 #[derive(Debug, PartialEq)]
 pub enum CodeGenType {
-    /// Just In Compiled
+    /// Just In Compiled, the main function is run by `run_main` once the program is compiled.
     JIT {
-        /// Run the main function.
-        run_main: bool,
+        /// Compile every function the first time it is called, instead of the whole module before running it.
+        /// This starts the big programs faster, since the functions that are never called are never compiled.
+        lazy: bool,
     },
     /// Repl
     Repl,
+    /// Just in time, the test functions are run one at a time by `run_test` once the program is compiled.
+    Test,
+    /// Ahead of time, the code is only emitted to files and never run.
+    Object,
+}
+
+/// The time spent generating a function, recorded once `record_function_times` is called.
+#[derive(Debug, Clone)]
+pub struct FunctionTime {
+    /// The name of the function in the source.
+    pub name: String,
+    /// When the code generator started generating the function.
+    pub start: Instant,
+    /// The time spent generating the function and running the function passes of LLVM on it.
+    /// The functions defined in a function are part of its time.
+    pub duration: Duration,
+}
+
+/// The options of the machine to generate the code for.
+#[derive(Debug, Default)]
+pub struct TargetOptions {
+    /// The target triple, the machine the compiler runs on if it is `None`.
+    pub triple: Option<String>,
+    /// The CPU to generate the code for. It is `native` on the machine the compiler runs on and `generic`
+    /// otherwise if it is `None`.
+    pub cpu: Option<String>,
+    /// The comma separated features to enable or disable on top of the ones of the CPU, like `+avx2,-sse4.1`.
+    pub features: Option<String>,
+    /// The relocation model.
+    pub reloc_model: RelocModel,
+    /// The code model.
+    pub code_model: CodeModel,
+    /// The optimization level.
+    pub opt_level: OptLevel,
+}
+
+/// The relocation model, how the addresses of the code and of the data are computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocModel {
+    /// The default model of the target.
+    Default,
+    /// The code is position independent, which is needed by the shared libraries and the PIE executables.
+    Pic,
+    /// The code and the data are at fixed addresses.
+    Static,
+}
+
+impl Default for RelocModel {
+    fn default() -> Self {
+        RelocModel::Default
+    }
+}
+
+impl FromStr for RelocModel {
+    type Err = String;
+
+    fn from_str(model: &str) -> Result<Self, Self::Err> {
+        match model {
+            "default" => Ok(RelocModel::Default),
+            "pic" => Ok(RelocModel::Pic),
+            "static" => Ok(RelocModel::Static),
+            _ => Err(format!("invalid relocation model `{}`, expected `default`, `pic` or `static`", model)),
+        }
+    }
+}
+
+impl RelocModel {
+    /// Get the LLVM relocation model.
+    pub(crate) fn to_llvm(self) -> LLVMRelocMode {
+        match self {
+            RelocModel::Default => LLVMRelocMode::LLVMRelocDefault,
+            RelocModel::Pic => LLVMRelocMode::LLVMRelocPIC,
+            RelocModel::Static => LLVMRelocMode::LLVMRelocStatic,
+        }
+    }
+}
+
+/// The code model, how far apart the code and the data can be in memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodeModel {
+    /// The default model of the target.
+    Default,
+    /// The code and the data fit in a few kilobytes.
+    Tiny,
+    /// The code and the data fit in the lower 2 GB of the address space.
+    Small,
+    /// The code and the data fit in the higher 2 GB of the address space, like in the operating system kernels.
+    Kernel,
+    /// The code fits in the lower 2 GB of the address space, the data can be anywhere.
+    Medium,
+    /// The code and the data can be anywhere.
+    Large,
+}
+
+impl Default for CodeModel {
+    fn default() -> Self {
+        CodeModel::Default
+    }
+}
+
+impl FromStr for CodeModel {
+    type Err = String;
+
+    fn from_str(model: &str) -> Result<Self, Self::Err> {
+        match model {
+            "default" => Ok(CodeModel::Default),
+            "tiny" => Ok(CodeModel::Tiny),
+            "small" => Ok(CodeModel::Small),
+            "kernel" => Ok(CodeModel::Kernel),
+            "medium" => Ok(CodeModel::Medium),
+            "large" => Ok(CodeModel::Large),
+            _ => Err(format!("invalid code model `{}`, expected `default`, `tiny`, `small`, `kernel`, `medium` or `large`", model)),
+        }
+    }
+}
+
+impl CodeModel {
+    /// Get the LLVM code model.
+    pub(crate) fn to_llvm(self) -> LLVMCodeModel {
+        match self {
+            CodeModel::Default => LLVMCodeModel::LLVMCodeModelDefault,
+            CodeModel::Tiny => LLVMCodeModel::LLVMCodeModelTiny,
+            CodeModel::Small => LLVMCodeModel::LLVMCodeModelSmall,
+            CodeModel::Kernel => LLVMCodeModel::LLVMCodeModelKernel,
+            CodeModel::Medium => LLVMCodeModel::LLVMCodeModelMedium,
+            CodeModel::Large => LLVMCodeModel::LLVMCodeModelLarge,
+        }
+    }
+}
+
+/// The optimization level, how much time is spent on making the generated code faster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptLevel {
+    /// No optimizations, `0`.
+    None,
+    /// A few optimizations, `1`.
+    Less,
+    /// The optimizations that are worth the compile time, `2`.
+    Default,
+    /// All of the optimizations, `3`.
+    Aggressive,
+}
+
+impl Default for OptLevel {
+    fn default() -> Self {
+        OptLevel::None
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level {
+            "0" => Ok(OptLevel::None),
+            "1" => Ok(OptLevel::Less),
+            "2" => Ok(OptLevel::Default),
+            "3" => Ok(OptLevel::Aggressive),
+            _ => Err(format!("invalid optimization level `{}`, expected `0`, `1`, `2` or `3`", level)),
+        }
+    }
+}
+
+impl OptLevel {
+    /// Get the LLVM optimization level of the machine code.
+    pub(crate) fn to_llvm(self) -> LLVMCodeGenOptLevel {
+        match self {
+            OptLevel::None => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            OptLevel::Less => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            OptLevel::Default => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            OptLevel::Aggressive => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+        }
+    }
+}
+
+/// The signature of a function of the program, without the names of its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    /// The name of the function.
+    pub name: String,
+    /// The types of the arguments.
+    pub args: Vec<Type>,
+    /// The return type.
+    pub return_type: Type,
+}
+
+impl fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args = self.args.iter().map(Type::to_string).collect::<Vec<_>>();
+
+        write!(f, "{}({})", self.name, args.join(", "))?;
+
+        if self.return_type != Type::Void {
+            write!(f, " -> {}", self.return_type)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the target triple of the machine the compiler runs on.
+pub fn host_target() -> String {
+    host_triple()
+}
+
+/// Get the name of the CPU of the machine the compiler runs on, the code compiled for it is optimized for this CPU.
+pub fn host_cpu() -> String {
+    host_cpu_name()
+}
+
+/// Get the features of the CPU of the machine the compiler runs on that are enabled, like `sse2` or `avx`.
+pub fn host_features() -> Vec<String> {
+    host_cpu_features().split(',').filter_map(|feature| feature.strip_prefix('+')).map(str::to_owned).collect()
+}
+
+/// Load the shared library at the path into the compiler, so that the extern functions of the programs compiled just in
+/// time can be resolved to its functions. The library stays loaded until the compiler exits.
+pub fn load_library(path: &Path) -> Result<(), String> {
+    load_library_permanently(path)
+}
+
+/// Get the names and the descriptions of the targets that code can be generated for.
+pub fn available_targets() -> Vec<(String, String)> {
+    initialize_targets();
+
+    targets()
 }
 
+/// The error returned by `CodeGen::run` when the code could not be generated.
+/// The diagnostics explaining why were already emitted to the `DiagnosticHandler`.
+#[derive(Debug)]
+pub struct CodeGenError;
+
 /// The internal state when codegen the ast provided by the parser.
+///
+/// The LLVM objects are dropped in the order of the fields. Everything created in the context must be dropped
+/// before it, so the context is the last of them.
 pub struct CodeGen {
-    /// Reference to the LLVM context.
-    pub(crate) context: LLVMContextRef,
-    /// Reference to the current module.
-    pub(crate) module: LLVMModuleRef,
-    /// Reference to the builder.
-    pub(crate) builder: LLVMBuilderRef,
-    /// Reference to the execution engine.
-    pub(crate) execution_engine: LLVMExecutionEngineRef,
-    /// Reference to the pass manager.
-    pub(crate) pass_manager: LLVMPassManagerRef,
+    /// The builder.
+    pub(crate) builder: Builder,
+    /// The pass manager.
+    pub(crate) pass_manager: PassManager,
+    /// The execution engine, it owns the module. There is none when the code is compiled ahead of time.
+    pub(crate) execution_engine: Option<ExecutionEngine>,
+    /// The current module.
+    pub(crate) module: Module,
+    /// The target machine.
+    pub(crate) target_machine: TargetMachine,
+    /// The LLVM context.
+    pub(crate) context: Context,
     /// The symbol table.
     pub(crate) symbol_table: SymbolTable,
     /// The codegen type.
     pub(crate) codegen_type: CodeGenType,
-    /// The target machine.
-    pub(crate) target_machine: LLVMTargetMachineRef,
+    /// The mangling scheme of the function symbols.
+    pub(crate) mangling: Mangling,
+    /// The file we are generating, the spans of the errors point into it.
+    pub(crate) file: Arc<SourceFile>,
+    /// The names of the test functions along with their symbols, in the order of their definitions.
+    pub(crate) tests: Vec<(String, String)>,
+    /// The arguments given to the main function when it is run, the first one is the name of the program.
+    pub(crate) args: Vec<String>,
+    /// The return type of the main function, once it is generated.
+    pub(crate) main_return_type: Option<Type>,
+    /// The time spent in the function passes so far.
+    pub(crate) pass_time: Duration,
+    /// The time spent generating every function so far, in the order they were finished. `None` if the times are
+    /// not recorded.
+    pub(crate) function_times: Option<Vec<FunctionTime>>,
+    /// Whether code was generated in the REPL since its execution engine last compiled the module.
+    pub(crate) engine_outdated: bool,
 }
 
+// SAFETY: The LLVM objects of the codegen context are only reachable through it, since the handles never leave the
+// crate, and they all belong to its own LLVM context. LLVM allows a context to be used from any thread, as long as
+// it is used by one thread at a time, which moving the codegen context to another thread guarantees.
+unsafe impl Send for CodeGen {}
+
 impl CodeGen {
-    /// Create a new codegen context.
-    pub fn new<S: Into<String>>(module: S, codegen_type: CodeGenType) -> Self {
-        // Set the panic hook.
-        panic::set_hook(Box::new(|info| {
-            let backtrace = Backtrace::new();
-
-            eprintln!(
-                "error: Internal codegen error.
-                info: {}
-                backtrace: {:?}
-
-                note: The compiler unexpectedly panicked. this is a bug.
-                note: We would appreciate a bug report: https://github.com/fluid-lang/fluid/issues/new",
-                info, backtrace
-            );
-        }));
-
-        let module = cstring!("{}", module.into());
-
-        unsafe {
-            // Initialize LLVM.
-            llvm::target::LLVM_InitializeAllTargetInfos();
-            llvm::target::LLVM_InitializeAllTargets();
-            llvm::target::LLVM_InitializeAllTargetMCs();
-            llvm::target::LLVM_InitializeAllAsmParsers();
-            llvm::target::LLVM_InitializeAllAsmPrinters();
-
-            // Get the default target triple of the machine.
-            let target_triple = target_machine::LLVMGetDefaultTargetTriple();
-
-            let mut target = ptr::null_mut();
-            let mut error_str = MaybeUninit::uninit();
-
-            if target_machine::LLVMGetTargetFromTriple(target_triple, &mut target, error_str.as_mut_ptr()) == 1 {
-                let error_str = error_str.assume_init();
-
-                println!("{}", CString::from_raw(error_str).to_string_lossy())
-            }
+    /// Create a new codegen context for the machine the compiler runs on.
+    /// Returns an error if LLVM can not create the target machine or the execution engine of the host, like `with_target`.
+    pub fn new<S: Into<String>>(module: S, codegen_type: CodeGenType) -> Result<Self, String> {
+        Self::with_target(module, codegen_type, &TargetOptions::default())
+    }
 
-            let opt_level = LLVMCodeGenOptLevel::LLVMCodeGenLevelNone;
-            let reloc_mode = LLVMRelocMode::LLVMRelocDefault;
-            let code_model = LLVMCodeModel::LLVMCodeModelDefault;
+    /// Create a new codegen context for the target.
+    /// Returns an error if the target is unknown, or if the code is compiled just in time for a target that is not the machine the compiler runs on.
+    pub fn with_target<S: Into<String>>(module: S, codegen_type: CodeGenType, target: &TargetOptions) -> Result<Self, String> {
+        let module = module.into();
+        let file = Arc::new(SourceFile::new(module.as_str(), ""));
 
-            let cpu = cstring!("native").as_ptr();
-            let features = cstring!("").as_ptr();
+        initialize_targets();
 
-            let target_machine = LLVMCreateTargetMachine(target, target_triple, cpu, features, opt_level, reloc_mode, code_model);
+        let host = host_triple();
+        let triple = target.triple.clone().unwrap_or_else(|| host.clone());
+        let is_host = normalize_triple(&triple) == normalize_triple(&host);
 
-            LLVMLinkInMCJIT();
+        if !is_host && codegen_type != CodeGenType::Object {
+            return Err(format!("code compiled for the target `{}` cannot be run on this machine", triple));
+        }
 
-            let context = LLVMContextCreate();
-            let module = LLVMModuleCreateWithNameInContext(module.as_ptr(), context);
-            let builder = LLVMCreateBuilderInContext(context);
+        let default_cpu = if is_host { "native" } else { "generic" };
+        let cpu = target.cpu.as_deref().unwrap_or(default_cpu);
+        let features = target.features.as_deref().unwrap_or("");
+
+        let target_machine = TargetMachine::new(&triple, cpu, features, target.reloc_model.to_llvm(), target.code_model.to_llvm(), target.opt_level.to_llvm())?;
+
+        let context = Context::new();
+        let module = context.create_module(&module);
+        let builder = context.create_builder();
+
+        module.set_target(target_machine.triple());
+        module.set_data_layout(&target_machine);
+
+        // The execution engine takes the ownership of the module, so it is only created when the code is compiled just in time.
+        // The lazy JIT takes the ownership of the module instead, once the whole module is generated.
+        let execution_engine = match codegen_type {
+            CodeGenType::Object | CodeGenType::JIT { lazy: true, .. } => None,
+            _ => Some(ExecutionEngine::new(&module)?),
+        };
+        let pass_manager = PassManager::new(&module, target.opt_level != OptLevel::None);
+
+        let symbol_table = SymbolTable::new();
+
+        Ok(Self {
+            builder,
+            pass_manager,
+            execution_engine,
+            module,
+            target_machine,
+            context,
+            codegen_type,
+            mangling: Mangling::default(),
+            symbol_table,
+            file,
+            tests: vec![],
+            args: vec![],
+            main_return_type: None,
+            pass_time: Duration::default(),
+            function_times: None,
+            engine_outdated: false,
+        })
+    }
 
-            LLVMSetTarget(module, target_triple);
+    /// Set the mangling scheme of the function symbols, it must be set before the code is generated.
+    pub fn set_mangling(&mut self, mangling: Mangling) {
+        self.mangling = mangling;
+    }
 
-            let mut execution_engine = MaybeUninit::uninit();
-            let mut err_string = MaybeUninit::uninit();
+    /// Set the arguments given to the main function when it is run, the first one is the name of the program.
+    /// There are none by default, the arguments of the compiler are never given to the program.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
 
-            if LLVMCreateExecutionEngineForModule(execution_engine.as_mut_ptr(), module, err_string.as_mut_ptr()) == 1 {
-                let err_string = err_string.assume_init();
+    /// Get the time spent in the function passes of LLVM so far, it is part of the time spent generating the code.
+    pub fn pass_time(&self) -> Duration {
+        self.pass_time
+    }
 
-                panic!("{}", CString::from_raw(err_string).to_string_lossy());
-            }
+    /// Record the time spent generating every function from now on, for the self-profile of the compiler.
+    pub fn record_function_times(&mut self) {
+        self.function_times = Some(vec![]);
+    }
 
-            let execution_engine = execution_engine.assume_init();
+    /// Take the times of the functions generated since the last call, empty if they are not recorded.
+    pub fn take_function_times(&mut self) -> Vec<FunctionTime> {
+        self.function_times.as_mut().map(mem::take).unwrap_or_default()
+    }
 
-            let pass_manager = LLVMCreateFunctionPassManagerForModule(module);
+    /// Run codegen on the AST of the file, which must have been type checked.
+    /// The errors are emitted to the handler.
+    pub fn run(&mut self, file: Arc<SourceFile>, ast: Ast, handler: &mut DiagnosticHandler) -> Result<(), CodeGenError> {
+        self.run_modules(vec![(file, ast)], handler)
+    }
 
-            LLVMAddInstructionCombiningPass(pass_manager);
-            LLVMAddReassociatePass(pass_manager);
-            LLVMAddGVNPass(pass_manager);
-            LLVMAddCFGSimplificationPass(pass_manager);
-            LLVMAddBasicAliasAnalysisPass(pass_manager);
-            LLVMAddPromoteMemoryToRegisterPass(pass_manager);
-            LLVMAddInstructionCombiningPass(pass_manager);
-            LLVMAddReassociatePass(pass_manager);
+    /// Run codegen on the AST of every module of the program, which must have been type checked.
+    /// The modules are generated in order into one LLVM module, so a module can call the functions of the modules before it.
+    /// The errors are emitted to the handler.
+    pub fn run_modules(&mut self, modules: Vec<(Arc<SourceFile>, Ast)>, handler: &mut DiagnosticHandler) -> Result<(), CodeGenError> {
+        // The code generator reports the user errors as diagnostics, so a panic is always a bug. It is reported as
+        // an internal error instead of unwinding into the application that embeds the compiler.
+        let errors = match panic::catch_unwind(AssertUnwindSafe(|| self.gen_modules(modules))) {
+            Ok(errors) => errors,
+            Err(payload) => vec![self.make_panic_error(payload)],
+        };
+
+        if !errors.is_empty() {
+            handler.extend(errors);
+
+            return Err(CodeGenError);
+        }
 
-            LLVMInitializeFunctionPassManager(pass_manager);
+        Ok(())
+    }
 
-            let symbol_table = SymbolTable::new();
+    /// Generate the modules, and return the errors.
+    fn gen_modules(&mut self, modules: Vec<(Arc<SourceFile>, Ast)>) -> Vec<Diagnostic> {
+        // The functions of all of the modules share one namespace.
+        let mut errors = self.check_duplicate_symbols(&modules);
 
-            Self {
-                context,
-                module,
-                builder,
-                pass_manager,
-                execution_engine,
-                codegen_type,
-                symbol_table,
-                target_machine,
-            }
+        if !errors.is_empty() {
+            return errors;
         }
-    }
 
-    /// Run codegen.
-    pub fn run(&mut self, mut parser: Parser) {
-        let ast = parser.run();
+        self.init_stdlib();
 
-        unsafe {
-            self.init_stdlib();
+        if self.codegen_type == CodeGenType::Repl {
+            self.engine_outdated = true;
+        }
 
-            match self.codegen_type {
-                CodeGenType::JIT { run_main } => {
-                    for statement in ast {
-                        self.gen_statement(statement);
-                    }
+        for (file, ast) in modules {
+            self.file = file;
 
-                    if run_main {
-                        self.run_main()
-                    }
-                }
-                CodeGenType::Repl => {
-                    for statement in ast {
-                        if let Statement::Expression(expression) = statement {
-                            self.run_top_level_expression(&expression);
-                        } else {
-                            self.gen_statement(statement);
-                        }
-                    }
+            for statement in ast.statements {
+                let result = match statement.kind {
+                    StatementKind::Expression(expression) if self.codegen_type == CodeGenType::Repl => self.run_top_level_expression(&ast.exprs, expression),
+                    _ => self.gen_statement(&ast.exprs, statement),
+                };
+
+                if let Err(error) = result {
+                    errors.push(error);
                 }
             }
         }
+
+        errors
     }
 
     /// Reset the codegen context.
     pub fn reset(&mut self) {}
 
-    /// Emit LLVM IR.
-    pub fn emit_llvm(&mut self, file: &str) {
-        unsafe {
-            let file_name = Path::new(file).file_name().unwrap().to_str().unwrap().replace(".fluid", ".ll");
-            let ir = CString::from_raw(LLVMPrintModuleToString(self.module));
+    /// Get the textual LLVM IR of the module, or only the one of the functions with the name if there is one.
+    /// The name is the one of the source, the symbols of the functions that are not in the source work too.
+    pub fn print_ir(&self, function: Option<&str>) -> Result<String, String> {
+        let name = match function {
+            Some(name) => name,
+            None => return Ok(self.module.print_to_string()),
+        };
+
+        // The functions with the same name and different arguments have different symbols.
+        let mut functions = self
+            .symbol_table
+            .global_functions()
+            .filter(|function| function.name == name)
+            .map(|function| function.value)
+            .collect::<Vec<_>>();
+
+        if functions.is_empty() {
+            functions.extend(self.module.get_function(name));
+        }
 
-            fs::write(file_name, ir.to_str().unwrap()).unwrap();
+        if functions.is_empty() {
+            return Err(format!("there is no function named `{}`", name));
         }
+
+        Ok(functions.iter().map(|function| function.as_value().print_to_string()).collect::<Vec<_>>().join("\n"))
     }
 
-    /// Emit an object file.
-    pub fn emit_object(&mut self, path: &Path) {
-        let mut error_str = MaybeUninit::uninit();
-        let file_name = cstring!("{}", path.to_string_lossy()).into_raw();
+    /// Emit textual LLVM IR.
+    pub fn emit_llvm(&mut self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.module.print_to_string()).map_err(|error| format!("could not write the LLVM IR to `{}`: {}", path.display(), error))
+    }
 
-        unsafe {
-            LLVMTargetMachineEmitToFile(self.target_machine, self.module, file_name, LLVMCodeGenFileType::LLVMObjectFile, error_str.as_mut_ptr());
+    /// Emit LLVM bitcode, which can be given to the LLVM tools like `llc` and `opt`.
+    pub fn emit_bitcode(&mut self, path: &Path) -> Result<(), String> {
+        if self.module.write_bitcode_to_file(path) {
+            Ok(())
+        } else {
+            Err(format!("could not write the bitcode to `{}`", path.display()))
         }
     }
 
-    /// Free all of the resources.
-    pub fn free(&mut self) {
-        unsafe {
-            LLVMContextDispose(self.context);
-            LLVMDisposeBuilder(self.builder);
-            LLVMDisposeModule(self.module);
-            LLVMDisposeExecutionEngine(self.execution_engine);
+    /// Emit the assembly of the target.
+    pub fn emit_assembly(&mut self, path: &Path) -> Result<(), String> {
+        self.target_machine.emit_to_file(&self.module, path, LLVMCodeGenFileType::LLVMAssemblyFile)
+    }
+
+    /// Emit an object file.
+    pub fn emit_object(&mut self, path: &Path) -> Result<(), String> {
+        self.target_machine.emit_to_file(&self.module, path, LLVMCodeGenFileType::LLVMObjectFile)
+    }
 
-            LLVMShutdown();
+    /// Get the signatures of the functions that were generated in the global scope, sorted by their names.
+    pub fn functions(&self) -> Vec<FunctionSignature> {
+        let mut functions = self
+            .symbol_table
+            .global_functions()
+            .map(|function| FunctionSignature {
+                name: function.name.clone(),
+                args: function.args.clone(),
+                return_type: function.return_type,
+            })
+            .collect::<Vec<_>>();
+
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        functions
+    }
+
+    /// Get the names of the test functions of the program, in the order of their definitions.
+    pub fn tests(&self) -> Vec<&str> {
+        self.tests.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Run the test function with the name. It returns if the test passed, a failed assertion exits the process.
+    pub fn run_test(&self, name: &str) -> Result<(), String> {
+        let symbol = match self.tests.iter().find(|(test, _)| test == name) {
+            Some((_, symbol)) => symbol,
+            None => return Err(format!("there is no test named `{}`", name)),
+        };
+
+        let execution_engine = self.execution_engine.as_ref().ok_or("the tests can only be run by the codegen of the `Test` type")?;
+
+        // SAFETY: The type checker checked that the test functions take no arguments and return nothing.
+        let test: extern "C" fn() = unsafe { mem::transmute(execution_engine.function_address(symbol)?) };
+
+        let _crash_handler = CrashHandler::install();
+
+        test();
+
+        Ok(())
+    }
+
+    /// Get the address of the function with the name of the source, compiled by the codegen of the `Repl` type, so
+    /// that the application that embeds the compiler can call it. The address is valid until code is generated again.
+    /// The function must not be overloaded, since the functions with the same name can not be told apart.
+    pub fn function_address(&mut self, name: &str) -> Result<u64, String> {
+        if self.codegen_type != CodeGenType::Repl {
+            return Err("only the functions compiled by the codegen of the `Repl` type can be called".to_owned());
+        }
+
+        let symbols = self
+            .symbol_table
+            .global_functions()
+            .filter(|function| function.name == name)
+            .map(|function| function.value.as_value().name())
+            .collect::<Vec<_>>();
+
+        let symbol = match &symbols[..] {
+            [symbol] => symbol,
+            [] => return Err(format!("there is no function named `{}`", name)),
+            _ => return Err(format!("the function `{}` is overloaded, its address is ambiguous", name)),
+        };
+
+        // MCJIT compiles a module only once, so the functions generated since then are not compiled yet.
+        if self.engine_outdated {
+            self.reload_execution_engine()?;
         }
+
+        self.execution_engine.as_ref().ok_or("the REPL has no execution engine")?.function_address(symbol)
     }
 
-    unsafe fn run_top_level_expression(&mut self, _expression: &Expression) {}
+    /// Run the main function of the program compiled just in time, and return its exit code. The exit code of a main
+    /// function that returns nothing is `0`. A crash of the program is reported with a readable message, and exits
+    /// the process.
+    pub fn run_main(&mut self) -> Result<i32, String> {
+        let return_type = self.main_return_type.ok_or("the program has no `main` function")?;
 
-    /// Run the main function.
-    unsafe fn run_main(&mut self) -> ! {
-        let args = std::env::args().skip(1);
-        let argc = args.len() as i64;
+        let argc = self.args.len() as i64;
 
-        let vec_args = args.map(|string| std::ffi::CString::new(string).unwrap()).collect::<Vec<_>>();
+        // An argument can not contain a nul byte, since the arguments of a process can not either.
+        let vec_args = self.args.iter().map(|string| std::ffi::CString::new(string.as_str()).unwrap()).collect::<Vec<_>>();
         let argv = vec_args.iter().map(|cstr| cstr.as_ptr() as *const u8).collect::<Vec<_>>();
 
-        let main_function_addr = LLVMGetFunctionAddress(self.execution_engine, cstring!("main").as_ptr());
-        let main_function: extern "C" fn(i64, *const *const u8) -> i64 = mem::transmute(main_function_addr);
+        // The lazy JIT must stay alive while the main function runs, since it compiles the functions that main calls.
+        let lazy_jit;
+
+        let main_function_addr = match self.codegen_type {
+            CodeGenType::JIT { lazy: true } => {
+                let target_machine = self.target_machine.duplicate()?;
+
+                lazy_jit = LazyJit::new(target_machine, &self.module).map_err(|error| format!("could not create the lazy JIT: {}", error))?;
+                lazy_jit.function_address("main")?
+            }
+            _ => {
+                let execution_engine = self.execution_engine.as_ref().ok_or("only the programs compiled just in time can be run")?;
+
+                execution_engine.function_address("main")?
+            }
+        };
+
+        let _crash_handler = CrashHandler::install();
+
+        // SAFETY: The main function takes at most the number of arguments and the arguments, and the C calling
+        // convention allows a function to be given more arguments than it takes.
+        let exit_code = if return_type == Type::Void {
+            let main_function: extern "C" fn(i64, *const *const u8) = unsafe { mem::transmute(main_function_addr) };
+
+            main_function(argc, argv.as_ptr());
+
+            0
+        } else {
+            let main_function: extern "C" fn(i64, *const *const u8) -> i64 = unsafe { mem::transmute(main_function_addr) };
+
+            main_function(argc, argv.as_ptr())
+        };
+
+        Ok(exit_code as i32)
+    }
+
+    /// Make an error with a message and code, annotating the span with a label.
+    pub(crate) fn make_error(&self, message: impl Into<String>, code: &str, span: Range<usize>, label: impl Into<String>) -> Diagnostic {
+        DiagnosticBuilder::new(Severity::Error, message)
+            .set_code(code)
+            .push_slice(Slice::new(self.file.clone()).push_annotation(SourceAnnotation::new(span, AnnotationType::Error).set_label(label)))
+            .build()
+    }
+
+    /// Make an error about a valid program using a feature that the code generator does not support yet.
+    pub(crate) fn unsupported(&self, message: impl Into<String>, span: Range<usize>) -> Diagnostic {
+        self.make_error(message, codes::E0016, span, "not supported by the code generator")
+    }
+
+    /// Make the internal error reported when the code generated for a function is not valid IR, which is a bug.
+    /// The IR of the function is written to a temporary file, so that it can be attached to the bug report.
+    pub(crate) fn make_invalid_ir(&self, name: &str, span: Range<usize>, function: FnValue, message: &str) -> Diagnostic {
+        let path = env::temp_dir().join(format!("fluid-{}-{}.ll", name, process::id()));
+
+        let dump = match fs::write(&path, function.as_value().print_to_string()) {
+            Ok(()) => format!("the IR of the function was written to `{}`", path.display()),
+            Err(error) => format!("the IR of the function could not be written to `{}`: {}", path.display(), error),
+        };
+
+        DiagnosticBuilder::new(Severity::Error, format!("internal compiler error: the code generated for `{}` is invalid", name))
+            .push_slice(Slice::new(self.file.clone()).push_annotation(SourceAnnotation::new(span, AnnotationType::Error).set_label("while generating this function")))
+            .set_note(format!("the LLVM verifier failed: {}", message.trim()))
+            .set_note(dump)
+            .set_help("this is a bug, we would appreciate a bug report: https://github.com/fluid-lang/fluid/issues/new")
+            .build()
+    }
 
-        process::exit(main_function(argc, argv.as_ptr()) as i32);
+    /// Make the internal error reported when the code generator panicked, with the message of the panic.
+    fn make_panic_error(&self, payload: Box<dyn Any + Send>) -> Diagnostic {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => (*message).to_owned(),
+            None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "unknown panic payload".to_owned()),
+        };
+
+        DiagnosticBuilder::new(Severity::Error, "internal compiler error: the code generator panicked")
+            .set_note(format!("panicked with: {}", message))
+            .set_note("run with `RUST_BACKTRACE=1` to display a backtrace")
+            .set_help("this is a bug, we would appreciate a bug report: https://github.com/fluid-lang/fluid/issues/new")
+            .build()
     }
 
     /// Dump the given value.
     #[inline]
-    pub(crate) unsafe fn dump_value(&self, value: LLVMValueRef) {
+    pub(crate) fn dump_value(&self, value: Value) {
         if DEBUG {
-            LLVMDumpValue(value);
+            value.dump();
         }
     }
 }
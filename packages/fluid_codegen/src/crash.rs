@@ -0,0 +1,82 @@
+//! Readable messages for the crashes of the programs run just in time.
+//!
+//! A program run just in time runs in the process of the compiler, so its crashes are signals sent to the compiler.
+//! While the program runs, the signals of the crashes print which crash it was and exit with the code a shell
+//! would report for the signal, `128` plus the signal.
+
+/// The handlers of the crash signals, installed until it is dropped. The previous handlers are restored then, so
+/// the crashes of the compiler itself are still reported by Rust.
+pub(crate) struct CrashHandler {
+    #[cfg(unix)]
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{mem, ptr};
+
+    use super::CrashHandler;
+
+    /// The signals of the crashes of a program.
+    const SIGNALS: &[libc::c_int] = &[libc::SIGSEGV, libc::SIGBUS, libc::SIGFPE, libc::SIGILL, libc::SIGABRT];
+
+    /// Print the message of the crash and exit. Only the functions that are safe in a signal handler are called.
+    extern "C" fn handle_crash(signal: libc::c_int) {
+        let message: &[u8] = match signal {
+            libc::SIGSEGV => b"error: the program crashed: invalid memory access (SIGSEGV), it may have overflowed its stack\n",
+            libc::SIGBUS => b"error: the program crashed: invalid memory access (SIGBUS)\n",
+            libc::SIGFPE => b"error: the program crashed: arithmetic exception (SIGFPE), it may have divided an integer by zero\n",
+            libc::SIGILL => b"error: the program crashed: illegal instruction (SIGILL)\n",
+            libc::SIGABRT => b"error: the program aborted (SIGABRT)\n",
+            _ => b"error: the program crashed\n",
+        };
+
+        unsafe {
+            libc::write(libc::STDERR_FILENO, message.as_ptr() as *const libc::c_void, message.len());
+            libc::_exit(128 + signal);
+        }
+    }
+
+    impl CrashHandler {
+        /// Install the handlers of the crash signals.
+        pub(crate) fn install() -> Self {
+            let mut previous = vec![];
+
+            for &signal in SIGNALS {
+                unsafe {
+                    let mut action: libc::sigaction = mem::zeroed();
+                    let mut old_action: libc::sigaction = mem::zeroed();
+
+                    action.sa_sigaction = handle_crash as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                    // A stack overflow is handled on the alternate stack that Rust sets up for the main thread.
+                    action.sa_flags = libc::SA_ONSTACK;
+                    libc::sigemptyset(&mut action.sa_mask);
+
+                    if libc::sigaction(signal, &action, &mut old_action) == 0 {
+                        previous.push((signal, old_action));
+                    }
+                }
+            }
+
+            Self { previous }
+        }
+    }
+
+    impl Drop for CrashHandler {
+        fn drop(&mut self) {
+            for (signal, action) in self.previous.iter() {
+                unsafe {
+                    libc::sigaction(*signal, action, ptr::null_mut());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl CrashHandler {
+    /// The crashes are reported by the operating system on the other platforms.
+    pub(crate) fn install() -> Self {
+        Self {}
+    }
+}
@@ -1,86 +1,170 @@
 use crate::symbol::*;
 use crate::*;
 
-use fluid_mangle::mangle_function_name;
-use fluid_parser::{Function, Prototype, Type};
-use llvm::{analysis::*, core::*, prelude::*, *};
+use std::time::Instant;
 
-use crate::CodeGen;
+use fluid_mangle::{mangle, Symbol};
+use fluid_parser::{ExprArena, Function, Prototype, Type};
+use llvm::LLVMLinkage;
+
+use crate::{
+    llvm_safe::{BasicBlock, FnValue},
+    utils::CodeGenResult,
+    CodeGen,
+};
 
 impl CodeGen {
+    /// Get the key of a function in the symbol table, the calls find the functions by their name and the types of their arguments.
+    /// The calls are not qualified by a module, so the key does not contain the module path.
+    pub(crate) fn function_key(&self, name: &str, params: Vec<Type>) -> String {
+        mangle(&Symbol::new(vec![], name, params), self.mangling)
+    }
+
+    /// Get the symbol of the function of the prototype in the LLVM module.
+    /// The entry point and the functions that are not mangled keep their name, since they are called by name from C,
+    /// the C runtime and the JIT.
+    pub(crate) fn function_symbol(&self, prototype: &Prototype) -> String {
+        if prototype.no_mangle || prototype.name == "main" {
+            prototype.name.clone()
+        } else {
+            let params = prototype.args.iter().map(|arg| arg.typee).collect();
+
+            mangle(&Symbol::new(self.module_path(), prototype.name.as_str(), params), self.mangling)
+        }
+    }
+
     /// Generate the function prototype.
-    pub(crate) unsafe fn gen_prototype(&mut self, prototype: &Prototype) -> LLVMValueRef {
+    pub(crate) fn gen_prototype(&mut self, prototype: &Prototype) -> FnValue {
         let return_type = self.gen_type(prototype.return_type);
-        let mut argument_types = prototype.args.iter().map(|arg| self.gen_type(arg.typee)).collect::<Vec<_>>();
+        let argument_types = prototype.args.iter().map(|arg| self.gen_type(arg.typee)).collect::<Vec<_>>();
 
-        let function_type = LLVMFunctionType(return_type, argument_types.as_mut_ptr(), prototype.args.len() as u32, 0);
-        let function_value = LLVMAddFunction(self.module, cstring!("{}", prototype.name.as_str()).as_ptr(), function_type);
+        let function_type = return_type.function_type(&argument_types, false);
+        let function_value = self.module.add_function(&self.function_symbol(prototype), function_type);
 
-        LLVMSetLinkage(function_value, LLVMLinkage::LLVMExternalLinkage);
+        function_value.set_linkage(LLVMLinkage::LLVMExternalLinkage);
 
-        for i in 0..prototype.args.len() {
-            let arg = &prototype.args[i];
-
-            let param = LLVMGetParam(function_value, i as u32);
-            LLVMSetValueName2(param, cstring!("{}", arg.name).as_ptr(), arg.name.len());
+        for (i, arg) in prototype.args.iter().enumerate() {
+            function_value.param(i).set_name(&arg.name);
         }
 
-        if LLVMRunFunctionPassManager(self.pass_manager, function_value) == 1 {
+        let start = Instant::now();
+
+        if self.pass_manager.run(function_value) {
             panic!("Running FunctionPassManager failed.")
         }
 
+        self.pass_time += start.elapsed();
+
         function_value
     }
 
     /// Generate the function definition.
-    pub(crate) unsafe fn gen_function_def(&mut self, mut function: Function) {
-        function.prototype.name = mangle_function_name(function.prototype.name, function.prototype.args.iter().map(|arg| arg.typee).collect::<Vec<_>>());
-
-        let function_name = function.prototype.name.clone();
+    /// The builder is moved back to where it was afterwards, since functions can be defined inside of functions.
+    pub(crate) fn gen_function_def(&mut self, exprs: &ExprArena, function: Function) -> CodeGenResult<()> {
+        let start = Instant::now();
+        let function_name = self.function_key(&function.prototype.name, function.prototype.args.iter().map(|arg| arg.typee).collect());
         let function_value = self.gen_prototype(&function.prototype);
+        let previous_block = self.builder.insert_block();
 
-        self.symbol_table.push_scope();
+        // The function is visible in the scope it is defined in, so that its body and the functions next to it can call it.
+        let function_ref = FluidFunctionRef::new(
+            function.prototype.name.clone(),
+            function.prototype.args.iter().map(|arg| arg.typee).collect::<Vec<_>>(),
+            function.prototype.return_type,
+            function_value,
+        );
 
-        let entry = LLVMAppendBasicBlockInContext(self.context, function_value, cstring!("entry").as_ptr());
-        LLVMPositionBuilderAtEnd(self.builder, entry);
+        self.symbol_table.insert_function(function_name.clone(), function_ref);
+        self.symbol_table.push_scope();
 
-        for i in 0..function.prototype.args.len() {
-            let arg = &function.prototype.args[i];
+        let entry = self.context.append_basic_block(function_value, "entry");
+        self.builder.position_at_end(entry);
 
-            let param = LLVMGetParam(function_value, i as u32);
+        for (i, arg) in function.prototype.args.iter().enumerate() {
+            let param = function_value.param(i);
             let kind = self.gen_type(arg.typee);
 
-            let variable_alloca = LLVMBuildAlloca(self.builder, kind, cstring!("{}", arg.name).as_ptr());
-            LLVMBuildStore(self.builder, param, variable_alloca);
+            let variable_alloca = self.build_entry_alloca(kind, &arg.name);
+            self.builder.build_store(param, variable_alloca);
 
             let variable_ref = FluidVariableRef::new(true, arg.typee, variable_alloca);
 
             self.symbol_table.insert_variable(arg.name.clone(), variable_ref);
         }
 
-        let function_ref = FluidFunctionRef::new(function.prototype.args.iter().map(|arg| arg.typee).collect::<Vec<_>>(), function.prototype.return_type, function_value);
-
-        self.symbol_table.insert_function(function_name, function_ref);
-        self.gen_function_body(function.body);
+        let result = self.gen_function_body(exprs, function.body);
 
         self.symbol_table.pop_scope();
 
-        if function.prototype.return_type == Type::Void {
-            LLVMBuildRetVoid(self.builder);
+        if result.is_err() {
+            self.symbol_table.remove_function(&function_name);
+            self.restore_insert_block(previous_block);
+
+            // SAFETY: The function was removed from the symbol table, its variables were popped with its scope, and
+            // the builder was moved out of it.
+            unsafe { function_value.delete() };
+
+            return result;
+        }
+
+        // The body may already end with an explicit `return;`.
+        if function.prototype.return_type == Type::Void && !self.is_terminated() {
+            self.builder.build_ret_void();
         }
 
         // Dump the generated ir.
-        self.dump_value(function_value);
+        self.dump_value(function_value.as_value());
+
+        if let Err(message) = function_value.verify() {
+            let error = self.make_invalid_ir(&function.prototype.name, function.prototype.name_span.clone(), function_value, &message);
+
+            self.symbol_table.remove_function(&function_name);
+            self.restore_insert_block(previous_block);
 
-        if LLVMVerifyFunction(function_value, LLVMVerifierFailureAction::LLVMReturnStatusAction) == 1 {
-            LLVMDeleteFunction(function_value);
-            panic!("Fluid generated invalid function ir.")
+            // SAFETY: The function was removed from the symbol table, its variables were popped with its scope, and
+            // the builder was moved out of it.
+            unsafe { function_value.delete() };
+
+            return Err(error);
+        }
+
+        if function.prototype.name == "main" {
+            self.main_return_type = Some(function.prototype.return_type);
+        }
+
+        if function.prototype.test {
+            self.tests.push((function.prototype.name.clone(), self.function_symbol(&function.prototype)));
+        }
+
+        self.restore_insert_block(previous_block);
+
+        if let Some(function_times) = self.function_times.as_mut() {
+            function_times.push(FunctionTime {
+                name: function.prototype.name,
+                start,
+                duration: start.elapsed(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Position the builder at the end of the block, or clear its position if there is no block.
+    fn restore_insert_block(&mut self, block: Option<BasicBlock>) {
+        match block {
+            Some(block) => self.builder.position_at_end(block),
+            None => self.builder.clear_insertion_position(),
         }
     }
 
     /// Generate an external definition.
-    pub(crate) unsafe fn gen_extern_def(&mut self, prototype: Prototype) {
+    pub(crate) fn gen_extern_def(&mut self, prototype: Prototype) {
         let external_function = self.gen_prototype(&prototype);
-        self.dump_value(external_function);
+        self.dump_value(external_function.as_value());
+
+        let params = prototype.args.iter().map(|arg| arg.typee).collect::<Vec<_>>();
+        let function_ref = FluidFunctionRef::new(prototype.name.clone(), params.clone(), prototype.return_type, external_function);
+
+        self.symbol_table.insert_function(self.function_key(&prototype.name, params), function_ref);
     }
 }
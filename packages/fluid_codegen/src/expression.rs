@@ -1,125 +1,412 @@
-use fluid_mangle::mangle_function_name;
-use fluid_parser::{BinaryOp, Expression, Literal, Type, UnaryOp};
+use fluid_error::codes;
+use fluid_parser::{BinaryOp, Builtin, ExprArena, ExprId, ExpressionKind, Literal, Statement, Type, UnaryOp};
 
-use llvm::core::*;
+use std::ops::Range;
 
-use crate::{cstring, utils::FluidValueRef, CodeGen};
+use llvm::{LLVMIntPredicate, LLVMRealPredicate};
+
+use crate::{
+    utils::{CodeGenResult, FluidValueRef},
+    CodeGen,
+};
 
 impl CodeGen {
     /// Generate an expression.
-    pub(crate) unsafe fn gen_expression(&mut self, expression: &Expression) -> FluidValueRef {
-        match expression {
-            Expression::Literal(ref literal) => self.gen_literal(literal),
-            Expression::VarRef(ref name) => self.gen_var_ref(name),
-            Expression::FunctionCall(ref name, ref args) => self.gen_function_call(name, args),
-            Expression::BinaryOp(ref lhs, ref op, ref rhs) => self.gen_binary(lhs, op, rhs),
-            Expression::Unary(ref op, ref rhs) => self.gen_unary(op, rhs),
-            _ => unimplemented!(),
+    pub(crate) fn gen_expression(&mut self, exprs: &ExprArena, id: ExprId) -> CodeGenResult<FluidValueRef> {
+        let expression = &exprs[id];
+        let span = expression.span.clone();
+
+        match expression.kind {
+            ExpressionKind::Literal(ref literal) => self.gen_literal(literal, span),
+            ExpressionKind::VarRef(ref name) => self.gen_var_ref(name, span),
+            ExpressionKind::FunctionCall(ref name, ref args) => self.gen_function_call(exprs, name, args, span),
+            ExpressionKind::BinaryOp(lhs, BinaryOp::Coalesce, rhs) => self.gen_coalesce(exprs, lhs, rhs),
+            ExpressionKind::BinaryOp(lhs, ref op, rhs) if matches!(op, BinaryOp::And | BinaryOp::Or) => self.gen_logical(exprs, lhs, op, rhs),
+            ExpressionKind::BinaryOp(lhs, ref op, rhs) => self.gen_binary(exprs, lhs, op, rhs, span),
+            ExpressionKind::Unary(ref op, rhs) => self.gen_unary(exprs, op, rhs),
+            ExpressionKind::VarAssign(ref name, value) => self.gen_var_assign(exprs, name, value, span),
+            ExpressionKind::Paren(inner) => self.gen_expression(exprs, inner),
+            ExpressionKind::If(condition, then, otherwise) => self.gen_if_expression(exprs, condition, then, otherwise),
+            ExpressionKind::Block(ref block, value) => {
+                self.symbol_table.push_scope();
+
+                let result = self.gen_block_expression(exprs, block, value);
+
+                self.symbol_table.pop_scope();
+
+                result
+            }
+            ExpressionKind::Index(base, index) => self.gen_index(exprs, base, index),
         }
     }
 
+    /// Generate an index expression, the byte of a string at the index.
+    pub(crate) fn gen_index(&mut self, exprs: &ExprArena, base: ExprId, index: ExprId) -> CodeGenResult<FluidValueRef> {
+        let base = self.gen_expression(exprs, base)?;
+        let index = self.gen_expression(exprs, index)?;
+
+        let function = self.get_string_index();
+
+        Ok(FluidValueRef::new(Type::Char, self.builder.build_call(function, &[base.value, index.value], "char")))
+    }
+
     /// Generate a unary expression.
-    pub(crate) unsafe fn gen_unary(&mut self, op: &UnaryOp, rhs: &Expression) -> FluidValueRef {
-        let rhs = self.gen_expression(rhs);
+    pub(crate) fn gen_unary(&mut self, exprs: &ExprArena, op: &UnaryOp, rhs: ExprId) -> CodeGenResult<FluidValueRef> {
+        let rhs = self.gen_expression(exprs, rhs)?;
 
         match op {
-            UnaryOp::Neg => FluidValueRef::new(rhs.kind, LLVMBuildNeg(self.builder, rhs.value, cstring!("nottmp").as_ptr())),
-            UnaryOp::Not => {
-                unimplemented!()
-            }
+            UnaryOp::Neg if rhs.kind == Type::Float => Ok(FluidValueRef::new(rhs.kind, self.builder.build_fneg(rhs.value, "negtmp"))),
+            UnaryOp::Neg => Ok(FluidValueRef::new(rhs.kind, self.builder.build_neg(rhs.value, "negtmp"))),
+            UnaryOp::Plus => Ok(rhs),
+            UnaryOp::Not => Ok(FluidValueRef::new(rhs.kind, self.builder.build_not(rhs.value, "nottmp"))),
         }
     }
 
     /// Generate a binary expression.
-    pub(crate) unsafe fn gen_binary(&mut self, lhs: &Expression, op: &BinaryOp, rhs: &Expression) -> FluidValueRef {
-        let lhs = self.gen_expression(lhs);
-        let rhs = self.gen_expression(rhs);
+    pub(crate) fn gen_binary(&mut self, exprs: &ExprArena, lhs: ExprId, op: &BinaryOp, rhs: ExprId, span: Range<usize>) -> CodeGenResult<FluidValueRef> {
+        let lhs = self.gen_expression(exprs, lhs)?;
+        let rhs = self.gen_expression(exprs, rhs)?;
 
         let res = match op {
+            BinaryOp::Lesser | BinaryOp::Greater | BinaryOp::EqEq => return Ok(self.gen_comparison(lhs, op, rhs)),
             BinaryOp::Add => {
                 if lhs.kind == Type::Number {
-                    LLVMBuildAdd(self.builder, lhs.value, rhs.value, cstring!("addtmp").as_ptr())
+                    self.builder.build_add(lhs.value, rhs.value, "addtmp")
                 } else {
-                    LLVMBuildFAdd(self.builder, lhs.value, rhs.value, cstring!("addtmp").as_ptr())
+                    self.builder.build_fadd(lhs.value, rhs.value, "addtmp")
                 }
             }
             BinaryOp::Subtract => {
                 if lhs.kind == Type::Number {
-                    LLVMBuildSub(self.builder, lhs.value, rhs.value, cstring!("subtmp").as_ptr())
+                    self.builder.build_sub(lhs.value, rhs.value, "subtmp")
                 } else {
-                    LLVMBuildFSub(self.builder, lhs.value, rhs.value, cstring!("subtmp").as_ptr())
+                    self.builder.build_fsub(lhs.value, rhs.value, "subtmp")
                 }
             }
             BinaryOp::Mul => {
                 if lhs.kind == Type::Number {
-                    LLVMBuildMul(self.builder, lhs.value, rhs.value, cstring!("multmp").as_ptr())
+                    self.builder.build_mul(lhs.value, rhs.value, "multmp")
+                } else {
+                    self.builder.build_fmul(lhs.value, rhs.value, "multmp")
+                }
+            }
+            BinaryOp::Div => {
+                if lhs.kind == Type::Number {
+                    let function = self.get_divide();
+                    let location = self.builder.build_global_string_ptr(&self.location(span), "location");
+
+                    self.builder.build_call(function, &[lhs.value, rhs.value, location], "divtmp")
                 } else {
-                    LLVMBuildFMul(self.builder, lhs.value, rhs.value, cstring!("multmp").as_ptr())
+                    // A float division by zero is defined by IEEE 754, it results in an infinity or NaN.
+                    self.builder.build_fdiv(lhs.value, rhs.value, "divtmp")
                 }
             }
-            _ => unimplemented!(),
+            _ => return Err(self.unsupported(format!("the `{}` operator is not supported yet", op), span)),
         };
 
-        FluidValueRef::new(lhs.kind, res)
+        Ok(FluidValueRef::new(lhs.kind, res))
+    }
+
+    /// Generate a `&&` or a `||`. The right operand is only evaluated if the left one does not decide the result, when
+    /// it is true for `&&` and false for `||`.
+    pub(crate) fn gen_logical(&mut self, exprs: &ExprArena, lhs: ExprId, op: &BinaryOp, rhs: ExprId) -> CodeGenResult<FluidValueRef> {
+        let lhs = self.gen_expression(exprs, lhs)?;
+
+        let function = self.current_function();
+        let lhs_end = self.builder.insert_block().unwrap();
+
+        let is_and = matches!(op, BinaryOp::And);
+        let (name, rhs_name, merge_name) = if is_and { ("and", "and.rhs", "and.end") } else { ("or", "or.rhs", "or.end") };
+
+        let rhs_block = self.context.append_basic_block(function, rhs_name);
+        let merge_block = self.context.append_basic_block(function, merge_name);
+
+        if is_and {
+            self.builder.build_cond_br(lhs.value, rhs_block, merge_block);
+        } else {
+            self.builder.build_cond_br(lhs.value, merge_block, rhs_block);
+        }
+
+        self.builder.position_at_end(rhs_block);
+
+        let rhs = self.gen_expression(exprs, rhs)?;
+
+        // The right operand may have added blocks of its own, like a nested `&&`.
+        let rhs_end = self.builder.insert_block().unwrap();
+
+        self.builder.build_br(merge_block);
+        self.builder.position_at_end(merge_block);
+
+        // The result is the left operand if the right one was skipped.
+        let value = self.builder.build_phi(lhs.value.type_of(), &[(lhs.value, lhs_end), (rhs.value, rhs_end)], name);
+
+        Ok(FluidValueRef::new(Type::Bool, value))
+    }
+
+    /// Generate a comparison. The type checker only orders the numbers and the floats, and only compares the values
+    /// of the same type. A comparison of floats is false if one of them is NaN.
+    pub(crate) fn gen_comparison(&mut self, lhs: FluidValueRef, op: &BinaryOp, rhs: FluidValueRef) -> FluidValueRef {
+        let value = match lhs.kind {
+            Type::Float => {
+                let predicate = match op {
+                    BinaryOp::Lesser => LLVMRealPredicate::LLVMRealOLT,
+                    BinaryOp::Greater => LLVMRealPredicate::LLVMRealOGT,
+                    _ => LLVMRealPredicate::LLVMRealOEQ,
+                };
+
+                self.builder.build_fcmp(predicate, lhs.value, rhs.value, "cmptmp")
+            }
+            Type::String => {
+                let function = self.get_string_equals();
+
+                self.builder.build_call(function, &[lhs.value, rhs.value], "cmptmp")
+            }
+            _ => {
+                let predicate = match op {
+                    BinaryOp::Lesser => LLVMIntPredicate::LLVMIntSLT,
+                    BinaryOp::Greater => LLVMIntPredicate::LLVMIntSGT,
+                    _ => LLVMIntPredicate::LLVMIntEQ,
+                };
+
+                self.builder.build_icmp(predicate, lhs.value, rhs.value, "cmptmp")
+            }
+        };
+
+        FluidValueRef::new(Type::Bool, value)
+    }
+
+    /// Generate a `??` expression.
+    /// The right operand is only evaluated if the left one is null, the value comes from a `phi` in the block after them.
+    pub(crate) fn gen_coalesce(&mut self, exprs: &ExprArena, lhs: ExprId, rhs: ExprId) -> CodeGenResult<FluidValueRef> {
+        let lhs = self.gen_expression(exprs, lhs)?;
+        let is_null = self.builder.build_is_null(lhs.value, "isnull");
+
+        let function = self.current_function();
+        let lhs_end = self.builder.insert_block().unwrap();
+
+        let rhs_block = self.context.append_basic_block(function, "coalesce.rhs");
+        let merge_block = self.context.append_basic_block(function, "coalesce.end");
+
+        self.builder.build_cond_br(is_null, rhs_block, merge_block);
+
+        self.builder.position_at_end(rhs_block);
+
+        let rhs = self.gen_expression(exprs, rhs)?;
+
+        // The right operand may have added blocks of its own, like a nested `??`.
+        let rhs_end = self.builder.insert_block().unwrap();
+
+        self.builder.build_br(merge_block);
+        self.builder.position_at_end(merge_block);
+
+        let value = self.builder.build_phi(lhs.value.type_of(), &[(lhs.value, lhs_end), (rhs.value, rhs_end)], "coalesce");
+
+        Ok(FluidValueRef::new(lhs.kind, value))
+    }
+
+    /// Generate an if expression. The branches jump to a merge block, where a phi node takes the value of the branch
+    /// that was executed.
+    pub(crate) fn gen_if_expression(&mut self, exprs: &ExprArena, condition: ExprId, then: ExprId, otherwise: ExprId) -> CodeGenResult<FluidValueRef> {
+        let condition = self.gen_expression(exprs, condition)?;
+        let function = self.current_function();
+
+        let then_block = self.context.append_basic_block(function, "if.then");
+        let else_block = self.context.append_basic_block(function, "if.else");
+        let merge_block = self.context.append_basic_block(function, "if.end");
+
+        self.builder.build_cond_br(condition.value, then_block, else_block);
+
+        // The branches may have added blocks of their own, like a nested if expression, so they end in the current
+        // blocks.
+        self.builder.position_at_end(then_block);
+
+        let then = self.gen_expression(exprs, then)?;
+        let then_end = self.builder.insert_block().unwrap();
+
+        self.builder.build_br(merge_block);
+        self.builder.position_at_end(else_block);
+
+        let otherwise = self.gen_expression(exprs, otherwise)?;
+        let else_end = self.builder.insert_block().unwrap();
+
+        self.builder.build_br(merge_block);
+        self.builder.position_at_end(merge_block);
+
+        // There is no value to merge if the branches call functions that return nothing.
+        if then.kind == Type::Void {
+            return Ok(then);
+        }
+
+        let value = self.builder.build_phi(then.value.type_of(), &[(then.value, then_end), (otherwise.value, else_end)], "if");
+
+        Ok(FluidValueRef::new(then.kind, value))
+    }
+
+    /// Generate the statements of a block expression, and its value.
+    pub(crate) fn gen_block_expression(&mut self, exprs: &ExprArena, block: &[Statement], value: ExprId) -> CodeGenResult<FluidValueRef> {
+        for statement in block {
+            if self.is_terminated() {
+                break;
+            }
+
+            self.gen_statement(exprs, statement.clone())?;
+        }
+
+        // A statement of the block returned, so the value is generated in a block that is never reached.
+        if self.is_terminated() {
+            let unreachable_block = self.context.append_basic_block(self.current_function(), "block.unreachable");
+
+            self.builder.position_at_end(unreachable_block);
+        }
+
+        self.gen_expression(exprs, value)
     }
 
     /// Generate a variable reference.
-    pub(crate) unsafe fn gen_var_ref(&mut self, var_name: &str) -> FluidValueRef {
-        let var = self.symbol_table.get_variable(var_name).unwrap();
+    /// The type checker only accepts variables that are in scope, but the code generator can still miss the variables
+    /// of a REPL input that failed to compile.
+    /// Generate an assignment to a variable, whose value is the assigned value.
+    pub(crate) fn gen_var_assign(&mut self, exprs: &ExprArena, var_name: &str, value: ExprId, span: Range<usize>) -> CodeGenResult<FluidValueRef> {
+        let alloca = match self.symbol_table.get_variable(var_name) {
+            Some(var) => var.alloca,
+            None => {
+                let error = self.make_error(format!("cannot find value `{}` in this scope", var_name), codes::E0010, span, "not found in this scope");
+
+                return Err(error);
+            }
+        };
+
+        let value = self.gen_expression(exprs, value)?;
+        self.builder.build_store(value.value, alloca);
 
-        assert!(var.initialized);
+        Ok(value)
+    }
+
+    pub(crate) fn gen_var_ref(&mut self, var_name: &str, span: Range<usize>) -> CodeGenResult<FluidValueRef> {
+        let (kind, alloca) = match self.symbol_table.get_variable(var_name) {
+            Some(var) => {
+                assert!(var.initialized);
+
+                (var.kind, var.alloca)
+            }
+            None => {
+                let error = self.make_error(format!("cannot find value `{}` in this scope", var_name), codes::E0010, span, "not found in this scope");
+
+                return Err(error);
+            }
+        };
 
-        FluidValueRef::new(var.kind, LLVMBuildLoad(self.builder, var.alloca, cstring!("{}", var_name).as_ptr()))
+        Ok(FluidValueRef::new(kind, self.builder.build_load(alloca, var_name)))
     }
 
     /// Generate an literal.
-    pub(crate) unsafe fn gen_literal(&mut self, literal: &Literal) -> FluidValueRef {
+    pub(crate) fn gen_literal(&mut self, literal: &Literal, span: Range<usize>) -> CodeGenResult<FluidValueRef> {
         match literal {
-            Literal::Number(ref number) => self.gen_number_literal(*number),
-            Literal::Bool(ref bool) => self.gen_bool_literal(*bool),
-            _ => unimplemented!(),
+            Literal::Number(ref number) => Ok(self.gen_number_literal(*number)),
+            Literal::Bool(ref bool) => Ok(self.gen_bool_literal(*bool)),
+            Literal::Null => Ok(self.gen_null_literal()),
+            Literal::Float(ref float) => Ok(self.gen_float_literal(*float)),
+            // The strings are terminated by a null character, so it can not be in one.
+            Literal::String(string) if string.contains('\0') => Err(self.unsupported("null characters in string literals are not supported", span)),
+            Literal::String(string) => Ok(self.gen_string_literal(string)),
+            Literal::Char(char) if char.is_ascii() => Ok(self.gen_char_literal(*char as u8)),
+            Literal::Char(_) => Err(self.unsupported("non-ASCII character literals are not supported yet", span)),
         }
     }
 
     /// Generate a function call.
-    pub(crate) unsafe fn gen_function_call(&mut self, name: &str, args: &Vec<Expression>) -> FluidValueRef {
+    pub(crate) fn gen_function_call(&mut self, exprs: &ExprArena, name: &str, args: &[ExprId], span: Range<usize>) -> CodeGenResult<FluidValueRef> {
+        if let Some(format) = self.format_string(exprs, name, args) {
+            // The format string becomes a C string too, like the string literals.
+            if format.contains('\0') {
+                return Err(self.unsupported("null characters in format strings are not supported", exprs[args[0]].span.clone()));
+            }
+
+            return self.gen_format_call(exprs, format, &args[1..]);
+        }
+
         let mut cargs = vec![];
 
-        for arg in args {
-            let arg = self.gen_expression(arg);
+        for &arg in args {
+            let arg = self.gen_expression(exprs, arg)?;
 
             cargs.push(arg);
         }
 
-        let func_name = mangle_function_name(name.into(), cargs.iter().map(|fref| fref.kind).collect::<Vec<_>>());
-        let func = self.symbol_table.get_function(&func_name);
-        let func = match func {
+        let func_name = self.function_key(name, cargs.iter().map(|fref| fref.kind).collect());
+        let func = self.symbol_table.get_function(&func_name).map(|func| (func.value, func.return_type));
+
+        // The functions declared by the program shadow the builtins.
+        if func.is_none() {
+            if let Some(builtin) = Builtin::from_name(name) {
+                return Ok(self.gen_builtin_call(builtin, &cargs, span));
+            }
+        }
+
+        let (func_value, return_type) = match func {
             Some(func) => func,
-            None => self.symbol_table.current_scope_parent().get_function(&func_name).unwrap(),
+            None => {
+                let span = span.start..span.start + name.len();
+                let error = self.make_error(format!("cannot find function `{}` in this scope", name), codes::E0011, span, "not found in this scope");
+
+                return Err(error);
+            }
         };
 
-        let value = LLVMBuildCall(
-            self.builder,
-            func.value,
-            cargs.iter().map(|arg| arg.value).collect::<Vec<_>>().as_mut_ptr(),
-            cargs.len() as u32,
-            cstring!("").as_ptr(),
-        );
+        let value = self.builder.build_call(func_value, &cargs.iter().map(|arg| arg.value).collect::<Vec<_>>(), "");
 
-        FluidValueRef::new(func.return_type, value)
+        Ok(FluidValueRef::new(return_type, value))
+    }
+
+    /// Get the format string of a call to `format`, unless a function of the program shadows the builtin.
+    /// The format string is a string literal, which is read at compile time instead of being generated.
+    fn format_string<'a>(&self, exprs: &'a ExprArena, name: &str, args: &[ExprId]) -> Option<&'a str> {
+        if Builtin::from_name(name) != Some(Builtin::Format) || self.symbol_table.has_function_named(name) {
+            return None;
+        }
+
+        match exprs[*args.first()?].kind {
+            ExpressionKind::Literal(Literal::String(ref format)) => Some(format),
+            _ => None,
+        }
     }
 
     /// Generate an number literal.
     #[inline]
-    pub(crate) unsafe fn gen_number_literal(&mut self, number: u64) -> FluidValueRef {
-        FluidValueRef::new(Type::Number, LLVMConstInt(LLVMInt64TypeInContext(self.context), number, 0))
+    pub(crate) fn gen_number_literal(&mut self, number: u64) -> FluidValueRef {
+        FluidValueRef::new(Type::Number, self.context.i64_type().const_int(number, false))
+    }
+
+    /// Generate a float literal, rounded to the single precision of the floats.
+    #[inline]
+    pub(crate) fn gen_float_literal(&mut self, float: f64) -> FluidValueRef {
+        FluidValueRef::new(Type::Float, self.context.float_type().const_real(float))
+    }
+
+    /// Generate a null literal.
+    /// The type checker only allows `null` where a nullable type is expected, and all of the nullable types
+    /// are pointers to bytes, so the literal is a null byte pointer.
+    pub(crate) fn gen_null_literal(&mut self) -> FluidValueRef {
+        FluidValueRef::new(Type::String, self.gen_type(Type::String).const_null())
+    }
+
+    /// Generate a string literal, a pointer to a global constant of its UTF-8 bytes and a null character.
+    #[inline]
+    pub(crate) fn gen_string_literal(&mut self, string: &str) -> FluidValueRef {
+        FluidValueRef::new(Type::String, self.builder.build_global_string_ptr(string, "string"))
+    }
+
+    /// Generate a character literal, a byte like the characters of the strings.
+    #[inline]
+    pub(crate) fn gen_char_literal(&mut self, byte: u8) -> FluidValueRef {
+        FluidValueRef::new(Type::Char, self.context.i8_type().const_int(u64::from(byte), false))
     }
 
     /// Generate an boolean literal.
     #[inline]
-    pub(crate) unsafe fn gen_bool_literal(&mut self, bool: bool) -> FluidValueRef {
+    pub(crate) fn gen_bool_literal(&mut self, bool: bool) -> FluidValueRef {
         let value = if bool { 1 } else { 0 };
 
-        FluidValueRef::new(Type::Bool, LLVMConstInt(LLVMInt1TypeInContext(self.context), value, 0))
+        FluidValueRef::new(Type::Bool, self.context.i1_type().const_int(value, false))
     }
 }
@@ -1,12 +1,1333 @@
 //! Language builtin items.
+//!
+//! The builtins are implemented on top of the C standard library. They are generated in the module the first
+//! time they are used, so they work both in the JIT and in the executables linked with the C runtime.
 
-use crate::CodeGen;
+use std::ops::Range;
+
+use fluid_parser::{parse_format_string, Builtin, ExprArena, ExprId, FormatPiece, Type};
+use llvm::{LLVMIntPredicate, LLVMLinkage};
+
+use crate::{
+    llvm_safe::{self, FnValue, Value},
+    utils::{CodeGenResult, FluidValueRef},
+    CodeGen,
+};
 
 // TODO: Panic handler
 // TODO: Eh personality
-// TODO: String, println, etc...
+
+/// The initial capacity of the buffer of a line read by `input`.
+const INPUT_CAPACITY: u64 = 64;
+
+/// The exit code of a program whose assertion failed.
+const ASSERTION_EXIT_CODE: u64 = 101;
+
+/// The exit code of a program that indexed a string out of its bounds, the same as a failed assertion.
+const INDEX_OUT_OF_BOUNDS_EXIT_CODE: u64 = ASSERTION_EXIT_CODE;
+
+/// The exit code of a program that divided a number by zero or overflowed a division, the same as a failed assertion.
+const DIVISION_EXIT_CODE: u64 = ASSERTION_EXIT_CODE;
+
+/// The exit code of a program that ran out of memory, the same as a failed assertion.
+const OUT_OF_MEMORY_EXIT_CODE: u64 = ASSERTION_EXIT_CODE;
+
+/// The file descriptor of the standard error, which the messages of the runtime errors are written to.
+const STDERR_FILENO: u64 = 2;
+
+/// The initial capacity of the buffer of the contents read by `read_file`.
+const READ_FILE_CAPACITY: u64 = 4096;
+
+/// The `TIME_UTC` base of `timespec_get`, the time since the Unix epoch.
+const TIME_UTC: u64 = 1;
+
+/// The global variable of the state of the random number generator.
+const RANDOM_STATE: &str = "__fluid_random_state";
+
+/// The global variable that is true once the random number generator is seeded.
+const RANDOM_SEEDED: &str = "__fluid_random_seeded";
 
 impl CodeGen {
-    #[inline]
-    pub(crate) unsafe fn init_stdlib(&mut self) {}
+    /// Declare the functions of the C standard library that the builtins are implemented with.
+    pub(crate) fn init_stdlib(&mut self) {
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let i32_type = self.context.i32_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        self.declare_function("printf", i32_type.function_type(&[i8_pointer], true));
+        self.declare_function("dprintf", i32_type.function_type(&[i32_type, i8_pointer], true));
+        self.declare_function("snprintf", i32_type.function_type(&[i8_pointer, size_type, i8_pointer], true));
+        self.declare_function("getchar", i32_type.function_type(&[], false));
+        self.declare_function("malloc", i8_pointer.function_type(&[size_type], false));
+        self.declare_function("free", self.context.void_type().function_type(&[i8_pointer], false));
+        self.declare_function("realloc", i8_pointer.function_type(&[i8_pointer, size_type], false));
+        self.declare_function("exit", self.context.void_type().function_type(&[i32_type], false));
+        self.declare_function("strlen", size_type.function_type(&[i8_pointer], false));
+        self.declare_function("strcmp", i32_type.function_type(&[i8_pointer, i8_pointer], false));
+        self.declare_function("memcpy", i8_pointer.function_type(&[i8_pointer, i8_pointer, size_type], false));
+        self.declare_function("getenv", i8_pointer.function_type(&[i8_pointer], false));
+        self.declare_function("setenv", i32_type.function_type(&[i8_pointer, i8_pointer, i32_type], false));
+        self.declare_function("unsetenv", i32_type.function_type(&[i8_pointer], false));
+        // The generator is only seeded with the time, so the `time_t` of the 32-bit targets does not need to be exact.
+        self.declare_function("time", self.context.i64_type().function_type(&[i8_pointer], false));
+
+        // A `struct timespec` is an array of two `long`, the seconds and the nanoseconds, see `build_timespec`.
+        let timespec_pointer = size_type.pointer_type();
+
+        self.declare_function("timespec_get", i32_type.function_type(&[timespec_pointer, i32_type], false));
+        self.declare_function("nanosleep", i32_type.function_type(&[timespec_pointer, timespec_pointer], false));
+
+        // A `FILE *` is opaque, so it is an `i8 *`.
+        self.declare_function("fopen", i8_pointer.function_type(&[i8_pointer, i8_pointer], false));
+        self.declare_function("fread", size_type.function_type(&[i8_pointer, size_type, size_type, i8_pointer], false));
+        self.declare_function("fwrite", size_type.function_type(&[i8_pointer, size_type, size_type, i8_pointer], false));
+        self.declare_function("fclose", i32_type.function_type(&[i8_pointer], false));
+    }
+
+    /// Get the function with the name, declaring it with the type if the module does not have it yet.
+    fn declare_function(&mut self, name: &str, function_type: llvm_safe::Type) -> FnValue {
+        match self.module.get_function(name) {
+            Some(function) => function,
+            None => self.module.add_function(name, function_type),
+        }
+    }
+
+    /// Get a function of the C standard library declared by `init_stdlib`.
+    fn libc_function(&self, name: &str) -> FnValue {
+        self.module.get_function(name).expect("the C functions are declared by `init_stdlib`")
+    }
+
+    /// Generate a call to a builtin, the type checker checked the arguments. The span is the one of the call.
+    pub(crate) fn gen_builtin_call(&mut self, builtin: Builtin, args: &[FluidValueRef], span: Range<usize>) -> FluidValueRef {
+        match builtin {
+            Builtin::Print | Builtin::Println => {
+                let arg = &args[0];
+                let function = self.get_print(builtin, arg.kind);
+
+                FluidValueRef::new(Type::Void, self.builder.build_call(function, &[arg.value], ""))
+            }
+            Builtin::Input => {
+                let function = self.get_input();
+
+                FluidValueRef::new(Type::String, self.builder.build_call(function, &[], "input"))
+            }
+            Builtin::Assert => {
+                let function = self.get_assert();
+
+                // The message points at the assertion, since there is no backtrace.
+                let message = format!("assertion failed: `{}`, {}", self.file.snippet(span.clone()), self.location(span));
+                let message = self.builder.build_global_string_ptr(&message, "message");
+
+                FluidValueRef::new(Type::Void, self.builder.build_call(function, &[args[0].value, message], ""))
+            }
+            Builtin::ReadFile => {
+                let function = self.get_read_file();
+
+                FluidValueRef::new(Type::String, self.builder.build_call(function, &[args[0].value], "contents"))
+            }
+            Builtin::WriteFile | Builtin::AppendFile => {
+                let function = self.get_write_file(builtin);
+
+                FluidValueRef::new(Type::Bool, self.builder.build_call(function, &[args[0].value, args[1].value], "written"))
+            }
+            Builtin::Sqrt | Builtin::Pow | Builtin::Floor | Builtin::Ceil | Builtin::Sin | Builtin::Cos => {
+                let function = self.get_float_intrinsic(builtin.name(), args.len());
+                let values = args.iter().map(|arg| arg.value).collect::<Vec<_>>();
+
+                FluidValueRef::new(Type::Float, self.builder.build_call(function, &values, builtin.name()))
+            }
+            Builtin::Abs | Builtin::Min | Builtin::Max => self.gen_numeric_builtin(builtin, args),
+            Builtin::Format => unreachable!("`format` is generated by `gen_format_call`"),
+            Builtin::Random => {
+                let function = self.get_random();
+
+                FluidValueRef::new(Type::Float, self.builder.build_call(function, &[], "random"))
+            }
+            Builtin::RandomRange => {
+                let function = self.get_random_range();
+
+                FluidValueRef::new(Type::Number, self.builder.build_call(function, &[args[0].value, args[1].value], "random"))
+            }
+            Builtin::Seed => {
+                let (state, seeded) = self.random_state();
+
+                self.builder.build_store(args[0].value, state);
+
+                FluidValueRef::new(Type::Void, self.builder.build_store(self.context.i1_type().const_int(1, false), seeded))
+            }
+            Builtin::NowMillis => {
+                let function = self.get_now_millis();
+
+                FluidValueRef::new(Type::Number, self.builder.build_call(function, &[], "now"))
+            }
+            Builtin::Sleep => {
+                let function = self.get_sleep();
+
+                FluidValueRef::new(Type::Void, self.builder.build_call(function, &[args[0].value], ""))
+            }
+            Builtin::Env => {
+                let function = self.get_env();
+
+                FluidValueRef::new(Type::String, self.builder.build_call(function, &[args[0].value], "env"))
+            }
+            Builtin::SetEnv => {
+                let function = self.get_set_env();
+
+                FluidValueRef::new(Type::Void, self.builder.build_call(function, &[args[0].value, args[1].value], ""))
+            }
+        }
+    }
+
+    /// Get the LLVM intrinsic with the name of a function of the C math library on floats, like `llvm.sqrt.f32`.
+    /// The backend turns them into instructions, or into calls to the math library.
+    fn get_float_intrinsic(&mut self, name: &str, arity: usize) -> FnValue {
+        let float_type = self.context.float_type();
+        let function_type = float_type.function_type(&vec![float_type; arity], false);
+
+        self.declare_function(&format!("llvm.{}.f32", name), function_type)
+    }
+
+    /// Generate `abs`, `min` or `max`, whose arguments are either numbers or floats.
+    /// The floats use the intrinsics, and the numbers are compared and selected, since LLVM 11 has no intrinsics for
+    /// them.
+    fn gen_numeric_builtin(&mut self, builtin: Builtin, args: &[FluidValueRef]) -> FluidValueRef {
+        let kind = args[0].kind;
+        let name = builtin.name();
+
+        let value = match kind {
+            Type::Float => {
+                let intrinsic = match builtin {
+                    Builtin::Abs => "fabs",
+                    Builtin::Min => "minnum",
+                    _ => "maxnum",
+                };
+
+                let function = self.get_float_intrinsic(intrinsic, args.len());
+                let values = args.iter().map(|arg| arg.value).collect::<Vec<_>>();
+
+                self.builder.build_call(function, &values, name)
+            }
+            Type::Number => {
+                let value = args[0].value;
+
+                match builtin {
+                    Builtin::Abs => {
+                        let zero = self.gen_type(Type::Number).const_int(0, false);
+                        let is_negative = self.builder.build_icmp(LLVMIntPredicate::LLVMIntSLT, value, zero, "isnegative");
+                        let negated = self.builder.build_neg(value, "negated");
+
+                        self.builder.build_select(is_negative, negated, value, name)
+                    }
+                    _ => {
+                        let other = args[1].value;
+                        let predicate = if builtin == Builtin::Min { LLVMIntPredicate::LLVMIntSLT } else { LLVMIntPredicate::LLVMIntSGT };
+                        let is_first = self.builder.build_icmp(predicate, value, other, "isfirst");
+
+                        self.builder.build_select(is_first, value, other, name)
+                    }
+                }
+            }
+            _ => unreachable!("the type checker only accepts numbers and floats"),
+        };
+
+        FluidValueRef::new(kind, value)
+    }
+
+    /// Get the function of `assert`, which prints the message and exits the program if the condition is false.
+    fn get_assert(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_assert";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let function_type = self.context.void_type().function_type(&[self.context.i1_type(), i8_pointer], false);
+
+        let function = self.module.add_function(NAME, function_type);
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let fail = self.context.append_basic_block(function, "fail");
+        let pass = self.context.append_basic_block(function, "pass");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+        builder.build_cond_br(function.param(0), pass, fail);
+
+        builder.position_at_end(fail);
+        self.build_runtime_error(&builder, "%s\n", &[function.param(1)], ASSERTION_EXIT_CODE);
+
+        builder.position_at_end(pass);
+        builder.build_ret_void();
+
+        function
+    }
+
+    /// Get the function that prints a value of the type, for `print` or `println`.
+    /// There is one function for every type, it is generated the first time it is used. The strings and the
+    /// characters are printed as they are, the other values are printed in the form of `__fluid_display`.
+    fn get_print(&mut self, builtin: Builtin, kind: Type) -> FnValue {
+        let name = format!("__fluid_{}_{}", builtin.name(), kind);
+
+        if let Some(function) = self.module.get_function(&name) {
+            return function;
+        }
+
+        let param_type = self.gen_type(kind);
+        let function_type = self.context.void_type().function_type(&[param_type], false);
+
+        let function = self.module.add_function(&name, function_type);
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let newline = if builtin == Builtin::Println { "\n" } else { "" };
+
+        if let Type::String | Type::Char = kind {
+            let value = self.printf_argument(&builder, kind, function.param(0));
+            let format = builder.build_global_string_ptr(&format!("{}{}", printf_specifier(kind), newline), "format");
+
+            builder.build_call(self.libc_function("printf"), &[format, value], "");
+        } else {
+            let value = self.erase_value(&builder, kind, function.param(0));
+            let tag = self.context.i32_type().const_int(type_tag(kind), false);
+            let text = builder.build_call(self.get_display(), &[value, tag], "text");
+
+            let format = builder.build_global_string_ptr(&format!("%s{}", newline), "format");
+
+            builder.build_call(self.libc_function("printf"), &[format, text], "");
+            builder.build_call(self.libc_function("free"), &[text], "");
+        }
+
+        builder.build_ret_void();
+
+        function
+    }
+
+    /// Get the function that formats a value in its canonical form, into a new buffer. The value is given as an
+    /// `i64` made by `erase_value`, with the tag of its type. The bools are `true` or `false`, the strings and the
+    /// characters are quoted, and a `null` string is `null`.
+    /// This is the form of the values printed by `print` and `println`, except for the strings and the characters,
+    /// and of the values of the expressions of the REPL.
+    pub(crate) fn get_display(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_display";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let function_type = i8_pointer.function_type(&[self.context.i64_type(), self.context.i32_type()], false);
+
+        let function = self.module.add_function(NAME, function_type);
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let unknown = self.context.append_basic_block(function, "unknown");
+
+        let builder = self.context.create_builder();
+        let mut cases = vec![];
+
+        for &kind in &[Type::Number, Type::Float, Type::String, Type::Bool, Type::Char] {
+            let block = self.context.append_basic_block(function, &kind.to_string());
+
+            builder.position_at_end(block);
+
+            let value = self.restore_value(&builder, kind, function.param(0));
+            let argument = self.printf_argument(&builder, kind, value);
+
+            let format = match kind {
+                Type::String => {
+                    let quoted = builder.build_global_string_ptr("\"%s\"", "quoted");
+                    let plain = builder.build_global_string_ptr("%s", "plain");
+
+                    // `null` is not quoted, `printf_argument` turns it into the text `null`.
+                    builder.build_select(builder.build_is_null(value, "isnull"), plain, quoted, "format")
+                }
+                Type::Char => builder.build_global_string_ptr("'%c'", "format"),
+                _ => builder.build_global_string_ptr(printf_specifier(kind), "format"),
+            };
+
+            builder.build_ret(self.build_snprintf(&builder, format, &[argument]));
+
+            cases.push((self.context.i32_type().const_int(type_tag(kind), false), block));
+        }
+
+        builder.position_at_end(entry);
+        builder.build_switch(function.param(1), unknown, &cases);
+
+        // A `void` value is never displayed.
+        builder.position_at_end(unknown);
+        builder.build_unreachable();
+
+        function
+    }
+
+    /// Turn a value of the type into an `i64`, the argument of `__fluid_display`. The value is in the low bits.
+    pub(crate) fn erase_value(&self, builder: &llvm_safe::Builder, kind: Type, value: Value) -> Value {
+        let i64_type = self.context.i64_type();
+
+        match kind {
+            Type::Number => value,
+            Type::Float => builder.build_zext(builder.build_bit_cast(value, self.context.i32_type(), "bits"), i64_type, "value"),
+            Type::String => builder.build_ptr_to_int(value, i64_type, "value"),
+            Type::Bool | Type::Char => builder.build_zext(value, i64_type, "value"),
+            Type::Void => unreachable!("a `void` value can not be displayed"),
+        }
+    }
+
+    /// Turn an `i64` made by `erase_value` back into a value of the type.
+    fn restore_value(&self, builder: &llvm_safe::Builder, kind: Type, value: Value) -> Value {
+        match kind {
+            Type::Number => value,
+            Type::Float => builder.build_bit_cast(builder.build_trunc(value, self.context.i32_type(), "bits"), self.context.float_type(), "float"),
+            Type::String => builder.build_int_to_ptr(value, self.context.i8_type().pointer_type(), "string"),
+            Type::Bool => builder.build_trunc(value, self.context.i1_type(), "bool"),
+            Type::Char => builder.build_trunc(value, self.context.i8_type(), "char"),
+            Type::Void => unreachable!("a `void` value can not be displayed"),
+        }
+    }
+
+    /// Get the value to pass to `printf` for a value of the type, for the specifier of `printf_specifier`.
+    /// `null` strings are printed as `null`, and the bools as `true` or `false`.
+    fn printf_argument(&self, builder: &llvm_safe::Builder, kind: Type, value: Value) -> Value {
+        match kind {
+            Type::Number => value,
+            // The variadic arguments of C are promoted to `double`.
+            Type::Float => builder.build_fp_ext(value, self.context.double_type(), "double"),
+            Type::String => {
+                let null = builder.build_global_string_ptr("null", "null");
+                let is_null = builder.build_is_null(value, "isnull");
+
+                builder.build_select(is_null, null, value, "string")
+            }
+            Type::Bool => {
+                let true_string = builder.build_global_string_ptr("true", "true");
+                let false_string = builder.build_global_string_ptr("false", "false");
+
+                builder.build_select(value, true_string, false_string, "bool")
+            }
+            // The variadic arguments of C are promoted to `int`.
+            Type::Char => builder.build_sext(value, self.context.i32_type(), "int"),
+            Type::Void => unreachable!("the type checker rejects printing a `void` value"),
+        }
+    }
+
+    /// Format the values into a new buffer with `snprintf`, and return the buffer.
+    fn build_snprintf(&self, builder: &llvm_safe::Builder, format: Value, values: &[Value]) -> Value {
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        // The first call only computes the length of the formatted string, the second one writes it.
+        let snprintf = self.libc_function("snprintf");
+
+        let mut args = vec![i8_pointer.const_null(), size_type.const_int(0, false), format];
+        args.extend(values);
+
+        let length = builder.build_call(snprintf, &args, "length");
+        let length = builder.build_sext(length, size_type, "length");
+        let size = builder.build_add(length, size_type.const_int(1, false), "size");
+        let buffer = self.build_allocation(builder, "malloc", &[size], "buffer");
+
+        args[0] = buffer;
+        args[1] = size;
+
+        builder.build_call(snprintf, &args, "");
+
+        buffer
+    }
+
+    /// Generate a call to `format`. The format string is turned into the one of `snprintf` at compile time, so only
+    /// the values are generated. The format string does not contain null characters.
+    pub(crate) fn gen_format_call(&mut self, exprs: &ExprArena, format: &str, values: &[ExprId]) -> CodeGenResult<FluidValueRef> {
+        let pieces = parse_format_string(format).expect("the type checker checked the format string");
+
+        let mut args = vec![];
+
+        for &value in values {
+            args.push(self.gen_expression(exprs, value)?);
+        }
+
+        let kinds = args.iter().map(|arg| arg.kind).collect::<Vec<_>>();
+        let mut specifiers = kinds.iter().map(|&kind| printf_specifier(kind));
+
+        let mut c_format = String::new();
+
+        for piece in &pieces {
+            match piece {
+                FormatPiece::Text(text) => c_format.push_str(&text.replace('%', "%%")),
+                FormatPiece::Placeholder => c_format.push_str(specifiers.next().unwrap()),
+            }
+        }
+
+        let function = self.get_format(&kinds);
+        let format = self.builder.build_global_string_ptr(&c_format, "format");
+        let args = std::iter::once(format).chain(args.iter().map(|arg| arg.value)).collect::<Vec<_>>();
+
+        Ok(FluidValueRef::new(Type::String, self.builder.build_call(function, &args, "formatted")))
+    }
+
+    /// Get the function of `format` for values of the types, which formats them into a new buffer with `snprintf`.
+    /// There is one function for every list of types, it is generated the first time it is used.
+    fn get_format(&mut self, kinds: &[Type]) -> FnValue {
+        let name = kinds.iter().fold(String::from("__fluid_format"), |name, kind| format!("{}_{}", name, kind));
+
+        if let Some(function) = self.module.get_function(&name) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+
+        let mut param_types = vec![i8_pointer];
+        param_types.extend(kinds.iter().map(|&kind| self.gen_type(kind)));
+
+        let function = self.module.add_function(&name, i8_pointer.function_type(&param_types, false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let format = function.param(0);
+        let values = kinds
+            .iter()
+            .enumerate()
+            .map(|(index, &kind)| self.printf_argument(&builder, kind, function.param(index + 1)))
+            .collect::<Vec<_>>();
+
+        builder.build_ret(self.build_snprintf(&builder, format, &values));
+
+        function
+    }
+
+    /// Get the function of `input`, which reads a line from the standard input into a new buffer.
+    /// The line does not contain the new line, and it is empty at the end of the input.
+    fn get_input(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_input";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        let function = self.module.add_function(NAME, i8_type.pointer_type().function_type(&[], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let read = self.context.append_basic_block(function, "read");
+        let append = self.context.append_basic_block(function, "append");
+        let grow = self.context.append_basic_block(function, "grow");
+        let store = self.context.append_basic_block(function, "store");
+        let end = self.context.append_basic_block(function, "end");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let capacity = builder.build_alloca(size_type, "capacity");
+        let length = builder.build_alloca(size_type, "length");
+        let buffer = builder.build_alloca(i8_type.pointer_type(), "buffer");
+
+        let initial_capacity = size_type.const_int(INPUT_CAPACITY, false);
+        let allocated = self.build_allocation(&builder, "malloc", &[initial_capacity], "allocated");
+
+        builder.build_store(initial_capacity, capacity);
+        builder.build_store(size_type.const_int(0, false), length);
+        builder.build_store(allocated, buffer);
+        builder.build_br(read);
+
+        // Read the characters until the end of the line or of the input.
+        builder.position_at_end(read);
+
+        let character = builder.build_call(self.libc_function("getchar"), &[], "character");
+        let is_eof = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, character, i32_type.const_int(-1i64 as u64, true), "iseof");
+        let is_newline = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, character, i32_type.const_int(u64::from(b'\n'), false), "isnewline");
+        let is_end = builder.build_or(is_eof, is_newline, "isend");
+
+        builder.build_cond_br(is_end, end, append);
+
+        // The buffer grows when there is no room left for the character and the null terminator.
+        builder.position_at_end(append);
+
+        let current_length = builder.build_load(length, "length");
+        let current_capacity = builder.build_load(capacity, "capacity");
+        let next_length = builder.build_add(current_length, size_type.const_int(1, false), "nextlength");
+        let is_full = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, next_length, current_capacity, "isfull");
+
+        builder.build_cond_br(is_full, grow, store);
+
+        builder.position_at_end(grow);
+
+        let new_capacity = builder.build_mul(current_capacity, size_type.const_int(2, false), "newcapacity");
+        let old_buffer = builder.build_load(buffer, "oldbuffer");
+        let reallocated = self.build_allocation(&builder, "realloc", &[old_buffer, new_capacity], "reallocated");
+
+        builder.build_store(new_capacity, capacity);
+        builder.build_store(reallocated, buffer);
+        builder.build_br(store);
+
+        builder.position_at_end(store);
+
+        let current_buffer = builder.build_load(buffer, "buffer");
+        let pointer = builder.build_gep(current_buffer, &[current_length], "pointer");
+
+        builder.build_store(builder.build_trunc(character, i8_type, "byte"), pointer);
+        builder.build_store(next_length, length);
+        builder.build_br(read);
+
+        // Terminate the line with a null character.
+        builder.position_at_end(end);
+
+        let final_length = builder.build_load(length, "length");
+        let final_buffer = builder.build_load(buffer, "buffer");
+        let pointer = builder.build_gep(final_buffer, &[final_length], "pointer");
+
+        builder.build_store(i8_type.const_int(0, false), pointer);
+        builder.build_ret(final_buffer);
+
+        function
+    }
+
+    /// Get the function of `read_file`, which reads the whole file into a new buffer.
+    /// It returns `null` if the path is `null` or the file can not be opened.
+    fn get_read_file(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_read_file";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_type = self.context.i8_type();
+        let i8_pointer = i8_type.pointer_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        let function = self.module.add_function(NAME, i8_pointer.function_type(&[i8_pointer], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let open = self.context.append_basic_block(function, "open");
+        let fail = self.context.append_basic_block(function, "fail");
+        let start = self.context.append_basic_block(function, "start");
+        let read = self.context.append_basic_block(function, "read");
+        let check = self.context.append_basic_block(function, "check");
+        let grow = self.context.append_basic_block(function, "grow");
+        let end = self.context.append_basic_block(function, "end");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let capacity = builder.build_alloca(size_type, "capacity");
+        let length = builder.build_alloca(size_type, "length");
+        let buffer = builder.build_alloca(i8_pointer, "buffer");
+
+        let path = function.param(0);
+        builder.build_cond_br(builder.build_is_null(path, "isnull"), fail, open);
+
+        builder.position_at_end(open);
+
+        let mode = builder.build_global_string_ptr("rb", "mode");
+        let file = builder.build_call(self.libc_function("fopen"), &[path, mode], "file");
+
+        builder.build_cond_br(builder.build_is_null(file, "isnull"), fail, start);
+
+        builder.position_at_end(fail);
+        builder.build_ret(i8_pointer.const_null());
+
+        builder.position_at_end(start);
+
+        let initial_capacity = size_type.const_int(READ_FILE_CAPACITY, false);
+        let allocated = self.build_allocation(&builder, "malloc", &[initial_capacity], "allocated");
+
+        builder.build_store(initial_capacity, capacity);
+        builder.build_store(size_type.const_int(0, false), length);
+        builder.build_store(allocated, buffer);
+        builder.build_br(read);
+
+        // Read into the rest of the buffer, keeping a byte for the null terminator, until nothing is read.
+        // The size of the file is not known up front, so pipes and special files can be read too.
+        builder.position_at_end(read);
+
+        let one = size_type.const_int(1, false);
+        let current_length = builder.build_load(length, "length");
+        let current_capacity = builder.build_load(capacity, "capacity");
+        let current_buffer = builder.build_load(buffer, "buffer");
+        let pointer = builder.build_gep(current_buffer, &[current_length], "pointer");
+        let available = builder.build_sub(builder.build_sub(current_capacity, current_length, "free"), one, "available");
+        let count = builder.build_call(self.libc_function("fread"), &[pointer, one, available, file], "count");
+        let next_length = builder.build_add(current_length, count, "nextlength");
+
+        builder.build_store(next_length, length);
+
+        let is_done = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, count, size_type.const_int(0, false), "isdone");
+        builder.build_cond_br(is_done, end, check);
+
+        // The buffer grows when only the byte of the null terminator is left.
+        builder.position_at_end(check);
+
+        let is_full = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, builder.build_add(next_length, one, "used"), current_capacity, "isfull");
+        builder.build_cond_br(is_full, grow, read);
+
+        builder.position_at_end(grow);
+
+        let new_capacity = builder.build_mul(current_capacity, size_type.const_int(2, false), "newcapacity");
+        let reallocated = self.build_allocation(&builder, "realloc", &[current_buffer, new_capacity], "reallocated");
+
+        builder.build_store(new_capacity, capacity);
+        builder.build_store(reallocated, buffer);
+        builder.build_br(read);
+
+        // Close the file and terminate the contents with a null character.
+        builder.position_at_end(end);
+        builder.build_call(self.libc_function("fclose"), &[file], "");
+
+        let final_length = builder.build_load(length, "length");
+        let final_buffer = builder.build_load(buffer, "buffer");
+        let pointer = builder.build_gep(final_buffer, &[final_length], "pointer");
+
+        builder.build_store(i8_type.const_int(0, false), pointer);
+        builder.build_ret(final_buffer);
+
+        function
+    }
+
+    /// Get the function of `write_file` or `append_file`, which only differ by the mode the file is opened with.
+    /// It returns true if the file was opened, all of the contents were written and the file was closed.
+    /// The `null` contents are written as an empty string, but a `null` path is a failure.
+    fn get_write_file(&mut self, builtin: Builtin) -> FnValue {
+        let name = format!("__fluid_{}", builtin.name());
+
+        if let Some(function) = self.module.get_function(&name) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let bool_type = self.context.i1_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        let function = self.module.add_function(&name, bool_type.function_type(&[i8_pointer, i8_pointer], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let open = self.context.append_basic_block(function, "open");
+        let fail = self.context.append_basic_block(function, "fail");
+        let write = self.context.append_basic_block(function, "write");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let path = function.param(0);
+        builder.build_cond_br(builder.build_is_null(path, "isnull"), fail, open);
+
+        builder.position_at_end(open);
+
+        let mode = if builtin == Builtin::AppendFile { "ab" } else { "wb" };
+        let mode = builder.build_global_string_ptr(mode, "mode");
+        let file = builder.build_call(self.libc_function("fopen"), &[path, mode], "file");
+
+        builder.build_cond_br(builder.build_is_null(file, "isnull"), fail, write);
+
+        builder.position_at_end(fail);
+        builder.build_ret(bool_type.const_int(0, false));
+
+        builder.position_at_end(write);
+
+        let contents = function.param(1);
+        let empty = builder.build_global_string_ptr("", "empty");
+        let contents = builder.build_select(builder.build_is_null(contents, "isnull"), empty, contents, "contents");
+
+        let length = builder.build_call(self.libc_function("strlen"), &[contents], "length");
+        let count = builder.build_call(self.libc_function("fwrite"), &[contents, size_type.const_int(1, false), length, file], "count");
+        let closed = builder.build_call(self.libc_function("fclose"), &[file], "closed");
+
+        // `fclose` flushes the buffered contents, so it can fail too.
+        let is_written = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, count, length, "iswritten");
+        let is_closed = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, closed, self.context.i32_type().const_int(0, false), "isclosed");
+
+        builder.build_ret(builder.build_and(is_written, is_closed, "success"));
+
+        function
+    }
+
+    /// Get the global variables of the state of the random number generator and of whether it is seeded.
+    /// They are shared by all of the modules of a program, so that `seed` seeds the generator of every module.
+    fn random_state(&self) -> (Value, Value) {
+        let global = |name: &str, kind: llvm_safe::Type| match self.module.get_global(name) {
+            Some(global) => global,
+            None => {
+                let global = self.module.add_global(kind, name);
+
+                global.set_initializer(kind.const_int(0, false));
+                global.set_linkage(LLVMLinkage::LLVMLinkOnceODRLinkage);
+
+                global
+            }
+        };
+
+        (global(RANDOM_STATE, self.context.i64_type()), global(RANDOM_SEEDED, self.context.i1_type()))
+    }
+
+    /// Get the function that returns the next random 64 bits, with the SplitMix64 generator.
+    /// The generator is seeded with the time the first time it is used, unless `seed` was called before.
+    fn get_random_next(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_random_next";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+        let (state, seeded) = self.random_state();
+
+        let function = self.module.add_function(NAME, i64_type.function_type(&[], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let seed = self.context.append_basic_block(function, "seed");
+        let next = self.context.append_basic_block(function, "next");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+        builder.build_cond_br(builder.build_load(seeded, "seeded"), next, seed);
+
+        builder.position_at_end(seed);
+
+        let time = builder.build_call(self.libc_function("time"), &[self.context.i8_type().pointer_type().const_null()], "time");
+
+        builder.build_store(time, state);
+        builder.build_store(self.context.i1_type().const_int(1, false), seeded);
+        builder.build_br(next);
+
+        builder.position_at_end(next);
+
+        let current = builder.build_load(state, "state");
+        let advanced = builder.build_add(current, i64_type.const_int(0x9E37_79B9_7F4A_7C15, false), "advanced");
+
+        builder.build_store(advanced, state);
+
+        // Mix the bits of the state, so that close states give unrelated numbers.
+        let mix = |value: Value, shift: u64, multiplier: Option<u64>| {
+            let shifted = builder.build_lshr(value, i64_type.const_int(shift, false), "shifted");
+            let mixed = builder.build_xor(value, shifted, "mixed");
+
+            match multiplier {
+                Some(multiplier) => builder.build_mul(mixed, i64_type.const_int(multiplier, false), "mixed"),
+                None => mixed,
+            }
+        };
+
+        let mixed = mix(advanced, 30, Some(0xBF58_476D_1CE4_E5B9));
+        let mixed = mix(mixed, 27, Some(0x94D0_49BB_1331_11EB));
+
+        builder.build_ret(mix(mixed, 31, None));
+
+        function
+    }
+
+    /// Get the function of `random`, which turns the top 24 bits of the next random bits into a float.
+    /// A `float` has 24 bits of precision, so every float it returns is equally likely.
+    fn get_random(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_random";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+        let float_type = self.context.float_type();
+        let next = self.get_random_next();
+
+        let function = self.module.add_function(NAME, float_type.function_type(&[], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let bits = builder.build_call(next, &[], "bits");
+        let top = builder.build_lshr(bits, i64_type.const_int(40, false), "top");
+        let float = builder.build_ui_to_fp(top, float_type, "float");
+
+        builder.build_ret(builder.build_fmul(float, float_type.const_real(1.0 / (1u64 << 24) as f64), "random"));
+
+        function
+    }
+
+    /// Get the function of `random_range`, the remainder of the division of the next random bits by the size of the
+    /// range added to its start. An empty range, where `hi` is not greater than `lo`, always gives `lo`.
+    fn get_random_range(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_random_range";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+        let next = self.get_random_next();
+
+        let function = self.module.add_function(NAME, i64_type.function_type(&[i64_type, i64_type], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let lo = function.param(0);
+        let hi = function.param(1);
+
+        // The size of the range is unsigned, so that it does not overflow for the widest ranges.
+        let size = builder.build_sub(hi, lo, "size");
+        let is_empty = builder.build_icmp(LLVMIntPredicate::LLVMIntSLE, hi, lo, "isempty");
+        let size = builder.build_select(is_empty, i64_type.const_int(1, false), size, "size");
+
+        let bits = builder.build_call(next, &[], "bits");
+        let offset = builder.build_urem(bits, size, "offset");
+
+        builder.build_ret(builder.build_add(lo, offset, "random"));
+
+        function
+    }
+
+    /// Build an alloca of a `struct timespec`, and get the pointers to its seconds and to its nanoseconds. The pointer
+    /// to the seconds is also the pointer to the struct.
+    /// The fields are a `time_t` and a `long`, which have the size of a pointer for the `timespec_get` and `nanosleep`
+    /// symbols of the C libraries of the 32-bit and the 64-bit Unix targets, so they are built from the data layout.
+    fn build_timespec(&self, builder: &llvm_safe::Builder, name: &str) -> (Value, Value) {
+        let long_type = self.context.int_ptr_type(&self.module);
+        let i32_type = self.context.i32_type();
+
+        let timespec = builder.build_alloca(long_type.array_type(2), name);
+        let fields = builder.build_bit_cast(timespec, long_type.pointer_type(), "fields");
+
+        let seconds = builder.build_gep(fields, &[i32_type.const_int(0, false)], "seconds");
+        let nanoseconds = builder.build_gep(fields, &[i32_type.const_int(1, false)], "nanoseconds");
+
+        (seconds, nanoseconds)
+    }
+
+    /// Get the function of `now_millis`, which gets the time since the Unix epoch with `timespec_get`.
+    fn get_now_millis(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_now_millis";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+
+        let function = self.module.add_function(NAME, i64_type.function_type(&[], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let (seconds, nanoseconds) = self.build_timespec(&builder, "timespec");
+        let base = self.context.i32_type().const_int(TIME_UTC, false);
+
+        builder.build_call(self.libc_function("timespec_get"), &[seconds, base], "");
+
+        // The fields are `long`, which are sign extended to numbers on the 32-bit targets.
+        let seconds = builder.build_sext(builder.build_load(seconds, "seconds"), i64_type, "seconds");
+        let nanoseconds = builder.build_sext(builder.build_load(nanoseconds, "nanoseconds"), i64_type, "nanoseconds");
+
+        let seconds = builder.build_mul(seconds, i64_type.const_int(1000, false), "millis");
+        let nanoseconds = builder.build_sdiv(nanoseconds, i64_type.const_int(1_000_000, false), "submillis");
+
+        builder.build_ret(builder.build_add(seconds, nanoseconds, "now"));
+
+        function
+    }
+
+    /// Get the function of `sleep`, which sleeps with `nanosleep` for the milliseconds, or not at all if they are
+    /// negative. The time is at most `i32::MAX` seconds, so that it is valid for the 32-bit `time_t` too, and then
+    /// `nanosleep` only fails when it is interrupted by a signal, so it sleeps again for the remaining time.
+    fn get_sleep(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_sleep";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+
+        let function = self.module.add_function(NAME, self.context.void_type().function_type(&[i64_type], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let sleep = self.context.append_basic_block(function, "sleep");
+        let interrupted = self.context.append_basic_block(function, "interrupted");
+        let end = self.context.append_basic_block(function, "end");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let (seconds, nanoseconds) = self.build_timespec(&builder, "request");
+        let (remaining_seconds, remaining_nanoseconds) = self.build_timespec(&builder, "remaining");
+
+        let long_type = self.context.int_ptr_type(&self.module);
+        let zero = i64_type.const_int(0, false);
+        let thousand = i64_type.const_int(1000, false);
+        let max = i64_type.const_int(i32::MAX as u64 * 1000, false);
+
+        let millis = function.param(0);
+        let is_negative = builder.build_icmp(LLVMIntPredicate::LLVMIntSLT, millis, zero, "isnegative");
+        let millis = builder.build_select(is_negative, zero, millis, "millis");
+        let is_too_long = builder.build_icmp(LLVMIntPredicate::LLVMIntSGT, millis, max, "istoolong");
+        let millis = builder.build_select(is_too_long, max, millis, "millis");
+
+        let whole_seconds = builder.build_sdiv(millis, thousand, "seconds");
+        builder.build_store(builder.build_trunc(whole_seconds, long_type, "seconds"), seconds);
+
+        let remainder = builder.build_srem(millis, thousand, "remainder");
+        let remainder = builder.build_mul(remainder, i64_type.const_int(1_000_000, false), "nanoseconds");
+        builder.build_store(builder.build_trunc(remainder, long_type, "nanoseconds"), nanoseconds);
+        builder.build_br(sleep);
+
+        builder.position_at_end(sleep);
+
+        let result = builder.build_call(self.libc_function("nanosleep"), &[seconds, remaining_seconds], "result");
+        let is_done = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, result, self.context.i32_type().const_int(0, false), "isdone");
+
+        builder.build_cond_br(is_done, end, interrupted);
+
+        // The time that remains when a signal interrupts the sleep is the time of the next one.
+        builder.position_at_end(interrupted);
+
+        builder.build_store(builder.build_load(remaining_seconds, "seconds"), seconds);
+        builder.build_store(builder.build_load(remaining_nanoseconds, "nanoseconds"), nanoseconds);
+        builder.build_br(sleep);
+
+        builder.position_at_end(end);
+        builder.build_ret_void();
+
+        function
+    }
+
+    /// Get the function of `env`, which copies the value of the environment variable into a new buffer, since the
+    /// value of `getenv` can change with the environment. It returns `null` if the name is `null` or the variable is
+    /// not set.
+    fn get_env(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_env";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let size_type = self.context.int_ptr_type(&self.module);
+
+        let function = self.module.add_function(NAME, i8_pointer.function_type(&[i8_pointer], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let get = self.context.append_basic_block(function, "get");
+        let unset = self.context.append_basic_block(function, "unset");
+        let copy = self.context.append_basic_block(function, "copy");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let name = function.param(0);
+        builder.build_cond_br(builder.build_is_null(name, "isnull"), unset, get);
+
+        builder.position_at_end(get);
+
+        let value = builder.build_call(self.libc_function("getenv"), &[name], "value");
+        builder.build_cond_br(builder.build_is_null(value, "isnull"), unset, copy);
+
+        builder.position_at_end(unset);
+        builder.build_ret(i8_pointer.const_null());
+
+        builder.position_at_end(copy);
+
+        let length = builder.build_call(self.libc_function("strlen"), &[value], "length");
+        let size = builder.build_add(length, size_type.const_int(1, false), "size");
+        let buffer = self.build_allocation(&builder, "malloc", &[size], "buffer");
+
+        builder.build_call(self.libc_function("memcpy"), &[buffer, value, size], "");
+        builder.build_ret(buffer);
+
+        function
+    }
+
+    /// Get the function of `set_env`, which sets the environment variable with `setenv`, or removes it with
+    /// `unsetenv` if the value is `null`. Nothing is done if the name is `null`.
+    fn get_set_env(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_set_env";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+
+        let function = self.module.add_function(NAME, self.context.void_type().function_type(&[i8_pointer, i8_pointer], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let check = self.context.append_basic_block(function, "check");
+        let set = self.context.append_basic_block(function, "set");
+        let unset = self.context.append_basic_block(function, "unset");
+        let end = self.context.append_basic_block(function, "end");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let name = function.param(0);
+        let value = function.param(1);
+
+        builder.build_cond_br(builder.build_is_null(name, "isnull"), end, check);
+
+        builder.position_at_end(check);
+        builder.build_cond_br(builder.build_is_null(value, "isnull"), unset, set);
+
+        // The variable is replaced if it is already set.
+        builder.position_at_end(set);
+
+        let overwrite = self.context.i32_type().const_int(1, false);
+
+        builder.build_call(self.libc_function("setenv"), &[name, value, overwrite], "");
+        builder.build_br(end);
+
+        builder.position_at_end(unset);
+        builder.build_call(self.libc_function("unsetenv"), &[name], "");
+        builder.build_br(end);
+
+        builder.position_at_end(end);
+        builder.build_ret_void();
+
+        function
+    }
+
+    /// Get the function of the index expressions of the strings, which returns the byte at the index. The program
+    /// exits with a message if the index is negative or not less than the length of the string, a `null` string has
+    /// no bytes.
+    pub(crate) fn get_string_index(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_string_index";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_type = self.context.i8_type();
+        let i64_type = self.context.i64_type();
+
+        let function = self.module.add_function(NAME, i8_type.function_type(&[i8_type.pointer_type(), i64_type], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let length = self.context.append_basic_block(function, "length");
+        let check = self.context.append_basic_block(function, "check");
+        let fail = self.context.append_basic_block(function, "fail");
+        let load = self.context.append_basic_block(function, "load");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let string = function.param(0);
+        let index = function.param(1);
+
+        builder.build_cond_br(builder.build_is_null(string, "isnull"), check, length);
+
+        builder.position_at_end(length);
+
+        let string_length = builder.build_call(self.libc_function("strlen"), &[string], "length");
+        builder.build_br(check);
+
+        // A negative index is a large unsigned one, so a single comparison checks both of the bounds.
+        builder.position_at_end(check);
+
+        let string_length = builder.build_phi(i64_type, &[(i64_type.const_int(0, false), entry), (string_length, length)], "length");
+        let in_bounds = builder.build_icmp(LLVMIntPredicate::LLVMIntULT, index, string_length, "inbounds");
+
+        builder.build_cond_br(in_bounds, load, fail);
+
+        builder.position_at_end(fail);
+
+        let format = "index out of bounds: the length is %lld but the index is %lld\n";
+        self.build_runtime_error(&builder, format, &[string_length, index], INDEX_OUT_OF_BOUNDS_EXIT_CODE);
+
+        builder.position_at_end(load);
+
+        let pointer = builder.build_gep(string, &[index], "pointer");
+        builder.build_ret(builder.build_load(pointer, "byte"));
+
+        function
+    }
+
+    /// Get the function of the `==` of the strings, which compares their characters. A `null` string is only equal
+    /// to `null`.
+    pub(crate) fn get_string_equals(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_string_equals";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i8_pointer = self.context.i8_type().pointer_type();
+        let i1_type = self.context.i1_type();
+
+        let function = self.module.add_function(NAME, i1_type.function_type(&[i8_pointer, i8_pointer], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let check = self.context.append_basic_block(function, "check");
+        let compare = self.context.append_basic_block(function, "compare");
+        let same = self.context.append_basic_block(function, "same");
+        let different = self.context.append_basic_block(function, "different");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let (lhs, rhs) = (function.param(0), function.param(1));
+
+        // The same pointers are the same strings, which is also the case of two `null` strings.
+        builder.build_cond_br(builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, lhs, rhs, "issame"), same, check);
+
+        builder.position_at_end(check);
+
+        let is_null = builder.build_or(builder.build_is_null(lhs, "lhsnull"), builder.build_is_null(rhs, "rhsnull"), "isnull");
+        builder.build_cond_br(is_null, different, compare);
+
+        builder.position_at_end(compare);
+
+        let order = builder.build_call(self.libc_function("strcmp"), &[lhs, rhs], "order");
+        builder.build_ret(builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, order, self.context.i32_type().const_int(0, false), "equals"));
+
+        builder.position_at_end(same);
+        builder.build_ret(i1_type.const_int(1, false));
+
+        builder.position_at_end(different);
+        builder.build_ret(i1_type.const_int(0, false));
+
+        function
+    }
+
+    /// Get the function of the divisions of the numbers, which is given the location of the division. The program
+    /// exits with a message if the divisor is zero, or if the smallest number is divided by `-1`, whose quotient does
+    /// not fit in a number. Both are undefined behavior for `sdiv`.
+    pub(crate) fn get_divide(&mut self) -> FnValue {
+        const NAME: &str = "__fluid_divide";
+
+        if let Some(function) = self.module.get_function(NAME) {
+            return function;
+        }
+
+        let i64_type = self.context.i64_type();
+
+        let function = self
+            .module
+            .add_function(NAME, i64_type.function_type(&[i64_type, i64_type, self.context.i8_type().pointer_type()], false));
+        function.set_linkage(LLVMLinkage::LLVMInternalLinkage);
+
+        let entry = self.context.append_basic_block(function, "entry");
+        let check = self.context.append_basic_block(function, "check");
+        let zero = self.context.append_basic_block(function, "zero");
+        let overflow = self.context.append_basic_block(function, "overflow");
+        let divide = self.context.append_basic_block(function, "divide");
+
+        let builder = self.context.create_builder();
+
+        builder.position_at_end(entry);
+
+        let (dividend, divisor, location) = (function.param(0), function.param(1), function.param(2));
+        let is_zero = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, divisor, i64_type.const_int(0, false), "iszero");
+
+        builder.build_cond_br(is_zero, zero, check);
+
+        builder.position_at_end(check);
+
+        let is_minus_one = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, divisor, i64_type.const_int(-1i64 as u64, true), "isminusone");
+        let is_min = builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, dividend, i64_type.const_int(i64::MIN as u64, true), "ismin");
+
+        builder.build_cond_br(builder.build_and(is_minus_one, is_min, "overflows"), overflow, divide);
+
+        for &(block, message) in &[(zero, "attempt to divide by zero, %s\n"), (overflow, "attempt to divide with overflow, %s\n")] {
+            builder.position_at_end(block);
+            self.build_runtime_error(&builder, message, &[location], DIVISION_EXIT_CODE);
+        }
+
+        builder.position_at_end(divide);
+        builder.build_ret(builder.build_sdiv(dividend, divisor, "quotient"));
+
+        function
+    }
+
+    /// End the block of the builder with a runtime error: the message is formatted with the values like `printf` does,
+    /// and written to the standard error so that it is not mixed with the output of the program, which exits with the
+    /// code.
+    fn build_runtime_error(&self, builder: &llvm_safe::Builder, format: &str, values: &[Value], exit_code: u64) {
+        let i32_type = self.context.i32_type();
+        let format = builder.build_global_string_ptr(format, "error");
+
+        let mut args = vec![i32_type.const_int(STDERR_FILENO, false), format];
+        args.extend_from_slice(values);
+
+        builder.build_call(self.libc_function("dprintf"), &args, "");
+        builder.build_call(self.libc_function("exit"), &[i32_type.const_int(exit_code, false)], "");
+        builder.build_unreachable();
+    }
+
+    /// Allocate a buffer with `malloc` or `realloc`, whose last argument is the size of the buffer. The program exits
+    /// with a message if there is no memory left, so the buffer is never `null`, and the builder is positioned after
+    /// the check.
+    fn build_allocation(&self, builder: &llvm_safe::Builder, allocator: &str, args: &[Value], name: &str) -> Value {
+        let function = builder.insert_block().expect("the builder is positioned in a function").parent();
+        let buffer = builder.build_call(self.libc_function(allocator), args, name);
+
+        let out_of_memory = self.context.append_basic_block(function, "outofmemory");
+        let has_memory = self.context.append_basic_block(function, "hasmemory");
+
+        builder.build_cond_br(builder.build_is_null(buffer, "isnull"), out_of_memory, has_memory);
+
+        builder.position_at_end(out_of_memory);
+
+        let size = *args.last().expect("the size of the buffer is the last argument");
+        self.build_runtime_error(builder, "memory allocation of %zu bytes failed\n", &[size], OUT_OF_MEMORY_EXIT_CODE);
+
+        builder.position_at_end(has_memory);
+
+        buffer
+    }
+
+    /// Get the location of the start of the span, which the messages of the runtime errors point at since there is
+    /// no backtrace.
+    pub(crate) fn location(&self, span: Range<usize>) -> String {
+        let (line, column) = self.file.line_col(span.start);
+
+        format!("{}:{}:{}", self.file.name(), line, column)
+    }
+}
+
+/// Get the `printf` conversion specifier of a value of the type, whose argument is made by `printf_argument`.
+fn printf_specifier(kind: Type) -> &'static str {
+    match kind {
+        Type::Number => "%lld",
+        Type::Float => "%g",
+        Type::String | Type::Bool => "%s",
+        Type::Char => "%c",
+        Type::Void => unreachable!("the type checker rejects printing a `void` value"),
+    }
+}
+
+/// Get the tag of a type, which tells `__fluid_display` the type of the value it is given.
+pub(crate) fn type_tag(kind: Type) -> u64 {
+    match kind {
+        Type::Void => 0,
+        Type::Number => 1,
+        Type::Float => 2,
+        Type::String => 3,
+        Type::Bool => 4,
+        Type::Char => 5,
+    }
 }
@@ -3,9 +3,13 @@
 #![deny(missing_docs, trivial_numeric_casts, unused_extern_crates, unstable_features)]
 
 mod codegen;
+mod crash;
 mod declaration;
 mod expression;
 mod language;
+mod llvm_safe;
+mod module;
+mod repl;
 mod statement;
 mod symbol;
 mod types;
@@ -0,0 +1,946 @@
+//! Safe wrappers around the raw LLVM C API.
+//!
+//! The owning types (`Context`, `Module`, `Builder`, `PassManager`, `ExecutionEngine`, `LazyJit` and `TargetMachine`)
+//! dispose their LLVM object when they are dropped. The handle types (`Type`, `Value`, `FnValue` and `BasicBlock`)
+//! are `Copy` references to objects owned by the context. They are only valid while the context is alive, which
+//! `CodeGen` guarantees by dropping its context last and never handing the handles out of the crate.
+//!
+//! The safe methods can not break these guarantees: the constants check the kind of their type, which LLVM assumes,
+//! and `FnValue::delete`, which leaves the copies of the handle dangling, is `unsafe`.
+
+use std::{
+    cell::Cell,
+    ffi::CStr,
+    mem,
+    os::raw::{c_char, c_void},
+    path::Path,
+    ptr, slice,
+};
+
+use llvm::{analysis::*, bit_writer::*, core::*, error::*, execution_engine::*, orc::*, prelude::*, support::*, target::*, target_machine::*, transforms::scalar::*, transforms::util::*, *};
+
+use crate::cstring;
+
+/// Take the ownership of a message allocated by LLVM.
+unsafe fn take_message(message: *mut c_char) -> String {
+    let string = CStr::from_ptr(message).to_string_lossy().into_owned();
+
+    LLVMDisposeMessage(message);
+
+    string
+}
+
+/// Take the error message of an error returned by LLVM, and consume the error.
+unsafe fn take_error(error: LLVMErrorRef) -> String {
+    let message = LLVMGetErrorMessage(error);
+    let string = CStr::from_ptr(message).to_string_lossy().into_owned();
+
+    LLVMDisposeErrorMessage(message);
+
+    string
+}
+
+/// Initialize all of the targets and link in the MCJIT.
+pub(crate) fn initialize_targets() {
+    unsafe {
+        llvm::target::LLVM_InitializeAllTargetInfos();
+        llvm::target::LLVM_InitializeAllTargets();
+        llvm::target::LLVM_InitializeAllTargetMCs();
+        llvm::target::LLVM_InitializeAllAsmParsers();
+        llvm::target::LLVM_InitializeAllAsmPrinters();
+
+        LLVMLinkInMCJIT();
+    }
+}
+
+/// Get the target triple of the machine the compiler runs on.
+pub(crate) fn host_triple() -> String {
+    unsafe { take_message(LLVMGetDefaultTargetTriple()) }
+}
+
+/// Get the name of the CPU of the machine the compiler runs on.
+pub(crate) fn host_cpu_name() -> String {
+    unsafe { take_message(LLVMGetHostCPUName()) }
+}
+
+/// Get the features of the CPU of the machine the compiler runs on, each one prefixed by `+` if it is enabled and
+/// by `-` otherwise, separated by commas.
+pub(crate) fn host_cpu_features() -> String {
+    unsafe { take_message(LLVMGetHostCPUFeatures()) }
+}
+
+/// Load the shared library at the path into the process, so that its symbols are available to the JIT.
+pub(crate) fn load_library_permanently(path: &Path) -> Result<(), String> {
+    // LLVM does not say why the library could not be loaded.
+    match unsafe { LLVMLoadLibraryPermanently(cstring!("{}", path.display()).as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(format!("could not load the library `{}`", path.display())),
+    }
+}
+
+/// Normalize a target triple, so that the equivalent triples compare equal.
+pub(crate) fn normalize_triple(triple: &str) -> String {
+    unsafe { take_message(LLVMNormalizeTargetTriple(cstring!("{}", triple).as_ptr())) }
+}
+
+/// Get the names and the descriptions of the registered targets.
+pub(crate) fn targets() -> Vec<(String, String)> {
+    let mut targets = vec![];
+
+    unsafe {
+        let mut target = LLVMGetFirstTarget();
+
+        while !target.is_null() {
+            let name = CStr::from_ptr(LLVMGetTargetName(target)).to_string_lossy().into_owned();
+            let description = CStr::from_ptr(LLVMGetTargetDescription(target)).to_string_lossy().into_owned();
+
+            targets.push((name, description));
+            target = LLVMGetNextTarget(target);
+        }
+    }
+
+    targets
+}
+
+/// An LLVM context, which owns all of the types and values created in it.
+pub(crate) struct Context(LLVMContextRef);
+
+impl Context {
+    /// Create a new context.
+    pub(crate) fn new() -> Self {
+        Self(unsafe { LLVMContextCreate() })
+    }
+
+    /// Create a new module in the context.
+    pub(crate) fn create_module(&self, name: &str) -> Module {
+        let raw = unsafe { LLVMModuleCreateWithNameInContext(cstring!("{}", name).as_ptr(), self.0) };
+
+        Module {
+            raw,
+            owned_by_engine: Cell::new(false),
+        }
+    }
+
+    /// Create a new builder in the context.
+    pub(crate) fn create_builder(&self) -> Builder {
+        Builder(unsafe { LLVMCreateBuilderInContext(self.0) })
+    }
+
+    /// Append a basic block at the end of the function.
+    pub(crate) fn append_basic_block(&self, function: FnValue, name: &str) -> BasicBlock {
+        BasicBlock(unsafe { LLVMAppendBasicBlockInContext(self.0, function.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// The `void` type.
+    pub(crate) fn void_type(&self) -> Type {
+        Type(unsafe { LLVMVoidTypeInContext(self.0) })
+    }
+
+    /// The `i1` type.
+    pub(crate) fn i1_type(&self) -> Type {
+        Type(unsafe { LLVMInt1TypeInContext(self.0) })
+    }
+
+    /// The `i8` type.
+    pub(crate) fn i8_type(&self) -> Type {
+        Type(unsafe { LLVMInt8TypeInContext(self.0) })
+    }
+
+    /// The `i32` type.
+    pub(crate) fn i32_type(&self) -> Type {
+        Type(unsafe { LLVMInt32TypeInContext(self.0) })
+    }
+
+    /// The `i64` type.
+    pub(crate) fn i64_type(&self) -> Type {
+        Type(unsafe { LLVMInt64TypeInContext(self.0) })
+    }
+
+    /// The integer type with the size of a pointer in the data layout of the module, like `size_t` in C.
+    pub(crate) fn int_ptr_type(&self, module: &Module) -> Type {
+        Type(unsafe { LLVMIntPtrTypeInContext(self.0, LLVMGetModuleDataLayout(module.raw)) })
+    }
+
+    /// The `float` type.
+    pub(crate) fn float_type(&self) -> Type {
+        Type(unsafe { LLVMFloatTypeInContext(self.0) })
+    }
+
+    /// The `double` type.
+    pub(crate) fn double_type(&self) -> Type {
+        Type(unsafe { LLVMDoubleTypeInContext(self.0) })
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { LLVMContextDispose(self.0) }
+    }
+}
+
+/// An LLVM module. It is owned by the execution engine once one is created for it.
+pub(crate) struct Module {
+    /// The raw module.
+    raw: LLVMModuleRef,
+    /// Is the module disposed by an execution engine.
+    owned_by_engine: Cell<bool>,
+}
+
+impl Module {
+    /// Set the target triple of the module.
+    pub(crate) fn set_target(&self, triple: &str) {
+        unsafe { LLVMSetTarget(self.raw, cstring!("{}", triple).as_ptr()) }
+    }
+
+    /// Set the data layout of the module to the one of the target machine.
+    pub(crate) fn set_data_layout(&self, target_machine: &TargetMachine) {
+        unsafe {
+            let data_layout = LLVMCreateTargetDataLayout(target_machine.raw);
+
+            LLVMSetModuleDataLayout(self.raw, data_layout);
+            LLVMDisposeTargetData(data_layout);
+        }
+    }
+
+    /// Add a function to the module.
+    pub(crate) fn add_function(&self, name: &str, function_type: Type) -> FnValue {
+        FnValue(unsafe { LLVMAddFunction(self.raw, cstring!("{}", name).as_ptr(), function_type.0) })
+    }
+
+    /// Get the function with the name, if the module has one.
+    pub(crate) fn get_function(&self, name: &str) -> Option<FnValue> {
+        let function = unsafe { LLVMGetNamedFunction(self.raw, cstring!("{}", name).as_ptr()) };
+
+        if function.is_null() {
+            None
+        } else {
+            Some(FnValue(function))
+        }
+    }
+
+    /// Add a global variable of the type to the module.
+    pub(crate) fn add_global(&self, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMAddGlobal(self.raw, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Get the global variable with the name, if the module has one.
+    pub(crate) fn get_global(&self, name: &str) -> Option<Value> {
+        let global = unsafe { LLVMGetNamedGlobal(self.raw, cstring!("{}", name).as_ptr()) };
+
+        if global.is_null() {
+            None
+        } else {
+            Some(Value(global))
+        }
+    }
+
+    /// Write the bitcode of the module to the path. Returns false if the file could not be written.
+    pub(crate) fn write_bitcode_to_file(&self, path: &Path) -> bool {
+        unsafe { LLVMWriteBitcodeToFile(self.raw, cstring!("{}", path.to_string_lossy()).as_ptr()) == 0 }
+    }
+
+    /// Print the textual IR of the module.
+    pub(crate) fn print_to_string(&self) -> String {
+        unsafe { take_message(LLVMPrintModuleToString(self.raw)) }
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        if !self.owned_by_engine.get() {
+            unsafe { LLVMDisposeModule(self.raw) }
+        }
+    }
+}
+
+/// An LLVM type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Type(LLVMTypeRef);
+
+impl Type {
+    /// A pointer to this type.
+    pub(crate) fn pointer_type(self) -> Type {
+        Type(unsafe { LLVMPointerType(self.0, 0) })
+    }
+
+    /// An array type of this type with the number of elements.
+    pub(crate) fn array_type(self, count: u32) -> Type {
+        Type(unsafe { LLVMArrayType(self.0, count) })
+    }
+
+    /// A function type returning this type.
+    pub(crate) fn function_type(self, params: &[Type], is_var_arg: bool) -> Type {
+        let mut params = params.iter().map(|param| param.0).collect::<Vec<_>>();
+
+        Type(unsafe { LLVMFunctionType(self.0, params.as_mut_ptr(), params.len() as u32, is_var_arg as LLVMBool) })
+    }
+
+    /// The kind of the type.
+    pub(crate) fn kind(self) -> LLVMTypeKind {
+        unsafe { LLVMGetTypeKind(self.0) }
+    }
+
+    /// An integer constant of this type, which must be an integer type.
+    pub(crate) fn const_int(self, value: u64, sign_extend: bool) -> Value {
+        assert_eq!(self.kind(), LLVMTypeKind::LLVMIntegerTypeKind, "integer constant of a non-integer type");
+
+        Value(unsafe { LLVMConstInt(self.0, value, sign_extend as LLVMBool) })
+    }
+
+    /// The null constant of this type, which must be a pointer type.
+    pub(crate) fn const_null(self) -> Value {
+        assert_eq!(self.kind(), LLVMTypeKind::LLVMPointerTypeKind, "null constant of a non-pointer type");
+
+        Value(unsafe { LLVMConstPointerNull(self.0) })
+    }
+
+    /// A floating point constant of this type, which must be `float` or `double`.
+    pub(crate) fn const_real(self, value: f64) -> Value {
+        assert!(
+            matches!(self.kind(), LLVMTypeKind::LLVMFloatTypeKind | LLVMTypeKind::LLVMDoubleTypeKind),
+            "floating point constant of a non-floating point type"
+        );
+
+        Value(unsafe { LLVMConstReal(self.0, value) })
+    }
+}
+
+/// An LLVM value, like an instruction or a constant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Value(LLVMValueRef);
+
+impl Value {
+    /// The type of the value.
+    pub(crate) fn type_of(self) -> Type {
+        Type(unsafe { LLVMTypeOf(self.0) })
+    }
+
+    /// Set the name of the value in the IR.
+    pub(crate) fn set_name(self, name: &str) {
+        unsafe { LLVMSetValueName2(self.0, name.as_ptr() as *const c_char, name.len()) }
+    }
+
+    /// The name of the value in the IR, the symbol of a function.
+    pub(crate) fn name(self) -> String {
+        let mut length = 0;
+
+        unsafe {
+            let name = LLVMGetValueName2(self.0, &mut length);
+
+            String::from_utf8_lossy(slice::from_raw_parts(name as *const u8, length)).into_owned()
+        }
+    }
+
+    /// Print the textual IR of the value.
+    pub(crate) fn print_to_string(self) -> String {
+        unsafe { take_message(LLVMPrintValueToString(self.0)) }
+    }
+
+    /// Set the initial value of the global variable.
+    pub(crate) fn set_initializer(self, value: Value) {
+        unsafe { LLVMSetInitializer(self.0, value.0) }
+    }
+
+    /// Set the linkage of the global variable.
+    pub(crate) fn set_linkage(self, linkage: LLVMLinkage) {
+        unsafe { LLVMSetLinkage(self.0, linkage) }
+    }
+
+    /// Print the value to the standard error.
+    pub(crate) fn dump(self) {
+        unsafe { LLVMDumpValue(self.0) }
+    }
+}
+
+/// An LLVM function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FnValue(LLVMValueRef);
+
+impl FnValue {
+    /// The function as a value.
+    pub(crate) fn as_value(self) -> Value {
+        Value(self.0)
+    }
+
+    /// Get the parameter at the index.
+    pub(crate) fn param(self, index: usize) -> Value {
+        Value(unsafe { LLVMGetParam(self.0, index as u32) })
+    }
+
+    /// Set the linkage of the function.
+    pub(crate) fn set_linkage(self, linkage: LLVMLinkage) {
+        unsafe { LLVMSetLinkage(self.0, linkage) }
+    }
+
+    /// The entry block of the function.
+    pub(crate) fn entry_block(self) -> BasicBlock {
+        BasicBlock(unsafe { LLVMGetEntryBasicBlock(self.0) })
+    }
+
+    /// Check that the function is valid IR, returns the message of the verifier if it is not.
+    pub(crate) fn verify(self) -> Result<(), String> {
+        unsafe {
+            if LLVMVerifyFunction(self.0, LLVMVerifierFailureAction::LLVMReturnStatusAction) == 0 {
+                return Ok(());
+            }
+
+            // Only the verification of a module describes the problems.
+            let mut message = ptr::null_mut();
+            LLVMVerifyModule(LLVMGetGlobalParent(self.0), LLVMVerifierFailureAction::LLVMReturnStatusAction, &mut message);
+
+            Err(take_message(message))
+        }
+    }
+
+    /// Delete the function from its module.
+    ///
+    /// # Safety
+    ///
+    /// The function, its blocks and its values are freed, so none of the copies of their handles may be used
+    /// afterwards, and the builder must not be positioned in one of its blocks.
+    pub(crate) unsafe fn delete(self) {
+        LLVMDeleteFunction(self.0)
+    }
+}
+
+/// An LLVM basic block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BasicBlock(LLVMBasicBlockRef);
+
+impl BasicBlock {
+    /// The function that contains the block.
+    pub(crate) fn parent(self) -> FnValue {
+        FnValue(unsafe { LLVMGetBasicBlockParent(self.0) })
+    }
+
+    /// The terminator of the block, if it has one.
+    pub(crate) fn terminator(self) -> Option<Value> {
+        let terminator = unsafe { LLVMGetBasicBlockTerminator(self.0) };
+
+        if terminator.is_null() {
+            None
+        } else {
+            Some(Value(terminator))
+        }
+    }
+
+    /// The first instruction of the block, if it has one.
+    pub(crate) fn first_instruction(self) -> Option<Value> {
+        let instruction = unsafe { LLVMGetFirstInstruction(self.0) };
+
+        if instruction.is_null() {
+            None
+        } else {
+            Some(Value(instruction))
+        }
+    }
+}
+
+/// An LLVM instruction builder.
+pub(crate) struct Builder(LLVMBuilderRef);
+
+impl Builder {
+    /// Position the builder at the end of the block.
+    pub(crate) fn position_at_end(&self, block: BasicBlock) {
+        unsafe { LLVMPositionBuilderAtEnd(self.0, block.0) }
+    }
+
+    /// Position the builder before the instruction.
+    pub(crate) fn position_before(&self, instruction: Value) {
+        unsafe { LLVMPositionBuilderBefore(self.0, instruction.0) }
+    }
+
+    /// Clear the position of the builder, it is not in any block afterwards.
+    pub(crate) fn clear_insertion_position(&self) {
+        unsafe { LLVMClearInsertionPosition(self.0) }
+    }
+
+    /// The block the builder is in, if it is in one.
+    pub(crate) fn insert_block(&self) -> Option<BasicBlock> {
+        let block = unsafe { LLVMGetInsertBlock(self.0) };
+
+        if block.is_null() {
+            None
+        } else {
+            Some(BasicBlock(block))
+        }
+    }
+
+    /// Build an `alloca` instruction.
+    pub(crate) fn build_alloca(&self, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildAlloca(self.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `store` instruction.
+    pub(crate) fn build_store(&self, value: Value, pointer: Value) -> Value {
+        Value(unsafe { LLVMBuildStore(self.0, value.0, pointer.0) })
+    }
+
+    /// Build a `load` instruction.
+    pub(crate) fn build_load(&self, pointer: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildLoad(self.0, pointer.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `ret` instruction.
+    pub(crate) fn build_ret(&self, value: Value) -> Value {
+        Value(unsafe { LLVMBuildRet(self.0, value.0) })
+    }
+
+    /// Build a `ret void` instruction.
+    pub(crate) fn build_ret_void(&self) -> Value {
+        Value(unsafe { LLVMBuildRetVoid(self.0) })
+    }
+
+    /// Build an unconditional `br` instruction.
+    pub(crate) fn build_br(&self, destination: BasicBlock) -> Value {
+        Value(unsafe { LLVMBuildBr(self.0, destination.0) })
+    }
+
+    /// Build a conditional `br` instruction.
+    pub(crate) fn build_cond_br(&self, condition: Value, then_block: BasicBlock, else_block: BasicBlock) -> Value {
+        Value(unsafe { LLVMBuildCondBr(self.0, condition.0, then_block.0, else_block.0) })
+    }
+
+    /// Build a `switch` instruction, which jumps to the block of the case equal to the value, or to the default block.
+    pub(crate) fn build_switch(&self, value: Value, default: BasicBlock, cases: &[(Value, BasicBlock)]) -> Value {
+        unsafe {
+            let switch = LLVMBuildSwitch(self.0, value.0, default.0, cases.len() as u32);
+
+            for (case, block) in cases {
+                LLVMAddCase(switch, case.0, block.0);
+            }
+
+            Value(switch)
+        }
+    }
+
+    /// Build an `unreachable` instruction.
+    pub(crate) fn build_unreachable(&self) -> Value {
+        Value(unsafe { LLVMBuildUnreachable(self.0) })
+    }
+
+    /// Build a `call` instruction.
+    pub(crate) fn build_call(&self, function: FnValue, args: &[Value], name: &str) -> Value {
+        let mut args = args.iter().map(|arg| arg.0).collect::<Vec<_>>();
+
+        Value(unsafe { LLVMBuildCall(self.0, function.0, args.as_mut_ptr(), args.len() as u32, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `bitcast` instruction.
+    pub(crate) fn build_bit_cast(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildBitCast(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build an `fpext` instruction.
+    pub(crate) fn build_fp_ext(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildFPExt(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `trunc` instruction.
+    pub(crate) fn build_trunc(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildTrunc(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `uitofp` instruction.
+    pub(crate) fn build_ui_to_fp(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildUIToFP(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `zext` instruction.
+    pub(crate) fn build_zext(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildZExt(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `ptrtoint` instruction.
+    pub(crate) fn build_ptr_to_int(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildPtrToInt(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build an `inttoptr` instruction.
+    pub(crate) fn build_int_to_ptr(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildIntToPtr(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `sext` instruction.
+    pub(crate) fn build_sext(&self, value: Value, kind: Type, name: &str) -> Value {
+        Value(unsafe { LLVMBuildSExt(self.0, value.0, kind.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `getelementptr` instruction.
+    pub(crate) fn build_gep(&self, pointer: Value, indices: &[Value], name: &str) -> Value {
+        let mut indices = indices.iter().map(|index| index.0).collect::<Vec<_>>();
+
+        Value(unsafe { LLVMBuildGEP(self.0, pointer.0, indices.as_mut_ptr(), indices.len() as u32, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `select` instruction.
+    pub(crate) fn build_select(&self, condition: Value, then_value: Value, else_value: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildSelect(self.0, condition.0, then_value.0, else_value.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a `phi` instruction, the value coming from each of the blocks.
+    pub(crate) fn build_phi(&self, kind: Type, incoming: &[(Value, BasicBlock)], name: &str) -> Value {
+        let mut values = incoming.iter().map(|(value, _)| value.0).collect::<Vec<_>>();
+        let mut blocks = incoming.iter().map(|(_, block)| block.0).collect::<Vec<_>>();
+
+        unsafe {
+            let phi = LLVMBuildPhi(self.0, kind.0, cstring!("{}", name).as_ptr());
+
+            LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), incoming.len() as u32);
+
+            Value(phi)
+        }
+    }
+
+    /// Build a comparison of the pointer with null.
+    pub(crate) fn build_is_null(&self, value: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildIsNull(self.0, value.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a global string constant, and get a pointer to its first character.
+    pub(crate) fn build_global_string_ptr(&self, string: &str, name: &str) -> Value {
+        Value(unsafe { LLVMBuildGlobalStringPtr(self.0, cstring!("{}", string).as_ptr(), cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build an integer comparison.
+    pub(crate) fn build_icmp(&self, predicate: LLVMIntPredicate, lhs: Value, rhs: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildICmp(self.0, predicate, lhs.0, rhs.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a float comparison.
+    pub(crate) fn build_fcmp(&self, predicate: LLVMRealPredicate, lhs: Value, rhs: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildFCmp(self.0, predicate, lhs.0, rhs.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build an integer negation.
+    pub(crate) fn build_neg(&self, value: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildNeg(self.0, value.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    pub(crate) fn build_fneg(&self, value: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildFNeg(self.0, value.0, cstring!("{}", name).as_ptr()) })
+    }
+
+    /// Build a bitwise not, the negation of a boolean.
+    pub(crate) fn build_not(&self, value: Value, name: &str) -> Value {
+        Value(unsafe { LLVMBuildNot(self.0, value.0, cstring!("{}", name).as_ptr()) })
+    }
+}
+
+/// Generate the methods building a binary instruction.
+macro_rules! binary_instructions {
+    ($($(#[$doc:meta])* $name:ident => $function:ident;)*) => {
+        impl Builder {
+            $(
+                $(#[$doc])*
+                pub(crate) fn $name(&self, lhs: Value, rhs: Value, name: &str) -> Value {
+                    Value(unsafe { $function(self.0, lhs.0, rhs.0, cstring!("{}", name).as_ptr()) })
+                }
+            )*
+        }
+    };
+}
+
+binary_instructions! {
+    /// Build an integer addition.
+    build_add => LLVMBuildAdd;
+    /// Build a float addition.
+    build_fadd => LLVMBuildFAdd;
+    /// Build an integer subtraction.
+    build_sub => LLVMBuildSub;
+    /// Build a float subtraction.
+    build_fsub => LLVMBuildFSub;
+    /// Build an integer multiplication.
+    build_mul => LLVMBuildMul;
+    /// Build a float multiplication.
+    build_fmul => LLVMBuildFMul;
+    /// Build a bitwise or.
+    build_or => LLVMBuildOr;
+    /// Build a bitwise and.
+    build_and => LLVMBuildAnd;
+    /// Build a bitwise exclusive or.
+    build_xor => LLVMBuildXor;
+    /// Build a logical shift to the right.
+    build_lshr => LLVMBuildLShr;
+    /// Build an unsigned integer remainder.
+    build_urem => LLVMBuildURem;
+    /// Build a signed integer division.
+    build_sdiv => LLVMBuildSDiv;
+    /// Build a signed integer remainder.
+    build_srem => LLVMBuildSRem;
+    /// Build a float division.
+    build_fdiv => LLVMBuildFDiv;
+}
+
+impl Drop for Builder {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeBuilder(self.0) }
+    }
+}
+
+/// A function pass manager, which optimizes the functions of a module.
+pub(crate) struct PassManager(LLVMPassManagerRef);
+
+impl PassManager {
+    /// Create the pass manager of the functions of the module, with the optimizations if `optimize` is true and
+    /// without any passes otherwise.
+    pub(crate) fn new(module: &Module, optimize: bool) -> Self {
+        unsafe {
+            let pass_manager = LLVMCreateFunctionPassManagerForModule(module.raw);
+
+            if optimize {
+                LLVMAddInstructionCombiningPass(pass_manager);
+                LLVMAddReassociatePass(pass_manager);
+                LLVMAddGVNPass(pass_manager);
+                LLVMAddCFGSimplificationPass(pass_manager);
+                LLVMAddBasicAliasAnalysisPass(pass_manager);
+                LLVMAddPromoteMemoryToRegisterPass(pass_manager);
+                LLVMAddInstructionCombiningPass(pass_manager);
+                LLVMAddReassociatePass(pass_manager);
+            }
+
+            LLVMInitializeFunctionPassManager(pass_manager);
+
+            Self(pass_manager)
+        }
+    }
+
+    /// Run the passes on the function. Returns true if the function was modified.
+    pub(crate) fn run(&self, function: FnValue) -> bool {
+        unsafe { LLVMRunFunctionPassManager(self.0, function.0) == 1 }
+    }
+}
+
+impl Drop for PassManager {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposePassManager(self.0) }
+    }
+}
+
+/// An execution engine, which compiles a module just in time.
+pub(crate) struct ExecutionEngine(LLVMExecutionEngineRef);
+
+impl ExecutionEngine {
+    /// Create an execution engine for the module. The engine disposes the module when it is dropped.
+    pub(crate) fn new(module: &Module) -> Result<Self, String> {
+        let mut execution_engine = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        unsafe {
+            if LLVMCreateExecutionEngineForModule(&mut execution_engine, module.raw, &mut error) == 1 {
+                return Err(take_message(error));
+            }
+        }
+
+        module.owned_by_engine.set(true);
+
+        Ok(Self(execution_engine))
+    }
+
+    /// Remove the module from the engine, the module is not disposed by the engine anymore.
+    pub(crate) fn remove_module(&self, module: &Module) -> Result<(), String> {
+        let mut removed = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        unsafe {
+            if LLVMRemoveModule(self.0, module.raw, &mut removed, &mut error) == 1 {
+                return Err(take_message(error));
+            }
+        }
+
+        module.owned_by_engine.set(false);
+
+        Ok(())
+    }
+
+    /// Compile the module and get the address of the function with the name.
+    pub(crate) fn function_address(&self, name: &str) -> Result<u64, String> {
+        match unsafe { LLVMGetFunctionAddress(self.0, cstring!("{}", name).as_ptr()) } {
+            0 => Err(format!("the function `{}` is not defined", name)),
+            address => Ok(address),
+        }
+    }
+}
+
+impl Drop for ExecutionEngine {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeExecutionEngine(self.0) }
+    }
+}
+
+/// A JIT that compiles every function of a module the first time it is called, instead of the whole module up front.
+pub(crate) struct LazyJit(LLVMOrcJITStackRef);
+
+impl LazyJit {
+    /// Create a lazy JIT for the module. The JIT takes the ownership of the target machine, and disposes the module
+    /// when it is dropped.
+    pub(crate) fn new(target_machine: TargetMachine, module: &Module) -> Result<Self, String> {
+        unsafe {
+            // Make the symbols of the process, like the functions of the C standard library, available to the resolver.
+            LLVMLoadLibraryPermanently(ptr::null());
+
+            let jit = Self(LLVMOrcCreateInstance(target_machine.into_raw()));
+            let mut handle = 0;
+
+            let error = LLVMOrcAddLazilyCompiledIR(jit.0, &mut handle, module.raw, Some(resolve_symbol), ptr::null_mut());
+
+            if !error.is_null() {
+                return Err(take_error(error));
+            }
+
+            module.owned_by_engine.set(true);
+
+            Ok(jit)
+        }
+    }
+
+    /// Get the address of the function with the name. The function is compiled when it is first called.
+    pub(crate) fn function_address(&self, name: &str) -> Result<u64, String> {
+        let mut address = 0;
+
+        unsafe {
+            let error = LLVMOrcGetSymbolAddress(self.0, &mut address, cstring!("{}", name).as_ptr());
+
+            if !error.is_null() {
+                return Err(take_error(error));
+            }
+        }
+
+        if address == 0 {
+            Err(format!("the function `{}` is not defined", name))
+        } else {
+            Ok(address)
+        }
+    }
+}
+
+impl Drop for LazyJit {
+    fn drop(&mut self) {
+        unsafe {
+            let error = LLVMOrcDisposeInstance(self.0);
+
+            if !error.is_null() {
+                LLVMConsumeError(error);
+            }
+        }
+    }
+}
+
+/// Resolve a symbol that the module of a `LazyJit` does not define in the process.
+/// The symbol names are mangled, so the global prefix of the targets that have one is removed if the name is not found.
+extern "C" fn resolve_symbol(name: *const c_char, _context: *mut c_void) -> u64 {
+    unsafe {
+        let address = LLVMSearchForAddressOfSymbol(name);
+
+        if !address.is_null() {
+            return address as u64;
+        }
+
+        if *name == b'_' as c_char {
+            return LLVMSearchForAddressOfSymbol(name.add(1)) as u64;
+        }
+
+        0
+    }
+}
+
+/// A target machine, which generates the machine code of a module.
+pub(crate) struct TargetMachine {
+    /// The raw target machine.
+    raw: LLVMTargetMachineRef,
+    /// The target triple of the machine.
+    triple: String,
+    /// The CPU of the machine, as given when it was created.
+    cpu: String,
+    /// The features of the machine, as given when it was created.
+    features: String,
+    /// The relocation model of the machine.
+    reloc_mode: LLVMRelocMode,
+    /// The code model of the machine.
+    code_model: LLVMCodeModel,
+    /// The optimization level of the machine code.
+    opt_level: LLVMCodeGenOptLevel,
+}
+
+impl TargetMachine {
+    /// Create a target machine for the target triple, CPU and comma separated features, like `+avx2,-sse4.1`.
+    /// The `native` CPU is the CPU of the machine the compiler runs on, with all of its features enabled.
+    pub(crate) fn new(triple: &str, cpu: &str, features: &str, reloc_mode: LLVMRelocMode, code_model: LLVMCodeModel, opt_level: LLVMCodeGenOptLevel) -> Result<Self, String> {
+        unsafe {
+            let c_triple = cstring!("{}", triple);
+
+            let mut target = ptr::null_mut();
+            let mut error = ptr::null_mut();
+
+            if LLVMGetTargetFromTriple(c_triple.as_ptr(), &mut target, &mut error) == 1 {
+                return Err(take_message(error));
+            }
+
+            // LLVM does not know the `native` CPU, it is resolved to the name and the features of the host CPU.
+            // The features given explicitly come last, so they override the ones of the host.
+            let (c_cpu, c_features) = if cpu == "native" {
+                let host_features = host_cpu_features();
+                let features = if features.is_empty() { host_features } else { format!("{},{}", host_features, features) };
+
+                (host_cpu_name(), features)
+            } else {
+                (cpu.to_owned(), features.to_owned())
+            };
+
+            let c_cpu = cstring!("{}", c_cpu);
+            let c_features = cstring!("{}", c_features);
+
+            let raw = LLVMCreateTargetMachine(target, c_triple.as_ptr(), c_cpu.as_ptr(), c_features.as_ptr(), opt_level, reloc_mode, code_model);
+
+            Ok(Self {
+                raw,
+                triple: triple.to_owned(),
+                cpu: cpu.to_owned(),
+                features: features.to_owned(),
+                reloc_mode,
+                code_model,
+                opt_level,
+            })
+        }
+    }
+
+    /// Create another target machine with the same options.
+    pub(crate) fn duplicate(&self) -> Result<Self, String> {
+        Self::new(&self.triple, &self.cpu, &self.features, self.reloc_mode, self.code_model, self.opt_level)
+    }
+
+    /// The target triple of the machine.
+    pub(crate) fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    /// Give up the ownership of the raw target machine, it is not disposed anymore.
+    fn into_raw(self) -> LLVMTargetMachineRef {
+        let raw = self.raw;
+
+        mem::forget(self);
+
+        raw
+    }
+
+    /// Write the object file or the assembly of the module to the path.
+    pub(crate) fn emit_to_file(&self, module: &Module, path: &Path, file_type: LLVMCodeGenFileType) -> Result<(), String> {
+        let path = cstring!("{}", path.to_string_lossy());
+        let mut error = ptr::null_mut();
+
+        unsafe {
+            // LLVM does not write to the file name, it only takes a mutable pointer for historical reasons.
+            if LLVMTargetMachineEmitToFile(self.raw, module.raw, path.as_ptr() as *mut c_char, file_type, &mut error) == 1 {
+                return Err(take_message(error));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TargetMachine {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeTargetMachine(self.raw) }
+    }
+}
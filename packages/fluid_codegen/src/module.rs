@@ -0,0 +1,85 @@
+//! Compilation of the programs made of several modules.
+
+use std::{collections::HashMap, ops::Range, path::Path, sync::Arc};
+
+use fluid_error::{codes, AnnotationType, Diagnostic, DiagnosticBuilder, Severity, Slice, SourceAnnotation, SourceFile};
+use fluid_parser::{Ast, Declaration, StatementKind};
+
+use crate::CodeGen;
+
+impl CodeGen {
+    /// Find the functions that are defined by more than one module.
+    /// The type checker only sees one module at a time, so it can not find them.
+    pub(crate) fn check_duplicate_symbols(&self, modules: &[(Arc<SourceFile>, Ast)]) -> Vec<Diagnostic> {
+        let mut symbols: HashMap<String, (&Arc<SourceFile>, Range<usize>)> = HashMap::new();
+        let mut errors = vec![];
+
+        for (file, ast) in modules {
+            for statement in &ast.statements {
+                let function = match statement.kind {
+                    StatementKind::Declaration(ref declaration) => match **declaration {
+                        Declaration::Function(ref function) => function,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+
+                let prototype = &function.prototype;
+                // The calls are not qualified by a module yet, so two modules can not define the same function even though
+                // the symbols of their functions contain the module path.
+                let symbol = if prototype.no_mangle {
+                    prototype.name.clone()
+                } else {
+                    self.function_key(&prototype.name, prototype.args.iter().map(|arg| arg.typee).collect())
+                };
+
+                match symbols.get(&symbol) {
+                    // The type checker reports the functions defined twice in the same module.
+                    Some((previous_file, _)) if Arc::ptr_eq(previous_file, file) => (),
+                    Some((previous_file, previous_span)) => {
+                        let error = Self::make_duplicate_symbol(&prototype.name, file, prototype.name_span.clone(), previous_file, previous_span.clone());
+
+                        errors.push(error);
+                    }
+                    None => {
+                        symbols.insert(symbol, (file, prototype.name_span.clone()));
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Get the path of the module being generated, it qualifies the symbols of its functions.
+    /// The module is named after its file, without the extension, and the characters that can not be in an identifier
+    /// are replaced with underscores.
+    pub(crate) fn module_path(&self) -> Vec<String> {
+        let stem = Path::new(self.file.name()).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let mut name = stem.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect::<String>();
+
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+
+        if name.is_empty() {
+            vec![]
+        } else {
+            vec![name]
+        }
+    }
+
+    /// Make the error about a function defined in two modules.
+    fn make_duplicate_symbol(name: &str, file: &Arc<SourceFile>, span: Range<usize>, previous_file: &Arc<SourceFile>, previous_span: Range<usize>) -> Diagnostic {
+        let redefinition = SourceAnnotation::new(span, AnnotationType::Error).set_label(format!("`{}` redefined here", name));
+        let previous = SourceAnnotation::new(previous_span, AnnotationType::Info).set_label(format!("previous definition of `{}` here", name));
+
+        DiagnosticBuilder::new(Severity::Error, format!("the function `{}` is defined in multiple modules", name))
+            .set_code(codes::E0018)
+            .push_slice(Slice::new(file.clone()).push_annotation(redefinition))
+            .push_slice(Slice::new(previous_file.clone()).push_annotation(previous))
+            .set_note("the functions of all of the modules of a program share one namespace")
+            .build()
+    }
+}
@@ -0,0 +1,126 @@
+//! Evaluation of the top-level expressions of the REPL.
+
+use std::{
+    ffi::CStr,
+    mem,
+    os::raw::{c_char, c_void},
+};
+
+use fluid_error::{AnnotationType, DiagnosticBuilder, Severity, Slice, SourceAnnotation};
+use fluid_parser::{ExprArena, ExprId, Type};
+
+use crate::{language::type_tag, llvm_safe::ExecutionEngine, utils::CodeGenResult, CodeGen};
+
+extern "C" {
+    /// Free a buffer allocated by `malloc`, like the text of `__fluid_display`.
+    fn free(pointer: *mut c_void);
+}
+
+/// The name of the anonymous function that evaluates a top-level expression.
+const REPL_EXPRESSION: &str = "__repl_expression";
+
+impl CodeGen {
+    /// Evaluate a top-level expression and print its value.
+    ///
+    /// The expression is wrapped in an anonymous function, which formats the value with `__fluid_display` and stores
+    /// the text through the pointer it takes, so the values are printed like the programs print them.
+    pub(crate) fn run_top_level_expression(&mut self, exprs: &ExprArena, expression: ExprId) -> CodeGenResult<()> {
+        let result_type = self.context.i8_type().pointer_type();
+        let function_type = self.context.void_type().function_type(&[result_type], false);
+
+        let function = self.module.add_function(REPL_EXPRESSION, function_type);
+        let entry = self.context.append_basic_block(function, "entry");
+
+        self.builder.position_at_end(entry);
+
+        let value = match self.gen_expression(exprs, expression) {
+            Ok(value) => value,
+            Err(error) => {
+                self.builder.clear_insertion_position();
+
+                // SAFETY: The function is not in the symbol table, and the builder was moved out of it.
+                unsafe { function.delete() };
+
+                return Err(error);
+            }
+        };
+
+        if value.kind != Type::Void {
+            let display = self.get_display();
+
+            let erased = self.erase_value(&self.builder, value.kind, value.value);
+            let tag = self.context.i32_type().const_int(type_tag(value.kind), false);
+            let text = self.builder.build_call(display, &[erased, tag], "text");
+
+            let pointer_type = self.context.i8_type().pointer_type().pointer_type();
+            let result = self.builder.build_bit_cast(function.param(0), pointer_type, "result");
+
+            self.builder.build_store(text, result);
+        }
+
+        self.builder.build_ret_void();
+        self.builder.clear_insertion_position();
+
+        self.dump_value(function.as_value());
+
+        let result = unsafe { self.call_repl_expression(value.kind) };
+
+        // The function is generated again for the next expression.
+        // SAFETY: The function is not in the symbol table, and the builder was moved out of it after its `ret`.
+        unsafe { function.delete() };
+
+        match result {
+            Ok(Some(result)) => println!("{}", result),
+            Ok(None) => (),
+            Err(message) => {
+                let error = DiagnosticBuilder::new(Severity::Error, format!("could not run the expression: {}", message))
+                    .push_slice(Slice::new(self.file.clone()).push_annotation(SourceAnnotation::new(exprs[expression].span.clone(), AnnotationType::Error)))
+                    .build();
+
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compile the module again, so that the functions added since the last compilation can be called.
+    /// MCJIT compiles a module only once, so the module is moved to a new execution engine.
+    pub(crate) fn reload_execution_engine(&mut self) -> Result<(), String> {
+        if let Some(execution_engine) = self.execution_engine.take() {
+            // The engine still owns the module if it could not be removed, so it must not be dropped.
+            if let Err(error) = execution_engine.remove_module(&self.module) {
+                self.execution_engine = Some(execution_engine);
+
+                return Err(format!("could not remove the module from the execution engine: {}", error));
+            }
+        }
+
+        let execution_engine = ExecutionEngine::new(&self.module).map_err(|error| format!("could not create the execution engine: {}", error))?;
+
+        self.execution_engine = Some(execution_engine);
+        self.engine_outdated = false;
+
+        Ok(())
+    }
+
+    /// Call the anonymous function of the expression, and get the text of the value it returned.
+    unsafe fn call_repl_expression(&mut self, kind: Type) -> Result<Option<String>, String> {
+        self.reload_execution_engine()?;
+
+        let execution_engine = self.execution_engine.as_ref().ok_or("the REPL has no execution engine")?;
+        let function: extern "C" fn(*mut *mut c_char) = mem::transmute(execution_engine.function_address(REPL_EXPRESSION)?);
+
+        let mut text = std::ptr::null_mut();
+        function(&mut text);
+
+        if kind == Type::Void {
+            return Ok(None);
+        }
+
+        let result = CStr::from_ptr(text).to_string_lossy().into_owned();
+        free(text as *mut c_void);
+
+        Ok(Some(result))
+    }
+}
@@ -1,78 +1,266 @@
-use fluid_parser::{Declaration, Expression, Statement, Type};
+use fluid_parser::{Declaration, ExprArena, ExprId, ForLoop, Statement, StatementKind, Type};
+use llvm::LLVMIntPredicate;
 
-use llvm::core::*;
-
-use crate::{cstring, symbol::FluidVariableRef, CodeGen};
+use crate::{
+    llvm_safe::{self, Value},
+    symbol::FluidVariableRef,
+    utils::CodeGenResult,
+    CodeGen,
+};
 
 impl CodeGen {
     /// Generate the function's body.
     #[inline(always)]
-    pub(crate) unsafe fn gen_function_body(&mut self, body: Statement) {
-        match body {
-            Statement::Block(block) => {
+    pub(crate) fn gen_function_body(&mut self, exprs: &ExprArena, body: Statement) -> CodeGenResult<()> {
+        match body.kind {
+            StatementKind::Block(block, value) => {
                 for statement in block {
-                    self.gen_statement(statement);
+                    // The statements after a `return` are unreachable.
+                    if self.is_terminated() {
+                        return Ok(());
+                    }
+
+                    self.gen_statement(exprs, statement)?;
+                }
+
+                // The value at the end of the body is returned, unless it is the value of a function that returns
+                // nothing.
+                if let Some(value) = value {
+                    if !self.is_terminated() {
+                        let value = self.gen_expression(exprs, value)?;
+
+                        if value.kind != Type::Void {
+                            self.builder.build_ret(value.value);
+                        }
+                    }
                 }
+
+                Ok(())
             }
             _ => unreachable!(),
         }
     }
 
     /// Generate a statement.
-    pub(crate) unsafe fn gen_statement(&mut self, statement: Statement) {
-        match statement {
-            Statement::Expression(expression) => {
-                self.gen_expression(&expression);
+    pub(crate) fn gen_statement(&mut self, exprs: &ExprArena, statement: Statement) -> CodeGenResult<()> {
+        let is_item = match statement.kind {
+            StatementKind::Declaration(ref decl) => !matches!(**decl, Declaration::VarDef(_)),
+            _ => false,
+        };
+
+        // Only the functions and the extern blocks can be generated outside of a function.
+        if !is_item && self.builder.insert_block().is_none() {
+            return Err(self.unsupported("statements outside of a function are not supported yet", statement.span));
+        }
+
+        match statement.kind {
+            StatementKind::Expression(expression) => {
+                self.gen_expression(exprs, expression)?;
+
+                Ok(())
             }
-            Statement::Return(expression) => self.gen_return_statement(*expression),
-            Statement::Block(block) => self.gen_block(block),
-            Statement::Declaration(decl) => self.gen_decl(*decl),
-            _ => unimplemented!(),
+            StatementKind::Return(expression) => self.gen_return_statement(exprs, expression),
+            StatementKind::If(condition, body, elif) => self.gen_if_statement(exprs, condition, *body, elif.map(|elif| *elif)),
+            StatementKind::Block(block, value) => self.gen_block(exprs, block, value),
+            StatementKind::Declaration(decl) => self.gen_decl(exprs, *decl),
+            StatementKind::For(for_loop) => self.gen_for_statement(exprs, *for_loop),
         }
     }
 
-    pub(crate) unsafe fn gen_decl(&mut self, decl: Declaration) {
+    pub(crate) fn gen_decl(&mut self, exprs: &ExprArena, decl: Declaration) -> CodeGenResult<()> {
         match decl {
-            Declaration::Function(function) => self.gen_function_def(function),
-            Declaration::VarDef(name, kind, value) => self.gen_var_def(name, kind, *value),
+            Declaration::Function(function) => self.gen_function_def(exprs, function),
+            Declaration::VarDef(var_def) => self.gen_var_def(exprs, var_def.name, var_def.typee, var_def.value),
             Declaration::Extern(externs) => {
                 for external in externs {
                     self.gen_extern_def(external);
                 }
+
+                Ok(())
             }
         }
     }
 
-    /// Generate a block statement.
-    pub(crate) unsafe fn gen_block(&mut self, block: Vec<Statement>) {
+    /// Generate a block statement, the value at its end is not used.
+    pub(crate) fn gen_block(&mut self, exprs: &ExprArena, block: Vec<Statement>, value: Option<ExprId>) -> CodeGenResult<()> {
         self.symbol_table.push_scope();
 
-        let mut result = vec![];
+        let mut result = Ok(());
 
         for statement in block {
-            result.push(self.gen_statement(statement));
+            if self.is_terminated() {
+                break;
+            }
+
+            result = self.gen_statement(exprs, statement);
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if let Some(value) = value {
+            if result.is_ok() && !self.is_terminated() {
+                result = self.gen_expression(exprs, value).map(|_| ());
+            }
+        }
+
+        self.symbol_table.pop_scope();
+
+        result
+    }
+
+    /// Generate an if statement.
+    /// The branches jump to a merge block where the generation continues, unless they both return.
+    pub(crate) fn gen_if_statement(&mut self, exprs: &ExprArena, condition: ExprId, body: Statement, elif: Option<Statement>) -> CodeGenResult<()> {
+        let condition = self.gen_expression(exprs, condition)?;
+        let function = self.current_function();
+
+        let then_block = self.context.append_basic_block(function, "then");
+        let else_block = self.context.append_basic_block(function, "else");
+
+        self.builder.build_cond_br(condition.value, then_block, else_block);
+
+        self.builder.position_at_end(then_block);
+        self.gen_statement(exprs, body)?;
+
+        // The branches may have added blocks of their own, so the branches end in the current blocks.
+        let then_end = self.builder.insert_block().unwrap();
+        let then_returns = self.is_terminated();
+
+        self.builder.position_at_end(else_block);
+
+        if let Some(elif) = elif {
+            self.gen_statement(exprs, elif)?;
+        }
+
+        let else_end = self.builder.insert_block().unwrap();
+        let else_returns = self.is_terminated();
+
+        // The code after the if statement is unreachable, the builder stays in a terminated block.
+        if then_returns && else_returns {
+            return Ok(());
+        }
+
+        let merge_block = self.context.append_basic_block(function, "merge");
+
+        for &(end, returns) in &[(then_end, then_returns), (else_end, else_returns)] {
+            if !returns {
+                self.builder.position_at_end(end);
+                self.builder.build_br(merge_block);
+            }
         }
 
+        self.builder.position_at_end(merge_block);
+
+        Ok(())
+    }
+
+    /// Generate a for statement, which walks a pointer over the bytes of the string until its nul terminator.
+    /// The body is not run if the string is `null`.
+    pub(crate) fn gen_for_statement(&mut self, exprs: &ExprArena, for_loop: ForLoop) -> CodeGenResult<()> {
+        let string = self.gen_expression(exprs, for_loop.iterable)?;
+        let function = self.current_function();
+
+        let i8_type = self.context.i8_type();
+
+        let cursor = self.build_entry_alloca(i8_type.pointer_type(), "cursor");
+        let variable_alloca = self.build_entry_alloca(i8_type, &for_loop.name);
+
+        let cond_block = self.context.append_basic_block(function, "for.cond");
+        let body_block = self.context.append_basic_block(function, "for.body");
+        let end_block = self.context.append_basic_block(function, "for.end");
+
+        self.builder.build_store(string.value, cursor);
+        self.builder.build_cond_br(self.builder.build_is_null(string.value, "isnull"), end_block, cond_block);
+
+        self.builder.position_at_end(cond_block);
+
+        let pointer = self.builder.build_load(cursor, "pointer");
+        let byte = self.builder.build_load(pointer, "byte");
+        let is_end = self.builder.build_icmp(LLVMIntPredicate::LLVMIntEQ, byte, i8_type.const_int(0, false), "isend");
+
+        self.builder.build_cond_br(is_end, end_block, body_block);
+
+        self.builder.position_at_end(body_block);
+
+        let next = self.builder.build_gep(pointer, &[self.context.i64_type().const_int(1, false)], "next");
+
+        self.builder.build_store(next, cursor);
+        self.builder.build_store(byte, variable_alloca);
+
+        self.symbol_table.push_scope();
+        self.symbol_table.insert_variable(for_loop.name, FluidVariableRef::new(true, Type::Char, variable_alloca));
+
+        let result = self.gen_statement(exprs, for_loop.body);
+
         self.symbol_table.pop_scope();
+        result?;
+
+        // The body may have added blocks of its own, or returned.
+        if !self.is_terminated() {
+            self.builder.build_br(cond_block);
+        }
+
+        self.builder.position_at_end(end_block);
+
+        Ok(())
+    }
+
+    /// Returns true if the current basic block already ends with a terminator, like a `return`.
+    /// No more instructions can be added to the block after it.
+    pub(crate) fn is_terminated(&self) -> bool {
+        self.builder.insert_block().unwrap().terminator().is_some()
+    }
+
+    /// The function the builder is currently generating.
+    pub(crate) fn current_function(&self) -> llvm_safe::FnValue {
+        self.builder.insert_block().unwrap().parent()
     }
 
     /// Generate a return statement.
-    pub(crate) unsafe fn gen_return_statement(&mut self, expression: Expression) {
-        let expression = self.gen_expression(&expression);
+    pub(crate) fn gen_return_statement(&mut self, exprs: &ExprArena, expression: Option<ExprId>) -> CodeGenResult<()> {
+        match expression {
+            Some(expression) => {
+                let expression = self.gen_expression(exprs, expression)?;
+
+                self.builder.build_ret(expression.value);
+            }
+            None => {
+                self.builder.build_ret_void();
+            }
+        }
 
-        LLVMBuildRet(self.builder, expression.value);
+        Ok(())
+    }
+
+    /// Build an alloca at the start of the entry block of the current function.
+    /// `mem2reg` only promotes the allocas of the entry block to registers, so the variables declared in
+    /// branches and loops must not be allocated where the builder currently is.
+    pub(crate) fn build_entry_alloca(&mut self, kind: llvm_safe::Type, name: &str) -> Value {
+        let entry = self.current_function().entry_block();
+        let builder = self.context.create_builder();
+
+        match entry.first_instruction() {
+            Some(first_instruction) => builder.position_before(first_instruction),
+            None => builder.position_at_end(entry),
+        }
+
+        builder.build_alloca(kind, name)
     }
 
     /// Generate variable definition.
-    pub(crate) unsafe fn gen_var_def(&mut self, name: String, kind: Type, value: Expression) {
+    pub(crate) fn gen_var_def(&mut self, exprs: &ExprArena, name: String, kind: Type, value: ExprId) -> CodeGenResult<()> {
         let llvm_type = self.gen_type(kind);
-        let var_value = self.gen_expression(&value);
+        let var_value = self.gen_expression(exprs, value)?;
 
-        let variable_alloca = LLVMBuildAlloca(self.builder, llvm_type, cstring!("{}", name).as_ptr());
-        LLVMBuildStore(self.builder, var_value.value, variable_alloca);
+        let variable_alloca = self.build_entry_alloca(llvm_type, &name);
+        self.builder.build_store(var_value.value, variable_alloca);
 
         let variable_ref = FluidVariableRef::new(true, kind, variable_alloca);
 
         self.symbol_table.insert_variable(name, variable_ref);
+
+        Ok(())
     }
 }
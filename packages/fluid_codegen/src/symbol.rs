@@ -1,93 +1,79 @@
 use std::collections::HashMap;
 
 use fluid_parser::Type;
-use llvm::prelude::LLVMValueRef;
 
-/// The scope's unique id.
-type ScopeId = usize;
+use crate::llvm_safe::{FnValue, Value};
 
-/// The symbol table.
+/// The symbol table, a stack of the scopes that are currently open.
+/// The global scope is at the bottom of the stack, and the innermost scope is at the top.
 #[derive(Debug)]
 pub(crate) struct SymbolTable {
-    /// All of the scopes.
+    /// The open scopes, from the global scope to the innermost one.
     scopes: Vec<Scope>,
-    /// The current scope's id.
-    current: ScopeId,
 }
 
 impl SymbolTable {
-    /// The global scope id.
-    const GLOBAL_SCOPE: ScopeId = 0;
-
     /// Create a new SymbolTable
     pub(crate) fn new() -> Self {
-        let global = Scope::new(None);
-        let scopes = vec![global];
-        let current = Self::GLOBAL_SCOPE;
-
-        Self { scopes, current }
+        Self { scopes: vec![Scope::new()] }
     }
 
-    /// Push a new scope in the symbol table.
+    /// Push a new scope in the symbol table, it is the child of the current scope.
     pub(crate) fn push_scope(&mut self) {
-        self.scopes.push(Scope::new(Some(self.current)));
-        self.current += 1;
+        self.scopes.push(Scope::new());
     }
 
-    /// Pop the current scope.
+    /// Pop the current scope, its symbols are not visible anymore.
     pub(crate) fn pop_scope(&mut self) {
-        self.current -= 1;
+        assert!(self.scopes.len() > 1, "the global scope cannot be popped");
+
+        self.scopes.pop();
     }
 
     /// Get the current scope.
     pub(crate) fn current_scope(&mut self) -> &mut Scope {
-        &mut self.scopes[self.current]
-    }
-
-    /// Get the parent of the current scope.
-    pub(crate) fn current_scope_parent(&mut self) -> &mut Scope {
-        let current = self.current_scope();
-        let parent_id = current.parent.unwrap();
-
-        &mut self.scopes[parent_id]
+        self.scopes.last_mut().expect("the global scope is never popped")
     }
 
     /// Insert a function in the current scope.
     pub(crate) fn insert_function(&mut self, function_name: String, function_ref: FluidFunctionRef) {
-        let current = self.current_scope();
+        self.current_scope().insert_function(function_name, function_ref);
+    }
 
-        current.insert_function(function_name, function_ref);
+    /// Remove a function from the current scope.
+    pub(crate) fn remove_function(&mut self, function_name: &str) {
+        self.current_scope().functions.remove(function_name);
     }
 
     /// Insert a variable in the current scope.
     pub(crate) fn insert_variable(&mut self, variable_name: String, variable_ref: FluidVariableRef) {
-        let current = self.current_scope();
-
-        current.insert_variable(variable_name, variable_ref);
+        self.current_scope().insert_variable(variable_name, variable_ref);
     }
 
-    /// Get a variable in the scope.
-    pub(crate) fn get_variable(&mut self, variable_name: &str) -> Option<&FluidVariableRef> {
-        let current = self.current_scope();
+    /// Get a variable, from the innermost scope that has it.
+    pub(crate) fn get_variable(&self, variable_name: &str) -> Option<&FluidVariableRef> {
+        self.scopes.iter().rev().find_map(|scope| scope.get_variable(variable_name))
+    }
 
-        current.get_variable(variable_name)
+    /// Get a function, from the innermost scope that has it.
+    pub(crate) fn get_function(&self, function_name: &str) -> Option<&FluidFunctionRef> {
+        self.scopes.iter().rev().find_map(|scope| scope.get_function(function_name))
     }
 
-    /// Get a function in the scope.
-    pub(crate) fn get_function(&mut self, function_name: &str) -> Option<&FluidFunctionRef> {
-        let current = self.current_scope();
+    /// Returns true if a function of any scope has the name, whatever its parameters are.
+    pub(crate) fn has_function_named(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.functions.values().any(|function| function.name == name))
+    }
 
-        current.get_function(function_name)
+    /// Get the functions of the global scope.
+    pub(crate) fn global_functions(&self) -> impl Iterator<Item = &FluidFunctionRef> {
+        self.scopes[0].functions.values()
     }
 }
 
 /// A scope
 #[derive(Debug)]
 pub(crate) struct Scope {
-    /// ScopeId of the parent scope.
-    /// The ScopeId of the parent scope will be None if its the global scope.
-    pub(crate) parent: Box<Option<ScopeId>>,
-
     /// List of all of the functions in the scope.
     pub(crate) functions: HashMap<String, FluidFunctionRef>,
     /// List of all of the variables in the scope.
@@ -96,13 +82,11 @@ pub(crate) struct Scope {
 
 impl Scope {
     /// Create a new scope.
-    pub(crate) fn new(parent: Option<ScopeId>) -> Self {
-        let parent = Box::new(parent);
-
+    pub(crate) fn new() -> Self {
         let functions = HashMap::new();
         let variables = HashMap::new();
 
-        Self { parent, functions, variables }
+        Self { functions, variables }
     }
 
     /// Insert a new function in the scope.
@@ -123,7 +107,7 @@ impl Scope {
         self.variables.get(variable_name)
     }
 
-    /// Get a variable in the scope.
+    /// Get a function in the scope.
     #[inline]
     pub(crate) fn get_function(&self, function_name: &str) -> Option<&FluidFunctionRef> {
         self.functions.get(function_name)
@@ -138,12 +122,12 @@ pub(crate) struct FluidVariableRef {
     /// The type of the variable.
     pub(crate) kind: Type,
     /// The alloca of the variable.
-    pub(crate) alloca: LLVMValueRef,
+    pub(crate) alloca: Value,
 }
 
 impl FluidVariableRef {
     /// Create a new variable reference.
-    pub(crate) fn new(initialized: bool, kind: Type, alloca: LLVMValueRef) -> Self {
+    pub(crate) fn new(initialized: bool, kind: Type, alloca: Value) -> Self {
         Self { initialized, kind, alloca }
     }
 }
@@ -151,17 +135,19 @@ impl FluidVariableRef {
 /// Fluid function reference.
 #[derive(Debug)]
 pub(crate) struct FluidFunctionRef {
+    /// Name of the function, the key of the function in the symbol table also has the types of its args.
+    pub(crate) name: String,
     /// Args of the function.
     pub(crate) args: Vec<Type>,
     /// Return type of the function.
     pub(crate) return_type: Type,
     /// Value of the generated function.
-    pub(crate) value: LLVMValueRef,
+    pub(crate) value: FnValue,
 }
 
 impl FluidFunctionRef {
     /// Create a new function reference.
-    pub(crate) fn new(args: Vec<Type>, return_type: Type, value: LLVMValueRef) -> Self {
-        Self { args, return_type, value }
+    pub(crate) fn new(name: String, args: Vec<Type>, return_type: Type, value: FnValue) -> Self {
+        Self { name, args, return_type, value }
     }
 }
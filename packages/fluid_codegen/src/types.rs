@@ -1,18 +1,18 @@
 use fluid_parser::Type;
 
-use llvm::{core::*, prelude::*};
-
-use crate::CodeGen;
+use crate::{llvm_safe, CodeGen};
 
 impl CodeGen {
     /// Generate type.
-    pub(crate) unsafe fn gen_type(&mut self, kind: Type) -> LLVMTypeRef {
+    pub(crate) fn gen_type(&mut self, kind: Type) -> llvm_safe::Type {
         match kind {
-            Type::Void => LLVMVoidTypeInContext(self.context),
-            Type::Number => LLVMInt64TypeInContext(self.context),
-            Type::Float => LLVMFloatTypeInContext(self.context),
-            Type::String => LLVMPointerType(LLVMInt8TypeInContext(self.context), 0),
-            Type::Bool => LLVMInt1TypeInContext(self.context),
+            Type::Void => self.context.void_type(),
+            Type::Number => self.context.i64_type(),
+            Type::Float => self.context.float_type(),
+            Type::String => self.context.i8_type().pointer_type(),
+            Type::Bool => self.context.i1_type(),
+            // The strings are indexed byte-wise, so a character is a byte.
+            Type::Char => self.context.i8_type(),
         }
     }
 }
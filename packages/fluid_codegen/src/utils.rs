@@ -1,5 +1,7 @@
+use fluid_error::Diagnostic;
 use fluid_parser::Type;
-use llvm::prelude::LLVMValueRef;
+
+use crate::llvm_safe::Value;
 
 /// Convert a string to CString
 #[macro_export]
@@ -10,18 +12,21 @@ macro_rules! cstring {
     }};
 }
 
+/// The result of generating code. The errors are user errors, like using an unsupported feature.
+pub(crate) type CodeGenResult<T> = Result<T, Diagnostic>;
+
 /// Reference to a fluid value.
 #[derive(Debug)]
 pub(crate) struct FluidValueRef {
     /// The fluid type of the value.
     pub(crate) kind: Type,
     /// The llvm value.
-    pub(crate) value: LLVMValueRef,
+    pub(crate) value: Value,
 }
 
 impl FluidValueRef {
     /// Create a new value reference.
-    pub(crate) fn new(kind: Type, value: LLVMValueRef) -> Self {
+    pub(crate) fn new(kind: Type, value: Value) -> Self {
         Self { kind, value }
     }
 }
@@ -0,0 +1,67 @@
+//! This file contains the artifacts that a compilation can write, the `Emit` interface.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// An artifact of a compilation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    /// The tokens of the lexer.
+    Tokens,
+    /// The AST, with its names resolved.
+    Ast,
+    /// Textual LLVM IR.
+    LlvmIr,
+    /// LLVM bitcode.
+    Bitcode,
+    /// Assembly.
+    Asm,
+    /// An object file.
+    Object,
+    /// An executable, linked from the object file.
+    Exe,
+    /// A C header declaring the `export` functions.
+    CHeader,
+}
+
+impl Emit {
+    /// Returns true if the artifact is produced by the code generator.
+    pub fn needs_codegen(self) -> bool {
+        !matches!(self, Emit::Tokens | Emit::Ast | Emit::CHeader)
+    }
+
+    /// Get the path of the artifact of the source file, next to it. The executables are named by the linker.
+    pub fn path(self, source: &Path) -> PathBuf {
+        let extension = match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::LlvmIr => "ll",
+            Emit::Bitcode => "bc",
+            Emit::Asm => "s",
+            Emit::Object | Emit::Exe => "obj",
+            Emit::CHeader => "h",
+        };
+
+        source.with_extension(extension)
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(emit: &str) -> Result<Self, Self::Err> {
+        match emit {
+            "tokens" => Ok(Emit::Tokens),
+            "ast" => Ok(Emit::Ast),
+            "llvm-ir" => Ok(Emit::LlvmIr),
+            "bc" => Ok(Emit::Bitcode),
+            "asm" => Ok(Emit::Asm),
+            "obj" => Ok(Emit::Object),
+            "exe" => Ok(Emit::Exe),
+            "c-header" => Ok(Emit::CHeader),
+            _ => Err(format!("invalid emit kind `{}`, expected `tokens`, `ast`, `llvm-ir`, `bc`, `asm`, `obj`, `exe` or `c-header`", emit)),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! This file contains the generator of the C headers of the programs, the `c_header` interface.
+
+use fluid_parser::{Declaration, Prototype, StatementKind, Type};
+
+use crate::Module;
+
+/// Get the C type of a Fluid type, as it is passed to and returned from the functions.
+fn c_type(kind: Type) -> &'static str {
+    match kind {
+        Type::Void => "void",
+        Type::Number => "int64_t",
+        Type::Float => "float",
+        Type::String => "const char *",
+        Type::Bool => "bool",
+        Type::Char => "char",
+    }
+}
+
+/// Get the C declaration of the name with the type. The pointer types are written next to the name, like
+/// `const char *name`.
+fn c_typed(kind: Type, name: &str) -> String {
+    let c_type = c_type(kind);
+
+    if c_type.ends_with('*') {
+        format!("{}{}", c_type, name)
+    } else {
+        format!("{} {}", c_type, name)
+    }
+}
+
+/// Get the C declaration of the function.
+fn c_declaration(prototype: &Prototype) -> String {
+    let args = if prototype.args.is_empty() {
+        "void".to_owned()
+    } else {
+        prototype.args.iter().map(|arg| c_typed(arg.typee, &arg.name)).collect::<Vec<_>>().join(", ")
+    };
+
+    format!("{}({});", c_typed(prototype.return_type, &prototype.name), args)
+}
+
+/// Get the include guard of the header of the program with the name, like `HELLO_WORLD_H` for `hello-world`.
+fn include_guard(name: &str) -> String {
+    let guard = name.chars().map(|char| if char.is_ascii_alphanumeric() { char.to_ascii_uppercase() } else { '_' }).collect::<String>();
+
+    if guard.starts_with(|char: char| char.is_ascii_digit()) {
+        format!("_{}_H", guard)
+    } else {
+        format!("{}_H", guard)
+    }
+}
+
+/// Generate the C header that declares the `export` functions of the program with the name, in the order of their
+/// definitions, so that its object files can be called from C and C++. The other functions have mangled symbols.
+///
+/// ```c
+/// int64_t add(int64_t a, int64_t b);
+/// ```
+pub fn c_header(name: &str, modules: &[Module]) -> String {
+    let guard = include_guard(name);
+    let mut header = format!(
+        "#ifndef {guard}\n#define {guard}\n\n/* The functions exported by `{name}`, generated by `fluid build --emit c-header`. */\n\n",
+        guard = guard,
+        name = name
+    );
+
+    header.push_str("#include <stdbool.h>\n#include <stdint.h>\n\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+    for (_, ast) in modules {
+        for statement in &ast.statements {
+            if let StatementKind::Declaration(declaration) = &statement.kind {
+                if let Declaration::Function(function) = &**declaration {
+                    if function.prototype.no_mangle {
+                        header.push_str(&c_declaration(&function.prototype));
+                        header.push('\n');
+                    }
+                }
+            }
+        }
+    }
+
+    header.push_str(&format!("\n#ifdef __cplusplus\n}}\n#endif\n\n#endif /* {} */\n", guard));
+    header
+}
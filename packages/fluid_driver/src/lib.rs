@@ -0,0 +1,19 @@
+//! The `fluid_driver` crate runs the whole pipeline of the compiler, from the source files to the artifacts, the
+//! `Session` interface. The command line, the REPL and the tests all compile through it, so the phases are wired
+//! together and their errors are handled in one place.
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features, missing_docs)]
+
+mod emit;
+mod header;
+mod link;
+pub mod profile;
+mod session;
+pub mod timings;
+
+#[cfg(test)]
+mod tests;
+
+pub use emit::*;
+pub use header::*;
+pub use session::*;
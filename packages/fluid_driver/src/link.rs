@@ -0,0 +1,95 @@
+//! Invoke the system linker to turn the object files into an executable.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use fluid_codegen::RelocModel;
+
+use crate::Options;
+
+/// The linker used when the `CC` environment variable is not set.
+#[cfg(windows)]
+const DEFAULT_LINKER: &str = "link.exe";
+
+/// The linker used when the `CC` environment variable is not set.
+#[cfg(not(windows))]
+const DEFAULT_LINKER: &str = "cc";
+
+/// The linker of the WebAssembly targets when the `WASM_LD` environment variable is not set.
+const DEFAULT_WASM_LINKER: &str = "wasm-ld";
+
+/// Returns true if the target triple is a WebAssembly target.
+fn is_wasm(triple: &str) -> bool {
+    triple.starts_with("wasm32") || triple.starts_with("wasm64")
+}
+
+/// Get the linker to invoke for the target, the `CC` and `WASM_LD` environment variables override the default ones.
+fn linker(target: Option<&str>) -> String {
+    if target.map_or(false, is_wasm) {
+        env::var("WASM_LD").unwrap_or_else(|_| DEFAULT_WASM_LINKER.to_owned())
+    } else {
+        env::var("CC").unwrap_or_else(|_| DEFAULT_LINKER.to_owned())
+    }
+}
+
+/// Link the object file into an executable for the target of the options at the output path, along with the libraries
+/// of the options. The code of an object file compiled with the static relocation model is not position independent,
+/// so the executable is not either.
+pub fn link(object: &Path, output: &Path, options: &Options) -> Result<(), String> {
+    let target = options.target.triple.as_deref();
+    let linker = linker(target);
+    let mut command = Command::new(&linker);
+
+    if linker.ends_with("link.exe") {
+        command.arg("/nologo").arg(format!("/OUT:{}", output.display())).arg(object);
+        command.args(options.library_paths.iter().map(|path| format!("/LIBPATH:{}", path.display())));
+        command.args(options.libraries.iter().map(|library| format!("{}.lib", library)));
+    } else {
+        // The libraries come after the object file, since the linker only keeps the symbols it already needs.
+        command.arg(object);
+        command.args(options.library_paths.iter().map(|path| format!("-L{}", path.display())));
+        command.args(options.libraries.iter().map(|library| format!("-l{}", library)));
+        command.arg("-o").arg(output);
+
+        if target.map_or(false, is_wasm) {
+            // There is no C runtime to call `main`, so the module has no entry point and exports `main` to the host
+            // instead. The extern functions are imported from the host.
+            command.arg("--no-entry").arg("--export=main").arg("--allow-undefined");
+        } else {
+            // The math builtins call the C math library, which is not a part of the C library on every system.
+            command.arg("-lm");
+
+            if options.target.reloc_model == RelocModel::Static {
+                command.arg("-no-pie");
+            }
+        }
+    }
+
+    let status = command.status().map_err(|error| format!("could not run the linker `{}`: {}", linker, error))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("linking with `{}` failed: {}", linker, status))
+    }
+}
+
+/// Get the path of the shared library with the name to load into the JIT, like `libm.so` for `m`. It is the first one
+/// in the directories, otherwise the file name alone, which the system searches for in its own directories.
+pub fn shared_library_path(name: &str, dirs: &[PathBuf]) -> PathBuf {
+    let file_name = format!("{}{}{}", env::consts::DLL_PREFIX, name, env::consts::DLL_SUFFIX);
+
+    dirs.iter().map(|dir| dir.join(&file_name)).find(|path| path.is_file()).unwrap_or_else(|| PathBuf::from(file_name))
+}
+
+/// Get the path of the executable linked from the object file for the target, next to it and without the `.obj` extension.
+pub fn executable_path(object: &Path, target: Option<&str>) -> PathBuf {
+    if target.map_or(false, is_wasm) {
+        object.with_extension("wasm")
+    } else {
+        object.with_extension(env::consts::EXE_EXTENSION)
+    }
+}
@@ -0,0 +1,221 @@
+//! The self-profile of the compiler, written with `--self-profile`.
+//!
+//! The profile records when every phase and the code generation of every function started and how long it took,
+//! along with the resident memory of the compiler at the start and at the end of every phase. It is written in the
+//! trace event format of Chrome, which `chrome://tracing`, Perfetto and speedscope open as a flame graph, or as
+//! folded stacks for `flamegraph.pl` and `inferno` if the file has the `.folded` extension.
+
+use std::{
+    cell::RefCell,
+    fs, io, mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use fluid_codegen::FunctionTime;
+use serde_json::{json, Value};
+
+thread_local! {
+    /// The profile being recorded, `None` if the compiler is not profiled.
+    static PROFILE: RefCell<Option<Profile>> = RefCell::new(None);
+}
+
+/// The events recorded since the profile was enabled or last written.
+#[derive(Debug)]
+struct Profile {
+    /// The file the profile is written to.
+    path: PathBuf,
+    /// When the profile was enabled, the times of the events are relative to it.
+    start: Instant,
+    /// The recorded events, in the order they ended.
+    events: Vec<Event>,
+}
+
+/// A phase of the compiler, or the code generation of a function.
+#[derive(Debug)]
+struct Event {
+    /// The name of the phase or of the function.
+    name: String,
+    /// `phase` or `function`.
+    category: &'static str,
+    /// When the event started, relative to the start of the profile.
+    start: Duration,
+    /// How long the event took.
+    duration: Duration,
+    /// The resident memory of the compiler in bytes at the start and at the end of the event, if it is known.
+    memory: Option<(u64, u64)>,
+}
+
+/// Record a profile of the compilation from now on, written to the path by `write`.
+pub fn enable(path: PathBuf) {
+    let profile = Profile {
+        path,
+        start: Instant::now(),
+        events: vec![],
+    };
+
+    PROFILE.with(|cell| *cell.borrow_mut() = Some(profile));
+}
+
+/// Returns true if the compilation is profiled.
+pub fn is_enabled() -> bool {
+    PROFILE.with(|cell| cell.borrow().is_some())
+}
+
+/// Run the phase, and record when it ran and the memory the compiler used before and after it.
+pub fn phase<T>(name: &'static str, run: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return run();
+    }
+
+    let memory = resident_memory();
+    let start = Instant::now();
+    let result = run();
+    let duration = start.elapsed();
+
+    let memory = memory.and_then(|before| resident_memory().map(|after| (before, after)));
+
+    record(name.to_owned(), "phase", start, duration, memory);
+
+    result
+}
+
+/// Record the code generation of the functions.
+pub fn functions(times: Vec<FunctionTime>) {
+    for time in times {
+        record(time.name, "function", time.start, time.duration, None);
+    }
+}
+
+/// Add an event to the profile, if there is one.
+fn record(name: String, category: &'static str, start: Instant, duration: Duration, memory: Option<(u64, u64)>) {
+    PROFILE.with(|cell| {
+        if let Some(profile) = cell.borrow_mut().as_mut() {
+            let start = start.saturating_duration_since(profile.start);
+
+            profile.events.push(Event {
+                name,
+                category,
+                start,
+                duration,
+                memory,
+            });
+        }
+    });
+}
+
+/// Write the events recorded so far to the file of the profile, then start recording again from there.
+/// Does nothing if the compilation is not profiled.
+pub fn write() -> io::Result<()> {
+    let (path, events) = match PROFILE.with(|cell| cell.borrow_mut().as_mut().map(|profile| (profile.path.clone(), mem::take(&mut profile.events)))) {
+        Some(profile) => profile,
+        None => return Ok(()),
+    };
+
+    let contents = if path.extension().map_or(false, |extension| extension == "folded") {
+        folded_stacks(events)
+    } else {
+        serde_json::to_string(&trace_events(&events))?
+    };
+
+    write_file(&path, &contents)
+}
+
+/// Write the file, creating its directory if needed.
+fn write_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)
+}
+
+/// Convert the events to the trace event format of Chrome: a complete event for every phase and function, and
+/// a counter of the resident memory at the start and at the end of every phase.
+fn trace_events(events: &[Event]) -> Value {
+    let microseconds = |duration: Duration| duration.as_secs_f64() * 1_000_000.0;
+    let mut trace = vec![];
+
+    for event in events {
+        let mut args = json!({});
+
+        if let Some((before, after)) = event.memory {
+            args = json!({ "memory_before": before, "memory_after": after });
+
+            for &(at, memory) in &[(event.start, before), (event.start + event.duration, after)] {
+                trace.push(json!({ "name": "resident memory", "ph": "C", "ts": microseconds(at), "pid": 1, "tid": 1, "args": { "bytes": memory } }));
+            }
+        }
+
+        trace.push(json!({
+            "name": event.name,
+            "cat": event.category,
+            "ph": "X",
+            "ts": microseconds(event.start),
+            "dur": microseconds(event.duration),
+            "pid": 1,
+            "tid": 1,
+            "args": args,
+        }));
+    }
+
+    json!({ "traceEvents": trace, "displayTimeUnit": "ms" })
+}
+
+/// Convert the events to folded stacks, one line per stack with its self time in microseconds.
+/// An event is nested in the events that started before it and ended after it, like a function in the code
+/// generation phase, and the stacks with the same frames are merged.
+///
+/// ```text
+/// lexing 412
+/// code generation 1395
+/// code generation;main 983
+/// ```
+fn folded_stacks(mut events: Vec<Event>) -> String {
+    // The outer events come before the events nested in them.
+    events.sort_by(|a, b| a.start.cmp(&b.start).then(b.duration.cmp(&a.duration)));
+
+    let mut frames: Vec<String> = vec![];
+    let mut self_times = events.iter().map(|event| event.duration).collect::<Vec<_>>();
+    let mut open: Vec<usize> = vec![];
+
+    for (index, event) in events.iter().enumerate() {
+        while let Some(&parent) = open.last() {
+            if events[parent].start + events[parent].duration > event.start {
+                break;
+            }
+
+            open.pop();
+        }
+
+        match open.last() {
+            Some(&parent) => {
+                frames.push(format!("{};{}", frames[parent], event.name));
+                self_times[parent] = self_times[parent].checked_sub(event.duration).unwrap_or_default();
+            }
+            None => frames.push(event.name.clone()),
+        }
+
+        open.push(index);
+    }
+
+    let mut stacks: Vec<(String, Duration)> = vec![];
+
+    for (frames, self_time) in frames.into_iter().zip(self_times) {
+        match stacks.iter_mut().find(|(stack, _)| *stack == frames) {
+            Some((_, total)) => *total += self_time,
+            None => stacks.push((frames, self_time)),
+        }
+    }
+
+    stacks.iter().map(|(frames, self_time)| format!("{} {}\n", frames, self_time.as_micros())).collect()
+}
+
+/// The resident memory of the compiler in bytes, read from `/proc/self/status`. `None` on the systems without it.
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line["VmRSS:".len()..].trim().trim_end_matches("kB").trim().parse::<u64>().ok()?;
+
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,341 @@
+//! This file contains the `Session` interface, which runs the phases of the compiler one after the other.
+
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use fluid_codegen::{load_library, CodeGen, CodeGenType, TargetOptions};
+use fluid_error::{DiagnosticHandler, SourceFile};
+use fluid_lexer::{dump_tokens, Lexer};
+use fluid_mangle::Mangling;
+use fluid_parser::{dump_ast, Ast, Parser, Type};
+use fluid_typeck::TypeChecker;
+
+use crate::{c_header, link, profile, timings, Emit};
+
+/// A file of the program along with its checked AST.
+pub type Module = (Arc<SourceFile>, Ast);
+
+/// The options of the compilations of a session.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The machine to generate the code for.
+    pub target: TargetOptions,
+    /// The mangling scheme of the function symbols.
+    pub mangling: Mangling,
+    /// The artifacts to write. The tokens and the AST are written next to every file as soon as it is checked, so
+    /// they are there even if a later phase fails, the C header by `Session::emit_header` and the others by
+    /// `Session::emit`.
+    pub emit: Vec<Emit>,
+    /// The libraries to link the executables with and to load into the JIT, like `m` for the math library.
+    pub libraries: Vec<String>,
+    /// The directories to search for the libraries in, before the ones of the system.
+    pub library_paths: Vec<PathBuf>,
+}
+
+/// The error of a compilation.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The program has errors, they were emitted to the diagnostic handler of the session.
+    Diagnostics,
+    /// A file could not be read or written, or the code could not be generated for the target.
+    Other(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Diagnostics => write!(f, "could not compile the program due to the previous errors"),
+            CompileError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+impl From<String> for CompileError {
+    fn from(message: String) -> Self {
+        CompileError::Other(message)
+    }
+}
+
+impl From<io::Error> for CompileError {
+    fn from(error: io::Error) -> Self {
+        CompileError::Other(error.to_string())
+    }
+}
+
+/// A session of the compiler. The diagnostics of every phase are emitted to its handler, which gives them to the
+/// application through its emitter, and its type checker keeps the declarations of the files checked so far, so a
+/// file can use the declarations of the files before it.
+pub struct Session {
+    /// The handler of the diagnostics of every phase.
+    handler: DiagnosticHandler,
+    /// The options of the compilations.
+    options: Options,
+    /// The type checker, shared by the files of the session.
+    checker: TypeChecker,
+}
+
+impl Session {
+    /// Create a session that emits the diagnostics to the handler.
+    pub fn new(handler: DiagnosticHandler, options: Options) -> Self {
+        let checker = TypeChecker::new(Arc::new(SourceFile::new("<session>", "")));
+
+        Self { handler, options, checker }
+    }
+
+    /// Get the diagnostic handler, to print the summary of the diagnostics for example.
+    pub fn handler_mut(&mut self) -> &mut DiagnosticHandler {
+        &mut self.handler
+    }
+
+    /// Get the options of the compilations.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Forget the declarations of the files checked so far.
+    pub fn reset(&mut self) {
+        self.checker.reset();
+    }
+
+    /// Lex, parse and type check a file, emitting the diagnostics to the handler.
+    /// Returns `CompileError::Diagnostics` if there were any errors so far, including denied warnings.
+    pub fn check_file(&mut self, file: Arc<SourceFile>) -> Result<Ast, CompileError> {
+        self.checker.set_file(file.clone());
+
+        let path = PathBuf::from(file.name());
+        let mut lexer = Lexer::from_file(&file);
+        let tokens = match timings::time("lexing", || lexer.run()) {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                self.handler.extend(errors);
+
+                return Err(CompileError::Diagnostics);
+            }
+        };
+
+        if self.options.emit.contains(&Emit::Tokens) {
+            fs::write(Emit::Tokens.path(&path), dump_tokens(&tokens, &lexer.file()))?;
+        }
+
+        let mut parser = Parser::new(&tokens, lexer.file());
+        let result = timings::time("parsing", || parser.run());
+        let warnings = parser.take_warnings();
+
+        let mut ast = match result {
+            Ok(ast) => ast,
+            Err(errors) => {
+                self.handler.extend(errors.into_iter().chain(warnings));
+
+                return Err(CompileError::Diagnostics);
+            }
+        };
+
+        let checker = &mut self.checker;
+        let errors = timings::time("type checking", || checker.run(&mut ast)).err().unwrap_or_default();
+
+        self.handler.extend(errors.into_iter().chain(warnings).chain(self.checker.take_warnings()));
+
+        if self.options.emit.contains(&Emit::Ast) {
+            fs::write(Emit::Ast.path(&path), dump_ast(&ast))?;
+        }
+
+        if self.handler.has_errors() {
+            Err(CompileError::Diagnostics)
+        } else {
+            Ok(ast)
+        }
+    }
+
+    /// Check the files in order. Every file is checked even if one has errors, so that the diagnostics of all of
+    /// them are reported.
+    pub fn check_files(&mut self, files: Vec<Arc<SourceFile>>) -> Result<Vec<Module>, CompileError> {
+        let mut modules = vec![];
+
+        for file in files {
+            match self.check_file(file.clone()) {
+                Ok(ast) => modules.push((file, ast)),
+                Err(CompileError::Diagnostics) => (),
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.handler.has_errors() {
+            Err(CompileError::Diagnostics)
+        } else {
+            Ok(modules)
+        }
+    }
+
+    /// Infer the type of the expression of the file from the declarations of the files checked so far.
+    /// Returns `None` for `null`, which has no type of its own.
+    pub fn infer_type(&mut self, file: Arc<SourceFile>) -> Result<Option<Type>, CompileError> {
+        self.checker.set_file(file.clone());
+
+        let checker = &mut self.checker;
+        let mut lexer = Lexer::from_file(&file);
+
+        let result = lexer
+            .run()
+            .and_then(|tokens| Parser::new(&tokens, lexer.file()).run_expression())
+            .and_then(|(expression, mut exprs)| checker.infer_expression(&mut exprs, expression));
+
+        result.map_err(|errors| {
+            self.handler.extend(errors);
+
+            CompileError::Diagnostics
+        })
+    }
+
+    /// Write the C header of the checked modules named after the stem, if it is one of the artifacts of the options.
+    pub fn emit_header(&self, modules: &[Module], stem: &Path) -> Result<(), CompileError> {
+        if self.options.emit.contains(&Emit::CHeader) {
+            let name = stem.file_stem().map_or_else(|| stem.to_string_lossy(), |name| name.to_string_lossy());
+
+            fs::write(Emit::CHeader.path(stem), c_header(&name, modules))?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a code generator for the target of the options, with the module named after the name.
+    pub fn codegen(&self, name: &str, codegen_type: CodeGenType) -> Result<CodeGen, CompileError> {
+        let mut codegen = CodeGen::with_target(name, codegen_type, &self.options.target)?;
+
+        codegen.set_mangling(self.options.mangling);
+
+        if profile::is_enabled() {
+            codegen.record_function_times();
+        }
+
+        Ok(codegen)
+    }
+
+    /// Load the libraries of the options into the compiler, so that the extern functions of the programs compiled just
+    /// in time resolve to their functions.
+    pub fn load_libraries(&self) -> Result<(), CompileError> {
+        for library in self.options.libraries.iter() {
+            // The compiler is linked with the C and the math libraries, so their functions are already available, and
+            // their `.so` files are linker scripts on glibc that can not be loaded.
+            if cfg!(unix) && (library == "c" || library == "m") {
+                continue;
+            }
+
+            load_library(&link::shared_library_path(library, &self.options.library_paths))?;
+        }
+
+        Ok(())
+    }
+
+    /// Generate the code of the modules, which must have been checked by the session.
+    /// The time spent in the function passes of LLVM is recorded apart from the rest of the code generation.
+    /// The self-profile has the time spent generating every function, including its function passes.
+    pub fn generate(&mut self, codegen: &mut CodeGen, modules: Vec<Module>) -> Result<(), CompileError> {
+        let pass_time = codegen.pass_time();
+        let start = Instant::now();
+        let handler = &mut self.handler;
+        let result = profile::phase("code generation", || codegen.run_modules(modules, handler));
+        let pass_time = codegen.pass_time() - pass_time;
+
+        profile::functions(codegen.take_function_times());
+
+        timings::record("code generation", start.elapsed() - pass_time);
+        timings::record("LLVM function passes", pass_time);
+
+        result.map_err(|_| CompileError::Diagnostics)
+    }
+
+    /// Check the files and generate their code, with the module named after the first file.
+    pub fn compile(&mut self, files: Vec<Arc<SourceFile>>, codegen_type: CodeGenType) -> Result<CodeGen, CompileError> {
+        let mut codegen = self.codegen(files[0].name(), codegen_type)?;
+        let modules = self.check_files(files)?;
+
+        self.generate(&mut codegen, modules)?;
+
+        Ok(codegen)
+    }
+
+    /// Write the artifacts of the options that are produced by the code generator, named after the stem.
+    /// The object file is only an intermediate artifact of the executable if it was not requested.
+    pub fn emit(&mut self, codegen: &mut CodeGen, stem: &Path) -> Result<(), CompileError> {
+        let emit = &self.options.emit;
+
+        if emit.contains(&Emit::LlvmIr) {
+            timings::time("emitting LLVM IR", || codegen.emit_llvm(&Emit::LlvmIr.path(stem)))?;
+        }
+
+        if emit.contains(&Emit::Bitcode) {
+            timings::time("emitting bitcode", || codegen.emit_bitcode(&Emit::Bitcode.path(stem)))?;
+        }
+
+        if emit.contains(&Emit::Asm) {
+            timings::time("emitting assembly", || codegen.emit_assembly(&Emit::Asm.path(stem)))?;
+        }
+
+        if emit.contains(&Emit::Object) || emit.contains(&Emit::Exe) {
+            let object = Emit::Object.path(stem);
+
+            timings::time("emitting the object file", || codegen.emit_object(&object))?;
+
+            if emit.contains(&Emit::Exe) {
+                let executable = link::executable_path(&object, self.options.target.triple.as_deref());
+
+                timings::time("linking", || link::link(&object, &executable, &self.options))?;
+
+                if !emit.contains(&Emit::Object) {
+                    fs::remove_file(&object)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the files of the paths, a directory is replaced by the `.fluid` files in it and its subdirectories in the
+/// order of their paths.
+pub fn read_files(paths: &[String]) -> Result<Vec<Arc<SourceFile>>, CompileError> {
+    let mut files = vec![];
+
+    for path in paths {
+        let count = files.len();
+
+        collect_fluid_files(Path::new(path), &mut files)?;
+
+        if files.len() == count {
+            return Err(format!("there are no Fluid files in `{}`", path).into());
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|file| Ok(Arc::new(SourceFile::new(file.to_string_lossy(), fs::read_to_string(&file)?))))
+        .collect()
+}
+
+/// Collect the path of the file, or the paths of the `.fluid` files in the directory and its subdirectories.
+pub fn collect_fluid_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().map_or(false, |extension| extension == "fluid") {
+            collect_fluid_files(&entry, files)?;
+        }
+    }
+
+    Ok(())
+}
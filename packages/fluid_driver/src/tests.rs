@@ -0,0 +1,177 @@
+//! This file contains all of the unit tests for the driver.
+
+use std::{
+    cell::RefCell,
+    env, fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fluid_codegen::{CodeGenType, FunctionTime};
+use fluid_error::{Diagnostic, DiagnosticHandler, Emitter, SourceFile};
+use fluid_parser::Type;
+
+use crate::{c_header, link::shared_library_path, profile, CompileError, Emit, Options, Session};
+
+/// Shares the emitted diagnostics with the test, the handler owns its emitter.
+struct SharedEmitter(Rc<RefCell<Vec<Diagnostic>>>);
+
+impl Emitter for SharedEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        self.0.borrow_mut().push(diagnostic.clone());
+    }
+}
+
+/// Create a session along with the diagnostics it emits.
+fn session() -> (Session, Rc<RefCell<Vec<Diagnostic>>>) {
+    let diagnostics = Rc::new(RefCell::new(vec![]));
+    let handler = DiagnosticHandler::new(Box::new(SharedEmitter(diagnostics.clone())));
+
+    (Session::new(handler, Options::default()), diagnostics)
+}
+
+#[inline]
+fn file(name: &str, source: &str) -> Arc<SourceFile> {
+    Arc::new(SourceFile::new(name, source))
+}
+
+#[test]
+fn test_check_files() {
+    let (mut session, diagnostics) = session();
+
+    let files = vec![
+        file("a.fluid", "function add(a: number, b: number) -> number {\n    return a + b;\n}\n"),
+        file("b.fluid", "function main() -> number {\n    return add(1, 2);\n}\n"),
+    ];
+
+    let modules = session.check_files(files).unwrap();
+
+    assert_eq!(modules.iter().map(|(file, _)| file.name()).collect::<Vec<_>>(), vec!["a.fluid", "b.fluid"]);
+    assert!(diagnostics.borrow().is_empty());
+}
+
+#[test]
+fn test_check_files_errors() {
+    let (mut session, diagnostics) = session();
+
+    // Every file is checked, so the errors of both of them are reported.
+    let files = vec![
+        file("a.fluid", "function main() -> number {\n    return x;\n}\n"),
+        file("b.fluid", "function f() -> number {\n    return 1 + true;\n}\n"),
+    ];
+
+    assert!(matches!(session.check_files(files), Err(CompileError::Diagnostics)));
+    assert_eq!(diagnostics.borrow().len(), 2);
+    assert!(session.handler_mut().has_errors());
+}
+
+#[test]
+fn test_infer_type() {
+    let (mut session, _) = session();
+
+    session.check_file(file("<stdin>", "function half(x: float) -> float {\n    return x / 2.0;\n}\n")).unwrap();
+
+    assert_eq!(session.infer_type(file("<stdin>", "half(1.0) * 2.0")).unwrap(), Some(Type::Float));
+    assert_eq!(session.infer_type(file("<stdin>", "null")).unwrap(), None);
+    assert!(matches!(session.infer_type(file("<stdin>", "half(1)")), Err(CompileError::Diagnostics)));
+
+    // The declarations are forgotten once the session is reset.
+    session.reset();
+    session.handler_mut().reset();
+
+    assert!(matches!(session.infer_type(file("<stdin>", "half(1.0)")), Err(CompileError::Diagnostics)));
+}
+
+#[test]
+fn test_run_tests() {
+    let (mut session, _) = session();
+
+    let source = "test function adds() {\n    assert(1 + 1 == 2);\n}\n\ntest function compares() {\n    var word: string = \"abc\";\n\n    assert(word == \"abc\");\n    assert(2 < 3);\n    assert('a' == 'a');\n}\n";
+    let logic = "function fails() -> bool {\n    assert(false);\n    return true;\n}\n\ntest function logic() {\n    var x: number = 1;\n\n    x = (x + 1) * 2;\n\n    assert(x == 4);\n    assert(!(x < 4));\n    assert(x == 4 && !false);\n    assert(x < 0 || true);\n    assert(!(false && fails()));\n    assert(true || fails());\n}\n";
+    let codegen = session.compile(vec![file("tests.fluid", source), file("logic.fluid", logic)], CodeGenType::Test).unwrap();
+
+    assert_eq!(codegen.tests(), vec!["adds", "compares", "logic"]);
+
+    // A failed assertion exits the process, so the tests passed if they return.
+    codegen.run_test("adds").unwrap();
+    codegen.run_test("compares").unwrap();
+
+    // The right operand of `&&` and `||` is not evaluated when the left one decides the result.
+    codegen.run_test("logic").unwrap();
+
+    assert!(codegen.run_test("missing").is_err());
+}
+
+#[test]
+fn test_emit() {
+    assert_eq!("llvm-ir".parse(), Ok(Emit::LlvmIr));
+    assert!("exe,obj".parse::<Emit>().is_err());
+
+    assert_eq!("c-header".parse(), Ok(Emit::CHeader));
+    assert!(!Emit::Ast.needs_codegen());
+    assert!(!Emit::CHeader.needs_codegen());
+    assert!(Emit::Exe.needs_codegen());
+    assert_eq!(Emit::Exe.path("src/main.fluid".as_ref()), PathBuf::from("src/main.obj"));
+}
+
+#[test]
+fn test_c_header() {
+    let (mut session, _) = session();
+
+    let source = "export function greet(name: string, times: number) -> float {\n    return 1.0;\n}\n\nfunction helper() {}\n\nexport function tick() -> number {\n    return 1;\n}\n";
+    let modules = session.check_files(vec![file("hello-world.fluid", source)]).unwrap();
+    let header = c_header("hello-world", &modules);
+
+    assert!(header.starts_with("#ifndef HELLO_WORLD_H\n#define HELLO_WORLD_H\n"));
+    assert!(header.contains("float greet(const char *name, int64_t times);\nint64_t tick(void);\n"));
+    assert!(!header.contains("helper"));
+    assert!(header.ends_with("#endif /* HELLO_WORLD_H */\n"));
+}
+
+#[test]
+fn test_shared_library_path() {
+    let file_name = format!("{}fluid_test{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX);
+    let dir = env::temp_dir().join("fluid_driver_test_shared_library_path");
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(&file_name), "").unwrap();
+
+    // The library is searched for in the directories, then left to the system.
+    assert_eq!(shared_library_path("fluid_test", &[PathBuf::from("missing"), dir.clone()]), dir.join(&file_name));
+    assert_eq!(shared_library_path("fluid_test", &[PathBuf::from("missing")]), PathBuf::from(&file_name));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_self_profile() {
+    let dir = env::temp_dir().join("fluid_driver_test_self_profile");
+    let function = || FunctionTime {
+        name: "main".to_owned(),
+        start: Instant::now(),
+        duration: Duration::from_micros(1),
+    };
+
+    // The functions are nested in the phase that generated them.
+    profile::enable(dir.join("profile.folded"));
+    profile::phase("code generation", || profile::functions(vec![function()]));
+    profile::write().unwrap();
+
+    let folded = fs::read_to_string(dir.join("profile.folded")).unwrap();
+    let stacks = folded.lines().map(|line| line.trim_end_matches(|c: char| c.is_ascii_digit()).trim_end()).collect::<Vec<_>>();
+
+    assert_eq!(stacks, vec!["code generation", "code generation;main"]);
+
+    profile::enable(dir.join("profile.json"));
+    profile::phase("lexing", || ());
+    profile::write().unwrap();
+
+    let trace = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(dir.join("profile.json")).unwrap()).unwrap();
+    let events = trace["traceEvents"].as_array().unwrap();
+
+    assert!(events.iter().any(|event| event["name"] == "lexing" && event["ph"] == "X"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,65 @@
+//! The time spent in each phase of the compiler, printed with `--timings`.
+
+use std::{
+    cell::RefCell,
+    mem,
+    time::{Duration, Instant},
+};
+
+use crate::profile;
+
+thread_local! {
+    /// The phases in the order they first ran, along with the total time spent in them. `None` if the timings are
+    /// not recorded.
+    static TIMINGS: RefCell<Option<Vec<(&'static str, Duration)>>> = RefCell::new(None);
+}
+
+/// Record the time spent in the phases from now on.
+pub fn enable() {
+    TIMINGS.with(|timings| *timings.borrow_mut() = Some(vec![]));
+}
+
+/// Add the duration to the time spent in the phase. The phases that run several times, like the lexer for every
+/// file, are summed.
+pub fn record(phase: &'static str, duration: Duration) {
+    TIMINGS.with(|timings| {
+        if let Some(timings) = timings.borrow_mut().as_mut() {
+            match timings.iter_mut().find(|(name, _)| *name == phase) {
+                Some((_, total)) => *total += duration,
+                None => timings.push((phase, duration)),
+            }
+        }
+    });
+}
+
+/// Run the phase, and record the time spent in it. The phase is part of the self-profile too, if there is one.
+pub fn time<T>(phase: &'static str, run: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = profile::phase(phase, run);
+
+    record(phase, start.elapsed());
+
+    result
+}
+
+/// Print the time spent in every phase to the standard error, then start recording again from zero.
+///
+/// ```text
+/// time:    0.412ms  lexing
+/// time:    0.983ms  parsing
+/// time:    1.395ms  total
+/// ```
+pub fn print() {
+    let timings = match TIMINGS.with(|timings| timings.borrow_mut().as_mut().map(mem::take)) {
+        Some(timings) => timings,
+        None => return,
+    };
+
+    let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+    for (phase, duration) in timings.iter() {
+        eprintln!("time: {:>9.3}ms  {}", milliseconds(*duration), phase);
+    }
+
+    eprintln!("time: {:>9.3}ms  total", milliseconds(timings.iter().map(|(_, duration)| *duration).sum()));
+}
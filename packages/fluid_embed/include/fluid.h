@@ -0,0 +1,64 @@
+#ifndef FLUID_H
+#define FLUID_H
+
+/* The C API of `src/lib.rs`, the tests check that it declares all of its functions. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A context of the JIT. The code evaluated in a context can use the declarations of the code evaluated in it before,
+ * like the inputs of the REPL, and the values of its top level expressions are printed to the standard output.
+ * A context must only be used by one thread at a time.
+ */
+typedef struct FluidContext FluidContext;
+
+/**
+ * Create a context. Returns null if the JIT can not be created for the machine.
+ */
+FluidContext *fluid_context_new(void);
+
+/**
+ * Evaluate the source in the context: declare its functions and variables, and run its top level expressions.
+ * Returns `0` if it succeeded, and `1` if the source has errors, which are described by `fluid_last_error`.
+ *
+ * # Safety
+ *
+ * The context must have been created by `fluid_context_new`, and the source must be a nul terminated string.
+ */
+int fluid_eval(FluidContext *context, const char *source);
+
+/**
+ * Get the address of the function with the name, to cast it to a function pointer of its signature and call it.
+ * The arguments and the return value of type `number` are `int64_t`, `float` is `float`, `bool` is `bool`, `char`
+ * is `char` and `string` is `const char *`. Returns null if there is no function with the name, or if it is overloaded.
+ * The address is valid until the next call to `fluid_eval` with the context, or until it is freed.
+ *
+ * # Safety
+ *
+ * The context must have been created by `fluid_context_new`, and the name must be a nul terminated string.
+ */
+const void *fluid_get_function(FluidContext *context, const char *name);
+
+/**
+ * Get the message of the last error of the context, an empty string if there was none. The message is valid until
+ * the next error of the context, or until it is freed.
+ *
+ * # Safety
+ *
+ * The context must have been created by `fluid_context_new`.
+ */
+const char *fluid_last_error(const FluidContext *context);
+
+/**
+ * Free the context and the code compiled in it. Freeing null does nothing.
+ *
+ * # Safety
+ *
+ * The context must have been created by `fluid_context_new`, and must not be used afterwards.
+ */
+void fluid_context_free(FluidContext *context);
+
+#endif /* FLUID_H */
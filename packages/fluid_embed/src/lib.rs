@@ -0,0 +1,191 @@
+//! The `fluid_embed` crate is the C API of the JIT, so that the applications written in other languages can embed
+//! Fluid as a scripting language. It is built as a shared and a static library, and `include/fluid.h` declares its
+//! functions.
+//!
+//! ```c
+//! FluidContext *context = fluid_context_new();
+//!
+//! if (fluid_eval(context, "function add(a: number, b: number) -> number { return a + b; }") != 0) {
+//!     fprintf(stderr, "%s\n", fluid_last_error(context));
+//! }
+//!
+//! int64_t (*add)(int64_t, int64_t) = (int64_t (*)(int64_t, int64_t))fluid_get_function(context, "add");
+//! printf("%lld\n", (long long)add(1, 2));
+//!
+//! fluid_context_free(context);
+//! ```
+
+#![deny(trivial_numeric_casts, unused_extern_crates, unstable_features, missing_docs)]
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    rc::Rc,
+    sync::Arc,
+};
+
+use fluid_codegen::{CodeGen, CodeGenType};
+use fluid_driver::{CompileError, Options, Session};
+use fluid_error::{Diagnostic, DiagnosticHandler, Emitter, SourceFile};
+
+/// Renders the diagnostics of an evaluation into the message of its error.
+struct MessageEmitter(Rc<RefCell<Vec<String>>>);
+
+impl Emitter for MessageEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        self.0.borrow_mut().push(diagnostic.render(false));
+    }
+}
+
+/// A context of the JIT. The code evaluated in a context can use the declarations of the code evaluated in it before,
+/// like the inputs of the REPL, and the values of its top level expressions are printed to the standard output.
+/// A context must only be used by one thread at a time.
+pub struct FluidContext {
+    session: Session,
+    codegen: CodeGen,
+    /// The rendered diagnostics of the current evaluation, shared with the emitter of the session.
+    diagnostics: Rc<RefCell<Vec<String>>>,
+    /// The message of the last error, empty if there was none.
+    last_error: CString,
+}
+
+impl FluidContext {
+    fn new() -> Result<Self, String> {
+        let diagnostics = Rc::new(RefCell::new(vec![]));
+        let handler = DiagnosticHandler::new(Box::new(MessageEmitter(diagnostics.clone())));
+        let session = Session::new(handler, Options::default());
+        let codegen = session.codegen("__embed__", CodeGenType::Repl).map_err(|error| error.to_string())?;
+
+        Ok(Self {
+            session,
+            codegen,
+            diagnostics,
+            last_error: CString::default(),
+        })
+    }
+
+    /// Check the source and generate its code. The error is the diagnostics of the source.
+    fn eval(&mut self, source: &str) -> Result<(), String> {
+        let file = Arc::new(SourceFile::new("<eval>", source));
+
+        self.session.handler_mut().reset();
+        self.diagnostics.borrow_mut().clear();
+
+        let result = self.session.check_file(file.clone()).and_then(|ast| self.session.generate(&mut self.codegen, vec![(file, ast)]));
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(CompileError::Diagnostics) => Err(self.diagnostics.borrow().join("\n")),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    /// Keep the message of the error, until the next one.
+    fn set_error(&mut self, message: String) {
+        // A message can not contain a nul byte, except if the source does.
+        self.last_error = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    }
+}
+
+/// Get the string of a C string, or an error if it is null or not UTF-8.
+unsafe fn read_string<'a>(string: *const c_char, what: &str) -> Result<&'a str, String> {
+    if string.is_null() {
+        return Err(format!("the {} is null", what));
+    }
+
+    CStr::from_ptr(string).to_str().map_err(|_| format!("the {} is not valid UTF-8", what))
+}
+
+/// Run a function of the API, the panics are errors since they must not unwind into C.
+fn catch_panic<T>(run: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|_| Err("internal compiler error: the compiler panicked".to_owned()))
+}
+
+/// Create a context. Returns null if the JIT can not be created for the machine.
+#[no_mangle]
+pub extern "C" fn fluid_context_new() -> *mut FluidContext {
+    match catch_panic(FluidContext::new) {
+        Ok(context) => Box::into_raw(Box::new(context)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Evaluate the source in the context: declare its functions and variables, and run its top level expressions.
+/// Returns `0` if it succeeded, and `1` if the source has errors, which are described by `fluid_last_error`.
+///
+/// # Safety
+///
+/// The context must have been created by `fluid_context_new`, and the source must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fluid_eval(context: *mut FluidContext, source: *const c_char) -> c_int {
+    let context = match context.as_mut() {
+        Some(context) => context,
+        None => return 1,
+    };
+
+    match catch_panic(|| context.eval(read_string(source, "source")?)) {
+        Ok(()) => 0,
+        Err(message) => {
+            context.set_error(message);
+
+            1
+        }
+    }
+}
+
+/// Get the address of the function with the name, to cast it to a function pointer of its signature and call it.
+/// The arguments and the return value of type `number` are `int64_t`, `float` is `float`, `bool` is `bool`, `char`
+/// is `char` and `string` is `const char *`. Returns null if there is no function with the name, or if it is overloaded.
+/// The address is valid until the next call to `fluid_eval` with the context, or until it is freed.
+///
+/// # Safety
+///
+/// The context must have been created by `fluid_context_new`, and the name must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fluid_get_function(context: *mut FluidContext, name: *const c_char) -> *const c_void {
+    let context = match context.as_mut() {
+        Some(context) => context,
+        None => return ptr::null(),
+    };
+
+    match catch_panic(|| context.codegen.function_address(read_string(name, "name")?)) {
+        Ok(address) => address as *const c_void,
+        Err(message) => {
+            context.set_error(message);
+
+            ptr::null()
+        }
+    }
+}
+
+/// Get the message of the last error of the context, an empty string if there was none. The message is valid until
+/// the next error of the context, or until it is freed.
+///
+/// # Safety
+///
+/// The context must have been created by `fluid_context_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fluid_last_error(context: *const FluidContext) -> *const c_char {
+    match context.as_ref() {
+        Some(context) => context.last_error.as_ptr(),
+        None => b"the context is null\0".as_ptr() as *const c_char,
+    }
+}
+
+/// Free the context and the code compiled in it. Freeing null does nothing.
+///
+/// # Safety
+///
+/// The context must have been created by `fluid_context_new`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fluid_context_free(context: *mut FluidContext) {
+    if !context.is_null() {
+        drop(Box::from_raw(context));
+    }
+}
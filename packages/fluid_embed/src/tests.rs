@@ -0,0 +1,126 @@
+//! This file contains all of the unit tests for the C API.
+
+use std::{
+    ffi::{CStr, CString},
+    fs, mem,
+    os::raw::c_char,
+    path::Path,
+    ptr,
+};
+
+use crate::{fluid_context_free, fluid_context_new, fluid_eval, fluid_get_function, fluid_last_error, FluidContext};
+
+#[inline]
+fn cstring(string: &str) -> CString {
+    CString::new(string).unwrap()
+}
+
+unsafe fn last_error(context: *const FluidContext) -> String {
+    CStr::from_ptr(fluid_last_error(context)).to_str().unwrap().to_owned()
+}
+
+#[test]
+fn test_eval() {
+    unsafe {
+        let context = fluid_context_new();
+
+        assert!(!context.is_null());
+        assert_eq!(fluid_eval(context, cstring("function add(a: number, b: number) -> number {\n    return a + b;\n}\n").as_ptr()), 0);
+        assert_eq!(last_error(context), "");
+
+        // The declarations of the previous sources can be used.
+        assert_eq!(fluid_eval(context, cstring("function twice(x: number) -> number {\n    return add(x, x);\n}\n").as_ptr()), 0);
+
+        let add = fluid_get_function(context, cstring("add").as_ptr());
+        let twice = fluid_get_function(context, cstring("twice").as_ptr());
+
+        assert!(!add.is_null() && !twice.is_null());
+
+        let add: extern "C" fn(i64, i64) -> i64 = mem::transmute(add);
+        let twice: extern "C" fn(i64) -> i64 = mem::transmute(twice);
+
+        assert_eq!(add(1, 2), 3);
+        assert_eq!(twice(-21), -42);
+
+        fluid_context_free(context);
+    }
+}
+
+#[test]
+fn test_errors() {
+    unsafe {
+        let context = fluid_context_new();
+
+        assert_eq!(fluid_eval(context, cstring("function f() -> number {\n    return x;\n}\n").as_ptr()), 1);
+
+        let error = last_error(context);
+
+        assert!(error.contains("E0010"), "{}", error);
+        assert!(error.contains("cannot find value `x` in this scope"), "{}", error);
+
+        // The functions of a source with errors are not declared.
+        assert!(fluid_get_function(context, cstring("f").as_ptr()).is_null());
+        assert_eq!(last_error(context), "there is no function named `f`");
+
+        // A format string is a C string, so it can not contain a null character.
+        assert_eq!(fluid_eval(context, cstring("function g() -> string {\n    return format(\"a\\0b\");\n}\n").as_ptr()), 1);
+
+        let error = last_error(context);
+
+        assert!(error.contains("E0016"), "{}", error);
+        assert!(error.contains("null characters in format strings are not supported"), "{}", error);
+
+        // The context can still be used after an error.
+        assert_eq!(fluid_eval(context, cstring("function one() -> number {\n    return 1;\n}\n").as_ptr()), 0);
+        assert!(!fluid_get_function(context, cstring("one").as_ptr()).is_null());
+
+        fluid_context_free(context);
+    }
+}
+
+#[test]
+fn test_null() {
+    unsafe {
+        let source = cstring("function f() {}");
+        let name = cstring("f");
+
+        assert_eq!(fluid_eval(ptr::null_mut(), source.as_ptr()), 1);
+        assert!(fluid_get_function(ptr::null_mut(), name.as_ptr()).is_null());
+        assert_eq!(last_error(ptr::null()), "the context is null");
+
+        let context = fluid_context_new();
+
+        assert_eq!(fluid_eval(context, ptr::null::<c_char>()), 1);
+        assert_eq!(last_error(context), "the source is null");
+
+        assert!(fluid_get_function(context, ptr::null::<c_char>()).is_null());
+        assert_eq!(last_error(context), "the name is null");
+
+        fluid_context_free(context);
+        fluid_context_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_header() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let header = fs::read_to_string(dir.join("include/fluid.h")).unwrap();
+    let source = fs::read_to_string(dir.join("src/lib.rs")).unwrap();
+
+    // The header is written by hand, so every function of the API must be declared in it.
+    let functions = source
+        .lines()
+        .filter_map(|line| line.split("extern \"C\" fn ").nth(1))
+        .map(|line| line.split('(').next().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(functions.len(), 5);
+
+    for function in functions {
+        assert!(
+            header.contains(&format!(" {}(", function)) || header.contains(&format!("*{}(", function)),
+            "`{}` is not declared in the header",
+            function
+        );
+    }
+}
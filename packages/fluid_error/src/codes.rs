@@ -0,0 +1,412 @@
+//! The registry of all of the diagnostic codes with their long-form explanations.
+//!
+//! Every code reported by the compiler must be registered here, the explanations
+//! are printed by `fluid explain <code>`.
+
+macro_rules! register_diagnostics {
+    ($($code:ident: $explanation:literal,)*) => {
+        $(pub const $code: &str = stringify!($code);)*
+
+        /// All of the registered codes along with their explanations.
+        pub const DIAGNOSTICS: &[(&str, &str)] = &[$((stringify!($code), $explanation),)*];
+    };
+}
+
+/// Get the long-form explanation of a diagnostic code.
+pub fn explain(code: &str) -> Option<&'static str> {
+    DIAGNOSTICS.iter().find(|(registered, _)| registered.eq_ignore_ascii_case(code)).map(|(_, explanation)| *explanation)
+}
+
+register_diagnostics! {
+    E0001: r#"An illegal character was encountered in the source.
+
+Erroneous code example:
+
+```fluid
+var price: number = $10;
+```
+
+The character `$` is not part of any Fluid token. Remove it or, if it is part
+of some text, put it inside of a string literal.
+"#,
+
+    E0002: r#"A string literal, character literal or block comment was not terminated.
+
+Erroneous code example:
+
+```fluid
+var greeting: string = "Hello, World!;
+```
+
+Add the missing closing delimiter:
+
+```fluid
+var greeting: string = "Hello, World!";
+```
+"#,
+
+    E0003: r#"A string literal contains an invalid escape sequence.
+
+Erroneous code example:
+
+```fluid
+var path: string = "C:\fluid";
+```
+
+The valid escapes are `\n`, `\t`, `\r`, `\0`, `\b`, `\"`, `\xHH` and
+`\u{HHHHHH}` (at most 6 hex digits). To write a backslash escape it as well.
+"#,
+
+    E0004: r#"A number literal does not fit in its type.
+
+Erroneous code example:
+
+```fluid
+var big: number = 99999999999999999999;
+```
+
+A `number` is a 64-bit integer, its literals must be at most
+`18446744073709551615`.
+"#,
+
+    E0005: r#"A number literal contains more than one decimal point.
+
+Erroneous code example:
+
+```fluid
+var version: float = 1.2.3;
+```
+
+A float literal can only have a single `.`, use a string for version numbers.
+"#,
+
+    E0006: r#"The parser encountered a token that it did not expect.
+
+Erroneous code example:
+
+```fluid
+var x: number = 1
+var y: number = 2;
+```
+
+Every statement must end with a `;`:
+
+```fluid
+var x: number = 1;
+var y: number = 2;
+```
+"#,
+
+    E0007: r#"The left-hand side of an assignment is not a variable.
+
+Erroneous code example:
+
+```fluid
+1 + 2 = 3;
+```
+
+Only variables can be assigned to.
+"#,
+
+    E0008: r#"An operator was applied to operands of types that it does not support.
+
+Erroneous code example:
+
+```fluid
+var x: number = 1 + "2";
+```
+
+The arithmetic operators and `<`, `>` need two `number`s or two `float`s, the
+unary `-` and `+` need a `number` or a `float`, `==` needs two operands of the
+same type and `&&`, `||` and `!` need `bool`s.
+There are no implicit conversions between the types.
+"#,
+
+    E0009: r#"An expression does not have the type that was expected.
+
+Erroneous code example:
+
+```fluid
+var greeting: number = "Hello, World!";
+```
+
+The type of the value must match the type annotation of the variable. The
+same applies to the arguments of a function call, the value of a `return`
+statement or at the end of the body of a function, which is returned, and the
+condition of an `if` statement, which must be a `bool`.
+"#,
+
+    E0010: r#"A variable was used but it is not in scope.
+
+Erroneous code example:
+
+```fluid
+function main() {
+    {
+        var x: number = 1;
+    }
+
+    x = 2;
+}
+```
+
+A variable can only be used after its declaration and inside of the block
+that declares it. Check the spelling of the name or declare the variable.
+"#,
+
+    E0011: r#"A function was called but it is not in scope.
+
+Erroneous code example:
+
+```fluid
+function main() {
+    prnt(1);
+}
+```
+
+A function must be defined, or declared in an `extern` block, before it is
+called. Check the spelling of the name.
+"#,
+
+    E0012: r#"A function was called with the wrong number of arguments.
+
+Erroneous code example:
+
+```fluid
+function add(a: number, b: number) -> number {
+    return a + b;
+}
+
+function main() {
+    add(1);
+}
+```
+
+Pass exactly one argument for every parameter of the function.
+"#,
+
+    E0013: r#"A name was defined more than once in the same scope.
+
+Erroneous code example:
+
+```fluid
+function main() {
+    var x: number = 1;
+    var x: number = 2;
+}
+```
+
+Every variable, argument and function in a scope must have a unique name.
+Rename one of the definitions, or assign to the existing variable instead.
+"#,
+
+    E0014: r#"A number was divided by the literal `0`.
+
+Erroneous code example:
+
+```fluid
+var x: number = 1 / 0;
+```
+
+Dividing a `number` by zero aborts the program at runtime, so a division by a
+literal zero is rejected. Dividing a `float` by zero is allowed and results in
+an infinity or NaN, as defined by IEEE 754.
+"#,
+
+    E0015: r#"The type of a `null` could not be inferred.
+
+Erroneous code example:
+
+```fluid
+function main() {
+    null;
+}
+```
+
+`null` is a value of every nullable type, which is currently only `string`.
+It can only be used where the type is known from the context, like the value
+of a variable with a type annotation, an argument or a returned value:
+
+```fluid
+var name: string = null;
+```
+"#,
+
+    E0016: r#"The program uses a feature that the code generator does not support yet.
+
+Erroneous code example:
+
+```fluid
+function main() {
+    for () {}
+}
+```
+
+The program is valid, but it can not be compiled yet. Rewrite the code without
+the feature, the error points at the unsupported code.
+"#,
+
+    E0017: r#"A value that can not be printed was given to `print`, `println` or `format`.
+
+Erroneous code example:
+
+```fluid
+function greet() {}
+
+function main() {
+    println(greet());
+}
+```
+
+Only `number`, `float`, `string`, `bool` and `char` values can be printed. A
+call to a function that returns nothing has no value to print.
+"#,
+
+    E0018: r#"A function is defined in more than one module of the program.
+
+Erroneous code example:
+
+```fluid
+// In `a.fluid`:
+function helper() {}
+
+// In `b.fluid`:
+function helper() {}
+```
+
+The functions of all of the modules of a program share one namespace, so every
+function must be defined by only one module. Rename one of the functions.
+"#,
+
+    E0019: r#"A test function takes arguments or returns a value.
+
+Erroneous code example:
+
+```fluid
+test function adds(a: number) -> number {
+    return a + 1;
+}
+```
+
+The tests are run by `fluid test` without any arguments, and they fail by
+calling `assert` with a false condition instead of returning a value:
+
+```fluid
+test function adds() {
+    assert(1 + 1 == 2);
+}
+```
+"#,
+
+    E0020: r#"The format string of a call to `format` is invalid.
+
+Erroneous code example:
+
+```fluid
+function greet(name: string) -> string {
+    return format("Hello, {}! {}", name);
+}
+```
+
+The format string must be a string literal, since its placeholders are checked
+at compile time. Every `{}` placeholder is replaced by the next value, so there
+must be as many values as placeholders. `{{` and `}}` stand for the braces
+themselves, any other brace is an error.
+"#,
+
+    E0021: r#"A type annotation names a type that does not exist.
+
+Erroneous code example:
+
+```fluid
+var done: boolean = false;
+```
+
+The types are `void`, `number`, `float`, `string`, `bool` and `char`. Check the
+spelling of the name.
+"#,
+
+    E0022: r#"A function with a return type can reach its end without returning a value.
+
+Erroneous code example:
+
+```fluid
+function sign(x: number) -> number {
+    if (x < 0) {
+        return -1;
+    }
+}
+```
+
+Every path through the body must end with a `return`, or the body must end
+with a value without a `;`:
+
+```fluid
+function sign(x: number) -> number {
+    if (x < 0) { -1 } else { 1 }
+}
+```
+"#,
+
+    E0023: r#"Statements or expressions are nested too deeply.
+
+Erroneous code example, with the parentheses repeated 100 times:
+
+```fluid
+var x: number = ((((((((((((((((((((((((((((((((((((1))))))))))))))))))))))))))))))));
+```
+
+Code can be nested at most 64 times, counting the blocks, statements,
+expressions in parentheses and unary operators. Move the nested code to a
+function, or split it with variables:
+
+```fluid
+var inner: number = (((1)));
+var x: number = (((inner)));
+```
+"#,
+
+    W0001: r#"A variable is declared but never used.
+
+Example:
+
+```fluid
+function main() {
+    var unused: number = 1;
+}
+```
+
+Remove the variable, or prefix its name with an underscore (`_unused`) if it
+is intentionally unused.
+"#,
+
+    W0002: r#"A statement can never be executed.
+
+Example:
+
+```fluid
+function answer() -> number {
+    return 42;
+    answer();
+}
+```
+
+Every statement after a `return`, or after an `if` statement whose branches
+all return, is unreachable. Remove the unreachable statements.
+"#,
+
+    W0003: r#"A variable has the same name as a parameter of its function.
+
+Example:
+
+```fluid
+function area(width: number) -> number {
+    var width: number = width * 2;
+    return width * width;
+}
+```
+
+The parameter can not be used anymore in the scope of the variable, which is
+often a mistake. Rename the variable, or assign to the parameter instead.
+"#,
+}
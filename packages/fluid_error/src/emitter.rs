@@ -0,0 +1,87 @@
+//! Emitters print the diagnostics reported by the compiler in different formats.
+
+use std::{env, str::FromStr};
+
+use crate::Diagnostic;
+
+/// Whether the diagnostics should be colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color the output if it is a terminal, unless the `NO_COLOR` environment variable is set.
+    Auto,
+    /// Always color the output.
+    Always,
+    /// Never color the output.
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve the choice into whether the output should be colored. The output is the standard output, where the
+    /// diagnostics are printed. An empty `NO_COLOR` is ignored, as <https://no-color.org> requires.
+    pub fn should_color(self) -> bool {
+        match self {
+            ColorChoice::Auto => env::var_os("NO_COLOR").map_or(true, |value| value.is_empty()) && atty::is(atty::Stream::Stdout),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<Self, Self::Err> {
+        match choice {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("invalid color choice `{}`, expected `auto`, `always` or `never`", choice)),
+        }
+    }
+}
+
+/// Something that the diagnostics can be emitted to.
+pub trait Emitter {
+    /// Emit a diagnostic.
+    fn emit(&mut self, diagnostic: &Diagnostic);
+}
+
+/// Prints human readable diagnostics, optionally colored.
+#[derive(Debug)]
+pub struct HumanEmitter {
+    color: bool,
+}
+
+impl HumanEmitter {
+    pub fn new(color: ColorChoice) -> Self {
+        Self { color: color.should_color() }
+    }
+}
+
+impl Emitter for HumanEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        println!("{}", diagnostic.render(self.color));
+    }
+}
+
+/// Prints every diagnostic as a JSON object on its own line.
+#[derive(Debug, Default)]
+pub struct JsonEmitter;
+
+impl Emitter for JsonEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        println!("{}", diagnostic.to_json());
+    }
+}
+
+/// Silently collects the diagnostics instead of printing them.
+#[derive(Debug, Default)]
+pub struct CollectingEmitter {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Emitter for CollectingEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        self.diagnostics.push(diagnostic.clone());
+    }
+}
@@ -0,0 +1,143 @@
+//! The `DiagnosticHandler` collects the diagnostics reported by every phase of the compiler.
+
+use std::{collections::HashSet, ops::Range};
+
+use crate::{Diagnostic, DiagnosticBuilder, Emitter, LintLevels, Severity};
+
+/// The default number of errors emitted before the rest are suppressed.
+pub const DEFAULT_ERROR_LIMIT: usize = 20;
+
+/// Applies the lint levels to the reported diagnostics, emits them and keeps count of them.
+/// Once the error limit is reached the following errors are counted but not emitted.
+pub struct DiagnosticHandler {
+    emitter: Box<dyn Emitter>,
+    levels: LintLevels,
+    /// The maximum number of errors to emit, zero means no limit.
+    error_limit: usize,
+    errors: usize,
+    warnings: usize,
+    /// The code and the primary span of the emitted diagnostics, a diagnostic is only emitted once.
+    emitted: HashSet<(Option<String>, String, Range<usize>)>,
+}
+
+impl DiagnosticHandler {
+    pub fn new(emitter: Box<dyn Emitter>) -> Self {
+        Self {
+            emitter,
+            levels: LintLevels::new(),
+            error_limit: DEFAULT_ERROR_LIMIT,
+            errors: 0,
+            warnings: 0,
+            emitted: HashSet::new(),
+        }
+    }
+
+    pub fn set_lint_levels(mut self, levels: LintLevels) -> Self {
+        self.levels = levels;
+
+        self
+    }
+
+    pub fn set_error_limit(mut self, error_limit: usize) -> Self {
+        self.error_limit = error_limit;
+
+        self
+    }
+
+    /// Report a diagnostic. Warnings are dropped or promoted to errors according to the lint levels.
+    /// A diagnostic with the same code and span as an already reported one is dropped.
+    pub fn emit(&mut self, diagnostic: Diagnostic) {
+        let diagnostic = match self.levels.apply(diagnostic) {
+            Some(diagnostic) => diagnostic,
+            None => return,
+        };
+
+        if let Some((file, range)) = diagnostic.primary_span() {
+            let key = (diagnostic.code().map(String::from), file.to_string(), range);
+
+            if !self.emitted.insert(key) {
+                return;
+            }
+        }
+
+        if diagnostic.is_error() {
+            self.errors += 1;
+
+            if self.error_limit != 0 && self.errors > self.error_limit {
+                return;
+            }
+        } else {
+            self.warnings += 1;
+        }
+
+        self.emitter.emit(&diagnostic);
+    }
+
+    /// Report all of the diagnostics, sorted by their position in the source.
+    pub fn extend(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        let mut diagnostics = diagnostics.into_iter().collect::<Vec<_>>();
+
+        diagnostics.sort_by(|a, b| {
+            let a = a.primary_span().map(|(file, range)| (file, range.start, range.end));
+            let b = b.primary_span().map(|(file, range)| (file, range.start, range.end));
+
+            a.cmp(&b)
+        });
+
+        for diagnostic in diagnostics {
+            self.emit(diagnostic);
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.warnings
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    /// Returns true if errors have been suppressed because of the error limit.
+    pub fn limit_reached(&self) -> bool {
+        self.error_limit != 0 && self.errors > self.error_limit
+    }
+
+    /// Emit the summary of the reported diagnostics, like `error: aborting due to 2 previous errors`.
+    pub fn print_summary(&mut self) {
+        if self.warnings > 0 {
+            let message = match self.warnings {
+                1 => String::from("1 warning emitted"),
+                warnings => format!("{} warnings emitted", warnings),
+            };
+
+            let summary = DiagnosticBuilder::new(Severity::Warning, message).build();
+            self.emitter.emit(&summary);
+        }
+
+        if self.errors > 0 {
+            let message = match self.errors {
+                1 => String::from("aborting due to previous error"),
+                errors => format!("aborting due to {} previous errors", errors),
+            };
+
+            let mut summary = DiagnosticBuilder::new(Severity::Error, message);
+
+            if self.limit_reached() {
+                summary = summary.set_note(format!("only the first {} errors were shown, use `--error-limit` to change the limit", self.error_limit));
+            }
+
+            self.emitter.emit(&summary.build());
+        }
+    }
+
+    /// Forget the counts, so that the handler can be reused. Used by the REPL after every input.
+    pub fn reset(&mut self) {
+        self.errors = 0;
+        self.warnings = 0;
+        self.emitted.clear();
+    }
+}
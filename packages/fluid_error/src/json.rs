@@ -0,0 +1,104 @@
+//! Machine readable JSON rendering of diagnostics, used by `--message-format=json`.
+
+use crate::{AnnotationType, Diagnostic, Severity};
+
+/// Quote and escape a string as a JSON string.
+fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+
+    escaped.push('"');
+
+    for char in string.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if (char as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+            char => escaped.push(char),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Optional string as a JSON value.
+fn json_option(string: Option<&str>) -> String {
+    string.map(json_string).unwrap_or_else(|| String::from("null"))
+}
+
+fn annotation_name(kind: AnnotationType) -> &'static str {
+    match kind {
+        AnnotationType::Error => "error",
+        AnnotationType::Warning => "warning",
+        AnnotationType::Info => "info",
+        AnnotationType::Note => "note",
+        AnnotationType::Help => "help",
+    }
+}
+
+impl Diagnostic {
+    /// Render the diagnostic as a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let title = self.snippet.title.as_ref();
+
+        let spans = self
+            .spans
+            .iter()
+            .map(|span| {
+                format!(
+                    "{{\"file\":{},\"byte_start\":{},\"byte_end\":{},\"is_primary\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{},\"label\":{}}}",
+                    json_string(&span.file),
+                    span.range.start,
+                    span.range.end,
+                    span.primary,
+                    span.start.0,
+                    span.start.1,
+                    span.end.0,
+                    span.end.1,
+                    json_string(&span.label)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let suggestion_labels = self.suggestions.len();
+        let footer = &self.snippet.footer[..self.snippet.footer.len() - suggestion_labels];
+
+        let mut children = footer
+            .iter()
+            .map(|child| {
+                format!(
+                    "{{\"severity\":{},\"message\":{}}}",
+                    json_string(annotation_name(child.annotation_type)),
+                    json_option(child.label.as_deref())
+                )
+            })
+            .collect::<Vec<_>>();
+
+        children.extend(self.suggestions.iter().map(|suggestion| {
+            format!(
+                "{{\"severity\":\"help\",\"message\":{},\"replacement\":{},\"span_start\":{},\"span_end\":{}}}",
+                json_string(&suggestion.message),
+                json_string(&suggestion.replacement),
+                suggestion.span.start,
+                suggestion.span.end
+            )
+        }));
+
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"spans\":[{}],\"children\":[{}]}}",
+            json_string(severity),
+            json_option(self.code()),
+            json_option(title.and_then(|title| title.label.as_deref())),
+            spans.join(","),
+            children.join(",")
+        )
+    }
+}
@@ -1,6 +1,13 @@
 #![deny(unsafe_code)]
 
-use std::{fmt::Display, ops::Range};
+pub mod codes;
+
+mod emitter;
+mod handler;
+mod json;
+mod source_map;
+
+use std::{collections::HashMap, fmt::Display, ops::Range, sync::Arc};
 
 use annotate_snippets::{
     display_list::{DisplayList, FormatOptions},
@@ -8,27 +15,20 @@ use annotate_snippets::{
 };
 
 pub use annotate_snippets::snippet::AnnotationType;
+pub use emitter::*;
+pub use handler::*;
+pub use source_map::*;
 
-fn source_line(source: &str, line_start: usize, line_end: usize) -> String {
-    source.split("\n").collect::<Vec<_>>()[line_start - 1..line_end].join("\n").to_string()
-}
-
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 pub struct SourceAnnotation {
-    range: Option<Range<usize>>,
+    range: Range<usize>,
     label: Option<String>,
-    kind: Option<AnnotationType>,
+    kind: AnnotationType,
 }
 
 impl SourceAnnotation {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn set_range(mut self, range: Range<usize>) -> Self {
-        self.range = Some(range);
-
-        self
+    pub fn new(range: Range<usize>, kind: AnnotationType) -> Self {
+        Self { range, label: None, kind }
     }
 
     pub fn set_label(mut self, label: impl Into<String>) -> Self {
@@ -37,33 +37,58 @@ impl SourceAnnotation {
         self
     }
 
-    pub fn set_kind(mut self, kind: AnnotationType) -> Self {
-        self.kind = Some(kind);
+    /// The range clamped to the file, so that a bad span can not crash the compiler while reporting an error.
+    fn range(&self, file: &SourceFile) -> Range<usize> {
+        let end = self.range.end.min(file.source().len());
+        let start = self.range.start.min(end);
 
-        self
+        start..end
     }
 
-    pub(crate) fn build(self) -> snippet::SourceAnnotation {
-        let range = self.range.unwrap();
+    /// Convert the absolute byte range into a character range relative to `slice_start`.
+    fn build(&self, file: &SourceFile, slice_start: usize) -> snippet::SourceAnnotation {
+        let range = self.range(file);
+        let start = file.source()[slice_start.min(range.start)..range.start].chars().count();
+        let end = start + file.snippet(range).chars().count();
 
         snippet::SourceAnnotation {
-            range: (range.start, range.end),
-            label: self.label.unwrap_or_default(),
-            annotation_type: self.kind.unwrap(),
+            range: (start, end),
+            label: self.label.clone().unwrap_or_default(),
+            annotation_type: self.kind,
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// A span of a diagnostic, resolved to lines and columns.
+#[derive(Debug, Clone)]
+pub(crate) struct DiagnosticSpan {
+    pub(crate) file: String,
+    pub(crate) range: Range<usize>,
+    pub(crate) primary: bool,
+    pub(crate) start: (usize, usize),
+    pub(crate) end: (usize, usize),
+    pub(crate) label: String,
+}
+
+/// A part of the source shown in a diagnostic.
+/// The annotation ranges are absolute byte ranges, the lines are computed from them unless they are set explicitly.
+/// Every slice has its own file, so one diagnostic can point into several files.
+#[derive(Debug)]
 pub struct Slice {
+    file: Arc<SourceFile>,
     line_start: Option<usize>,
     line_end: Option<usize>,
-    annotations: Vec<snippet::SourceAnnotation>,
+    annotations: Vec<SourceAnnotation>,
 }
 
 impl Slice {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(file: Arc<SourceFile>) -> Self {
+        Self {
+            file,
+            line_start: None,
+            line_end: None,
+            annotations: vec![],
+        }
     }
 
     pub fn set_line_start(mut self, line_start: usize) -> Self {
@@ -79,50 +104,236 @@ impl Slice {
     }
 
     pub fn push_annotation(mut self, source_annotation: SourceAnnotation) -> Self {
-        self.annotations.push(source_annotation.build());
+        self.annotations.push(source_annotation);
 
         self
     }
 
-    pub(crate) fn build(&self, source: &str, origin: &str) -> snippet::Slice {
-        snippet::Slice {
-            source: source_line(source, self.line_start.unwrap(), self.line_end.unwrap_or(self.line_start.unwrap())),
-            origin: Some(origin.to_string()),
-            line_start: self.line_start.unwrap(),
-            annotations: self.annotations.clone(),
+    pub(crate) fn build(&self) -> (snippet::Slice, Vec<DiagnosticSpan>) {
+        let file = &*self.file;
+        let line_count = file.line_count();
+        let ranges = self.annotations.iter().map(|annotation| annotation.range(file));
+
+        let line_start = self
+            .line_start
+            .unwrap_or_else(|| ranges.clone().map(|range| file.line_index(range.start)).min().unwrap_or(1))
+            .max(1)
+            .min(line_count);
+        let line_end = self
+            .line_end
+            .unwrap_or_else(|| ranges.map(|range| file.line_index(range.end.saturating_sub(1).max(range.start))).max().unwrap_or(line_start))
+            .max(line_start)
+            .min(line_count);
+
+        let slice_start = file.line_start(line_start);
+
+        let spans = self
+            .annotations
+            .iter()
+            .map(|annotation| {
+                let range = annotation.range(file);
+
+                DiagnosticSpan {
+                    file: file.name().to_string(),
+                    start: file.line_col(range.start),
+                    end: file.line_col(range.end),
+                    range,
+                    primary: annotation.kind != AnnotationType::Info,
+                    label: annotation.label.clone().unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let slice = snippet::Slice {
+            source: file.lines(line_start, line_end).to_string(),
+            origin: Some(file.name().to_string()),
+            line_start,
+            annotations: self.annotations.iter().map(|annotation| annotation.build(file, slice_start)).collect(),
             fold: true,
+        };
+
+        (slice, spans)
+    }
+}
+
+/// A fix-it suggestion, replacing the `span` of the source with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Range<usize>,
+    pub replacement: String,
+    pub message: String,
+}
+
+impl Suggestion {
+    pub fn new(span: Range<usize>, replacement: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        }
+    }
+
+    fn build(&self) -> snippet::Annotation {
+        snippet::Annotation {
+            id: None,
+            label: Some(format!("{}: `{}`", self.message, self.replacement)),
+            annotation_type: AnnotationType::Help,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Diagnostic(Snippet);
+/// The severity of a diagnostic. Only errors stop the compilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// The annotation type of the primary span of a diagnostic with this severity.
+    pub fn annotation_type(self) -> AnnotationType {
+        match self {
+            Severity::Error => AnnotationType::Error,
+            Severity::Warning => AnnotationType::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    snippet: Snippet,
+    severity: Severity,
+    suggestions: Vec<Suggestion>,
+    spans: Vec<DiagnosticSpan>,
+}
+
+impl Diagnostic {
+    /// Start building a diagnostic with a single slice annotating the span.
+    /// The lines of the slice are computed from the span.
+    pub fn spanned(source_map: &SourceMap, span: Span, severity: Severity, code: impl Into<String>, message: impl Into<String>) -> DiagnosticBuilder {
+        let annotation = SourceAnnotation::new(span.range(), severity.annotation_type());
+
+        DiagnosticBuilder::new(severity, message)
+            .set_code(code)
+            .push_slice(Slice::new(source_map.get(span.file)).push_annotation(annotation))
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        self.snippet.title.as_ref().and_then(|title| title.id.as_deref())
+    }
+
+    /// The message of the diagnostic, without its code.
+    pub fn message(&self) -> &str {
+        self.snippet.title.as_ref().and_then(|title| title.label.as_deref()).unwrap_or_default()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The file and the byte range of the first annotation, used to sort and deduplicate the diagnostics.
+    pub fn primary_span(&self) -> Option<(&str, Range<usize>)> {
+        self.spans.iter().find(|span| span.primary).map(|span| (span.file.as_str(), span.range.clone()))
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    /// Render the diagnostic in a human readable form.
+    pub fn render(&self, color: bool) -> String {
+        let mut snippet = self.snippet.clone();
+        snippet.opt.color = color;
+
+        DisplayList::from(snippet).to_string()
+    }
+
+    /// Render the diagnostic without colors and without trailing whitespace.
+    /// The output only depends on the diagnostic, so it can be compared byte for byte in tests.
+    pub fn render_plain(&self) -> String {
+        let rendered = self.render(false);
+
+        rendered.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim_end().to_string()
+    }
+
+    pub fn set_severity(&mut self, severity: Severity) {
+        self.severity = severity;
+
+        if let Some(title) = self.snippet.title.as_mut() {
+            title.annotation_type = severity.annotation_type();
+        }
+    }
+}
+
+/// The level of a lint, which can be changed with the `-A`, `-W` and `-D` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
 
+/// The levels of the lints, keyed by the diagnostic code.
 #[derive(Debug, Default)]
-pub struct DiagnosticBuilder {
-    kind: Option<AnnotationType>,
-    message: Option<String>,
-    code: Option<String>,
-    source: Option<String>,
-    origin: Option<String>,
-    slices: Vec<snippet::Slice>,
+pub struct LintLevels {
+    levels: HashMap<String, Level>,
 }
 
-impl DiagnosticBuilder {
+impl LintLevels {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn set_type(mut self, kind: AnnotationType) -> Self {
-        self.kind = Some(kind);
+    pub fn set_level(&mut self, code: impl Into<String>, level: Level) {
+        self.levels.insert(code.into(), level);
+    }
 
-        self
+    /// Apply the configured level to a warning. Returns `None` if the warning is allowed.
+    /// Errors can not be allowed or downgraded to warnings.
+    pub fn apply(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        if diagnostic.is_error() {
+            return Some(diagnostic);
+        }
+
+        match diagnostic.code().and_then(|code| self.levels.get(code)) {
+            Some(Level::Allow) => None,
+            Some(Level::Deny) => {
+                diagnostic.set_severity(Severity::Error);
+
+                Some(diagnostic)
+            }
+            Some(Level::Warn) | None => Some(diagnostic),
+        }
     }
+}
 
-    pub fn set_message(mut self, message: impl Into<String>) -> Self {
-        self.message = Some(message.into());
+/// Builds a `Diagnostic`. The severity and the message are required, everything else is optional.
+#[derive(Debug)]
+pub struct DiagnosticBuilder {
+    severity: Severity,
+    message: String,
+    code: Option<String>,
+    slices: Vec<snippet::Slice>,
+    spans: Vec<DiagnosticSpan>,
+    footer: Vec<snippet::Annotation>,
+    suggestions: Vec<Suggestion>,
+}
 
-        self
+impl DiagnosticBuilder {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            code: None,
+            slices: vec![],
+            spans: vec![],
+            footer: vec![],
+            suggestions: vec![],
+        }
     }
 
     pub fn set_code(mut self, code: impl Into<String>) -> Self {
@@ -131,55 +342,76 @@ impl DiagnosticBuilder {
         self
     }
 
-    pub fn set_source(mut self, source: impl Into<String>) -> Self {
-        self.source = Some(source.into());
+    pub fn push_slice(mut self, slice: Slice) -> Self {
+        let (slice, spans) = slice.build();
 
+        self.slices.push(slice);
+        self.spans.extend(spans);
         self
     }
 
-    pub fn set_origin(mut self, origin: impl Into<String>) -> Self {
-        self.origin = Some(origin.into());
+    /// Point at related code, like the original definition of a redefined variable.
+    /// The secondary span is rendered as its own slice.
+    pub fn push_secondary(self, source_map: &SourceMap, span: Span, label: impl Into<String>) -> Self {
+        let annotation = SourceAnnotation::new(span.range(), AnnotationType::Info).set_label(label);
+
+        self.push_slice(Slice::new(source_map.get(span.file)).push_annotation(annotation))
+    }
+
+    pub fn push_footer(mut self, kind: AnnotationType, text: impl Into<String>) -> Self {
+        self.footer.push(snippet::Annotation {
+            id: None,
+            label: Some(text.into()),
+            annotation_type: kind,
+        });
 
         self
     }
 
-    pub fn push_slice(mut self, slice: Slice) -> Self {
-        let origin = self.origin.as_ref().unwrap();
-        let source = self.source.as_ref().unwrap();
+    pub fn set_help(self, help: impl Into<String>) -> Self {
+        self.push_footer(AnnotationType::Help, help)
+    }
+
+    pub fn set_note(self, note: impl Into<String>) -> Self {
+        self.push_footer(AnnotationType::Note, note)
+    }
 
-        let slice = slice.build(source, origin);
+    pub fn push_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
 
-        self.slices.push(slice);
         self
     }
 
-    pub fn build(self) -> Diagnostic {
-        assert!(self.message.is_some());
-        assert!(self.kind.is_some());
-        assert!(self.message.is_some());
-
+    pub fn build(mut self) -> Diagnostic {
         let title = snippet::Annotation {
             id: self.code,
-            label: self.message,
-            annotation_type: self.kind.unwrap(),
+            label: Some(self.message),
+            annotation_type: self.severity.annotation_type(),
         };
 
-        Diagnostic(Snippet {
+        self.footer.extend(self.suggestions.iter().map(Suggestion::build));
+
+        let snippet = Snippet {
             title: Some(title),
-            footer: vec![],
+            footer: self.footer,
             slices: self.slices,
             opt: FormatOptions {
                 color: true,
                 anonymized_line_numbers: false,
             },
-        })
+        };
+
+        Diagnostic {
+            snippet,
+            severity: self.severity,
+            suggestions: self.suggestions,
+            spans: self.spans,
+        }
     }
 }
 
 impl Display for Diagnostic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let dl = DisplayList::from(self.0.clone());
-
-        write!(f, "{}", dl)
+        write!(f, "{}", self.render(self.snippet.opt.color))
     }
 }
@@ -0,0 +1,140 @@
+//! Mapping between absolute byte offsets and line/column positions.
+
+use std::{ops::Range, sync::Arc};
+
+/// A source file along with the byte offsets of the start of every line.
+/// The line starts are computed once, so converting an offset into a line and column is a binary search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(index, _)| index + 1));
+
+        Self {
+            name: name.into(),
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the 1-based line containing the byte offset.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line + 1,
+            Err(line) => line,
+        }
+    }
+
+    /// Returns the byte offset of the start of a 1-based line.
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line - 1]
+    }
+
+    /// Returns the byte offset of the end of a 1-based line, excluding the newline.
+    pub fn line_end(&self, line: usize) -> usize {
+        match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.source.len(),
+        }
+    }
+
+    /// Returns the 1-based line and column of the byte offset. The column is counted in characters.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_index(offset);
+        let column = self.source[self.line_start(line)..offset].chars().count() + 1;
+
+        (line, column)
+    }
+
+    /// Returns the text of the lines from `line_start` to `line_end` (both inclusive).
+    pub fn lines(&self, line_start: usize, line_end: usize) -> &str {
+        &self.source[self.line_start(line_start)..self.line_end(line_end)]
+    }
+
+    /// Returns the source text covered by the span.
+    pub fn snippet(&self, span: Range<usize>) -> &str {
+        &self.source[span]
+    }
+}
+
+/// An index into a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+/// A range of bytes in a file of a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, range: Range<usize>) -> Self {
+        Self {
+            file,
+            start: range.start,
+            end: range.end,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the span covering both spans. Both spans must be in the same file.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// All of the source files loaded by the compiler.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Arc<SourceFile>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.files.push(Arc::new(SourceFile::new(name, source)));
+
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn get(&self, id: FileId) -> Arc<SourceFile> {
+        self.files[id.0].clone()
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Arc<SourceFile>> {
+        self.files.iter()
+    }
+}
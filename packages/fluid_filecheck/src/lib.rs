@@ -0,0 +1,281 @@
+//! The `fluid_filecheck` crate is the test harness of the code generation, in the style of the `FileCheck` tool of
+//! LLVM.
+//!
+//! Every `.fluid` file of the `tests/codegen` directory at the root of the repository is compiled, and the LLVM IR
+//! of its module, as written by `--emit llvm-ir`, is matched against the directives in its comments:
+//!
+//! * `// CHECK: text` matches the first line with the text after the previous match.
+//! * `// CHECK-NEXT: text` matches the line right after the previous match, which must contain the text.
+//! * `// CHECK-NOT: text` fails if a line between the previous match and the next one contains the text.
+//! * `// MANGLING: itanium` compiles the file with the mangling scheme, `fluid` by default.
+//!
+//! The text is matched as is, apart from `{{...}}`, which matches any text like the `{{.*}}` of `FileCheck`.
+//!
+//! ```text
+//! function add(a: number, b: number) -> number {
+//!     // CHECK: define i64 @{{...}}3addEnn(i64 %a, i64 %b)
+//!     // CHECK-NOT: call
+//!     // CHECK: ret i64
+//!     return a + b;
+//! }
+//! ```
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    cell::RefCell,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+use fluid_codegen::CodeGenType;
+use fluid_driver::{collect_fluid_files, Options, Session};
+use fluid_error::{Diagnostic, DiagnosticHandler, Emitter, SourceFile};
+use fluid_mangle::Mangling;
+
+/// What a directive checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DirectiveKind {
+    /// `CHECK`, a line after the previous match has the text.
+    Check,
+    /// `CHECK-NEXT`, the line right after the previous match has the text.
+    Next,
+    /// `CHECK-NOT`, no line between the previous match and the next one has the text.
+    Not,
+}
+
+impl DirectiveKind {
+    /// The name of the directive in the comments.
+    pub fn name(self) -> &'static str {
+        match self {
+            DirectiveKind::Check => "CHECK",
+            DirectiveKind::Next => "CHECK-NEXT",
+            DirectiveKind::Not => "CHECK-NOT",
+        }
+    }
+}
+
+/// A check directive of a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    /// What the directive checks.
+    pub kind: DirectiveKind,
+    /// The text to match.
+    pub pattern: String,
+    /// The line of the directive in the source file, starting at 1.
+    pub line: usize,
+}
+
+impl Directive {
+    /// Returns true if the line of the IR contains the text of the directive, `{{...}}` matching any text.
+    pub fn matches(&self, line: &str) -> bool {
+        let mut rest = line;
+
+        for (index, fragment) in self.pattern.split("{{").enumerate() {
+            // The first fragment is before any `{{`, the text of the others starts at their `}}`.
+            let literal = match (index, fragment.find("}}")) {
+                (0, _) => fragment,
+                (_, Some(end)) => &fragment[end + 2..],
+                (_, None) => fragment,
+            };
+
+            match rest.find(literal) {
+                Some(start) => rest = &rest[start + literal.len()..],
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.name(), self.pattern)
+    }
+}
+
+/// Parse the directives of the comments of a source file, along with its mangling scheme.
+pub fn parse_directives(source: &str) -> Result<(Vec<Directive>, Mangling), String> {
+    let mut directives = vec![];
+    let mut mangling = Mangling::default();
+
+    for (index, line) in source.lines().enumerate() {
+        let comment = match line.find("//") {
+            Some(start) => line[start + 2..].trim(),
+            None => continue,
+        };
+
+        let kinds = [DirectiveKind::Check, DirectiveKind::Next, DirectiveKind::Not];
+        let directive = kinds
+            .iter()
+            .find_map(|&kind| comment.strip_prefix(kind.name())?.strip_prefix(':').map(|pattern| (kind, pattern.trim())));
+
+        if let Some((kind, pattern)) = directive {
+            if pattern.is_empty() {
+                return Err(format!("line {}: the `{}` directive has no text", index + 1, kind.name()));
+            }
+
+            directives.push(Directive {
+                kind,
+                pattern: pattern.to_owned(),
+                line: index + 1,
+            });
+        } else if let Some(scheme) = comment.strip_prefix("MANGLING:") {
+            mangling = scheme.trim().parse().map_err(|error| format!("line {}: {}", index + 1, error))?;
+        }
+    }
+
+    if directives.is_empty() {
+        return Err("there are no `CHECK` directives".to_owned());
+    }
+
+    Ok((directives, mangling))
+}
+
+/// Match the directives against the output, in order.
+/// Returns the directive that failed along with the line of the output where the search started, starting at 1.
+pub fn check<'a>(directives: &'a [Directive], output: &str) -> Result<(), (&'a Directive, usize)> {
+    let lines = output.lines().collect::<Vec<_>>();
+    // The index of the line after the previous match.
+    let mut position = 0;
+    // The `CHECK-NOT` directives since the previous match, checked once the next match is known.
+    let mut nots: Vec<&Directive> = vec![];
+
+    for directive in directives {
+        let start = position;
+
+        let found = match directive.kind {
+            DirectiveKind::Not => {
+                nots.push(directive);
+
+                continue;
+            }
+            DirectiveKind::Check => lines[position.min(lines.len())..].iter().position(|line| directive.matches(line)).map(|offset| position + offset),
+            DirectiveKind::Next => Some(position).filter(|&index| index < lines.len() && directive.matches(lines[index])),
+        };
+
+        let index = found.ok_or((directive, start + 1))?;
+
+        check_nots(&nots, &lines[start..index], start)?;
+        nots.clear();
+        position = index + 1;
+    }
+
+    check_nots(&nots, &lines[position.min(lines.len())..], position)
+}
+
+/// Check that none of the lines, which start at the line `start` of the output, match a `CHECK-NOT` directive.
+fn check_nots<'a>(nots: &[&'a Directive], lines: &[&str], start: usize) -> Result<(), (&'a Directive, usize)> {
+    for (offset, line) in lines.iter().enumerate() {
+        if let Some(directive) = nots.iter().find(|directive| directive.matches(line)) {
+            return Err((directive, start + offset + 1));
+        }
+    }
+
+    Ok(())
+}
+
+/// A source file whose LLVM IR did not match its directives, or that could not be compiled.
+#[derive(Debug)]
+pub struct Failure {
+    /// The path of the source file.
+    pub path: PathBuf,
+    /// Why the file failed.
+    pub message: String,
+    /// The LLVM IR of the file, if it could be compiled.
+    pub output: Option<String>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)?;
+
+        if let Some(output) = &self.output {
+            write!(f, "\n\nthe LLVM IR is:\n{}", output.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Shares the emitted diagnostics with the harness, the handler owns its emitter.
+struct SharedEmitter(Rc<RefCell<Vec<Diagnostic>>>);
+
+impl Emitter for SharedEmitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        self.0.borrow_mut().push(diagnostic.clone());
+    }
+}
+
+/// Compile the source file, named after the name, and get the LLVM IR of its module.
+/// Returns the rendered diagnostics if the file could not be compiled.
+pub fn compile(name: &str, source: &str, mangling: Mangling) -> Result<String, String> {
+    let diagnostics = Rc::new(RefCell::new(vec![]));
+    let handler = DiagnosticHandler::new(Box::new(SharedEmitter(diagnostics.clone())));
+    let options = Options { mangling, ..Options::default() };
+
+    let mut session = Session::new(handler, options);
+    let file = Arc::new(SourceFile::new(name, source));
+
+    match session.compile(vec![file], CodeGenType::Object) {
+        Ok(codegen) => codegen.print_ir(None),
+        Err(error) => {
+            let rendered = diagnostics.borrow().iter().map(Diagnostic::render_plain).collect::<Vec<_>>();
+
+            Err(if rendered.is_empty() { error.to_string() } else { rendered.join("\n\n") })
+        }
+    }
+}
+
+/// Compile the `.fluid` files in the directory and its subdirectories, and match their LLVM IR against their
+/// directives. Returns the files that failed.
+pub fn run(dir: &Path) -> io::Result<Vec<Failure>> {
+    let mut paths = vec![];
+    let mut failures = vec![];
+
+    collect_fluid_files(dir, &mut paths)?;
+
+    for path in paths {
+        // The name is relative to the directory, so the module and the mangled symbols do not depend on where the
+        // repository is.
+        let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        let source = fs::read_to_string(&path)?.replace("\r\n", "\n");
+
+        let failure = |message: String, output: Option<String>| Failure { path: path.clone(), message, output };
+
+        let (directives, mangling) = match parse_directives(&source) {
+            Ok(directives) => directives,
+            Err(message) => {
+                failures.push(failure(message, None));
+
+                continue;
+            }
+        };
+
+        let output = match compile(&name, &source, mangling) {
+            Ok(output) => output,
+            Err(message) => {
+                failures.push(failure(format!("the file could not be compiled\n\n{}", message), None));
+
+                continue;
+            }
+        };
+
+        if let Err((directive, line)) = check(&directives, &output) {
+            let message = match directive.kind {
+                DirectiveKind::Not => format!("line {}: `{}` matched line {} of the LLVM IR", directive.line, directive, line),
+                _ => format!("line {}: `{}` did not match from line {} of the LLVM IR", directive.line, directive, line),
+            };
+
+            failures.push(failure(message, Some(output)));
+        }
+    }
+
+    Ok(failures)
+}
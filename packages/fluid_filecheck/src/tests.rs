@@ -0,0 +1,55 @@
+//! This file contains all of the unit tests for the FileCheck harness, and the test of the `tests/codegen` directory.
+
+use std::path::Path;
+
+use fluid_mangle::Mangling;
+
+use crate::{check, parse_directives, run, DirectiveKind};
+
+#[test]
+fn test_codegen() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/codegen");
+    let failures = run(&dir).unwrap();
+
+    if !failures.is_empty() {
+        let messages = failures.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        panic!("{}\n\n{} files failed", messages.join("\n\n"), failures.len());
+    }
+}
+
+#[test]
+fn test_parse_directives() {
+    let source = "// MANGLING: itanium\n// CHECK: define\nfunction main() {} // CHECK-NEXT: entry:\n// CHECK-NOT: call\n";
+    let (directives, mangling) = parse_directives(source).unwrap();
+
+    let kinds = directives.iter().map(|directive| (directive.kind, directive.pattern.as_str(), directive.line)).collect::<Vec<_>>();
+
+    assert_eq!(mangling, Mangling::Itanium);
+    assert_eq!(kinds, vec![(DirectiveKind::Check, "define", 2), (DirectiveKind::Next, "entry:", 3), (DirectiveKind::Not, "call", 4)]);
+
+    assert!(parse_directives("function main() {}").is_err());
+    assert!(parse_directives("// CHECK:\n").is_err());
+    assert!(parse_directives("// MANGLING: none\n// CHECK: define").is_err());
+}
+
+#[test]
+fn test_check() {
+    let output = "define i64 @main() {\nentry:\n  %0 = call i64 @_F4main3addEnn(i64 1, i64 2)\n  ret i64 %0\n}\n";
+    let result = |source: &str| {
+        let (directives, _) = parse_directives(source).unwrap();
+
+        check(&directives, output).map_err(|(directive, line)| (directive.line, line))
+    };
+
+    assert_eq!(result("// CHECK: define i64 @main()\n// CHECK-NEXT: entry:\n// CHECK: ret i64"), Ok(()));
+    assert_eq!(result("// CHECK: call i64 @{{...}}3addEnn({{...}}, i64 2)"), Ok(()));
+    assert_eq!(result("// CHECK: define\n// CHECK-NOT: store\n// CHECK: ret"), Ok(()));
+
+    // The lines are matched in order, and the failures point at the directive and at the line the search started.
+    assert_eq!(result("// CHECK: ret\n// CHECK: call"), Err((2, 5)));
+    assert_eq!(result("// CHECK: define\n// CHECK-NEXT: call"), Err((2, 2)));
+    assert_eq!(result("// CHECK: {{...}}3addEnn({{...}}, i64 1)"), Err((1, 1)));
+    assert_eq!(result("// CHECK: entry:\n// CHECK-NOT: call\n// CHECK: ret"), Err((2, 3)));
+    assert_eq!(result("// CHECK: call\n// CHECK-NOT: }"), Err((2, 5)));
+}
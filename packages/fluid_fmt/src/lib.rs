@@ -0,0 +1,47 @@
+//! The `fluid_fmt` crate is the code formatter of Fluid.
+//! It parses a file and prints its AST back in the canonical style, with the comments of the source kept where they were.
+//!
+//! The canonical style:
+//! * The blocks are indented with four spaces, and their opening brace is on the line of their statement.
+//! * Every statement is on its own line, the binary operators are surrounded by spaces and the arguments are separated by `, `.
+//! * There is at most one blank line between two statements, and always one after a function definition.
+//! * A comment after the end of a statement stays on its line.
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+mod printer;
+
+#[cfg(test)]
+mod tests;
+
+use std::sync::Arc;
+
+use fluid_error::{Diagnostic, SourceFile};
+use fluid_lexer::{Lexer, TokenType};
+use fluid_parser::Parser;
+
+use crate::printer::{Comment, Printer};
+
+/// Format the source of a file. Returns the errors of the lexer or of the parser if the file is not valid.
+pub fn format(file: Arc<SourceFile>) -> Result<String, Vec<Diagnostic>> {
+    let tokens = Lexer::from_file(&file).set_preserve_comments(true).run()?;
+
+    // The parser does not know about the comments, they are printed between the statements.
+    let (comments, tokens): (Vec<_>, Vec<_>) = tokens.into_iter().partition(|token| matches!(token.kind, TokenType::Comment(_)));
+
+    let comments = comments
+        .into_iter()
+        .map(|token| Comment {
+            span: token.position.position_start..token.position.position_end,
+        })
+        .collect();
+
+    let ast = Parser::new(&tokens, file.clone()).run()?;
+
+    Ok(Printer::new(&file, &ast.exprs, comments).print(&ast.statements))
+}
+
+/// Format a source. Returns the errors of the lexer or of the parser if the source is not valid.
+pub fn format_source(source: &str, name: &str) -> Result<String, Vec<Diagnostic>> {
+    format(Arc::new(SourceFile::new(name, source)))
+}
@@ -0,0 +1,405 @@
+//! Print the AST in the canonical style.
+
+use std::ops::Range;
+
+use fluid_error::SourceFile;
+use fluid_parser::{BinaryOp, Declaration, ExprArena, ExprId, ExpressionKind, Prototype, Statement, StatementKind, UnaryOp};
+
+/// The indentation of one level of blocks.
+const INDENT: &str = "    ";
+
+/// A comment of the source.
+#[derive(Debug)]
+pub(crate) struct Comment {
+    /// The byte range of the comment, including its `//` or `/*` and `*/`.
+    pub(crate) span: Range<usize>,
+}
+
+/// What the last printed item of a list of statements was.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    /// Nothing was printed in the list yet.
+    None,
+    /// A comment on its own line.
+    Comment,
+    /// A function definition.
+    Function,
+    /// Any other statement.
+    Statement,
+}
+
+/// The state of the printer.
+pub(crate) struct Printer<'a> {
+    /// The file being formatted.
+    file: &'a SourceFile,
+    /// The expressions of the AST being printed.
+    exprs: &'a ExprArena,
+    /// The comments of the file, in order.
+    comments: Vec<Comment>,
+    /// The index of the first comment that is not printed yet.
+    next_comment: usize,
+    /// The formatted source.
+    output: String,
+    /// The current indentation level.
+    indent: usize,
+    /// The last printed item of the current list of statements.
+    last_item: Item,
+    /// The end of the last printed item of the current list of statements in the source.
+    last_end: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// Create a new printer for the file, the expressions of its AST and its comments.
+    pub(crate) fn new(file: &'a SourceFile, exprs: &'a ExprArena, comments: Vec<Comment>) -> Self {
+        Self {
+            file,
+            exprs,
+            comments,
+            next_comment: 0,
+            output: String::new(),
+            indent: 0,
+            last_item: Item::None,
+            last_end: 0,
+        }
+    }
+
+    /// Print the statements of the file, and return the formatted source.
+    pub(crate) fn print(mut self, ast: &[Statement]) -> String {
+        let source = self.file.source();
+
+        // The shebang is not part of the AST, it is kept as it is.
+        if source.starts_with("#!") {
+            let end = source.find('\n').unwrap_or(source.len());
+
+            self.output.push_str(source[..end].trim_end());
+            self.output.push('\n');
+
+            self.last_item = Item::Comment;
+            self.last_end = end;
+        }
+
+        self.print_statements(ast, None, source.len());
+
+        self.output
+    }
+
+    /// Print a list of statements, the value at the end of the block of the statements, and the comments before the
+    /// end of the list.
+    fn print_statements(&mut self, statements: &[Statement], value: Option<ExprId>, end: usize) {
+        for statement in statements {
+            self.print_comments_before(statement.span.start);
+            self.begin_item(statement.span.start, is_function(statement));
+
+            self.print_statement(statement);
+            self.print_trailing_comment(statement.span.end, end);
+            self.output.push('\n');
+
+            self.last_item = if is_function(statement) { Item::Function } else { Item::Statement };
+            self.last_end = statement.span.end;
+        }
+
+        if let Some(value) = value {
+            let span = self.exprs[value].span.clone();
+
+            self.print_comments_before(span.start);
+            self.begin_item(span.start, false);
+
+            self.print_expression(value);
+            self.print_trailing_comment(span.end, end);
+            self.output.push('\n');
+
+            self.last_item = Item::Statement;
+            self.last_end = span.end;
+        }
+
+        self.print_comments_before(end);
+    }
+
+    /// Print the comments that start before the position on their own lines.
+    fn print_comments_before(&mut self, position: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start >= position {
+                break;
+            }
+
+            let span = comment.span.clone();
+
+            self.begin_item(span.start, false);
+            self.output.push_str(self.file.snippet(span.clone()).trim_end());
+            self.output.push('\n');
+
+            self.next_comment += 1;
+            self.last_item = Item::Comment;
+            self.last_end = span.end;
+        }
+    }
+
+    /// Print the comment that starts on the line of the end of a statement, before the end of the list, after it.
+    fn print_trailing_comment(&mut self, statement_end: usize, end: usize) {
+        let comment = match self.comments.get(self.next_comment) {
+            Some(comment) if comment.span.start < end && self.file.line_index(comment.span.start) == self.file.line_index(statement_end) => comment,
+            _ => return,
+        };
+
+        let text = self.file.snippet(comment.span.clone()).trim_end().to_owned();
+
+        self.output.push(' ');
+        self.output.push_str(&text);
+
+        self.last_end = comment.span.end;
+        self.next_comment += 1;
+    }
+
+    /// Start a new item of a list of statements, on a new line.
+    /// It is separated from the previous item by a blank line if there is one in the source, and always after a
+    /// function definition or before a function definition that follows a statement.
+    fn begin_item(&mut self, start: usize, is_function: bool) {
+        let blank_line = match self.last_item {
+            Item::None => false,
+            Item::Function => true,
+            Item::Statement if is_function => true,
+            _ => self.file.snippet(self.last_end..start).matches('\n').count() > 1,
+        };
+
+        if blank_line {
+            self.output.push('\n');
+        }
+
+        self.push_indent();
+    }
+
+    /// Print the indentation of the current level.
+    fn push_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    /// Print a statement, without its indentation and the new line after it.
+    fn print_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Expression(expression) => {
+                self.print_expression(*expression);
+                self.output.push(';');
+            }
+            StatementKind::Return(value) => {
+                self.output.push_str("return");
+
+                if let Some(value) = value {
+                    self.output.push(' ');
+                    self.print_expression(*value);
+                }
+
+                self.output.push(';');
+            }
+            StatementKind::If(condition, body, otherwise) => {
+                self.output.push_str("if (");
+                self.print_expression(*condition);
+                self.output.push_str(") ");
+                self.print_statement(body);
+
+                if let Some(otherwise) = otherwise {
+                    self.output.push_str(" else ");
+                    self.print_statement(otherwise);
+                }
+            }
+            StatementKind::For(for_loop) => {
+                self.output.push_str("for (");
+                self.output.push_str(&for_loop.name);
+                self.output.push_str(" in ");
+                self.print_expression(for_loop.iterable);
+                self.output.push_str(") ");
+                self.print_statement(&for_loop.body);
+            }
+            StatementKind::Block(statements, value) => self.print_block(statements, *value, statement.span.clone()),
+            StatementKind::Declaration(declaration) => self.print_declaration(declaration, statement.span.clone()),
+        }
+    }
+
+    /// Print a block, the span includes its braces.
+    fn print_block(&mut self, statements: &[Statement], value: Option<ExprId>, span: Range<usize>) {
+        let end = span.end.saturating_sub(1);
+        let has_comments = self.has_comments_before(end);
+
+        if statements.is_empty() && value.is_none() && !has_comments {
+            self.output.push_str("{}");
+
+            return;
+        }
+
+        self.output.push_str("{\n");
+        self.indent += 1;
+
+        let (last_item, last_end) = (self.last_item, self.last_end);
+
+        self.last_item = Item::None;
+        self.last_end = span.start;
+
+        self.print_statements(statements, value, end);
+
+        self.last_item = last_item;
+        self.last_end = last_end;
+
+        self.indent -= 1;
+        self.push_indent();
+        self.output.push('}');
+    }
+
+    /// Returns true if there is a comment that is not printed yet before the position.
+    fn has_comments_before(&self, position: usize) -> bool {
+        self.comments.get(self.next_comment).map_or(false, |comment| comment.span.start < position)
+    }
+
+    /// Print a declaration.
+    fn print_declaration(&mut self, declaration: &Declaration, span: Range<usize>) {
+        match declaration {
+            Declaration::Function(function) => {
+                if function.prototype.no_mangle {
+                    self.output.push_str("export ");
+                } else if function.prototype.test {
+                    self.output.push_str("test ");
+                }
+
+                self.print_prototype(&function.prototype);
+                self.output.push(' ');
+                self.print_statement(&function.body);
+            }
+            Declaration::Extern(prototypes) => {
+                let end = span.end.saturating_sub(1);
+
+                self.output.push_str("extern {\n");
+                self.indent += 1;
+
+                for prototype in prototypes {
+                    self.print_comments_before(prototype.name_span.start);
+                    self.push_indent();
+                    self.print_prototype(prototype);
+                    self.output.push(';');
+                    self.print_trailing_comment(prototype.name_span.end, end);
+                    self.output.push('\n');
+                }
+
+                self.print_comments_before(end);
+
+                self.indent -= 1;
+                self.push_indent();
+                self.output.push('}');
+            }
+            Declaration::VarDef(var_def) => {
+                self.output.push_str(&format!("var {}: {} = ", var_def.name, var_def.typee));
+                self.print_expression(var_def.value);
+                self.output.push(';');
+            }
+        }
+    }
+
+    /// Print the prototype of a function, without `export`.
+    fn print_prototype(&mut self, prototype: &Prototype) {
+        let args = prototype.args.iter().map(|arg| format!("{}: {}", arg.name, arg.typee)).collect::<Vec<_>>();
+
+        self.output.push_str(&format!("function {}({})", prototype.name, args.join(", ")));
+
+        // The return type is only printed if it was written, `-> void` is kept.
+        if prototype.return_type_span.is_some() {
+            self.output.push_str(&format!(" -> {}", prototype.return_type));
+        }
+    }
+
+    /// Print an expression.
+    fn print_expression(&mut self, id: ExprId) {
+        let expression = &self.exprs[id];
+
+        match &expression.kind {
+            ExpressionKind::VarRef(name) => self.output.push_str(name),
+            ExpressionKind::VarAssign(name, value) => {
+                self.output.push_str(name);
+                self.output.push_str(" = ");
+                self.print_expression(*value);
+            }
+            ExpressionKind::FunctionCall(name, args) => {
+                self.output.push_str(name);
+                self.output.push('(');
+
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+
+                    self.print_expression(*arg);
+                }
+
+                self.output.push(')');
+            }
+            ExpressionKind::BinaryOp(left, op, right) => {
+                self.print_expression(*left);
+                self.output.push_str(&format!(" {} ", binary_op(op)));
+                self.print_expression(*right);
+            }
+            // The literals are printed as they were written, so that the strings keep their escapes and the floats
+            // keep their digits.
+            ExpressionKind::Literal(_) => self.output.push_str(self.file.snippet(expression.span.clone())),
+            ExpressionKind::Unary(op, operand) => {
+                self.output.push(match op {
+                    UnaryOp::Neg => '-',
+                    UnaryOp::Plus => '+',
+                    UnaryOp::Not => '!',
+                });
+
+                self.print_expression(*operand);
+            }
+            ExpressionKind::Paren(inner) => {
+                self.output.push('(');
+                self.print_expression(*inner);
+                self.output.push(')');
+            }
+            ExpressionKind::If(condition, then, otherwise) => {
+                self.output.push_str("if (");
+                self.print_expression(*condition);
+                self.output.push_str(") ");
+                self.print_expression(*then);
+                self.output.push_str(" else ");
+                self.print_expression(*otherwise);
+            }
+            // A block with only a value is printed on one line, like `{ 1 }`.
+            ExpressionKind::Block(statements, value) => {
+                if statements.is_empty() && !self.has_comments_before(expression.span.end) {
+                    self.output.push_str("{ ");
+                    self.print_expression(*value);
+                    self.output.push_str(" }");
+                } else {
+                    self.print_block(statements, Some(*value), expression.span.clone());
+                }
+            }
+            ExpressionKind::Index(base, index) => {
+                self.print_expression(*base);
+                self.output.push('[');
+                self.print_expression(*index);
+                self.output.push(']');
+            }
+        }
+    }
+}
+
+/// Returns true if the statement is a function definition.
+fn is_function(statement: &Statement) -> bool {
+    match &statement.kind {
+        StatementKind::Declaration(declaration) => matches!(**declaration, Declaration::Function(_)),
+        _ => false,
+    }
+}
+
+/// Get the source representation of a binary operator.
+fn binary_op(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Lesser => "<",
+        BinaryOp::Greater => ">",
+        BinaryOp::EqEq => "==",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Coalesce => "??",
+    }
+}
@@ -0,0 +1,122 @@
+//! This file contains all of the unit tests for the formatter.
+
+use crate::format_source;
+
+#[inline]
+fn format(source: &str) -> String {
+    let formatted = format_source(source, "<test>").unwrap();
+
+    // Formatting is idempotent.
+    assert_eq!(format_source(&formatted, "<test>").unwrap(), formatted);
+
+    formatted
+}
+
+#[test]
+fn test_canonical_style() {
+    let source = "function add(a:number,b:number)->number{return a+b;}
+function main( ) {
+var x : number=add(1,2)*3;
+    if(x>2&&!(x==4)){x=-x;}else if (x == 4) {} else { return ; }
+var y:number=if(x>2){1}else if(x==1){ 2 }else{3};
+var z:number={var w:number=y*2;w};
+for(c in \"fluid\"){print(c);} var d:char=\"fluid\"[z];
+}
+function double(x:number)->number{x*2}";
+
+    let expected = "function add(a: number, b: number) -> number {
+    return a + b;
+}
+
+function main() {
+    var x: number = add(1, 2) * 3;
+    if (x > 2 && !(x == 4)) {
+        x = -x;
+    } else if (x == 4) {} else {
+        return;
+    }
+    var y: number = if (x > 2) { 1 } else if (x == 1) { 2 } else { 3 };
+    var z: number = {
+        var w: number = y * 2;
+        w
+    };
+    for (c in \"fluid\") {
+        print(c);
+    }
+    var d: char = \"fluid\"[z];
+}
+
+function double(x: number) -> number {
+    x * 2
+}
+";
+
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn test_comments() {
+    let source = "#!/usr/bin/env fluid run
+
+// The entry point.
+function main() -> number { // Trailing.
+    var x: number = 1; // The answer.
+
+
+    /* A block comment. */
+    return x;
+    // At the end of the block.
+}
+// At the end of the file.
+";
+
+    let expected = "#!/usr/bin/env fluid run
+
+// The entry point.
+function main() -> number {
+    // Trailing.
+    var x: number = 1; // The answer.
+
+    /* A block comment. */
+    return x;
+    // At the end of the block.
+}
+
+// At the end of the file.
+";
+
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn test_declarations() {
+    let source = "extern{function puts(s:string)->number;
+// Not mangled.
+function exit(code: number);}
+export function exported(){}
+test   function it_works(){assert(true);}
+var s: string = \"a\\tb\"; var f: float = 1.50;";
+
+    let expected = "extern {
+    function puts(s: string) -> number;
+    // Not mangled.
+    function exit(code: number);
+}
+
+export function exported() {}
+
+test function it_works() {
+    assert(true);
+}
+
+var s: string = \"a\\tb\";
+var f: float = 1.50;
+";
+
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn test_invalid_source() {
+    assert!(format_source("var x: number = ;", "<test>").is_err());
+}
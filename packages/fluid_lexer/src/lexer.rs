@@ -1,6 +1,11 @@
 //! This file contains the actual lexer implementation, the `Lexer` interface.
 
-use fluid_error::{AnnotationType, Diagnostic, DiagnosticBuilder, Slice, SourceAnnotation};
+use std::{
+    cell::{Cell, RefCell},
+    sync::Arc,
+};
+
+use fluid_error::{codes, AnnotationType, Diagnostic, DiagnosticBuilder, Severity, Slice, SourceAnnotation, SourceFile};
 
 use crate::advance;
 use crate::token::*;
@@ -42,37 +47,81 @@ fn is_valid_start_of_identifier(char: char) -> bool {
 }
 
 /// Contains the internal state while processing a Fluid file.
+/// The lexer borrows the source, so scanning a buffer does not copy it.
 #[derive(Debug)]
-pub struct Lexer {
-    /// The name of the file we are going to scan.
-    pub file: String,
-    /// The contents of the file that we are going to scan.
-    pub code: String,
-    /// The current index.
-    index: usize,
-    /// The current position.
+pub struct Lexer<'src> {
+    /// The source that we are going to scan.
+    source: &'src str,
+    /// The name of the file of the source.
+    name: &'src str,
+    /// The file of the source for the diagnostics. It is only created from the source when it is first needed, unless
+    /// the lexer was created from a file.
+    file: RefCell<Option<Arc<SourceFile>>>,
+    /// The current position, as a byte offset into the file.
     position: usize,
-    /// The current line number.
-    line: usize,
+    /// The position of the last token along with its 1-based line, the line of the next token is counted from it.
+    line: Cell<(usize, usize)>,
+    /// Emit comments as `TokenType::Comment` tokens instead of skipping them.
+    preserve_comments: bool,
 }
 
-impl Lexer {
-    /// Create a new instance of the lexer.
-    pub fn new(code: impl Into<String>, file: impl Into<String>) -> Self {
-        let code = code.into();
-        let file = file.into();
+impl<'src> Lexer<'src> {
+    /// Create a new instance of the lexer for the source of the file with the name.
+    pub fn new(source: &'src str, name: &'src str) -> Self {
+        Self {
+            source,
+            name,
+            file: RefCell::new(None),
+            position: 0,
+            line: Cell::new((0, 1)),
+            preserve_comments: false,
+        }
+    }
 
-        let position = 0;
-        let index = 0;
-        let line = 1;
+    /// Create a new instance of the lexer for a file loaded in a `SourceMap`.
+    pub fn from_file(file: &'src Arc<SourceFile>) -> Self {
+        let lexer = Self::new(file.source(), file.name());
 
-        Self { file, code, index, position, line }
+        lexer.file.replace(Some(file.clone()));
+        lexer
+    }
+
+    /// Returns the file that is being scanned.
+    pub fn file(&self) -> Arc<SourceFile> {
+        self.file.borrow_mut().get_or_insert_with(|| Arc::new(SourceFile::new(self.name, self.source))).clone()
+    }
+
+    /// Keep the comments in the token stream as `TokenType::Comment` tokens.
+    /// This is required by tools like the formatter that need to reproduce the source.
+    pub fn set_preserve_comments(mut self, preserve_comments: bool) -> Self {
+        self.preserve_comments = preserve_comments;
+
+        self
     }
 
     /// Runs `self.get_next_token()` until the current character is not EOF.
     /// After it has encountered EOF it appends the EOF Token.
     /// Then it returns all of the collected tokens.
+    ///
+    /// It never panics, whatever the source is: the malformed code is reported as diagnostics.
     pub fn run(&mut self) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let (tokens, errors) = self.tokenize();
+
+        if errors.is_empty() {
+            // If the lexer has not panicked return the collected tokens.
+
+            Ok(tokens)
+        } else {
+            // If lexer has panicked then return the errors vector.
+
+            Err(errors)
+        }
+    }
+
+    /// Scans the whole file and returns every token along with all of the errors encountered.
+    /// Illegal characters are kept in the token stream as `TokenType::Unknown`, so tools like the
+    /// formatter and the language server still see a complete token stream for a broken file.
+    pub fn tokenize(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
         let mut tokens = vec![];
         let mut errors = vec![];
 
@@ -80,6 +129,8 @@ impl Lexer {
         self.skip_shebang();
 
         loop {
+            let position = self.position;
+
             match self.get_next_token() {
                 Ok(token) => {
                     if token.kind == TokenType::EOF {
@@ -87,42 +138,43 @@ impl Lexer {
 
                         // If the lexer has reached at the end of file then stop scanning for new tokens.
                         break;
+                    } else if let TokenType::Unknown(_) = token.kind {
+                        errors.push(self.unknown_char_error(&token));
+                        tokens.push(token);
                     } else {
                         tokens.push(token);
                     }
                 }
 
                 Err(err) => {
-                    // Enter panic mode if there is an Diagnostic.
+                    // Enter panic mode if there is an Diagnostic. The malformed literals are consumed
+                    // by their scanner already, skipping another character would drop the next token.
                     errors.push(err);
 
-                    self.advance();
+                    if self.position == position {
+                        self.advance();
+                    }
                 }
             }
         }
 
-        if errors.is_empty() {
-            // If the lexer has not panicked return the collected tokens.
-
-            Ok(tokens)
-        } else {
-            // If lexer has panicked then return the errors vector.
-
-            Err(errors)
-        }
+        (tokens, errors)
     }
 
     /// Scans the next character and return a new `Token`. The source end is indicated by token.EOF.
-    /// It will fail if an illegal character is encountered. Thus, in that case it will result in returning a `Diagnostic`.
+    /// An illegal character is returned as a `TokenType::Unknown` token.
+    /// It will fail if a malformed literal or comment is encountered. Thus, in that case it will result in returning a `Diagnostic`.
     pub fn get_next_token(&mut self) -> Result<Token, Diagnostic> {
         self.skip_whitespaces_and_comments()?;
 
         if self.is_eof() {
             // Return the EOF token if the lexer has reached at the end of the file.
-            return Ok(self.new_token(TokenType::EOF, self.index, self.index));
+            return Ok(self.new_token(TokenType::EOF, self.position, self.position));
+        } else if let Some(token) = self.collect_comment()? {
+            return Ok(token);
         } else if let Some(token) = self.collect_id() {
             return Ok(token);
-        } else if let Some(token) = self.collect_number() {
+        } else if let Some(token) = self.collect_number()? {
             return Ok(token);
         }
 
@@ -141,7 +193,7 @@ impl Lexer {
             ':' => advance!(self, TokenType::Colon),
             '>' => advance!(self, TokenType::Greater),
             '<' => advance!(self, TokenType::Lesser),
-            '?' => advance!(self, TokenType::Question),
+            '?' => advance!(self, ['?' => TokenType::QuestionQuestion], TokenType::Question),
             '#' => advance!(self, TokenType::Hash),
             '-' => advance!(self, ['>' => TokenType::TArrow], TokenType::Minus),
             '!' => advance!(self, ['=' => TokenType::BangEq], TokenType::Bang),
@@ -150,10 +202,33 @@ impl Lexer {
             '=' => advance!(self, ['=' => TokenType::EqEq, '>' => TokenType::EArrow], TokenType::Eq),
             '"' => self.collect_str(),
             '\'' => self.collect_char(),
-            _ => Err(self.throw_unexpected_char()),
+            _ => advance!(self, TokenType::Unknown(self.current_char())),
         }
     }
 
+    /// Collect a comment if the lexer is preserving comments.
+    fn collect_comment(&mut self) -> Result<Option<Token>, Diagnostic> {
+        if !self.preserve_comments || self.is_next_eof() || self.current_char() != '/' {
+            return Ok(None);
+        }
+
+        let start = self.position;
+
+        match self.next_char() {
+            '/' => {
+                while !self.is_eof() && self.current_char() != '\n' {
+                    self.advance();
+                }
+            }
+            '*' => self.skip_block_comment()?,
+            _ => return Ok(None),
+        }
+
+        let comment = self.source[start..self.position].to_string();
+
+        Ok(Some(self.new_token(TokenType::Comment(comment), start, self.position)))
+    }
+
     /// Collect a string.
     fn collect_str(&mut self) -> Result<Token, Diagnostic> {
         macro_rules! escape {
@@ -166,9 +241,9 @@ impl Lexer {
         }
 
         macro_rules! next_hex {
-            ($self:ident, $line_start:ident, $index_start:ident, $alloca:ident) => {
+            ($self:ident, $index_start:ident, $alloca:ident) => {
                 if $self.is_eof() {
-                    unterminated_str!(self, $line_start, $index_start);
+                    unterminated_str!(self, $index_start);
                 }
 
                 $alloca = $self.current_char();
@@ -177,21 +252,24 @@ impl Lexer {
         }
 
         macro_rules! unterminated_str {
-            ($self:ident, $line_start:ident, $index_start:ident) => {
+            ($self:ident, $index_start:ident) => {
                 return Err($self
-                    .make_error("unterminated string literal", "E0002")
-                    .push_slice(
-                        Slice::new()
-                            .set_line_start($line_start)
-                            .set_line_end(self.line)
-                            .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range($index_start..$self.index)),
-                    )
+                    .make_error("unterminated string literal", codes::E0002)
+                    .push_slice(Slice::new($self.file()).push_annotation(SourceAnnotation::new($index_start..$self.position, AnnotationType::Error)))
                     .build());
             };
         }
 
-        let index_start = self.index;
-        let line_start = self.line;
+        macro_rules! invalid_escape {
+            ($self:ident, $escape_start:ident, $message:expr) => {{
+                return Err($self
+                    .make_error($message, codes::E0003)
+                    .push_slice(Slice::new($self.file()).push_annotation(SourceAnnotation::new($escape_start..$self.position, AnnotationType::Error)))
+                    .build());
+            }};
+        }
+
+        let index_start = self.position;
 
         // Advance '"'
         self.advance();
@@ -200,15 +278,14 @@ impl Lexer {
 
         while !self.is_eof() && self.current_char() != '"' {
             match self.current_char() {
-                '\n' => self.line += 1,
                 '\\' => {
                     self.advance();
 
                     if self.is_eof() {
-                        unterminated_str!(self, line_start, index_start);
+                        unterminated_str!(self, index_start);
                     }
 
-                    let escape_start = self.index;
+                    let escape_start = self.position;
 
                     match self.current_char() {
                         'n' => escape!(self, string.push('\n')),
@@ -222,14 +299,13 @@ impl Lexer {
 
                             let (h1, h2);
 
-                            next_hex!(self, index_start, line_start, h1);
-                            next_hex!(self, index_start, line_start, h2);
-
-                            let h1 = h1.to_digit(16).unwrap();
-                            let h2 = h2.to_digit(16).unwrap();
+                            next_hex!(self, index_start, h1);
+                            next_hex!(self, index_start, h2);
 
-                            let value = h1 * 16 + h2;
-                            let value = value as u8;
+                            let value = match (h1.to_digit(16), h2.to_digit(16)) {
+                                (Some(h1), Some(h2)) => (h1 * 16 + h2) as u8,
+                                _ => invalid_escape!(self, escape_start, "invalid hex escape, expected two hexadecimal digits"),
+                            };
 
                             string.push(value as char);
 
@@ -240,16 +316,11 @@ impl Lexer {
                             self.advance();
 
                             if self.is_eof() {
-                                unterminated_str!(self, line_start, index_start);
+                                unterminated_str!(self, index_start);
                             } else if self.current_char() != '{' {
                                 return Err(self
-                                    .make_error("incorrect unicode escape sequence", "E0003")
-                                    .push_slice(
-                                        Slice::new()
-                                            .set_line_start(line_start)
-                                            .set_line_end(self.line)
-                                            .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range(index_start..self.index)),
-                                    )
+                                    .make_error("incorrect unicode escape sequence", codes::E0003)
+                                    .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(index_start..self.position, AnnotationType::Error)))
                                     .build());
                             }
 
@@ -259,34 +330,36 @@ impl Lexer {
                             let mut n_digits = 1;
 
                             if self.is_eof() {
-                                unterminated_str!(self, line_start, index_start);
+                                unterminated_str!(self, index_start);
                             }
 
-                            let mut value = self.current_char().to_digit(16).unwrap();
+                            let mut value = match self.current_char().to_digit(16) {
+                                Some(digit) => digit,
+                                None if self.current_char() == '}' => invalid_escape!(self, escape_start, "empty unicode escape, expected at least one hexadecimal digit"),
+                                None => invalid_escape!(self, escape_start, "invalid character in unicode escape, expected a hexadecimal digit"),
+                            };
 
                             self.advance();
 
                             loop {
                                 if self.is_eof() {
-                                    unterminated_str!(self, line_start, index_start);
+                                    unterminated_str!(self, index_start);
                                 } else if self.current_char() == '}' {
                                     self.advance();
 
                                     if n_digits > 6 {
                                         return Err(self
-                                            .make_error("overlong unicode escape (must have at most 6 hex digits)", "E0003")
-                                            .push_slice(
-                                                Slice::new()
-                                                    .set_line_start(line_start)
-                                                    .set_line_end(self.line)
-                                                    .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range(index_start..self.index)),
-                                            )
+                                            .make_error("overlong unicode escape (must have at most 6 hex digits)", codes::E0003)
+                                            .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(index_start..self.position, AnnotationType::Error)))
                                             .build());
                                     }
 
                                     break;
                                 } else {
-                                    let digit = self.current_char().to_digit(16).unwrap();
+                                    let digit = match self.current_char().to_digit(16) {
+                                        Some(digit) => digit,
+                                        None => invalid_escape!(self, escape_start, "invalid character in unicode escape, expected a hexadecimal digit"),
+                                    };
 
                                     self.advance();
                                     n_digits += 1;
@@ -300,24 +373,23 @@ impl Lexer {
                                 }
                             }
 
-                            string.push(std::char::from_u32(value).unwrap());
+                            match std::char::from_u32(value) {
+                                Some(char) => string.push(char),
+                                None => invalid_escape!(self, escape_start, "invalid unicode escape, it must be at most `10FFFF` and not a surrogate"),
+                            }
 
                             continue;
                         }
 
                         '"' => escape!(self, string.push('"')),
 
-                        _ => {
+                        escape => {
                             self.advance();
 
                             return Err(self
-                                .make_error(format!("unknown character escape: {}", self.current_char()), "E0003")
-                                .push_slice(
-                                    Slice::new()
-                                        .set_line_start(line_start)
-                                        .set_line_end(self.line)
-                                        .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range(escape_start..self.index)),
-                                )
+                                .make_error(format!("unknown character escape: {}", escape), codes::E0003)
+                                .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(escape_start..self.position, AnnotationType::Error)))
+                                .set_note("valid escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\b`, `\\\"`, `\\xHH` and `\\u{HHHHHH}`")
                                 .build());
                         }
                     }
@@ -331,36 +403,33 @@ impl Lexer {
         }
 
         if self.is_eof() {
-            unterminated_str!(self, line_start, index_start);
+            unterminated_str!(self, index_start);
         }
 
         // Advance '"'
         self.advance();
 
-        Ok(self.new_token(TokenType::String(string), index_start, self.index))
+        Ok(self.new_token(TokenType::String(string), index_start, self.position))
     }
 
     /// Collect a character
     fn collect_char(&mut self) -> Result<Token, Diagnostic> {
-        let start = self.index;
+        let start = self.position;
 
         // Advance "'"
         self.advance();
 
+        if self.is_eof() {
+            return Err(self.unterminated_char_error(start));
+        }
+
         let char_v = self.current_char();
 
         // Advance the char.
         self.advance();
 
         if self.is_eof() || self.current_char() != '\'' {
-            let err = Err(self
-                .make_error("unterminated character literal", "E0002")
-                .push_slice(
-                    Slice::new()
-                        .set_line_start(self.line)
-                        .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range(start..self.index)),
-                )
-                .build());
+            let err = Err(self.unterminated_char_error(start));
 
             if !self.is_eof() && self.current_char() != '\'' {
                 self.advance();
@@ -372,13 +441,13 @@ impl Lexer {
         // Advance "'"
         self.advance();
 
-        Ok(self.new_token(TokenType::Char(char_v), start, self.index))
+        Ok(self.new_token(TokenType::Char(char_v), start, self.position))
     }
 
     /// Collect an identifier.
     fn collect_id(&mut self) -> Option<Token> {
         if is_valid_start_of_identifier(self.current_char()) {
-            let start = self.index;
+            let start = self.position;
             let mut id = String::new();
 
             id.push(self.current_char());
@@ -390,42 +459,23 @@ impl Lexer {
                 self.advance();
             }
 
-            // Get the substring.
-            let id = id.as_str();
-
-            match id {
-                "function" => Some(self.new_token(TokenType::Keyword(Keyword::Fn), start, self.index)),
-                "extern" => Some(self.new_token(TokenType::Keyword(Keyword::Extern), start, self.index)),
-
-                "return" => Some(self.new_token(TokenType::Keyword(Keyword::Return), start, self.index)),
-                "var" => Some(self.new_token(TokenType::Keyword(Keyword::Var), start, self.index)),
-
-                "as" => Some(self.new_token(TokenType::Keyword(Keyword::As), start, self.index)),
-                "unsafe" => Some(self.new_token(TokenType::Keyword(Keyword::Unsafe), start, self.index)),
-
-                "null" => Some(self.new_token(TokenType::Keyword(Keyword::Null), start, self.index)),
-
-                "if" => Some(self.new_token(TokenType::Keyword(Keyword::If), start, self.index)),
-                "else" => Some(self.new_token(TokenType::Keyword(Keyword::Else), start, self.index)),
-
-                "true" => Some(self.new_token(TokenType::Keyword(Keyword::True), start, self.index)),
-                "false" => Some(self.new_token(TokenType::Keyword(Keyword::False), start, self.index)),
-
-                "for" => Some(self.new_token(TokenType::Keyword(Keyword::For), start, self.index)),
-                "loop" => Some(self.new_token(TokenType::Keyword(Keyword::Loop), start, self.index)),
+            let kind = match id.parse() {
+                Ok(keyword) => TokenType::Keyword(keyword),
+                Err(_) => TokenType::Identifier(id),
+            };
 
-                _ => Some(self.new_token(TokenType::Identifier(id.into()), start, self.index)),
-            }
+            Some(self.new_token(kind, start, self.position))
         } else {
             None
         }
     }
 
     /// Collect a number.
-    fn collect_number(&mut self) -> Option<Token> {
-        let start = self.index;
+    fn collect_number(&mut self) -> Result<Option<Token>, Diagnostic> {
+        let start = self.position;
         let mut number = String::new();
         let mut typee = "number";
+        let mut decimal_points = 0;
 
         while !self.is_eof() && self.current_char().is_ascii_digit() {
             number.push(self.current_char());
@@ -433,21 +483,37 @@ impl Lexer {
 
             if !self.is_eof() && self.current_char() == '.' {
                 typee = "float";
+                decimal_points += 1;
                 number.push('.');
 
                 self.advance();
             }
         }
 
+        if decimal_points > 1 {
+            return Err(self
+                .make_error("number literal has more than one decimal point", codes::E0005)
+                .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(start..self.position, AnnotationType::Error).set_label("only one `.` is allowed in a float literal")))
+                .build());
+        }
+
         if number != String::new() {
-            match typee {
-                "number" => return Some(self.new_token(TokenType::Number(number.parse().unwrap()), start, self.index)),
-                "float" => return Some(self.new_token(TokenType::Float(number.parse().unwrap()), start, self.index)),
+            let kind = match typee {
+                "number" => number.parse().map(TokenType::Number).map_err(|_| "literal out of range for number"),
+                "float" => number.parse().map(TokenType::Float).map_err(|_| "invalid float literal"),
                 _ => unreachable!(),
-            }
+            };
+
+            return match kind {
+                Ok(kind) => Ok(Some(self.new_token(kind, start, self.position))),
+                Err(message) => Err(self
+                    .make_error(message, codes::E0004)
+                    .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(start..self.position, AnnotationType::Error)))
+                    .build()),
+            };
         }
 
-        None
+        Ok(None)
     }
 
     /// Skip all of the white spaces and comments.
@@ -460,13 +526,12 @@ impl Lexer {
             }
 
             match self.current_char() {
-                '\n' => {
-                    self.position += 1;
-                    self.line += 1;
-                    self.index = 0;
-                }
+                '\n' => self.advance(),
                 '/' => {
-                    if !self.is_next_eof() && self.next_char() == '/' {
+                    if self.preserve_comments {
+                        // Comments are collected as tokens by `collect_comment`.
+                        break;
+                    } else if !self.is_next_eof() && self.next_char() == '/' {
                         self.skip_to_end_of_line();
                     } else if !self.is_next_eof() && self.next_char() == '*' {
                         self.skip_block_comment()?;
@@ -496,30 +561,37 @@ impl Lexer {
     ///     Hello World!
     /// */
     /// ```
+    ///
+    /// The nested comments are counted instead of being skipped recursively, so that any depth of nesting can be skipped.
     fn skip_block_comment(&mut self) -> Result<(), Diagnostic> {
-        let block_start = self.index;
+        let block_start = self.position;
+        let mut depth = 0usize;
 
         loop {
-            self.advance();
-
             if self.is_eof() {
                 return Err(self
-                    .make_error("unterminated block comment", "E0002")
-                    .push_slice(
-                        Slice::new()
-                            .set_line_start(self.line)
-                            .push_annotation(SourceAnnotation::new().set_kind(AnnotationType::Error).set_range(block_start..self.index)),
-                    )
+                    .make_error("unterminated block comment", codes::E0002)
+                    .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(block_start..self.position, AnnotationType::Error)))
+                    .set_help("add `*/` to close the block comment")
                     .build());
             } else if !self.is_next_eof() && self.current_char() == '/' && self.next_char() == '*' {
-                self.skip_block_comment()?;
-            } else if self.current_char() == '*' && self.next_char() == '/' {
-                // Advance '*'
+                // Advance '/*'
                 self.advance();
-                // Advance '/'
                 self.advance();
 
-                break;
+                depth += 1;
+            } else if !self.is_next_eof() && self.current_char() == '*' && self.next_char() == '/' {
+                // Advance '*/'
+                self.advance();
+                self.advance();
+
+                depth -= 1;
+
+                if depth == 0 {
+                    break;
+                }
+            } else {
+                self.advance();
             }
         }
 
@@ -565,67 +637,79 @@ impl Lexer {
 
     /// Make a error with a message, code.
     fn make_error(&self, message: impl Into<String>, code: impl Into<String>) -> DiagnosticBuilder {
-        DiagnosticBuilder::new()
-            .set_source(&self.code)
-            .set_origin(&self.file)
-            .set_type(AnnotationType::Error)
-            .set_message(message.into())
-            .set_code(code.into())
+        DiagnosticBuilder::new(Severity::Error, message).set_code(code)
     }
 
-    /// Throw a unexpected char error.
-    #[inline]
-    fn throw_unexpected_char(&mut self) -> Diagnostic {
-        let err = self
-            .make_error("illegal character encountered", "E0001")
+    /// Make the error for a character literal that starts at the position and is not closed.
+    fn unterminated_char_error(&self, start: usize) -> Diagnostic {
+        self.make_error("unterminated character literal", codes::E0002)
+            .push_slice(Slice::new(self.file()).push_annotation(SourceAnnotation::new(start..self.position, AnnotationType::Error)))
+            .build()
+    }
+
+    /// Make the error for an unknown character token.
+    fn unknown_char_error(&self, token: &Token) -> Diagnostic {
+        self.make_error("illegal character encountered", codes::E0001)
             .push_slice(
-                Slice::new().set_line_start(self.line).push_annotation(
-                    SourceAnnotation::new()
-                        .set_kind(AnnotationType::Error)
-                        .set_label("unknown character")
-                        .set_range(self.index..self.index + 1),
-                ),
+                Slice::new(self.file()).push_annotation(SourceAnnotation::new(token.position.position_start..token.position.position_end, AnnotationType::Error).set_label("unknown character")),
             )
-            .build();
-
-        self.advance();
-
-        err
+            .build()
     }
 
     /// Advance to the next character.
     fn advance(&mut self) {
-        self.position += 1;
-        self.index += 1;
+        if let Some(char) = self.rest().chars().next() {
+            self.position += char.len_utf8();
+        }
+    }
+
+    /// Returns the part of the file that has not been scanned yet.
+    #[inline]
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
     }
 
     /// Returns the current character.
     #[inline]
     fn current_char(&self) -> char {
-        self.code.chars().nth(self.position).unwrap()
+        self.rest().chars().next().unwrap()
     }
 
     /// Returns the next character.
     #[inline]
     fn next_char(&self) -> char {
-        self.code.chars().nth(self.position + 1).unwrap()
+        self.rest().chars().nth(1).unwrap()
     }
 
     /// Check if lexer has reached the EOF (End of File)
     #[inline]
     fn is_eof(&self) -> bool {
-        self.code.chars().nth(self.position).is_none()
+        self.position >= self.source.len()
     }
 
     /// Check if the next character is EOF (End of File)
     #[inline]
     fn is_next_eof(&self) -> bool {
-        self.code.chars().nth(self.position + 1).is_none()
+        self.rest().chars().nth(1).is_none()
+    }
+
+    /// Returns the 1-based line of the position. The tokens are scanned in order, so only the lines since the last
+    /// token are counted.
+    fn line_index(&self, position: usize) -> usize {
+        let (last_position, last_line) = match self.line.get() {
+            (last_position, _) if last_position > position => (0, 1),
+            line => line,
+        };
+
+        let line = last_line + self.source[last_position..position].matches('\n').count();
+
+        self.line.set((position, line));
+        line
     }
 
     /// Create a token with its mentioned type
     fn new_token(&self, kind: TokenType, pos_start: usize, pos_end: usize) -> Token {
-        let position = TokenPosition::new(pos_start, pos_end, self.line);
+        let position = TokenPosition::new(pos_start, pos_end, self.line_index(pos_start));
 
         Token::new(kind, position)
     }
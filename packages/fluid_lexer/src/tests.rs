@@ -1,6 +1,10 @@
 //! This file contains all of the unit tests for the lexer.
 
-use crate::{Keyword, Lexer, Token, TokenType};
+use std::sync::Arc;
+
+use fluid_error::SourceFile;
+
+use crate::{dump_tokens, Keyword, Lexer, Token, TokenType};
 
 #[inline]
 fn get_token_type(tokens: Vec<Token>) -> Vec<TokenType> {
@@ -65,6 +69,28 @@ fn test_comment() {
     assert_eq!(tokens, vec![TokenType::EOF]);
 }
 
+#[test]
+fn test_deeply_nested_comments() {
+    // The nesting is not limited by the stack.
+    let source = format!("{}{} 1 /* /* */*/ 2", "/*".repeat(100_000), "*/".repeat(100_000));
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(&source, filename);
+    let tokens = get_token_type(lexer.run().unwrap());
+
+    assert_eq!(tokens, vec![TokenType::Number(1), TokenType::Number(2), TokenType::EOF]);
+
+    let source = format!("1 {}{}", "/*".repeat(100_000), "*/".repeat(99_999));
+
+    let mut lexer = Lexer::new(&source, filename);
+    let errors = lexer.run().unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0002"));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 2..source.len())));
+}
+
 #[test]
 fn string_test() {
     let source = "
@@ -143,3 +169,227 @@ fn test_invalid_shebang() {
         ]
     );
 }
+
+#[test]
+fn test_preserve_comments() {
+    let source = "
+        // Hello World!
+        var /* inline */ x
+        /*
+            /* nested */
+        */
+    ";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename).set_preserve_comments(true);
+    let tokens = get_token_type(lexer.run().unwrap());
+
+    assert_eq!(
+        tokens,
+        vec![
+            TokenType::Comment(String::from("// Hello World!")),
+            TokenType::Keyword(Keyword::Var),
+            TokenType::Comment(String::from("/* inline */")),
+            TokenType::Identifier(String::from("x")),
+            TokenType::Comment(String::from("/*\n            /* nested */\n        */")),
+            TokenType::EOF
+        ]
+    );
+}
+
+#[test]
+fn test_number_out_of_range() {
+    let source = "18446744073709551615 18446744073709551616;";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let (tokens, errors) = lexer.tokenize();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0004"));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 21..41)));
+
+    // The token after the malformed literal is kept.
+    assert_eq!(get_token_type(tokens), vec![TokenType::Number(18446744073709551615), TokenType::Semi, TokenType::EOF]);
+}
+
+#[test]
+fn test_unknown_token() {
+    let source = "var $x = @;";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let (tokens, errors) = lexer.tokenize();
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        get_token_type(tokens),
+        vec![
+            TokenType::Keyword(Keyword::Var),
+            TokenType::Unknown('$'),
+            TokenType::Identifier(String::from("x")),
+            TokenType::Eq,
+            TokenType::Unknown('@'),
+            TokenType::Semi,
+            TokenType::EOF
+        ]
+    );
+}
+
+#[test]
+fn test_token_classification() {
+    assert_eq!("function".parse(), Ok(Keyword::Fn));
+    assert_eq!("loop".parse(), Ok(Keyword::Loop));
+    assert_eq!("export".parse(), Ok(Keyword::Export));
+    assert_eq!("test".parse(), Ok(Keyword::Test));
+    assert_eq!("print".parse::<Keyword>(), Err(()));
+    assert!(Keyword::ALL.iter().all(|keyword| keyword.to_string().parse() == Ok(*keyword)));
+
+    assert!(TokenType::Keyword(Keyword::If).is_keyword());
+    assert!(TokenType::Keyword(Keyword::Null).is_literal());
+    assert!(TokenType::Number(1).is_literal());
+    assert!(!TokenType::Identifier(String::from("x")).is_literal());
+    assert!(TokenType::BangEq.is_operator());
+    assert!(!TokenType::Semi.is_operator());
+
+    assert!(TokenType::Star.binding_power() > TokenType::Plus.binding_power());
+    assert!(TokenType::Plus.binding_power() > TokenType::EqEq.binding_power());
+    assert!(TokenType::PipePipe.binding_power() > TokenType::QuestionQuestion.binding_power());
+    assert_eq!(TokenType::Comma.binding_power(), None);
+}
+
+#[test]
+fn test_question_question() {
+    let mut lexer = Lexer::new("a ?? b ? c???", "<test>");
+    let tokens = get_token_type(lexer.run().unwrap());
+
+    assert_eq!(
+        tokens,
+        vec![
+            TokenType::Identifier(String::from("a")),
+            TokenType::QuestionQuestion,
+            TokenType::Identifier(String::from("b")),
+            TokenType::Question,
+            TokenType::Identifier(String::from("c")),
+            TokenType::QuestionQuestion,
+            TokenType::Question,
+            TokenType::EOF,
+        ]
+    );
+    assert!(TokenType::QuestionQuestion.is_operator());
+}
+
+#[test]
+fn test_multiple_decimal_points() {
+    let source = "1.5 1.2.3";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let (tokens, errors) = lexer.tokenize();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0005"));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 4..9)));
+    assert_eq!(get_token_type(tokens), vec![TokenType::Float(1.5), TokenType::EOF]);
+}
+
+#[test]
+fn test_token_positions() {
+    let source = "/* a\n   comment */ var x = \"ü\";\n// end\nx";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+
+    let positions = tokens
+        .iter()
+        .map(|token| (token.position.position_start, token.position.position_end, token.position.line))
+        .collect::<Vec<_>>();
+
+    assert_eq!(positions, vec![(19, 22, 2), (23, 24, 2), (25, 26, 2), (27, 31, 2), (31, 32, 2), (40, 41, 4), (41, 41, 4)]);
+}
+
+#[test]
+fn test_render_plain() {
+    let source = "var x = $;";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let errors = lexer.run().unwrap_err();
+
+    let rendered = errors[0].render_plain();
+
+    assert!(rendered.contains("E0001"));
+    assert!(!rendered.contains('\u{1b}'));
+    assert!(rendered.lines().all(|line| line == line.trim_end()));
+}
+
+#[test]
+fn test_dump_tokens() {
+    let source = "function main() {\n    print(\"a\\tb\");\n}";
+    let file = SourceFile::new("<test>", source);
+
+    let mut lexer = Lexer::new(source, "<test>");
+    let tokens = lexer.run().unwrap();
+
+    let expected = r#"1:1     0..8     Keyword     function
+1:10    9..13    Identifier  main
+1:14    13..14   OpenParen   (
+1:15    14..15   CloseParen  )
+1:17    16..17   OpenBrace   {
+2:5     22..27   Identifier  print
+2:10    27..28   OpenParen   (
+2:11    28..34   String      "a\tb"
+2:17    34..35   CloseParen  )
+2:18    35..36   Semi        ;
+3:1     37..38   CloseBrace  }
+3:2     38..38   EOF
+"#;
+
+    assert_eq!(dump_tokens(&tokens, &file), expected);
+}
+
+#[test]
+fn test_from_file() {
+    let source = "function main() {\n    // a comment\n    print(1);\n}\n";
+    let file = Arc::new(SourceFile::new("<test>", source));
+
+    let mut lexer = Lexer::from_file(&file);
+    let tokens = lexer.run().unwrap();
+
+    // The lexer of a file uses it for the diagnostics, the lexer of a source creates an equal one.
+    assert!(Arc::ptr_eq(&lexer.file(), &file));
+    assert_eq!(
+        get_token_type(Lexer::new(source, "<test>").run().unwrap()),
+        tokens.iter().map(|token| token.kind.clone()).collect::<Vec<_>>()
+    );
+    assert_eq!(*Lexer::new(source, "<test>").file(), *file);
+    assert_eq!(tokens.iter().map(|token| token.position.line).collect::<Vec<_>>(), vec![1, 1, 1, 1, 1, 3, 3, 3, 3, 3, 4, 5]);
+}
+
+#[test]
+fn test_malformed_literals() {
+    // Every malformed literal is an error, the lexer must not panic on any of them.
+    let sources = [
+        ("\"\\xzz\"", "E0003"),
+        ("\"\\u{}\"", "E0003"),
+        ("\"\\u{zz}\"", "E0003"),
+        ("\"\\u{110000}\"", "E0003"),
+        ("\"\\u{D800}\"", "E0003"),
+        ("\"\\", "E0002"),
+        ("'", "E0002"),
+        ("/* *", "E0002"),
+    ];
+
+    for (source, code) in sources.iter() {
+        let errors = Lexer::new(source, "<test>").run().unwrap_err();
+
+        assert_eq!(errors[0].code(), Some(*code), "{}", source);
+    }
+}
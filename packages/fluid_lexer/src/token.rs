@@ -1,9 +1,12 @@
 //! This file contains the `TokenType` and `Token` interfaces.
 
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
+
+use fluid_error::SourceFile;
 
 /// A enum representing the type of the token.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum TokenType {
     // Single character tokens
     /// `(`
@@ -62,6 +65,8 @@ pub enum TokenType {
     AmpAmp,
     /// `||`
     PipePipe,
+    /// `??`
+    QuestionQuestion,
 
     /// A Keyword
     Keyword(Keyword),
@@ -81,17 +86,30 @@ pub enum TokenType {
     /// A character
     Char(char),
 
+    /// A comment, including its delimiters.
+    /// Only emitted when the lexer is preserving comments.
+    Comment(String),
+
+    /// An illegal character.
+    /// The lexer reports an error for it but keeps it so the token stream is complete.
+    Unknown(char),
+
     /// End of File
     EOF,
 }
 
 /// A enum specifying all of the reserved and used keywords.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Keyword {
     /// `function`
     Fn,
     /// `extern`
     Extern,
+    /// `export`
+    Export,
+    /// `test`
+    Test,
     /// `var`
     Var,
     /// `unsafe`
@@ -112,10 +130,114 @@ pub enum Keyword {
     Null,
     /// `for`
     For,
+    /// `in`
+    In,
     /// `loop`
     Loop,
 }
 
+impl TokenType {
+    /// Returns true if the token is a keyword.
+    pub fn is_keyword(&self) -> bool {
+        matches!(self, TokenType::Keyword(_))
+    }
+
+    /// Returns true if the token is a literal.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Number(_)
+                | TokenType::Float(_)
+                | TokenType::String(_)
+                | TokenType::Char(_)
+                | TokenType::Keyword(Keyword::True)
+                | TokenType::Keyword(Keyword::False)
+                | TokenType::Keyword(Keyword::Null)
+        )
+    }
+
+    /// Returns true if the token is an unary, binary or assignment operator.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Slash
+                | TokenType::Star
+                | TokenType::Eq
+                | TokenType::Bang
+                | TokenType::Greater
+                | TokenType::Lesser
+                | TokenType::Amp
+                | TokenType::Pipe
+                | TokenType::EqEq
+                | TokenType::BangEq
+                | TokenType::AmpAmp
+                | TokenType::PipePipe
+                | TokenType::QuestionQuestion
+        )
+    }
+
+    /// Returns the name of the kind of the token, the name of its variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenType::OpenParen => "OpenParen",
+            TokenType::CloseParen => "CloseParen",
+            TokenType::OpenBrace => "OpenBrace",
+            TokenType::CloseBrace => "CloseBrace",
+            TokenType::OpenBrac => "OpenBrac",
+            TokenType::CloseBrac => "CloseBrac",
+            TokenType::Semi => "Semi",
+            TokenType::Comma => "Comma",
+            TokenType::Plus => "Plus",
+            TokenType::Minus => "Minus",
+            TokenType::Slash => "Slash",
+            TokenType::Star => "Star",
+            TokenType::Eq => "Eq",
+            TokenType::Bang => "Bang",
+            TokenType::Colon => "Colon",
+            TokenType::Greater => "Greater",
+            TokenType::Lesser => "Lesser",
+            TokenType::Question => "Question",
+            TokenType::Amp => "Amp",
+            TokenType::Pipe => "Pipe",
+            TokenType::Hash => "Hash",
+            TokenType::EqEq => "EqEq",
+            TokenType::BangEq => "BangEq",
+            TokenType::TArrow => "TArrow",
+            TokenType::EArrow => "EArrow",
+            TokenType::AmpAmp => "AmpAmp",
+            TokenType::PipePipe => "PipePipe",
+            TokenType::QuestionQuestion => "QuestionQuestion",
+            TokenType::Keyword(_) => "Keyword",
+            TokenType::Identifier(_) => "Identifier",
+            TokenType::Number(_) => "Number",
+            TokenType::Float(_) => "Float",
+            TokenType::String(_) => "String",
+            TokenType::Char(_) => "Char",
+            TokenType::Comment(_) => "Comment",
+            TokenType::Unknown(_) => "Unknown",
+            TokenType::EOF => "EOF",
+        }
+    }
+
+    /// Returns the binding power of the token if its a binary operator.
+    /// A higher binding power binds tighter, see the precedence table in the parser.
+    pub fn binding_power(&self) -> Option<u8> {
+        match self {
+            TokenType::Eq => Some(1),
+            TokenType::QuestionQuestion => Some(2),
+            TokenType::PipePipe => Some(3),
+            TokenType::AmpAmp => Some(4),
+            TokenType::EqEq | TokenType::BangEq => Some(5),
+            TokenType::Lesser | TokenType::Greater => Some(6),
+            TokenType::Plus | TokenType::Minus => Some(7),
+            TokenType::Star | TokenType::Slash => Some(8),
+            _ => None,
+        }
+    }
+}
+
 /// A struct representing a token with a type and its location.
 #[derive(Debug)]
 pub struct Token {
@@ -126,7 +248,8 @@ pub struct Token {
 }
 
 impl Token {
-    pub(crate) fn new(kind: TokenType, position: TokenPosition) -> Self {
+    /// Create a token, the tokens of a source are usually created by the lexer.
+    pub fn new(kind: TokenType, position: TokenPosition) -> Self {
         Self { kind, position }
     }
 }
@@ -134,16 +257,17 @@ impl Token {
 /// The token's position.
 #[derive(Debug)]
 pub struct TokenPosition {
-    /// Start position of the token.
+    /// Start of the token, as a byte offset into the file.
     pub position_start: usize,
-    /// End position of the token.
+    /// End of the token, as a byte offset into the file.
     pub position_end: usize,
-    /// Line of the token.
+    /// Line of the start of the token.
     pub line: usize,
 }
 
 impl TokenPosition {
-    pub(crate) fn new(position_start: usize, position_end: usize, line: usize) -> Self {
+    /// Create the position of a token.
+    pub fn new(position_start: usize, position_end: usize, line: usize) -> Self {
         Self { position_start, position_end, line }
     }
 }
@@ -153,6 +277,8 @@ impl Display for Keyword {
         match self {
             Keyword::Fn => write!(f, "function"),
             Keyword::Extern => write!(f, "extern"),
+            Keyword::Export => write!(f, "export"),
+            Keyword::Test => write!(f, "test"),
             Keyword::Var => write!(f, "var"),
             Keyword::Unsafe => write!(f, "unsafe"),
             Keyword::Return => write!(f, "return"),
@@ -163,11 +289,61 @@ impl Display for Keyword {
             Keyword::False => write!(f, "false"),
             Keyword::Null => write!(f, "null"),
             Keyword::For => write!(f, "for"),
+            Keyword::In => write!(f, "in"),
             Keyword::Loop => write!(f, "loop"),
         }
     }
 }
 
+impl Keyword {
+    /// All of the keywords, in the order of their declarations.
+    pub const ALL: &'static [Keyword] = &[
+        Keyword::Fn,
+        Keyword::Extern,
+        Keyword::Export,
+        Keyword::Test,
+        Keyword::Var,
+        Keyword::Unsafe,
+        Keyword::Return,
+        Keyword::As,
+        Keyword::If,
+        Keyword::Else,
+        Keyword::True,
+        Keyword::False,
+        Keyword::Null,
+        Keyword::For,
+        Keyword::In,
+        Keyword::Loop,
+    ];
+}
+
+impl FromStr for Keyword {
+    type Err = ();
+
+    /// Get the keyword from its source representation.
+    fn from_str(keyword: &str) -> Result<Self, Self::Err> {
+        match keyword {
+            "function" => Ok(Keyword::Fn),
+            "extern" => Ok(Keyword::Extern),
+            "export" => Ok(Keyword::Export),
+            "test" => Ok(Keyword::Test),
+            "var" => Ok(Keyword::Var),
+            "unsafe" => Ok(Keyword::Unsafe),
+            "return" => Ok(Keyword::Return),
+            "as" => Ok(Keyword::As),
+            "if" => Ok(Keyword::If),
+            "else" => Ok(Keyword::Else),
+            "true" => Ok(Keyword::True),
+            "false" => Ok(Keyword::False),
+            "null" => Ok(Keyword::Null),
+            "for" => Ok(Keyword::For),
+            "in" => Ok(Keyword::In),
+            "loop" => Ok(Keyword::Loop),
+            _ => Err(()),
+        }
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -198,13 +374,47 @@ impl Display for TokenType {
             TokenType::EArrow => write!(f, "=>"),
             TokenType::AmpAmp => write!(f, "&&"),
             TokenType::PipePipe => write!(f, "||"),
+            TokenType::QuestionQuestion => write!(f, "??"),
             TokenType::Keyword(keyword) => write!(f, "{}", keyword),
             TokenType::Identifier(identifier) => write!(f, "{}", identifier),
             TokenType::Number(number) => write!(f, "{}", number),
             TokenType::Float(float) => write!(f, "{}", float),
             TokenType::String(string) => write!(f, "{}", string),
             TokenType::Char(char) => write!(f, "{}", char),
+            TokenType::Comment(comment) => write!(f, "{}", comment),
+            TokenType::Unknown(char) => write!(f, "{}", char),
             TokenType::EOF => write!(f, "EOF"),
         }
     }
 }
+
+/// Print the tokens one per line, with the kind, the line and column, the byte range and the value of every token.
+/// The strings, characters and comments are quoted and escaped, so every token is on its own line.
+///
+/// ```text
+/// 1:1     0..8     Keyword     function
+/// 1:10    9..13    Identifier  main
+/// ```
+pub fn dump_tokens(tokens: &[Token], file: &SourceFile) -> String {
+    let mut output = String::new();
+
+    for token in tokens {
+        let (line, column) = file.line_col(token.position.position_start);
+
+        let value = match &token.kind {
+            TokenType::String(string) | TokenType::Comment(string) => format!("{:?}", string),
+            TokenType::Char(char) | TokenType::Unknown(char) => format!("{:?}", char),
+            TokenType::EOF => String::new(),
+            kind => kind.to_string(),
+        };
+
+        let position = format!("{}:{}", line, column);
+        let span = format!("{}..{}", token.position.position_start, token.position.position_end);
+        let line = format!("{:<7} {:<8} {:<11} {}", position, span, token.kind.name(), value);
+
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
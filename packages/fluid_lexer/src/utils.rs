@@ -2,31 +2,32 @@
 #[macro_export]
 macro_rules! advance {
     ($self:ident, [$($char:tt => $ret:expr),*], $default:expr) => {{
+        let start = $self.position;
+
         let tok = {
             $self.advance();
 
             $(
                 if !$self.is_eof() && $char == $self.current_char() {
-                    let token = $self.new_token($ret, $self.position, $self.position + 2);
-
                     $self.advance();
 
-                    return Ok(token);
+                    return Ok($self.new_token($ret, start, $self.position));
                 }
             )*
 
             $default
         };
 
-        let token = $self.new_token(tok, $self.position, $self.position + 1);
+        let token = $self.new_token(tok, start, $self.position);
 
         return Ok(token);
     }};
     ($self:ident, $token:expr) => {{
-        let token = $self.new_token($token, $self.position, $self.position + 1);
+        let token = $token;
+        let start = $self.position;
 
         $self.advance();
 
-        return Ok(token);
+        return Ok($self.new_token(token, start, $self.position));
     }};
 }
@@ -0,0 +1,381 @@
+//! The analysis of an open document: its diagnostics, and the queries of the editor on its AST.
+
+use std::{ops::Range, sync::Arc};
+
+use fluid_error::{Diagnostic, SourceFile};
+use fluid_lexer::Lexer;
+use fluid_parser::{Ast, Builtin, Declaration, ExprArena, ExprId, Expression, ExpressionKind, Parser, Prototype, Statement, StatementKind, Type};
+use fluid_typeck::TypeChecker;
+
+/// What a document symbol is, with the values of the `SymbolKind` of the protocol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Function = 12,
+    Variable = 13,
+}
+
+/// A symbol declared in a document, shown in the outline of the editor.
+#[derive(Debug, PartialEq)]
+pub struct DocumentSymbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// The type of a variable or the signature of a function.
+    pub detail: String,
+    /// What the symbol is.
+    pub kind: SymbolKind,
+    /// The byte range of the whole declaration.
+    pub span: Range<usize>,
+    /// The byte range of the name in the declaration.
+    pub name_span: Range<usize>,
+    /// The symbols declared inside of the symbol, the variables and the parameters of a function.
+    pub children: Vec<DocumentSymbol>,
+}
+
+/// A name declared in a document, found by the span of its name.
+#[derive(Debug)]
+struct NameDecl {
+    /// The byte range of the name.
+    name_span: Range<usize>,
+    /// The declaration, as it would be written in the source.
+    signature: String,
+}
+
+/// The analysis of a document.
+/// The file is lexed, parsed and checked when the analysis is created, and the queries are answered from
+/// the AST. The names are resolved even if the checker finds errors, so the queries work on invalid programs
+/// as long as they can be parsed.
+#[derive(Debug)]
+pub struct Analysis {
+    /// The file of the document.
+    file: Arc<SourceFile>,
+    /// The AST of the document, empty if it could not be parsed.
+    ast: Ast,
+    /// The errors and the warnings of every pass that ran.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// Analyse a file. The passes stop at the first one that fails, like in the compiler.
+    pub fn new(file: Arc<SourceFile>) -> Self {
+        let mut analysis = Self {
+            file: file.clone(),
+            ast: Ast::default(),
+            diagnostics: vec![],
+        };
+
+        let tokens = match Lexer::from_file(&file).run() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                analysis.diagnostics = errors;
+
+                return analysis;
+            }
+        };
+
+        let mut parser = Parser::new(&tokens, file.clone());
+        let result = parser.run();
+
+        analysis.diagnostics.extend(parser.take_warnings());
+
+        analysis.ast = match result {
+            Ok(ast) => ast,
+            Err(errors) => {
+                analysis.diagnostics.extend(errors);
+
+                return analysis;
+            }
+        };
+
+        let mut checker = TypeChecker::new(file);
+
+        if let Err(errors) = checker.run(&mut analysis.ast) {
+            analysis.diagnostics.extend(errors);
+        }
+
+        analysis.diagnostics.extend(checker.take_warnings());
+        analysis
+    }
+
+    /// The file of the document.
+    pub fn file(&self) -> &Arc<SourceFile> {
+        &self.file
+    }
+
+    /// The errors and the warnings of the document.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Get the declaration of the name at the byte offset, as it would be written in the source.
+    /// Works on the references to a name and on the name of a declaration.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let declarations = self.declarations();
+
+        if let Some(declaration) = declarations.iter().find(|declaration| contains(&declaration.name_span, offset)) {
+            return Some(declaration.signature.clone());
+        }
+
+        let expression = find_expression(&self.ast.exprs, &self.ast.statements, offset)?;
+
+        match expression.def_site {
+            Some(ref def_site) => declarations
+                .iter()
+                .find(|declaration| declaration.name_span == def_site.span)
+                .map(|declaration| declaration.signature.clone()),
+            None => match expression.kind {
+                ExpressionKind::FunctionCall(ref name, _) => Builtin::from_name(name).map(builtin_signature),
+                _ => None,
+            },
+        }
+    }
+
+    /// Get the byte range of the name in the declaration of the name referenced at the byte offset.
+    pub fn definition(&self, offset: usize) -> Option<Range<usize>> {
+        find_expression(&self.ast.exprs, &self.ast.statements, offset)?.def_site.as_ref().map(|def_site| def_site.span.clone())
+    }
+
+    /// Get the symbols declared at the top level of the document, and the ones declared in its functions.
+    pub fn symbols(&self) -> Vec<DocumentSymbol> {
+        let mut symbols = vec![];
+
+        for statement in &self.ast.statements {
+            collect_symbols(statement, &mut symbols);
+        }
+
+        symbols
+    }
+
+    /// Get every name declared in the document.
+    fn declarations(&self) -> Vec<NameDecl> {
+        let mut declarations = vec![];
+
+        for statement in &self.ast.statements {
+            collect_declarations(statement, &mut declarations);
+        }
+
+        declarations
+    }
+}
+
+/// Returns true if the byte offset is in the span, or right after it, where the cursor is after typing a name.
+fn contains(span: &Range<usize>, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// Find the innermost variable reference, assignment or function call whose name is at the byte offset.
+fn find_expression<'a>(exprs: &'a ExprArena, statements: &[Statement], offset: usize) -> Option<&'a Expression> {
+    statements
+        .iter()
+        .filter(|statement| contains(&statement.span, offset))
+        .find_map(|statement| find_in_statement(exprs, statement, offset))
+}
+
+fn find_in_statement<'a>(exprs: &'a ExprArena, statement: &Statement, offset: usize) -> Option<&'a Expression> {
+    match statement.kind {
+        StatementKind::Expression(expression) => find_in_expression(exprs, expression, offset),
+        StatementKind::Return(value) => value.and_then(|value| find_in_expression(exprs, value, offset)),
+        StatementKind::If(condition, ref body, ref otherwise) => find_in_expression(exprs, condition, offset)
+            .or_else(|| find_in_statement(exprs, body, offset))
+            .or_else(|| otherwise.as_ref().and_then(|otherwise| find_in_statement(exprs, otherwise, offset))),
+        StatementKind::For(ref for_loop) => find_in_expression(exprs, for_loop.iterable, offset).or_else(|| find_in_statement(exprs, &for_loop.body, offset)),
+        StatementKind::Block(ref statements, value) => find_expression(exprs, statements, offset).or_else(|| value.and_then(|value| find_in_expression(exprs, value, offset))),
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::Function(ref function) => find_in_statement(exprs, &function.body, offset),
+            Declaration::Extern(_) => None,
+            Declaration::VarDef(ref var_def) => find_in_expression(exprs, var_def.value, offset),
+        },
+    }
+}
+
+fn find_in_expression(exprs: &ExprArena, id: ExprId, offset: usize) -> Option<&Expression> {
+    let expression = &exprs[id];
+
+    if !contains(&expression.span, offset) {
+        return None;
+    }
+
+    // The name of a reference, an assignment or a call is at the start of the expression.
+    let name_at = |name: &str| contains(&(expression.span.start..expression.span.start + name.len()), offset);
+
+    match expression.kind {
+        ExpressionKind::VarRef(_) => Some(expression),
+        ExpressionKind::VarAssign(ref name, value) => {
+            if name_at(name) {
+                Some(expression)
+            } else {
+                find_in_expression(exprs, value, offset)
+            }
+        }
+        ExpressionKind::FunctionCall(ref name, ref args) => {
+            if name_at(name) {
+                Some(expression)
+            } else {
+                args.iter().find_map(|&arg| find_in_expression(exprs, arg, offset))
+            }
+        }
+        ExpressionKind::BinaryOp(lhs, _, rhs) | ExpressionKind::Index(lhs, rhs) => find_in_expression(exprs, lhs, offset).or_else(|| find_in_expression(exprs, rhs, offset)),
+        ExpressionKind::Unary(_, operand) | ExpressionKind::Paren(operand) => find_in_expression(exprs, operand, offset),
+        ExpressionKind::If(condition, then, otherwise) => [condition, then, otherwise].iter().find_map(|&id| find_in_expression(exprs, id, offset)),
+        ExpressionKind::Block(ref statements, value) => find_expression(exprs, statements, offset).or_else(|| find_in_expression(exprs, value, offset)),
+        ExpressionKind::Literal(_) => None,
+    }
+}
+
+fn collect_declarations(statement: &Statement, declarations: &mut Vec<NameDecl>) {
+    match statement.kind {
+        StatementKind::If(_, ref body, ref otherwise) => {
+            collect_declarations(body, declarations);
+
+            if let Some(otherwise) = otherwise {
+                collect_declarations(otherwise, declarations);
+            }
+        }
+        StatementKind::For(ref for_loop) => {
+            declarations.push(NameDecl {
+                name_span: for_loop.name_span.clone(),
+                signature: format!("{}: {}", for_loop.name, Type::Char),
+            });
+
+            collect_declarations(&for_loop.body, declarations);
+        }
+        StatementKind::Block(ref statements, _) => {
+            for statement in statements {
+                collect_declarations(statement, declarations);
+            }
+        }
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::Function(ref function) => {
+                declare_prototype(&function.prototype, declarations);
+                collect_declarations(&function.body, declarations);
+            }
+            Declaration::Extern(ref prototypes) => {
+                for prototype in prototypes {
+                    declare_prototype(prototype, declarations);
+                }
+            }
+            Declaration::VarDef(ref var_def) => declarations.push(NameDecl {
+                name_span: var_def.name_span.clone(),
+                signature: format!("var {}: {}", var_def.name, var_def.typee),
+            }),
+        },
+        StatementKind::Expression(_) | StatementKind::Return(_) => (),
+    }
+}
+
+/// Declare a function and its parameters.
+fn declare_prototype(prototype: &Prototype, declarations: &mut Vec<NameDecl>) {
+    declarations.push(NameDecl {
+        name_span: prototype.name_span.clone(),
+        signature: prototype_signature(prototype),
+    });
+
+    for arg in &prototype.args {
+        declarations.push(NameDecl {
+            name_span: arg.name_span.clone(),
+            signature: format!("{}: {}", arg.name, arg.typee),
+        });
+    }
+}
+
+fn collect_symbols(statement: &Statement, symbols: &mut Vec<DocumentSymbol>) {
+    match statement.kind {
+        StatementKind::If(_, ref body, ref otherwise) => {
+            collect_symbols(body, symbols);
+
+            if let Some(otherwise) = otherwise {
+                collect_symbols(otherwise, symbols);
+            }
+        }
+        StatementKind::For(ref for_loop) => collect_symbols(&for_loop.body, symbols),
+        StatementKind::Block(ref statements, _) => {
+            for statement in statements {
+                collect_symbols(statement, symbols);
+            }
+        }
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::Function(ref function) => {
+                let mut children = function
+                    .prototype
+                    .args
+                    .iter()
+                    .map(|arg| DocumentSymbol {
+                        name: arg.name.clone(),
+                        detail: arg.typee.to_string(),
+                        kind: SymbolKind::Variable,
+                        span: arg.name_span.start..arg.type_span.end,
+                        name_span: arg.name_span.clone(),
+                        children: vec![],
+                    })
+                    .collect();
+
+                collect_symbols(&function.body, &mut children);
+
+                symbols.push(DocumentSymbol {
+                    name: function.prototype.name.clone(),
+                    detail: prototype_signature(&function.prototype),
+                    kind: SymbolKind::Function,
+                    span: statement.span.clone(),
+                    name_span: function.prototype.name_span.clone(),
+                    children,
+                });
+            }
+            Declaration::Extern(ref prototypes) => symbols.extend(prototypes.iter().map(|prototype| DocumentSymbol {
+                name: prototype.name.clone(),
+                detail: prototype_signature(prototype),
+                kind: SymbolKind::Function,
+                span: prototype.name_span.clone(),
+                name_span: prototype.name_span.clone(),
+                children: vec![],
+            })),
+            Declaration::VarDef(ref var_def) => symbols.push(DocumentSymbol {
+                name: var_def.name.clone(),
+                detail: var_def.typee.to_string(),
+                kind: SymbolKind::Variable,
+                span: statement.span.clone(),
+                name_span: var_def.name_span.clone(),
+                children: vec![],
+            }),
+        },
+        StatementKind::Expression(_) | StatementKind::Return(_) => (),
+    }
+}
+
+/// The signature of a function, as it would be written in the source.
+fn prototype_signature(prototype: &Prototype) -> String {
+    let args = prototype.args.iter().map(|arg| format!("{}: {}", arg.name, arg.typee)).collect::<Vec<_>>();
+    let signature = format!("function {}({})", prototype.name, args.join(", "));
+
+    match prototype.return_type {
+        Type::Void => signature,
+        return_type => format!("{} -> {}", signature, return_type),
+    }
+}
+
+/// The signature of a builtin. `print`, `println` and `format` take any value that can be printed, and `abs`, `min`
+/// and `max` take numbers or floats.
+fn builtin_signature(builtin: Builtin) -> String {
+    match builtin {
+        Builtin::Print | Builtin::Println => format!("function {}(value: number | float | string | bool)", builtin.name()),
+        Builtin::Input => format!("function {}() -> {}", builtin.name(), Type::String),
+        Builtin::Assert => format!("function {}(condition: {})", builtin.name(), Type::Bool),
+        Builtin::ReadFile => format!("function {}(path: {}) -> {}", builtin.name(), Type::String, Type::String),
+        Builtin::WriteFile | Builtin::AppendFile => format!("function {}(path: {}, contents: {}) -> {}", builtin.name(), Type::String, Type::String, Type::Bool),
+        Builtin::Sqrt | Builtin::Floor | Builtin::Ceil | Builtin::Sin | Builtin::Cos => format!("function {}(x: {}) -> {}", builtin.name(), Type::Float, Type::Float),
+        Builtin::Pow => format!("function {}(base: {}, exponent: {}) -> {}", builtin.name(), Type::Float, Type::Float, Type::Float),
+        Builtin::Abs => format!("function {}(x: number | float) -> number | float", builtin.name()),
+        Builtin::Min | Builtin::Max => format!("function {}(a: number | float, b: number | float) -> number | float", builtin.name()),
+        Builtin::Format => format!(
+            "function {}(format: {}, values: number | float | string | bool | char...) -> {}",
+            builtin.name(),
+            Type::String,
+            Type::String
+        ),
+        Builtin::Random => format!("function {}() -> {}", builtin.name(), Type::Float),
+        Builtin::RandomRange => format!("function {}(lo: {}, hi: {}) -> {}", builtin.name(), Type::Number, Type::Number, Type::Number),
+        Builtin::Seed => format!("function {}(n: {})", builtin.name(), Type::Number),
+        Builtin::NowMillis => format!("function {}() -> {}", builtin.name(), Type::Number),
+        Builtin::Sleep => format!("function {}(ms: {})", builtin.name(), Type::Number),
+        Builtin::Env => format!("function {}(name: {}) -> {}", builtin.name(), Type::String, Type::String),
+        Builtin::SetEnv => format!("function {}(name: {}, value: {})", builtin.name(), Type::String, Type::String),
+    }
+}
@@ -0,0 +1,95 @@
+//! The `fluid_lsp` crate is the language server of Fluid.
+//! It speaks the Language Server Protocol over the standard input and output, and provides the diagnostics of the
+//! lexer, the parser and the type checker, the declaration of a name on hover, the go-to-definition of the names
+//! resolved by the type checker and the symbols of a document.
+//!
+//! The documents are synchronized in full: the client sends the whole text of a document every time it changes.
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+mod analysis;
+mod server;
+
+#[cfg(test)]
+mod tests;
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+pub use crate::{
+    analysis::{Analysis, DocumentSymbol, SymbolKind},
+    server::Server,
+};
+
+/// Run the language server on the standard input and output until the client asks it to exit.
+/// Returns the exit code of the server.
+pub fn run() -> io::Result<i32> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+
+    let mut server = Server::new();
+
+    while let Some(content) = read_message(&mut input)? {
+        let responses = match serde_json::from_str(&content) {
+            Ok(message) => server.handle(&message),
+            Err(_) => vec![server::error_response(Value::Null, server::PARSE_ERROR, "the message is not valid JSON")],
+        };
+
+        for response in responses {
+            write_message(&mut output, &response)?;
+        }
+
+        if let Some(exit_code) = server.exit_code() {
+            return Ok(exit_code);
+        }
+    }
+
+    // The client went away without asking the server to exit.
+    Ok(1)
+}
+
+/// Read the content of a message, after its headers. Returns `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        let mut parts = header.splitn(2, ':');
+
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the message has no `Content-Length` header"))?;
+    let mut content = vec![0; content_length];
+
+    input.read_exact(&mut content)?;
+
+    String::from_utf8(content).map(Some).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Write a message with its headers.
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()
+}
@@ -0,0 +1,273 @@
+//! The state of the language server, and the handling of the messages of the protocol.
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use fluid_error::{Diagnostic, Severity, SourceFile};
+use serde_json::{json, Value};
+
+use crate::analysis::{Analysis, DocumentSymbol};
+
+/// The error code of a request whose JSON could not be parsed.
+pub const PARSE_ERROR: i64 = -32700;
+/// The error code of a message that is not a valid request.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The error code of a request whose method is not supported.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The error code of a request whose parameters are not valid.
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// The state of the language server.
+/// The documents are analysed every time they change, and the requests are answered from their analysis.
+#[derive(Debug, Default)]
+pub struct Server {
+    /// The open documents, by their URI.
+    documents: HashMap<String, Analysis>,
+    /// Whether the client asked the server to shut down.
+    shutdown: bool,
+    /// The exit code of the server, once the client asked it to exit.
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    /// Create a new server, without open documents.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The exit code of the server, once the client asked it to exit.
+    /// It is 0 if the client asked the server to shut down before, and 1 otherwise.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handle a message of the client, and return the messages to send back.
+    /// A request gets a response, a notification about a document gets its diagnostics.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            // The client never sends responses, the server does not send requests.
+            None => return vec![error_response(message.get("id").cloned().unwrap_or(Value::Null), INVALID_REQUEST, "the message has no method")],
+        };
+
+        let params = message.get("params").unwrap_or(&Value::Null);
+
+        match message.get("id").cloned() {
+            Some(id) => {
+                let response = if self.shutdown {
+                    Err((INVALID_REQUEST, "the server is shutting down".to_owned()))
+                } else {
+                    self.handle_request(method, params)
+                };
+
+                let response = match response {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => error_response(id, code, &message),
+                };
+
+                vec![response]
+            }
+            None => self.handle_notification(method, params),
+        }
+    }
+
+    /// Handle a request, and return its result or its error code and message.
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shutdown = true;
+
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (analysis, offset) = self.document_position(params)?;
+
+                Ok(match analysis.hover(offset) {
+                    Some(signature) => json!({ "contents": { "kind": "markdown", "value": format!("```fluid\n{}\n```", signature) } }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let (analysis, offset) = self.document_position(params)?;
+                let uri = text_document_uri(params).unwrap_or_default();
+
+                Ok(match analysis.definition(offset) {
+                    Some(span) => json!({ "uri": uri, "range": range(analysis.file(), span) }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/documentSymbol" => {
+                let analysis = self.document(params)?;
+
+                Ok(Value::Array(analysis.symbols().iter().map(|symbol| document_symbol(analysis.file(), symbol)).collect()))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("the method `{}` is not supported", method))),
+        }
+    }
+
+    /// Handle a notification, and return the notifications to send back.
+    /// The notifications that are not supported are ignored.
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+
+                vec![]
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                let uri = document.and_then(|document| document.get("uri")).and_then(Value::as_str);
+                let text = document.and_then(|document| document.get("text")).and_then(Value::as_str);
+
+                match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => vec![],
+                }
+            }
+            "textDocument/didChange" => {
+                // The server only supports the full synchronization, so the last change is the whole text.
+                let uri = text_document_uri(params);
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str);
+
+                match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => vec![],
+                }
+            }
+            "textDocument/didClose" => match text_document_uri(params) {
+                Some(uri) => {
+                    self.documents.remove(uri);
+
+                    vec![publish_diagnostics(uri, Value::Array(vec![]))]
+                }
+                None => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Analyse the new text of a document, and return the notification of its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Value {
+        let analysis = Analysis::new(Arc::new(SourceFile::new(uri, text)));
+        let diagnostics = analysis.diagnostics().iter().map(|diagnostic| diagnostic_json(analysis.file(), diagnostic)).collect();
+
+        self.documents.insert(uri.to_owned(), analysis);
+
+        publish_diagnostics(uri, Value::Array(diagnostics))
+    }
+
+    /// Get the analysis of the document of the parameters of a request.
+    fn document(&self, params: &Value) -> Result<&Analysis, (i64, String)> {
+        let uri = text_document_uri(params).ok_or_else(|| (INVALID_PARAMS, "the parameters have no text document".to_owned()))?;
+
+        self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("the document `{}` is not open", uri)))
+    }
+
+    /// Get the analysis of the document of the parameters of a request, and the byte offset of their position.
+    fn document_position(&self, params: &Value) -> Result<(&Analysis, usize), (i64, String)> {
+        let analysis = self.document(params)?;
+        let position = params.get("position");
+        let line = position.and_then(|position| position.get("line")).and_then(Value::as_u64);
+        let character = position.and_then(|position| position.get("character")).and_then(Value::as_u64);
+
+        match (line, character) {
+            (Some(line), Some(character)) => Ok((analysis, offset(analysis.file(), line as usize, character as usize))),
+            _ => Err((INVALID_PARAMS, "the parameters have no position".to_owned())),
+        }
+    }
+}
+
+/// The result of the `initialize` request, the capabilities of the server.
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            // The whole text of a document is sent when it changes.
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "documentSymbolProvider": true,
+        },
+        "serverInfo": { "name": "fluid", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Get the URI of the text document of the parameters of a request or a notification.
+fn text_document_uri(params: &Value) -> Option<&str> {
+    params.get("textDocument")?.get("uri")?.as_str()
+}
+
+pub(crate) fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } })
+}
+
+/// Convert a diagnostic to the one of the protocol. A diagnostic without a span is shown at the start of the file.
+fn diagnostic_json(file: &SourceFile, diagnostic: &Diagnostic) -> Value {
+    let span = diagnostic.primary_span().map_or(0..0, |(_, span)| span);
+
+    let severity = match diagnostic.severity() {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    let mut json = json!({ "range": range(file, span), "severity": severity, "source": "fluid", "message": diagnostic.message() });
+
+    if let Some(code) = diagnostic.code() {
+        json["code"] = code.into();
+    }
+
+    json
+}
+
+fn document_symbol(file: &SourceFile, symbol: &DocumentSymbol) -> Value {
+    json!({
+        "name": symbol.name,
+        "detail": symbol.detail,
+        "kind": symbol.kind as usize,
+        "range": range(file, symbol.span.clone()),
+        "selectionRange": range(file, symbol.name_span.clone()),
+        "children": symbol.children.iter().map(|child| document_symbol(file, child)).collect::<Vec<_>>(),
+    })
+}
+
+/// Convert a byte range to a range of the protocol.
+fn range(file: &SourceFile, span: Range<usize>) -> Value {
+    json!({ "start": position(file, span.start), "end": position(file, span.end) })
+}
+
+/// Convert a byte offset to a position of the protocol.
+/// The lines are 0-based, and the characters are counted in UTF-16 code units.
+fn position(file: &SourceFile, offset: usize) -> Value {
+    let line = file.line_index(offset);
+    let character = file.snippet(file.line_start(line)..offset).encode_utf16().count();
+
+    json!({ "line": line - 1, "character": character })
+}
+
+/// Convert a position of the protocol to a byte offset. A position past the end of its line is at the end of the line.
+fn offset(file: &SourceFile, line: usize, character: usize) -> usize {
+    if line >= file.line_count() {
+        return file.source().len();
+    }
+
+    let start = file.line_start(line + 1);
+    let mut units = 0;
+
+    for (index, char) in file.lines(line + 1, line + 1).char_indices() {
+        if units >= character {
+            return start + index;
+        }
+
+        units += char.len_utf16();
+    }
+
+    file.line_end(line + 1)
+}
@@ -0,0 +1,167 @@
+//! This file contains all of the unit tests for the language server.
+
+use serde_json::{json, Value};
+
+use crate::{read_message, write_message, Server};
+
+const URI: &str = "file:///test.fl";
+
+const SOURCE: &str = "function add(a: number, b: number) -> number {
+    return a + b;
+}
+
+function main() {
+    var x: number = add(1, 2);
+    println(x);
+}
+";
+
+#[inline]
+fn parse(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+/// Create a server with the document open, and return the notification of its diagnostics.
+fn open(source: &str) -> (Server, Value) {
+    let mut server = Server::new();
+    let text = Value::from(source).to_string();
+
+    let mut messages = server.handle(&parse(&format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"fluid","version":1,"text":{}}}}}}}"#,
+        URI, text
+    )));
+
+    assert_eq!(messages.len(), 1);
+
+    (server, messages.remove(0))
+}
+
+/// Send a request about a position of the document, and return its result.
+fn request(server: &mut Server, method: &str, line: usize, character: usize) -> Value {
+    let mut messages = server.handle(&parse(&format!(
+        r#"{{"jsonrpc":"2.0","id":1,"method":"{}","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}}}"#,
+        method, URI, line, character
+    )));
+
+    assert_eq!(messages.len(), 1);
+
+    messages.remove(0).get("result").cloned().unwrap()
+}
+
+#[test]
+fn test_framing() {
+    let mut output = vec![];
+
+    write_message(&mut output, &parse(r#"{"id":"é"}"#)).unwrap();
+
+    assert_eq!(String::from_utf8(output.clone()).unwrap(), "Content-Length: 11\r\n\r\n{\"id\":\"é\"}");
+
+    let mut input = &output[..];
+
+    assert_eq!(read_message(&mut input).unwrap(), Some("{\"id\":\"é\"}".to_owned()));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+}
+
+#[test]
+fn test_lifecycle() {
+    let mut server = Server::new();
+
+    let response = server.handle(&parse(r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{}}"#)).remove(0);
+    let capabilities = response.get("result").and_then(|result| result.get("capabilities")).unwrap();
+
+    assert_eq!(capabilities.get("hoverProvider"), Some(&Value::Bool(true)));
+
+    let response = server.handle(&parse(r#"{"jsonrpc":"2.0","id":1,"method":"unknown"}"#)).remove(0);
+
+    assert_eq!(response.get("error").and_then(|error| error.get("code")), Some(&json!(-32601)));
+
+    server.handle(&parse(r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#));
+    server.handle(&parse(r#"{"jsonrpc":"2.0","method":"exit"}"#));
+
+    assert_eq!(server.exit_code(), Some(0));
+}
+
+#[test]
+fn test_diagnostics() {
+    let (_, notification) = open("function main() {\n    var x: number = \"é\" + y;\n    println(x);\n}\n");
+    let diagnostics = notification.get("params").and_then(|params| params.get("diagnostics")).and_then(Value::as_array).unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].get("severity"), Some(&json!(1)));
+    assert_eq!(diagnostics[0].get("code").and_then(Value::as_str), Some("E0010"));
+    assert_eq!(diagnostics[0].get("source").and_then(Value::as_str), Some("fluid"));
+
+    // The characters are counted in UTF-16 code units.
+    let start = diagnostics[0].get("range").and_then(|range| range.get("start")).unwrap();
+
+    assert_eq!(start, &parse(r#"{"line":1,"character":26}"#));
+
+    let (_, notification) = open(SOURCE);
+
+    assert_eq!(notification.get("params").and_then(|params| params.get("diagnostics")), Some(&json!([])));
+}
+
+#[test]
+fn test_hover() {
+    let (mut server, _) = open(SOURCE);
+
+    let hover = |server: &mut Server, line, character| {
+        let result = request(server, "textDocument/hover", line, character);
+
+        result.get("contents").and_then(|contents| contents.get("value")).and_then(Value::as_str).map(str::to_owned)
+    };
+
+    let add = "```fluid\nfunction add(a: number, b: number) -> number\n```";
+
+    assert_eq!(hover(&mut server, 5, 21).as_deref(), Some(add));
+    assert_eq!(hover(&mut server, 0, 10).as_deref(), Some(add));
+    assert_eq!(hover(&mut server, 1, 11).as_deref(), Some("```fluid\na: number\n```"));
+    assert_eq!(hover(&mut server, 6, 12).as_deref(), Some("```fluid\nvar x: number\n```"));
+    assert_eq!(hover(&mut server, 6, 5).as_deref(), Some("```fluid\nfunction println(value: number | float | string | bool)\n```"));
+    assert_eq!(hover(&mut server, 5, 27), None);
+}
+
+#[test]
+fn test_definition() {
+    let (mut server, _) = open(SOURCE);
+
+    assert_eq!(
+        request(&mut server, "textDocument/definition", 5, 21),
+        parse(&format!(r#"{{"uri":"{}","range":{{"start":{{"line":0,"character":9}},"end":{{"line":0,"character":12}}}}}}"#, URI))
+    );
+
+    assert_eq!(
+        request(&mut server, "textDocument/definition", 1, 15).get("range").and_then(|range| range.get("start")),
+        Some(&parse(r#"{"line":0,"character":24}"#))
+    );
+
+    assert_eq!(request(&mut server, "textDocument/definition", 6, 6), Value::Null);
+}
+
+#[test]
+fn test_document_symbols() {
+    let (mut server, _) = open(SOURCE);
+
+    let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0);
+    let names = |symbols: &Value| {
+        symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| symbol.get("name").and_then(Value::as_str).unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(&symbols), vec!["add", "main"]);
+
+    let add = &symbols.as_array().unwrap()[0];
+
+    assert_eq!(add.get("kind"), Some(&json!(12)));
+    assert_eq!(add.get("detail").and_then(Value::as_str), Some("function add(a: number, b: number) -> number"));
+    assert_eq!(names(add.get("children").unwrap()), vec!["a", "b"]);
+
+    let main = &symbols.as_array().unwrap()[1];
+
+    assert_eq!(names(main.get("children").unwrap()), vec!["x"]);
+    assert_eq!(main.get("range").and_then(|range| range.get("end")), Some(&parse(r#"{"line":7,"character":1}"#)));
+}
@@ -0,0 +1,89 @@
+//! The Fluid mangling scheme.
+//!
+//! A mangled symbol starts with `_F`, followed by the segments of the module path and the name of the function, then
+//! `E` and the types of the parameters:
+//!
+//! ```text
+//! <symbol>     ::= "_F" <identifier>+ "E" <parameters>
+//! <identifier> ::= <length> <characters>
+//! <parameters> ::= "v" | <type>+
+//! <type>       ::= "n" (number) | "f" (float) | "s" (string) | "b" (bool) | "c" (char)
+//! ```
+//!
+//! The last identifier is the name of the function, the ones before it are the module path. A function without
+//! parameters is encoded with the `void` type `v`. For example, `add(a: number, b: number)` in the module `math` is
+//! mangled into `_F4math3addEnn`.
+
+use fluid_parser::Type;
+
+use crate::{parse_identifiers, Symbol};
+
+/// The prefix of the mangled symbols.
+const PREFIX: &str = "_F";
+
+/// Get the code of a type.
+fn type_code(kind: Type) -> char {
+    match kind {
+        Type::Void => 'v',
+        Type::Number => 'n',
+        Type::Float => 'f',
+        Type::String => 's',
+        Type::Bool => 'b',
+        Type::Char => 'c',
+    }
+}
+
+/// Get the type of a code.
+fn code_type(code: char) -> Option<Type> {
+    match code {
+        'v' => Some(Type::Void),
+        'n' => Some(Type::Number),
+        'f' => Some(Type::Float),
+        's' => Some(Type::String),
+        'b' => Some(Type::Bool),
+        'c' => Some(Type::Char),
+        _ => None,
+    }
+}
+
+/// Mangle a symbol.
+pub(crate) fn mangle(symbol: &Symbol) -> String {
+    let mut mangled = String::from(PREFIX);
+
+    for identifier in symbol.path.iter().chain(Some(&symbol.name)) {
+        mangled.push_str(&identifier.len().to_string());
+        mangled.push_str(identifier);
+    }
+
+    mangled.push('E');
+
+    if symbol.params.is_empty() {
+        mangled.push(type_code(Type::Void));
+    } else {
+        mangled.extend(symbol.params.iter().map(|param| type_code(*param)));
+    }
+
+    mangled
+}
+
+/// Demangle a symbol. Returns `None` if the symbol is not a valid mangled symbol.
+pub(crate) fn demangle(mangled: &str) -> Option<Symbol> {
+    if !mangled.starts_with(PREFIX) {
+        return None;
+    }
+
+    let (mut identifiers, rest) = parse_identifiers(&mangled[PREFIX.len()..])?;
+
+    if !rest.starts_with('E') {
+        return None;
+    }
+
+    let name = identifiers.pop()?;
+    let params = match &rest[1..] {
+        "v" => vec![],
+        codes if !codes.is_empty() => codes.chars().map(code_type).collect::<Option<Vec<_>>>().filter(|params| !params.contains(&Type::Void))?,
+        _ => return None,
+    };
+
+    Some(Symbol::new(identifiers, name, params))
+}
@@ -0,0 +1,103 @@
+//! The mangling scheme of the Itanium C++ ABI, used by the C++ compilers of most of the platforms.
+//!
+//! A function is mangled like a C++ function of a namespace named after the module path, with the C++ type of the
+//! parameters. The `number` type is a `long long`, the `float` type is a `float`, the `string` type is a `char*`,
+//! the `bool` type is a `bool` and the `char` type is a `char`. For example, `add(a: number, b: number)` in the
+//! module `math` is mangled into `_ZN4math3addExx`, which `c++filt` demangles into `math::add(long long, long long)`.
+//!
+//! See https://itanium-cxx-abi.github.io/cxx-abi/abi.html#mangling for the whole scheme.
+
+use fluid_parser::Type;
+
+use crate::{parse_identifiers, Symbol};
+
+/// The prefix of the mangled symbols.
+const PREFIX: &str = "_Z";
+
+/// Get the code of a type.
+fn type_code(kind: Type) -> &'static str {
+    match kind {
+        Type::Void => "v",
+        Type::Number => "x",
+        Type::Float => "f",
+        Type::String => "Pc",
+        Type::Bool => "b",
+        Type::Char => "c",
+    }
+}
+
+/// Mangle a symbol.
+pub(crate) fn mangle(symbol: &Symbol) -> String {
+    let mut mangled = String::from(PREFIX);
+
+    // The names in a namespace are nested names, between `N` and `E`.
+    let nested = !symbol.path.is_empty();
+
+    if nested {
+        mangled.push('N');
+    }
+
+    for identifier in symbol.path.iter().chain(Some(&symbol.name)) {
+        mangled.push_str(&identifier.len().to_string());
+        mangled.push_str(identifier);
+    }
+
+    if nested {
+        mangled.push('E');
+    }
+
+    if symbol.params.is_empty() {
+        mangled.push_str(type_code(Type::Void));
+    } else {
+        mangled.extend(symbol.params.iter().map(|param| type_code(*param)));
+    }
+
+    mangled
+}
+
+/// Demangle a symbol mangled by `mangle`. Returns `None` for the other symbols, including the C++ symbols that
+/// use the parts of the scheme that Fluid does not use.
+pub(crate) fn demangle(mangled: &str) -> Option<Symbol> {
+    if !mangled.starts_with(PREFIX) {
+        return None;
+    }
+
+    let rest = &mangled[PREFIX.len()..];
+    let nested = rest.starts_with('N');
+
+    let (mut identifiers, mut rest) = parse_identifiers(if nested { &rest[1..] } else { rest })?;
+
+    if nested {
+        if identifiers.len() < 2 || !rest.starts_with('E') {
+            return None;
+        }
+
+        rest = &rest[1..];
+    } else if identifiers.len() != 1 {
+        return None;
+    }
+
+    let name = identifiers.pop()?;
+
+    if rest == "v" {
+        return Some(Symbol::new(identifiers, name, vec![]));
+    }
+
+    let mut params = vec![];
+
+    while !rest.is_empty() {
+        let (kind, code) = [Type::Number, Type::Float, Type::String, Type::Bool, Type::Char]
+            .iter()
+            .map(|kind| (*kind, type_code(*kind)))
+            .find(|(_, code)| rest.starts_with(code))?;
+
+        params.push(kind);
+        rest = &rest[code.len()..];
+    }
+
+    if params.is_empty() {
+        return None;
+    }
+
+    Some(Symbol::new(identifiers, name, params))
+}
@@ -2,10 +2,154 @@
 //! Name mangling is a technique used to solve various problems caused by the need to resolve unique names for programming entities.
 //!
 //! For more information about name mangling: https://en.wikipedia.org/wiki/Name_mangling
+//!
+//! There are two mangling schemes. The Fluid scheme is the default one, the Itanium C++ ABI scheme makes the symbols
+//! readable by the tools that demangle C++ symbols, like `c++filt`, the profilers and the debuggers.
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+mod fluid;
+mod itanium;
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 use fluid_parser::Type;
 
-/// Mangle a function name.
-pub fn mangle_function_name(name: String, _params: Vec<Type>) -> String {
-    name
+/// A mangling scheme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mangling {
+    /// The Fluid mangling scheme.
+    Fluid,
+    /// The mangling scheme of the Itanium C++ ABI.
+    Itanium,
+}
+
+impl Default for Mangling {
+    fn default() -> Self {
+        Mangling::Fluid
+    }
+}
+
+impl FromStr for Mangling {
+    type Err = String;
+
+    fn from_str(mangling: &str) -> Result<Self, Self::Err> {
+        match mangling {
+            "fluid" => Ok(Mangling::Fluid),
+            "itanium" => Ok(Mangling::Itanium),
+            _ => Err(format!("invalid mangling scheme `{}`, expected `fluid` or `itanium`", mangling)),
+        }
+    }
+}
+
+/// A function symbol, before it is mangled or after it is demangled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// The segments of the path of the module that defines the function.
+    pub path: Vec<String>,
+    /// The name of the function.
+    pub name: String,
+    /// The types of the parameters of the function.
+    pub params: Vec<Type>,
+}
+
+impl Symbol {
+    /// Create a new symbol.
+    pub fn new(path: Vec<String>, name: impl Into<String>, params: Vec<Type>) -> Self {
+        Self { path, name: name.into(), params }
+    }
+}
+
+impl Display for Symbol {
+    /// Format the symbol as a Fluid signature, like `math.add(number, number)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.path {
+            write!(f, "{}.", segment)?;
+        }
+
+        write!(f, "{}(", self.name)?;
+
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{}", param)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// Mangle a symbol with the mangling scheme.
+pub fn mangle(symbol: &Symbol, mangling: Mangling) -> String {
+    match mangling {
+        Mangling::Fluid => fluid::mangle(symbol),
+        Mangling::Itanium => itanium::mangle(symbol),
+    }
+}
+
+/// Demangle a symbol mangled with any of the mangling schemes.
+/// Returns `None` if the symbol is not a valid mangled Fluid symbol.
+pub fn demangle(mangled: &str) -> Option<Symbol> {
+    fluid::demangle(mangled).or_else(|| itanium::demangle(mangled))
+}
+
+/// Demangle all of the mangled symbols in a text, like the output of a linker, a stack trace or `objdump`.
+/// The symbols are replaced with their Fluid signature, the rest of the text is left as it is.
+pub fn demangle_text(text: &str) -> String {
+    let mut demangled = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let is_symbol_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let end = rest.find(|c: char| !is_symbol_char(c)).unwrap_or(rest.len());
+
+        if end == 0 {
+            let c = rest.chars().next().unwrap();
+
+            demangled.push(c);
+            rest = &rest[c.len_utf8()..];
+
+            continue;
+        }
+
+        let word = &rest[..end];
+
+        // The symbols of some platforms, like macOS, have an extra underscore at their start.
+        match demangle(word).or_else(|| word.strip_prefix('_').and_then(demangle)) {
+            Some(symbol) => demangled.push_str(&symbol.to_string()),
+            None => demangled.push_str(word),
+        }
+
+        rest = &rest[end..];
+    }
+
+    demangled
+}
+
+/// Parse the `<length> <characters>` identifiers at the start of a mangled symbol, until the first character
+/// that is not a digit. Returns the identifiers and the rest of the symbol.
+fn parse_identifiers(mut rest: &str) -> Option<(Vec<String>, &str)> {
+    let mut identifiers = vec![];
+
+    while rest.starts_with(|c: char| c.is_ascii_digit()) {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let length = rest[..digits].parse::<usize>().ok().filter(|length| *length > 0)?;
+
+        rest = &rest[digits..];
+
+        let identifier = rest.get(..length)?;
+
+        identifiers.push(identifier.to_owned());
+        rest = &rest[length..];
+    }
+
+    Some((identifiers, rest))
 }
@@ -0,0 +1,87 @@
+//! This file contains all of the unit tests for the name mangling.
+
+use fluid_parser::Type;
+
+use crate::{demangle, demangle_text, mangle, Mangling, Symbol};
+
+fn symbol(path: &[&str], name: &str, params: &[Type]) -> Symbol {
+    Symbol::new(path.iter().map(|segment| segment.to_string()).collect(), name, params.to_vec())
+}
+
+fn symbols() -> Vec<Symbol> {
+    vec![
+        symbol(&[], "f", &[]),
+        symbol(&[], "add", &[Type::Number, Type::Number]),
+        symbol(&["math"], "sqrt2", &[Type::Float]),
+        symbol(&["a", "very_long_module_name"], "_private", &[Type::String, Type::Bool]),
+    ]
+}
+
+#[test]
+fn test_mangle() {
+    assert_eq!(mangle(&symbol(&[], "main", &[]), Mangling::Fluid), "_F4mainEv");
+    assert_eq!(mangle(&symbol(&["math"], "add", &[Type::Number, Type::Number]), Mangling::Fluid), "_F4math3addEnn");
+    assert_eq!(
+        mangle(&symbol(&["std", "io"], "write", &[Type::String, Type::Bool, Type::Float]), Mangling::Fluid),
+        "_F3std2io5writeEsbf"
+    );
+}
+
+#[test]
+fn test_mangle_itanium() {
+    assert_eq!(mangle(&symbol(&[], "main", &[]), Mangling::Itanium), "_Z4mainv");
+    assert_eq!(mangle(&symbol(&[], "add", &[Type::Number, Type::Number]), Mangling::Itanium), "_Z3addxx");
+    assert_eq!(
+        mangle(&symbol(&["std", "io"], "write", &[Type::String, Type::Bool, Type::Float]), Mangling::Itanium),
+        "_ZN3std2io5writeEPcbf"
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    for symbol in symbols() {
+        assert_eq!(demangle(&mangle(&symbol, Mangling::Fluid)), Some(symbol.clone()));
+        assert_eq!(demangle(&mangle(&symbol, Mangling::Itanium)), Some(symbol));
+    }
+}
+
+#[test]
+fn test_demangle_invalid() {
+    let invalid = [
+        "main",
+        "_F",
+        "_F3add",
+        "_F3addE",
+        "_F4addEn",
+        "_F3addEx",
+        "_F3addEnv",
+        "_F0Ev",
+        "_FE",
+        "_Z",
+        "_Z3add",
+        "_Z3addi",
+        "_ZN3addEv",
+        "_Z4math3addv",
+        "_ZN4math3addv",
+    ];
+
+    for mangled in invalid.iter() {
+        assert_eq!(demangle(mangled), None, "{}", mangled);
+    }
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(symbol(&["math"], "add", &[Type::Number, Type::Float]).to_string(), "math.add(number, float)");
+    assert_eq!(symbol(&[], "main", &[]).to_string(), "main()");
+}
+
+#[test]
+fn test_demangle_text() {
+    let text = "undefined reference to `_F4math3addEnn'\n0000000000001130 <_ZN4math3subExx>:\n__F4mainEv _Z3fooi é";
+
+    assert_eq!(
+        demangle_text(text),
+        "undefined reference to `math.add(number, number)'\n0000000000001130 <math.sub(number, number)>:\nmain() _Z3fooi é"
+    );
+}
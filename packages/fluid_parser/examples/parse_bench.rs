@@ -0,0 +1,103 @@
+//! Measure the time it takes to parse a large generated file and to walk its AST.
+//!
+//! Run it with `cargo run --release --example parse_bench`, optionally followed by the number of functions
+//! of the generated file.
+
+use std::{env, time::Instant};
+
+use fluid_lexer::Lexer;
+use fluid_parser::{Ast, Declaration, ExprArena, ExprId, ExpressionKind, Parser, Statement, StatementKind};
+
+/// The number of times every measure is repeated, the fastest run is reported.
+const RUNS: usize = 5;
+
+/// Generate a file of functions made of deeply nested expressions.
+fn generate(functions: usize) -> String {
+    let mut source = String::new();
+
+    for i in 0..functions {
+        source.push_str(&format!("function f{}(a: number, b: number) -> number {{\n", i));
+
+        for j in 0..20 {
+            source.push_str(&format!("    var x{}: number = (a + b * {}) - -(a / (b + 1)) * f{}(a, b + {}, (a - b) * 2);\n", j, j, i, j));
+        }
+
+        source.push_str("    if (a == b) {\n        return a + 1;\n    } else {\n        return b * 2;\n    }\n}\n");
+    }
+
+    source
+}
+
+/// Count the nodes of a statement, walking the tree like the passes of the compiler do.
+fn count_statement(exprs: &ExprArena, statement: &Statement) -> usize {
+    1 + match statement.kind {
+        StatementKind::Expression(expression) => count_expression(exprs, expression),
+        StatementKind::Return(value) => value.map_or(0, |value| count_expression(exprs, value)),
+        StatementKind::If(condition, ref body, ref otherwise) => {
+            count_expression(exprs, condition) + count_statement(exprs, body) + otherwise.as_ref().map_or(0, |otherwise| count_statement(exprs, otherwise))
+        }
+        StatementKind::For(ref for_loop) => count_expression(exprs, for_loop.iterable) + count_statement(exprs, &for_loop.body),
+        StatementKind::Block(ref statements, value) => count_block(exprs, statements, value),
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::Function(ref function) => count_statement(exprs, &function.body),
+            Declaration::VarDef(ref var_def) => count_expression(exprs, var_def.value),
+            Declaration::Extern(_) => 0,
+        },
+    }
+}
+
+/// Count the nodes of the statements and the value of a block.
+fn count_block(exprs: &ExprArena, statements: &[Statement], value: Option<ExprId>) -> usize {
+    statements.iter().map(|statement| count_statement(exprs, statement)).sum::<usize>() + value.map_or(0, |value| count_expression(exprs, value))
+}
+
+/// Count the nodes of an expression.
+fn count_expression(exprs: &ExprArena, id: ExprId) -> usize {
+    1 + match exprs[id].kind {
+        ExpressionKind::VarAssign(_, operand) | ExpressionKind::Unary(_, operand) | ExpressionKind::Paren(operand) => count_expression(exprs, operand),
+        ExpressionKind::FunctionCall(_, ref args) => args.iter().map(|&arg| count_expression(exprs, arg)).sum(),
+        ExpressionKind::BinaryOp(lhs, _, rhs) | ExpressionKind::Index(lhs, rhs) => count_expression(exprs, lhs) + count_expression(exprs, rhs),
+        ExpressionKind::If(condition, then, otherwise) => count_expression(exprs, condition) + count_expression(exprs, then) + count_expression(exprs, otherwise),
+        ExpressionKind::Block(ref statements, value) => count_block(exprs, statements, Some(value)),
+        ExpressionKind::VarRef(_) | ExpressionKind::Literal(_) => 0,
+    }
+}
+
+/// Count the nodes of the AST.
+fn count(ast: &Ast) -> usize {
+    ast.statements.iter().map(|statement| count_statement(&ast.exprs, statement)).sum()
+}
+
+/// Run the closure `RUNS` times, and return the duration of the fastest run in milliseconds with its last result.
+fn measure<T>(mut run: impl FnMut() -> T) -> (f64, T) {
+    let mut fastest = f64::INFINITY;
+    let mut result = None;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = run();
+
+        fastest = fastest.min(start.elapsed().as_secs_f64() * 1000.0);
+        result = Some(value);
+    }
+
+    (fastest, result.unwrap())
+}
+
+fn main() {
+    let functions = env::args().nth(1).and_then(|functions| functions.parse().ok()).unwrap_or(5000);
+    let source = generate(functions);
+
+    let mut lexer = Lexer::new(&source, "<bench>");
+    let tokens = lexer.run().unwrap_or_else(|_| panic!("the generated source is invalid"));
+    let file = lexer.file();
+
+    let (parse, ast) = measure(|| Parser::new(&tokens, file.clone()).run().unwrap_or_else(|_| panic!("the generated source is invalid")));
+    let (walk, nodes) = measure(|| count(&ast));
+    let (drop, _) = measure(|| drop(Parser::new(&tokens, file.clone()).run()));
+
+    println!("{} bytes, {} tokens, {} nodes, {} expressions", source.len(), tokens.len(), nodes, ast.exprs.len());
+    println!("parse: {:.2} ms", parse);
+    println!("walk:  {:.2} ms", walk);
+    println!("parse and drop: {:.2} ms", drop);
+}
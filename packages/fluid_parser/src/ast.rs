@@ -1,35 +1,318 @@
 //! This file contains all of the AST interfaces.
 
+use std::{
+    fmt::Display,
+    ops::{Index, IndexMut, Range},
+};
+
+/// The AST of a file: its statements, and the arena of all of the expressions in them.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Ast {
+    /// The top-level statements.
+    pub statements: Vec<Statement>,
+    /// The expressions of the statements, which refer to them by their `ExprId`.
+    pub exprs: ExprArena,
+}
+
+/// The index of an expression in the arena of its AST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExprId(u32);
+
+/// The expressions of an AST, allocated next to each other instead of being boxed one by one.
+/// A parent refers to its children by their `ExprId`, and the children are always allocated before their parent.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ExprArena {
+    exprs: Vec<Expression>,
+}
+
+impl ExprArena {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an expression to the arena, and return its id.
+    pub fn alloc(&mut self, expression: Expression) -> ExprId {
+        let id = ExprId(self.exprs.len() as u32);
+
+        self.exprs.push(expression);
+
+        id
+    }
+
+    /// The number of expressions in the arena.
+    pub fn len(&self) -> usize {
+        self.exprs.len()
+    }
+
+    /// Returns true if there are no expressions in the arena.
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    /// Iterate over the expressions and their ids, in the order they were allocated.
+    pub fn iter(&self) -> impl Iterator<Item = (ExprId, &Expression)> {
+        self.exprs.iter().enumerate().map(|(index, expression)| (ExprId(index as u32), expression))
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = Expression;
+
+    fn index(&self, id: ExprId) -> &Expression {
+        &self.exprs[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for ExprArena {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expression {
+        &mut self.exprs[id.0 as usize]
+    }
+}
+
 /// An expression.
 #[derive(Debug)]
-pub enum Expression {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Expression {
+    /// The kind of the expression.
+    pub kind: ExpressionKind,
+    /// The byte range of the expression in the source.
+    pub span: Range<usize>,
+    /// The declaration of the variable or function that the expression refers to.
+    /// Filled in by the name resolution, for variable references, assignments and function calls.
+    pub def_site: Option<DefSite>,
+}
+
+impl Expression {
+    /// Create a new expression.
+    pub fn new(kind: ExpressionKind, span: Range<usize>) -> Self {
+        Self { kind, span, def_site: None }
+    }
+}
+
+/// The declaration of a name.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DefSite {
+    /// What the name is declared as.
+    pub kind: DefKind,
+    /// The byte range of the name in the declaration.
+    /// This is in the file that declares the name, which is only a different file than the reference in the REPL.
+    pub span: Range<usize>,
+}
+
+/// What a name is declared as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DefKind {
+    /// A variable defined with `var`.
+    Variable,
+    /// A function argument.
+    Parameter,
+    /// A function, either defined or declared in an `extern` block.
+    Function,
+}
+
+impl Display for DefKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefKind::Variable => write!(f, "variable"),
+            DefKind::Parameter => write!(f, "parameter"),
+            DefKind::Function => write!(f, "function"),
+        }
+    }
+}
+
+/// A function that is built into the language.
+/// The builtins are in scope everywhere, unless a function with the same name is declared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    /// `print(value)`, print a number, float, string or bool.
+    Print,
+    /// `println(value)`, print a number, float, string or bool followed by a new line.
+    Println,
+    /// `input() -> string`, read a line from the standard input, without the new line.
+    Input,
+    /// `assert(condition)`, exit the program with a message if the condition is false.
+    Assert,
+    /// `read_file(path) -> string`, read the contents of a file, or `null` if it can not be read.
+    ReadFile,
+    /// `write_file(path, contents) -> bool`, replace the contents of a file, returns true if they were written.
+    WriteFile,
+    /// `append_file(path, contents) -> bool`, add to the end of a file, returns true if the contents were written.
+    AppendFile,
+    /// `sqrt(x) -> float`, the square root of a float.
+    Sqrt,
+    /// `pow(base, exponent) -> float`, a float raised to the power of another.
+    Pow,
+    /// `abs(x)`, the absolute value of a number or a float.
+    Abs,
+    /// `floor(x) -> float`, the largest integral float less than or equal to a float.
+    Floor,
+    /// `ceil(x) -> float`, the smallest integral float greater than or equal to a float.
+    Ceil,
+    /// `sin(x) -> float`, the sine of an angle in radians.
+    Sin,
+    /// `cos(x) -> float`, the cosine of an angle in radians.
+    Cos,
+    /// `min(a, b)`, the smallest of two numbers or two floats.
+    Min,
+    /// `max(a, b)`, the largest of two numbers or two floats.
+    Max,
+    /// `format(format, values...) -> string`, replace the `{}` placeholders of a string literal by the values.
+    Format,
+    /// `random() -> float`, a random float from 0 included to 1 excluded.
+    Random,
+    /// `random_range(lo, hi) -> number`, a random number from `lo` included to `hi` excluded.
+    RandomRange,
+    /// `seed(n)`, seed the random number generator, so that the same seed always gives the same numbers.
+    Seed,
+    /// `now_millis() -> number`, the number of milliseconds since the Unix epoch.
+    NowMillis,
+    /// `sleep(ms)`, pause the program for a number of milliseconds.
+    Sleep,
+    /// `env(name) -> string`, the value of an environment variable, or `null` if it is not set.
+    Env,
+    /// `set_env(name, value)`, set an environment variable, or remove it if the value is `null`.
+    SetEnv,
+}
+
+impl Builtin {
+    /// All of the builtins.
+    pub const ALL: &'static [Builtin] = &[
+        Builtin::Print,
+        Builtin::Println,
+        Builtin::Input,
+        Builtin::Assert,
+        Builtin::ReadFile,
+        Builtin::WriteFile,
+        Builtin::AppendFile,
+        Builtin::Sqrt,
+        Builtin::Pow,
+        Builtin::Abs,
+        Builtin::Floor,
+        Builtin::Ceil,
+        Builtin::Sin,
+        Builtin::Cos,
+        Builtin::Min,
+        Builtin::Max,
+        Builtin::Format,
+        Builtin::Random,
+        Builtin::RandomRange,
+        Builtin::Seed,
+        Builtin::NowMillis,
+        Builtin::Sleep,
+        Builtin::Env,
+        Builtin::SetEnv,
+    ];
+
+    /// Get the builtin with the name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "println" => Some(Builtin::Println),
+            "input" => Some(Builtin::Input),
+            "assert" => Some(Builtin::Assert),
+            "read_file" => Some(Builtin::ReadFile),
+            "write_file" => Some(Builtin::WriteFile),
+            "append_file" => Some(Builtin::AppendFile),
+            "sqrt" => Some(Builtin::Sqrt),
+            "pow" => Some(Builtin::Pow),
+            "abs" => Some(Builtin::Abs),
+            "floor" => Some(Builtin::Floor),
+            "ceil" => Some(Builtin::Ceil),
+            "sin" => Some(Builtin::Sin),
+            "cos" => Some(Builtin::Cos),
+            "min" => Some(Builtin::Min),
+            "max" => Some(Builtin::Max),
+            "format" => Some(Builtin::Format),
+            "random" => Some(Builtin::Random),
+            "random_range" => Some(Builtin::RandomRange),
+            "seed" => Some(Builtin::Seed),
+            "now_millis" => Some(Builtin::NowMillis),
+            "sleep" => Some(Builtin::Sleep),
+            "env" => Some(Builtin::Env),
+            "set_env" => Some(Builtin::SetEnv),
+            _ => None,
+        }
+    }
+
+    /// The name of the builtin.
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Println => "println",
+            Builtin::Input => "input",
+            Builtin::Assert => "assert",
+            Builtin::ReadFile => "read_file",
+            Builtin::WriteFile => "write_file",
+            Builtin::AppendFile => "append_file",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Pow => "pow",
+            Builtin::Abs => "abs",
+            Builtin::Floor => "floor",
+            Builtin::Ceil => "ceil",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Format => "format",
+            Builtin::Random => "random",
+            Builtin::RandomRange => "random_range",
+            Builtin::Seed => "seed",
+            Builtin::NowMillis => "now_millis",
+            Builtin::Sleep => "sleep",
+            Builtin::Env => "env",
+            Builtin::SetEnv => "set_env",
+        }
+    }
+}
+
+/// The kind of an expression.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExpressionKind {
     /// A variable reference.
     VarRef(String),
     /// A variable assign.
-    VarAssign(String, Box<Expression>),
+    VarAssign(String, ExprId),
     /// A function call.
-    FunctionCall(String, Vec<Expression>),
+    FunctionCall(String, Vec<ExprId>),
     /// A binary operator.
-    BinaryOp(Box<Expression>, BinaryOp, Box<Expression>),
+    BinaryOp(ExprId, BinaryOp, ExprId),
     /// A literal expression.
     Literal(Literal),
     /// An unary expression.
-    Unary(UnaryOp, Box<Expression>),
+    Unary(UnaryOp, ExprId),
     /// A paren expression.
-    Paren(Box<Expression>),
+    Paren(ExprId),
+    /// An if expression, with the condition and the values of the branches.
+    If(ExprId, ExprId, ExprId),
+    /// A block expression, with its statements and the expression at its end that is its value.
+    Block(Vec<Statement>, ExprId),
+    /// An index expression, with the indexed value and the index.
+    Index(ExprId, ExprId),
 }
 
 /// An unary operator.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryOp {
     /// `-`
     Neg,
+    /// `+`, which does not change the value.
+    Plus,
     /// `!`
     Not,
 }
 
 /// A binary operator.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryOp {
     /// `+`
     Add,
@@ -49,10 +332,13 @@ pub enum BinaryOp {
     And,
     /// `||`
     Or,
+    /// `??`, the left operand if it is not `null` and the right operand otherwise.
+    Coalesce,
 }
 
 /// A literal.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Literal {
     /// A boolean literal.
     Bool(bool),
@@ -69,35 +355,86 @@ pub enum Literal {
 }
 
 /// A statement.
-#[derive(Debug)]
-pub enum Statement {
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Statement {
+    /// The kind of the statement.
+    pub kind: StatementKind,
+    /// The byte range of the statement in the source.
+    pub span: Range<usize>,
+}
+
+impl Statement {
+    /// Create a new statement.
+    pub fn new(kind: StatementKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+/// The kind of a statement.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StatementKind {
     /// An expression statement.
-    Expression(Box<Expression>),
-    /// Return statement.
-    Return(Box<Expression>),
+    Expression(ExprId),
+    /// Return statement, with an optional value.
+    Return(Option<ExprId>),
     /// If statement.
-    If(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
+    If(ExprId, Box<Statement>, Option<Box<Statement>>),
     /// For statement.
-    For(),
-    /// A block statement.
-    Block(Vec<Statement>),
+    For(Box<ForLoop>),
+    /// A block statement, with the expression at its end without a `;`, if there is one.
+    /// The value of the body of a function is returned.
+    Block(Vec<Statement>, Option<ExprId>),
     /// A declaration statement.
     Declaration(Box<Declaration>),
 }
 
+/// A `for` loop over the characters of a string.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ForLoop {
+    /// The name of the variable of the character.
+    pub name: String,
+    /// The byte range of the name.
+    pub name_span: Range<usize>,
+    /// The string that is iterated over.
+    pub iterable: ExprId,
+    /// The body of the loop, a block.
+    pub body: Statement,
+}
+
 /// A declaration.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Declaration {
     /// A function declaration.
     Function(Function),
     /// An external declaration.
     Extern(Vec<Prototype>),
     /// A variable declaration.
-    VarDef(String, Type, Box<Expression>),
+    VarDef(VarDef),
+}
+
+/// A variable definition.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VarDef {
+    /// The variable name.
+    pub name: String,
+    /// The byte range of the name.
+    pub name_span: Range<usize>,
+    /// The type of the variable.
+    pub typee: Type,
+    /// The byte range of the type annotation.
+    pub type_span: Range<usize>,
+    /// The initial value.
+    pub value: ExprId,
 }
 
 /// A function
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Function {
     /// The function prototype.
     pub prototype: Prototype,
@@ -106,27 +443,43 @@ pub struct Function {
 }
 
 /// Function's prototype.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Prototype {
     /// The function name.
     pub name: String,
+    /// The byte range of the name.
+    pub name_span: Range<usize>,
     /// The function args.
     pub args: Vec<Arg>,
     /// The function return type.
     pub return_type: Type,
+    /// The byte range of the return type annotation, if the return type is not the default.
+    pub return_type_span: Option<Range<usize>>,
+    /// Whether the symbol of the function is its name, so that it can be called from C or call C.
+    /// It is true for the `extern` functions and the `export` functions.
+    pub no_mangle: bool,
+    /// Whether the function is a test, run by `fluid test`. A test takes no arguments and returns nothing.
+    pub test: bool,
 }
 
 /// A function argument
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Arg {
     /// Name of the argument.
     pub name: String,
+    /// The byte range of the name.
+    pub name_span: Range<usize>,
     /// Type of the argument.
     pub typee: Type,
+    /// The byte range of the type annotation.
+    pub type_span: Range<usize>,
 }
 
 /// A type.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Type {
     /// void
     Void,
@@ -138,6 +491,8 @@ pub enum Type {
     String,
     /// bool
     Bool,
+    /// char, a byte of a string
+    Char,
 }
 
 impl Default for Type {
@@ -146,3 +501,50 @@ impl Default for Type {
         Self::Void
     }
 }
+
+impl Type {
+    /// Returns true if the values of the type are pointers, so `null` can be used as a value of the type.
+    pub fn is_nullable(&self) -> bool {
+        matches!(self, Type::String)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Number => write!(f, "number"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+        }
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Plus => write!(f, "+"),
+            UnaryOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Subtract => write!(f, "-"),
+            BinaryOp::Mul => write!(f, "*"),
+            BinaryOp::Div => write!(f, "/"),
+            BinaryOp::Lesser => write!(f, "<"),
+            BinaryOp::Greater => write!(f, ">"),
+            BinaryOp::EqEq => write!(f, "=="),
+            BinaryOp::And => write!(f, "&&"),
+            BinaryOp::Or => write!(f, "||"),
+            BinaryOp::Coalesce => write!(f, "??"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! This file contains the printer of the AST as a readable tree, the `dump_ast` interface.
+
+use std::ops::Range;
+
+use crate::{Ast, Declaration, DefSite, ExprArena, ExprId, ExpressionKind, Prototype, Statement, StatementKind};
+
+/// Print the AST as a tree, one node per line, with the children of a node indented under it.
+/// Every node has the byte range of its source, and the names that were resolved have the declaration they refer to.
+///
+/// ```text
+/// Function main() -> number 0..38
+///   Block 25..38
+///     Return 27..36
+///       Literal Number(1) 34..35
+/// ```
+pub fn dump_ast(ast: &Ast) -> String {
+    let mut output = String::new();
+
+    for statement in &ast.statements {
+        dump_statement(&ast.exprs, statement, 0, &mut output);
+    }
+
+    output
+}
+
+/// Print a node of the tree at the depth, followed by the declaration that it refers to.
+fn push_node(output: &mut String, depth: usize, node: String, span: &Range<usize>, def_site: Option<&DefSite>) {
+    for _ in 0..depth {
+        output.push_str("  ");
+    }
+
+    output.push_str(&format!("{} {}..{}", node, span.start, span.end));
+
+    if let Some(def_site) = def_site {
+        output.push_str(&format!(" -> {} {}..{}", def_site.kind, def_site.span.start, def_site.span.end));
+    }
+
+    output.push('\n');
+}
+
+fn dump_statement(exprs: &ExprArena, statement: &Statement, depth: usize, output: &mut String) {
+    match &statement.kind {
+        StatementKind::Expression(expression) => {
+            push_node(output, depth, "Expression".into(), &statement.span, None);
+            dump_expression(exprs, *expression, depth + 1, output);
+        }
+        StatementKind::Return(value) => {
+            push_node(output, depth, "Return".into(), &statement.span, None);
+
+            if let Some(value) = value {
+                dump_expression(exprs, *value, depth + 1, output);
+            }
+        }
+        StatementKind::If(condition, body, otherwise) => {
+            push_node(output, depth, "If".into(), &statement.span, None);
+            dump_expression(exprs, *condition, depth + 1, output);
+            dump_statement(exprs, body, depth + 1, output);
+
+            if let Some(otherwise) = otherwise {
+                dump_statement(exprs, otherwise, depth + 1, output);
+            }
+        }
+        StatementKind::For(for_loop) => {
+            push_node(output, depth, format!("For {}", for_loop.name), &statement.span, None);
+            dump_expression(exprs, for_loop.iterable, depth + 1, output);
+            dump_statement(exprs, &for_loop.body, depth + 1, output);
+        }
+        StatementKind::Block(statements, value) => {
+            push_node(output, depth, "Block".into(), &statement.span, None);
+
+            for statement in statements {
+                dump_statement(exprs, statement, depth + 1, output);
+            }
+
+            if let Some(value) = value {
+                dump_expression(exprs, *value, depth + 1, output);
+            }
+        }
+        StatementKind::Declaration(declaration) => match &**declaration {
+            Declaration::Function(function) => {
+                let modifier = if function.prototype.no_mangle {
+                    "export "
+                } else if function.prototype.test {
+                    "test "
+                } else {
+                    ""
+                };
+
+                push_node(output, depth, format!("Function {}{}", modifier, signature(&function.prototype)), &statement.span, None);
+                dump_statement(exprs, &function.body, depth + 1, output);
+            }
+            Declaration::Extern(prototypes) => {
+                push_node(output, depth, "Extern".into(), &statement.span, None);
+
+                for prototype in prototypes {
+                    push_node(output, depth + 1, format!("Prototype {}", signature(prototype)), &prototype.name_span, None);
+                }
+            }
+            Declaration::VarDef(var_def) => {
+                push_node(output, depth, format!("VarDef {}: {}", var_def.name, var_def.typee), &statement.span, None);
+                dump_expression(exprs, var_def.value, depth + 1, output);
+            }
+        },
+    }
+}
+
+fn dump_expression(exprs: &ExprArena, id: ExprId, depth: usize, output: &mut String) {
+    let expression = &exprs[id];
+    let node = match &expression.kind {
+        ExpressionKind::VarRef(name) => format!("VarRef {}", name),
+        ExpressionKind::VarAssign(name, _) => format!("VarAssign {}", name),
+        ExpressionKind::FunctionCall(name, _) => format!("Call {}", name),
+        ExpressionKind::BinaryOp(_, op, _) => format!("BinaryOp {}", op),
+        ExpressionKind::Literal(literal) => format!("Literal {:?}", literal),
+        ExpressionKind::Unary(op, _) => format!("Unary {}", op),
+        ExpressionKind::Paren(_) => "Paren".to_owned(),
+        ExpressionKind::If(..) => "If".to_owned(),
+        ExpressionKind::Block(..) => "Block".to_owned(),
+        ExpressionKind::Index(..) => "Index".to_owned(),
+    };
+
+    push_node(output, depth, node, &expression.span, expression.def_site.as_ref());
+
+    match &expression.kind {
+        ExpressionKind::VarAssign(_, value) => dump_expression(exprs, *value, depth + 1, output),
+        ExpressionKind::FunctionCall(_, args) => {
+            for arg in args {
+                dump_expression(exprs, *arg, depth + 1, output);
+            }
+        }
+        ExpressionKind::BinaryOp(lhs, _, rhs) | ExpressionKind::Index(lhs, rhs) => {
+            dump_expression(exprs, *lhs, depth + 1, output);
+            dump_expression(exprs, *rhs, depth + 1, output);
+        }
+        ExpressionKind::Unary(_, operand) | ExpressionKind::Paren(operand) => dump_expression(exprs, *operand, depth + 1, output),
+        ExpressionKind::If(condition, then, otherwise) => {
+            dump_expression(exprs, *condition, depth + 1, output);
+            dump_expression(exprs, *then, depth + 1, output);
+            dump_expression(exprs, *otherwise, depth + 1, output);
+        }
+        ExpressionKind::Block(statements, value) => {
+            for statement in statements {
+                dump_statement(exprs, statement, depth + 1, output);
+            }
+
+            dump_expression(exprs, *value, depth + 1, output);
+        }
+        ExpressionKind::VarRef(_) | ExpressionKind::Literal(_) => (),
+    }
+}
+
+/// The signature of a function, as it would be written in the source without `function`.
+fn signature(prototype: &Prototype) -> String {
+    let args = prototype.args.iter().map(|arg| format!("{}: {}", arg.name, arg.typee)).collect::<Vec<_>>();
+
+    format!("{}({}) -> {}", prototype.name, args.join(", "), prototype.return_type)
+}
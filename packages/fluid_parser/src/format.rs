@@ -0,0 +1,53 @@
+//! The format strings of the `format` builtin.
+
+use std::mem;
+
+/// A piece of a format string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPiece {
+    /// Text that is copied as is, the `{{` and `}}` escapes are already replaced by the braces.
+    Text(String),
+    /// A `{}` placeholder, which is replaced by the next value.
+    Placeholder,
+}
+
+/// Split a format string into its text and its `{}` placeholders. `{{` and `}}` are the escapes of the braces.
+/// Returns the first brace that is neither a part of a placeholder nor of an escape as an error.
+pub fn parse_format_string(format: &str) -> Result<Vec<FormatPiece>, char> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(character) = chars.next() {
+        let next = chars.peek().copied();
+
+        match character {
+            '{' | '}' if next == Some(character) => {
+                chars.next();
+                text.push(character);
+            }
+            '{' if next == Some('}') => {
+                chars.next();
+
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(mem::take(&mut text)));
+                }
+
+                pieces.push(FormatPiece::Placeholder);
+            }
+            '{' | '}' => return Err(character),
+            _ => text.push(character),
+        }
+    }
+
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+
+    Ok(pieces)
+}
+
+/// The number of placeholders of the pieces of a format string.
+pub fn placeholder_count(pieces: &[FormatPiece]) -> usize {
+    pieces.iter().filter(|piece| **piece == FormatPiece::Placeholder).count()
+}
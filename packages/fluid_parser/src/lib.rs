@@ -4,7 +4,14 @@
 #![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
 
 mod ast;
+mod dump;
+mod format;
 mod parser;
 
+#[cfg(test)]
+mod tests;
+
 pub use ast::*;
+pub use dump::*;
+pub use format::*;
 pub use parser::*;
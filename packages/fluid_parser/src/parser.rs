@@ -3,54 +3,164 @@
 //! Here is the binary precedence table for reference.
 //!
 //! Assignment = 1                 =          (1 case) \
-//! Or = 2                         ||         (1 case) \
-//! And = 3                        &&         (1 case) \
-//! Equality = 4                   ==         (1 case) \
-//! Comparison = 5                 <, >       (2 cases) \
-//! Term = 6                       +, -       (2 cases) \
-//! Factor = 7                     *, /       (2 cases) \
+//! Coalesce = 2                   ??         (1 case) \
+//! Or = 3                         ||         (1 case) \
+//! And = 4                        &&         (1 case) \
+//! Equality = 5                   ==         (1 case) \
+//! Comparison = 6                 <, >       (2 cases) \
+//! Term = 7                       +, -       (2 cases) \
+//! Factor = 8                     *, /       (2 cases) \
 //!
 
+use std::{mem, ops::Range, sync::Arc};
+
+use fluid_error::{codes, AnnotationType, Diagnostic, DiagnosticBuilder, Severity, Slice, SourceAnnotation, SourceFile, Suggestion};
 use fluid_lexer::{Keyword, Token, TokenType};
 
 use crate::ast::*;
 
+/// The result of parsing a node.
+type ParseResult<T> = Result<T, Diagnostic>;
+
+/// The maximum number of statements and expressions that can be nested in each other. The parser, the type checker
+/// and the code generator are recursive, so deeper code is reported instead of overflowing the stack.
+pub const MAX_NESTING: usize = 64;
+
+/// A variable declared in a block, tracked for the unused variable lint.
+#[derive(Debug)]
+struct Local {
+    /// The name of the variable.
+    name: String,
+    /// Index of the token with the variable's name.
+    index: usize,
+    /// Is the variable used after its declaration.
+    used: bool,
+}
+
 /// Contains the internal state while processing the tokens provided by the lexer.
+/// The parser borrows the tokens, so they can be parsed again or kept by the caller without copying them.
 #[derive(Debug)]
-pub struct Parser {
+pub struct Parser<'src> {
     /// The tokens generated by the lexer.
-    pub tokens: Vec<Token>,
+    pub tokens: &'src [Token],
     /// The current index of the parser.
     pub index: usize,
+    /// The file we are parsing.
+    pub file: Arc<SourceFile>,
+    /// The variables declared in each of the enclosing blocks.
+    locals: Vec<Vec<Local>>,
+    /// The warnings reported while parsing.
+    warnings: Vec<Diagnostic>,
+    /// The expressions parsed so far.
+    exprs: ExprArena,
+    /// The number of statements and expressions being parsed in each other.
+    depth: usize,
+    /// Was the code nested more than `MAX_NESTING` times.
+    too_deep: bool,
 }
 
-impl Parser {
-    /// Create a new instance of the parser.
-    pub fn new(tokens: Vec<Token>) -> Self {
+impl<'src> Parser<'src> {
+    /// Create a new instance of the parser. The tokens must end with the `EOF` token, like the ones of the lexer.
+    pub fn new(tokens: &'src [Token], file: Arc<SourceFile>) -> Self {
         let index = 0;
 
-        Self { tokens, index }
+        let locals = vec![];
+        let warnings = vec![];
+        let exprs = ExprArena::new();
+        let depth = 0;
+        let too_deep = false;
+
+        Self {
+            tokens,
+            index,
+            file,
+            locals,
+            warnings,
+            exprs,
+            depth,
+            too_deep,
+        }
+    }
+
+    /// Take the warnings reported while parsing.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.warnings)
     }
 
     /// Run the parser.
-    pub fn run(&mut self) -> Vec<Statement> {
-        let mut ast = vec![];
+    /// After an error the parser skips to the next statement, so that it can report more than one error.
+    ///
+    /// It never panics, whatever the tokens are: the malformed code is reported as diagnostics. The code nested more
+    /// than `MAX_NESTING` times is reported too, and stops the parser.
+    pub fn run(&mut self) -> Result<Ast, Vec<Diagnostic>> {
+        let mut statements = vec![];
+        let mut errors = vec![];
+
+        while !self.is_eof() {
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    errors.push(err);
+
+                    // The rest of the deeply nested code would only report more errors about its closing tokens.
+                    if self.too_deep {
+                        break;
+                    }
+
+                    // The blocks that were being parsed are abandoned.
+                    self.locals.clear();
+                    self.synchronize();
+                }
+            }
+        }
+
+        let exprs = mem::take(&mut self.exprs);
 
-        while self.index < self.tokens.len() && *self.peek() != TokenType::EOF {
-            ast.push(self.parse_statement());
+        if errors.is_empty() {
+            Ok(Ast { statements, exprs })
+        } else {
+            Err(errors)
         }
+    }
+
+    /// Run the parser on a single expression, like the one of the `.type` command of the REPL.
+    /// The expression may be followed by a `;`, but not by anything else.
+    /// It returns the id of the expression in the arena of the expressions that were parsed.
+    pub fn run_expression(&mut self) -> Result<(ExprId, ExprArena), Vec<Diagnostic>> {
+        let expression = self.parse_expression().map_err(|error| vec![error])?;
 
-        ast
+        if *self.peek() == TokenType::Semi {
+            self.advance();
+        }
+
+        if self.is_eof() {
+            Ok((expression, mem::take(&mut self.exprs)))
+        } else {
+            Err(vec![self.unexpected_token("the end of the expression")])
+        }
     }
 
     /// Parse a function definition.
-    fn parse_fn_def(&mut self) -> Statement {
-        let prototype = self.parse_proto();
-        let body = self.parse_block();
+    fn parse_fn_def(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
+
+        // An exported function keeps its name as its symbol, so that it can be called from C.
+        let no_mangle = *self.peek() == TokenType::Keyword(Keyword::Export);
+        let test = *self.peek() == TokenType::Keyword(Keyword::Test);
+
+        if no_mangle || test {
+            self.advance();
+        }
+
+        let mut prototype = self.parse_proto()?;
+        let body = self.parse_block()?;
+
+        prototype.no_mangle = no_mangle;
+        prototype.test = test;
 
         let func = Function { prototype, body };
 
-        Statement::Declaration(Box::new(Declaration::Function(func)))
+        Ok(self.make_statement(StatementKind::Declaration(Box::new(Declaration::Function(func))), start))
     }
 
     /// Parse a type.
@@ -60,458 +170,898 @@ impl Parser {
     ///     => number \
     ///     => float \
     ///     => string \
+    ///     => bool \
+    ///     => char \
     ///     => $tuple($(type),*)
     ///
     /// TODO: `void` should be a type alais for `()` an empty tuple.
-    fn parse_type(&mut self) -> Type {
+    fn parse_type(&mut self) -> ParseResult<Type> {
         let kind = match self.peek() {
             TokenType::Identifier(kind) => match kind.as_str() {
                 "void" => Type::Void,
                 "number" => Type::Number,
                 "float" => Type::Float,
                 "string" => Type::String,
-                _ => unimplemented!(),
+                "bool" => Type::Bool,
+                "char" => Type::Char,
+                _ => {
+                    return Err(self
+                        .make_error(format!("cannot find type `{}` in this scope", kind), codes::E0021, self.index, "unknown type")
+                        .set_help("the types are `void`, `number`, `float`, `string`, `bool` and `char`")
+                        .build())
+                }
             },
-            TokenType::OpenParen => self.parse_tuple_type(),
+            TokenType::OpenParen => self.parse_tuple_type()?,
 
-            _ => panic!("Expected a type."),
+            _ => return Err(self.unexpected_token("a type")),
         };
 
         self.advance();
 
-        kind
+        Ok(kind)
     }
 
-    /// Parse a tuple type.
+    /// Parse a tuple type. There is no type for the tuples yet, so it is an error once the types in it are parsed.
     ///
     /// $tuple($(type),*)
-    fn parse_tuple_type(&mut self) -> Type {
+    fn parse_tuple_type(&mut self) -> ParseResult<Type> {
+        let start = self.index;
         let mut tuple_kind_inner = vec![];
 
-        self.expect(TokenType::OpenParen);
+        self.expect(TokenType::OpenParen)?;
 
         while *self.peek() != TokenType::CloseParen {
-            let kind = self.parse_type();
+            let kind = self.parse_type()?;
 
             if *self.peek() != TokenType::CloseParen {
-                self.expect(TokenType::Comma);
+                self.expect(TokenType::Comma)?;
             }
 
             tuple_kind_inner.push(kind);
         }
 
-        self.expect(TokenType::CloseParen);
+        self.expect(TokenType::CloseParen)?;
 
-        todo!()
+        Err(self.make_error("tuple types are not supported yet", codes::E0016, start, "not supported yet").build())
     }
 
     /// Parse function prototype.
-    fn parse_proto(&mut self) -> Prototype {
-        self.expect(TokenType::Keyword(Keyword::Fn));
+    fn parse_proto(&mut self) -> ParseResult<Prototype> {
+        self.expect(TokenType::Keyword(Keyword::Fn))?;
 
-        let name = self.expect_identifier();
+        let name_start = self.index;
+        let name = self.expect_identifier()?;
+        let name_span = self.span_from(name_start);
         let mut args = vec![];
 
-        self.expect(TokenType::OpenParen);
+        self.expect(TokenType::OpenParen)?;
 
         while *self.peek() != TokenType::CloseParen {
-            let arg_name = self.expect_identifier();
+            let name_start = self.index;
+            let arg_name = self.expect_identifier()?;
+            let name_span = self.span_from(name_start);
 
-            self.expect(TokenType::Colon);
+            self.expect(TokenType::Colon)?;
 
-            let arg_type = self.parse_type();
+            let type_start = self.index;
+            let arg_type = self.parse_type()?;
+            let type_span = self.span_from(type_start);
 
             if *self.peek() != TokenType::CloseParen {
-                self.expect(TokenType::Comma);
+                self.expect(TokenType::Comma)?;
             }
 
-            args.push(Arg { name: arg_name, typee: arg_type });
+            args.push(Arg {
+                name: arg_name,
+                name_span,
+                typee: arg_type,
+                type_span,
+            });
         }
 
-        self.expect(TokenType::CloseParen);
+        self.expect(TokenType::CloseParen)?;
 
         let return_type;
+        let return_type_span;
 
         if *self.peek() == TokenType::TArrow {
-            self.expect(TokenType::TArrow);
+            self.expect(TokenType::TArrow)?;
+
+            let type_start = self.index;
 
-            return_type = self.parse_type();
+            return_type = self.parse_type()?;
+            return_type_span = Some(self.span_from(type_start));
         } else {
             return_type = Type::default();
+            return_type_span = None;
         }
 
-        Prototype { name, args, return_type }
+        Ok(Prototype {
+            name,
+            name_span,
+            args,
+            return_type,
+            return_type_span,
+            no_mangle: false,
+            test: false,
+        })
     }
 
     /// Parse a extern definition
-    fn parse_extern(&mut self) -> Statement {
+    fn parse_extern(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
         let mut externs = vec![];
 
-        self.expect(TokenType::Keyword(Keyword::Extern));
-        self.expect(TokenType::OpenBrace);
+        self.expect(TokenType::Keyword(Keyword::Extern))?;
+        self.expect(TokenType::OpenBrace)?;
 
         while *self.peek() != TokenType::CloseBrace {
-            externs.push(self.parse_proto());
-            self.expect(TokenType::Semi);
+            let mut prototype = self.parse_proto()?;
+
+            prototype.no_mangle = true;
+
+            externs.push(prototype);
+            self.expect(TokenType::Semi)?;
         }
 
-        self.expect(TokenType::CloseBrace);
+        self.expect(TokenType::CloseBrace)?;
 
-        Statement::Declaration(Box::new(Declaration::Extern(externs)))
+        Ok(self.make_statement(StatementKind::Declaration(Box::new(Declaration::Extern(externs))), start))
     }
 
     /// Parse a block.
-    fn parse_block(&mut self) -> Statement {
-        self.expect(TokenType::OpenBrace);
+    fn parse_block(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
+        let (body, value) = self.parse_block_contents()?;
+
+        Ok(self.make_statement(StatementKind::Block(body, value), start))
+    }
+
+    /// Parse a block expression, which must end with its value.
+    ///
+    /// { $(statement)* $value }
+    fn parse_block_expression(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+        let (body, value) = self.parse_block_contents()?;
+
+        let value = match value {
+            Some(value) => value,
+            None => {
+                return Err(self
+                    .make_error("expected a value at the end of the block", codes::E0006, self.index - 1, "expected a value before this")
+                    .set_help("the last expression of a block, without a `;`, is the value of the block")
+                    .build())
+            }
+        };
+
+        Ok(self.make_expression(ExpressionKind::Block(body, value), start))
+    }
+
+    /// Parse the statements of a block in braces, and the expression without a `;` at its end.
+    fn parse_block_contents(&mut self) -> ParseResult<(Vec<Statement>, Option<ExprId>)> {
+        self.expect(TokenType::OpenBrace)?;
+        self.locals.push(vec![]);
 
         let mut body = vec![];
+        let mut value = None;
 
         while *self.peek() != TokenType::CloseBrace {
-            body.push(self.parse_statement());
+            if self.at_statement_keyword() {
+                let statement = self.parse_statement()?;
+
+                // A block or an if statement at the end of the block that ends with a value is the value of the block,
+                // like a block or an if expression.
+                if *self.peek() == TokenType::CloseBrace && Self::has_value(&statement) {
+                    value = Some(self.value_expression(statement));
+
+                    break;
+                }
+
+                body.push(statement);
+
+                continue;
+            }
+
+            let start = self.index;
+            let expression = self.parse_expression()?;
+
+            // The last expression of the block is its value if it is not followed by a `;`.
+            if *self.peek() == TokenType::CloseBrace {
+                value = Some(expression);
+
+                break;
+            }
+
+            self.expect(TokenType::Semi)?;
+
+            body.push(self.make_statement(StatementKind::Expression(expression), start));
         }
 
-        self.expect(TokenType::CloseBrace);
+        self.expect(TokenType::CloseBrace)?;
+
+        let locals = self.locals.pop().unwrap_or_default();
+        self.report_unused(locals);
 
-        Statement::Block(body)
+        Ok((body, value))
+    }
+
+    /// Report the variables that are never used after their declaration, unless their name starts with `_`.
+    fn report_unused(&mut self, locals: Vec<Local>) {
+        for local in locals.into_iter().filter(|local| !local.used && !local.name.starts_with('_')) {
+            let position = &self.tokens[local.index].position;
+            let span = position.position_start..position.position_start;
+
+            let warning = self
+                .make_diagnostic(
+                    Severity::Warning,
+                    format!("unused variable: `{}`", local.name),
+                    codes::W0001,
+                    local.index,
+                    "this variable is never used",
+                )
+                .push_suggestion(Suggestion::new(span, "_", "if this is intentional, prefix it with an underscore"))
+                .build();
+
+            self.warnings.push(warning);
+        }
     }
 
     /// Parse a statement.
-    pub fn parse_statement(&mut self) -> Statement {
-        let stat = match *self.peek() {
+    pub fn parse_statement(&mut self) -> ParseResult<Statement> {
+        self.nested(Self::parse_statement_kind)
+    }
+
+    /// Parse a statement, according to its first token.
+    fn parse_statement_kind(&mut self) -> ParseResult<Statement> {
+        match *self.peek() {
             TokenType::Keyword(Keyword::Return) => self.parse_return(),
             TokenType::Keyword(Keyword::If) => self.parse_if(),
             TokenType::Keyword(Keyword::Var) => self.parse_var_def(),
             TokenType::Keyword(Keyword::For) => self.parse_for(),
-            TokenType::Keyword(Keyword::Fn) => self.parse_fn_def(),
+            TokenType::Keyword(Keyword::Fn) | TokenType::Keyword(Keyword::Export) | TokenType::Keyword(Keyword::Test) => self.parse_fn_def(),
             TokenType::Keyword(Keyword::Extern) => self.parse_extern(),
             TokenType::OpenBrace => self.parse_block(),
-            _ => Statement::Expression(Box::new(self.parse_expression_statement())),
+            _ => {
+                let start = self.index;
+                let expression = self.parse_expression_statement()?;
+
+                Ok(self.make_statement(StatementKind::Expression(expression), start))
+            }
+        }
+    }
+
+    /// Returns true if the statement is a block that ends with a value, or an if statement with an `else` branch whose
+    /// branches all end with a value.
+    fn has_value(statement: &Statement) -> bool {
+        match statement.kind {
+            StatementKind::If(_, ref body, Some(ref otherwise)) => Self::has_value(body) && Self::has_value(otherwise),
+            StatementKind::Block(_, value) => value.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Turn a statement for which `has_value` is true into the equivalent block or if expression.
+    fn value_expression(&mut self, statement: Statement) -> ExprId {
+        let kind = match statement.kind {
+            StatementKind::If(condition, body, Some(otherwise)) => ExpressionKind::If(condition, self.value_expression(*body), self.value_expression(*otherwise)),
+            StatementKind::Block(statements, Some(value)) => ExpressionKind::Block(statements, value),
+            _ => unreachable!(),
         };
 
-        stat
+        self.exprs.alloc(Expression::new(kind, statement.span))
+    }
+
+    /// Returns true if the next token starts a statement that is not an expression statement.
+    fn at_statement_keyword(&self) -> bool {
+        matches!(
+            self.peek(),
+            TokenType::Keyword(Keyword::Return)
+                | TokenType::Keyword(Keyword::If)
+                | TokenType::Keyword(Keyword::Var)
+                | TokenType::Keyword(Keyword::For)
+                | TokenType::Keyword(Keyword::Fn)
+                | TokenType::Keyword(Keyword::Export)
+                | TokenType::Keyword(Keyword::Test)
+                | TokenType::Keyword(Keyword::Extern)
+                | TokenType::OpenBrace
+        )
     }
 
-    fn parse_for(&mut self) -> Statement {
-        self.expect(TokenType::Keyword(Keyword::For));
+    /// Parse a for statement, which iterates over the characters of a string.
+    /// The variable of the character is only in scope in the body.
+    ///
+    /// for ($name in $iterable) { $(statement)* }
+    fn parse_for(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
+
+        self.expect(TokenType::Keyword(Keyword::For))?;
+        self.expect(TokenType::OpenParen)?;
+
+        let name_index = self.index;
+        let name = self.expect_identifier()?;
+        let name_span = self.span_from(name_index);
+
+        self.expect(TokenType::Keyword(Keyword::In))?;
+
+        let iterable = self.parse_expression()?;
+
+        self.expect(TokenType::CloseParen)?;
+
+        self.locals.push(vec![Local {
+            name: name.clone(),
+            index: name_index,
+            used: false,
+        }]);
 
-        self.expect(TokenType::OpenParen);
-        self.expect(TokenType::CloseParen);
+        // The variable is popped even if the body has a syntax error, so that it is not in scope after the loop.
+        let body = self.parse_block();
+        let locals = self.locals.pop().unwrap_or_default();
+        let body = body?;
+
+        self.report_unused(locals);
 
-        let _body = self.parse_block();
+        let for_loop = ForLoop { name, name_span, iterable, body };
 
-        todo!()
+        Ok(self.make_statement(StatementKind::For(Box::new(for_loop)), start))
     }
 
     /// Parse a variable definition.
-    fn parse_var_def(&mut self) -> Statement {
-        self.expect(TokenType::Keyword(Keyword::Var));
+    fn parse_var_def(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
+
+        self.expect(TokenType::Keyword(Keyword::Var))?;
 
-        let name = self.expect_identifier();
+        let name_index = self.index;
+        let name = self.expect_identifier()?;
+        let name_span = self.span_from(name_index);
 
-        self.expect(TokenType::Colon);
+        self.expect(TokenType::Colon)?;
 
-        let typee = self.parse_type();
+        let type_start = self.index;
+        let typee = self.parse_type()?;
+        let type_span = self.span_from(type_start);
 
-        self.expect(TokenType::Eq);
+        self.expect(TokenType::Eq)?;
 
-        let value = self.parse_expression();
+        let value = self.parse_expression()?;
 
-        self.expect(TokenType::Semi);
+        self.expect(TokenType::Semi)?;
 
-        Statement::Declaration(Box::new(Declaration::VarDef(name, typee, Box::new(value))))
+        if let Some(block) = self.locals.last_mut() {
+            block.push(Local {
+                name: name.clone(),
+                index: name_index,
+                used: false,
+            });
+        }
+
+        let var_def = VarDef {
+            name,
+            name_span,
+            typee,
+            type_span,
+            value,
+        };
+
+        Ok(self.make_statement(StatementKind::Declaration(Box::new(Declaration::VarDef(var_def))), start))
     }
 
     /// Parse if statement.
-    fn parse_if(&mut self) -> Statement {
-        self.expect(TokenType::Keyword(Keyword::If));
+    fn parse_if(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
 
-        self.expect(TokenType::OpenParen);
+        self.expect(TokenType::Keyword(Keyword::If))?;
 
-        let condition = self.parse_expression();
+        self.expect(TokenType::OpenParen)?;
 
-        self.expect(TokenType::CloseParen);
+        let condition = self.parse_expression()?;
 
-        let body = self.parse_block();
+        self.expect(TokenType::CloseParen)?;
+
+        let body = self.parse_block()?;
         let elif = {
             if *self.peek() == TokenType::Keyword(Keyword::Else) {
-                Some(Box::new(self.parse_statement()))
+                self.expect(TokenType::Keyword(Keyword::Else))?;
+
+                // An `else if` is an if statement nested in the else branch.
+                if *self.peek() == TokenType::Keyword(Keyword::If) {
+                    Some(Box::new(self.nested(Self::parse_if)?))
+                } else {
+                    Some(Box::new(self.parse_block()?))
+                }
             } else {
                 None
             }
         };
 
-        Statement::If(Box::new(condition), Box::new(body), elif)
+        Ok(self.make_statement(StatementKind::If(condition, Box::new(body), elif), start))
     }
 
     /// Parse return statement.
-    fn parse_return(&mut self) -> Statement {
-        self.expect(TokenType::Keyword(Keyword::Return));
+    fn parse_return(&mut self) -> ParseResult<Statement> {
+        let start = self.index;
 
-        let value = self.parse_expression();
+        self.expect(TokenType::Keyword(Keyword::Return))?;
 
-        self.expect(TokenType::Semi);
+        let value = if *self.peek() == TokenType::Semi { None } else { Some(self.parse_expression()?) };
 
-        Statement::Return(Box::new(value))
+        self.expect(TokenType::Semi)?;
+
+        Ok(self.make_statement(StatementKind::Return(value), start))
     }
 
     /// Parse an expression statement.
-    pub fn parse_expression_statement(&mut self) -> Expression {
-        let expression = self.parse_expression();
+    pub fn parse_expression_statement(&mut self) -> ParseResult<ExprId> {
+        let expression = self.parse_expression()?;
 
-        self.expect(TokenType::Semi);
+        self.expect(TokenType::Semi)?;
 
-        expression
+        Ok(expression)
     }
 
     /// Parse an expression.
-    fn parse_expression(&mut self) -> Expression {
-        self.parse_assignment()
+    fn parse_expression(&mut self) -> ParseResult<ExprId> {
+        self.nested(Self::parse_assignment)
     }
 
     /// Parse an identifier.
-    fn parse_id(&mut self) -> Expression {
-        let id = self.expect_identifier();
+    fn parse_id(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+        let id = self.expect_identifier()?;
 
         if *self.peek() == TokenType::OpenParen {
             let mut params = vec![];
 
-            self.expect(TokenType::OpenParen);
+            self.expect(TokenType::OpenParen)?;
 
             while *self.peek() != TokenType::CloseParen {
-                params.push(self.parse_expression());
+                params.push(self.parse_expression()?);
 
                 if *self.peek() != TokenType::CloseParen {
-                    self.expect(TokenType::Comma);
+                    self.expect(TokenType::Comma)?;
                 }
             }
 
-            self.expect(TokenType::CloseParen);
+            self.expect(TokenType::CloseParen)?;
 
-            Expression::FunctionCall(id, params)
+            Ok(self.make_expression(ExpressionKind::FunctionCall(id, params), start))
         } else {
-            Expression::VarRef(id)
+            self.mark_used(&id);
+
+            Ok(self.make_expression(ExpressionKind::VarRef(id), start))
         }
     }
 
     /// Parse a primary expression.
-    fn parse_primary(&mut self) -> Expression {
-        match self.peek().clone() {
+    fn parse_primary(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+
+        let kind = match self.peek().clone() {
             TokenType::Keyword(Keyword::True) => {
                 self.advance();
-                Expression::Literal(Literal::Bool(true))
+                ExpressionKind::Literal(Literal::Bool(true))
             }
             TokenType::Keyword(Keyword::False) => {
                 self.advance();
-                Expression::Literal(Literal::Bool(false))
+                ExpressionKind::Literal(Literal::Bool(false))
             }
             TokenType::Keyword(Keyword::Null) => {
                 self.advance();
-                Expression::Literal(Literal::Null)
+                ExpressionKind::Literal(Literal::Null)
             }
             TokenType::Number(number) => {
                 self.advance();
-                Expression::Literal(Literal::Number(number))
+                ExpressionKind::Literal(Literal::Number(number))
             }
             TokenType::Float(float) => {
                 self.advance();
-                Expression::Literal(Literal::Float(float))
+                ExpressionKind::Literal(Literal::Float(float))
             }
             TokenType::String(string) => {
                 self.advance();
-                Expression::Literal(Literal::String(string))
+                ExpressionKind::Literal(Literal::String(string))
             }
             TokenType::Char(char) => {
                 self.advance();
-                Expression::Literal(Literal::Char(char))
+                ExpressionKind::Literal(Literal::Char(char))
             }
-            TokenType::Identifier(_) => self.parse_id(),
-            TokenType::OpenParen => self.parse_paren(),
-            _ => panic!("Expected an expression, found `{:?}`", self.peek()),
-        }
+            TokenType::Identifier(_) => return self.parse_id(),
+            TokenType::OpenParen => return self.parse_paren(),
+            TokenType::Keyword(Keyword::If) => return self.parse_if_expression(),
+            TokenType::OpenBrace => return self.parse_block_expression(),
+            _ => return Err(self.unexpected_token("an expression")),
+        };
+
+        Ok(self.make_expression(kind, start))
     }
 
     /// Parse a paren expresion.
-    fn parse_paren(&mut self) -> Expression {
-        self.expect(TokenType::OpenParen);
+    fn parse_paren(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+
+        self.expect(TokenType::OpenParen)?;
+
+        let prime = self.parse_expression()?;
+        self.expect(TokenType::CloseParen)?;
+
+        Ok(self.make_expression(ExpressionKind::Paren(prime), start))
+    }
+
+    /// Parse an if expression. The branches are block expressions, and the `else` branch is required so that the
+    /// expression always has a value.
+    ///
+    /// if ($condition) { $value } else { $value }
+    fn parse_if_expression(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+
+        self.expect(TokenType::Keyword(Keyword::If))?;
+        self.expect(TokenType::OpenParen)?;
+
+        let condition = self.parse_expression()?;
 
-        let prime = self.parse_expression();
-        self.expect(TokenType::CloseParen);
+        self.expect(TokenType::CloseParen)?;
+
+        let then = self.parse_block_expression()?;
+
+        if *self.peek() != TokenType::Keyword(Keyword::Else) {
+            return Err(self
+                .make_error(format!("expected `else`, found `{}`", self.peek()), codes::E0006, self.index, "expected `else`")
+                .set_help("an `if` expression must have an `else` branch, the value of the expression when the condition is false")
+                .build());
+        }
+
+        self.advance();
+
+        // An `else if` is an if expression nested in the else branch.
+        let otherwise = if *self.peek() == TokenType::Keyword(Keyword::If) {
+            self.nested(Self::parse_if_expression)?
+        } else {
+            self.parse_block_expression()?
+        };
 
-        Expression::Paren(Box::new(prime))
+        Ok(self.make_expression(ExpressionKind::If(condition, then, otherwise), start))
     }
 
     /// Parse a unary expression.
-    fn parse_unary(&mut self) -> Expression {
+    fn parse_unary(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+
         match self.peek() {
             TokenType::Minus => {
                 self.advance();
 
-                let right = self.parse_unary();
-                Expression::Unary(UnaryOp::Neg, Box::new(right))
+                let right = self.nested(Self::parse_unary)?;
+                Ok(self.make_expression(ExpressionKind::Unary(UnaryOp::Neg, right), start))
+            }
+            TokenType::Plus => {
+                self.advance();
+
+                let right = self.nested(Self::parse_unary)?;
+                Ok(self.make_expression(ExpressionKind::Unary(UnaryOp::Plus, right), start))
             }
             TokenType::Bang => {
                 self.advance();
 
-                let right = self.parse_unary();
-                Expression::Unary(UnaryOp::Not, Box::new(right))
+                let right = self.nested(Self::parse_unary)?;
+                Ok(self.make_expression(ExpressionKind::Unary(UnaryOp::Not, right), start))
             }
-            _ => self.parse_primary(),
+            _ => self.parse_index(),
+        }
+    }
+
+    /// Parse a primary expression followed by any number of indexes.
+    ///
+    /// $primary $([$index])*
+    fn parse_index(&mut self) -> ParseResult<ExprId> {
+        let start = self.index;
+        let mut node = self.parse_primary()?;
+
+        while *self.peek() == TokenType::OpenBrac {
+            self.advance();
+
+            let index = self.parse_expression()?;
+            self.expect(TokenType::CloseBrac)?;
+
+            node = self.make_expression(ExpressionKind::Index(node, index), start);
         }
+
+        Ok(node)
     }
 
     /// Parse assignment.
-    fn parse_assignment(&mut self) -> Expression {
-        let node = self.parse_or();
+    fn parse_assignment(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_coalesce()?;
 
         if let TokenType::Eq = *self.peek() {
+            let eq = self.index;
+
             self.advance();
 
-            let value = self.parse_expression();
-            let var = match node {
-                Expression::VarRef(var) => var,
-                _ => panic!("Cannot assign value to `{:?}`", node),
+            let value = self.parse_expression()?;
+            let span = self.exprs[node].span.start..self.exprs[value].span.end;
+            let var = match self.exprs[node].kind {
+                ExpressionKind::VarRef(ref var) => var.clone(),
+                _ => return Err(self.make_error("invalid left-hand side of assignment", codes::E0007, eq, "cannot assign to this expression").build()),
             };
 
-            return Expression::VarAssign(var, Box::new(value));
+            return Ok(self.exprs.alloc(Expression::new(ExpressionKind::VarAssign(var, value), span)));
         }
 
-        node
+        Ok(node)
+    }
+
+    /// Parse a null-coalescing expression, which is right associative: `a ?? b ?? c` is `a ?? (b ?? c)`.
+    fn parse_coalesce(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_or()?;
+
+        match self.peek() {
+            TokenType::QuestionQuestion => {
+                self.advance();
+
+                let rhs = self.parse_coalesce()?;
+                Ok(self.make_binary(node, BinaryOp::Coalesce, rhs))
+            }
+            _ => Ok(node),
+        }
     }
 
     /// Parse or.
-    fn parse_or(&mut self) -> Expression {
-        let node = self.parse_and();
+    fn parse_or(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_and()?;
 
         match self.peek() {
             TokenType::PipePipe => {
                 self.advance();
 
-                let rhs = self.parse_and();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Or, Box::new(rhs))
+                let rhs = self.parse_and()?;
+                Ok(self.make_binary(node, BinaryOp::Or, rhs))
             }
-            _ => node,
+            _ => Ok(node),
         }
     }
 
     /// Parse and.
-    fn parse_and(&mut self) -> Expression {
-        let node = self.parse_equality();
+    fn parse_and(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_equality()?;
 
         match self.peek() {
             TokenType::AmpAmp => {
                 self.advance();
 
-                let rhs = self.parse_equality();
-                Expression::BinaryOp(Box::new(node), BinaryOp::And, Box::new(rhs))
+                let rhs = self.parse_equality()?;
+                Ok(self.make_binary(node, BinaryOp::And, rhs))
             }
-            _ => node,
+            _ => Ok(node),
         }
     }
 
     /// Parse equality.
-    fn parse_equality(&mut self) -> Expression {
-        let node = self.parse_comparison();
+    fn parse_equality(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_comparison()?;
 
         match self.peek() {
             TokenType::EqEq => {
                 self.advance();
 
-                let rhs = self.parse_comparison();
-                Expression::BinaryOp(Box::new(node), BinaryOp::EqEq, Box::new(rhs))
+                let rhs = self.parse_comparison()?;
+                Ok(self.make_binary(node, BinaryOp::EqEq, rhs))
             }
-            _ => node,
+            _ => Ok(node),
         }
     }
 
     /// Parse comparison.
-    fn parse_comparison(&mut self) -> Expression {
-        let node = self.parse_term();
+    fn parse_comparison(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_term()?;
 
         match self.peek() {
             TokenType::Greater => {
                 self.advance();
 
-                let rhs = self.parse_term();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Greater, Box::new(rhs))
+                let rhs = self.parse_term()?;
+                Ok(self.make_binary(node, BinaryOp::Greater, rhs))
             }
             TokenType::Lesser => {
                 self.advance();
 
-                let rhs = self.parse_term();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Lesser, Box::new(rhs))
+                let rhs = self.parse_term()?;
+                Ok(self.make_binary(node, BinaryOp::Lesser, rhs))
             }
-            _ => node,
+            _ => Ok(node),
         }
     }
 
     /// Parse a term.
-    fn parse_term(&mut self) -> Expression {
-        let node = self.parse_factor();
+    fn parse_term(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_factor()?;
 
         match self.peek() {
             TokenType::Plus => {
                 self.advance();
 
-                let rhs = self.parse_factor();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Add, Box::new(rhs))
+                let rhs = self.parse_factor()?;
+                Ok(self.make_binary(node, BinaryOp::Add, rhs))
             }
             TokenType::Minus => {
                 self.advance();
 
-                let rhs = self.parse_factor();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Subtract, Box::new(rhs))
+                let rhs = self.parse_factor()?;
+                Ok(self.make_binary(node, BinaryOp::Subtract, rhs))
             }
-            _ => node,
+            _ => Ok(node),
         }
     }
 
     /// Parse a factor.
-    fn parse_factor(&mut self) -> Expression {
-        let node = self.parse_unary();
+    fn parse_factor(&mut self) -> ParseResult<ExprId> {
+        let node = self.parse_unary()?;
 
         match self.peek() {
             TokenType::Star => {
                 self.advance();
 
-                let rhs = self.parse_unary();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Mul, Box::new(rhs))
+                let rhs = self.parse_unary()?;
+                Ok(self.make_binary(node, BinaryOp::Mul, rhs))
             }
             TokenType::Slash => {
                 self.advance();
 
-                let rhs = self.parse_unary();
-                Expression::BinaryOp(Box::new(node), BinaryOp::Div, Box::new(rhs))
+                let rhs = self.parse_unary()?;
+                Ok(self.make_binary(node, BinaryOp::Div, rhs))
             }
-            _ => node,
+            _ => Ok(node),
+        }
+    }
+
+    /// Returns the byte range from the token at `start` to the previous token.
+    fn span_from(&self, start: usize) -> Range<usize> {
+        let end = self.tokens[self.index.max(start + 1) - 1].position.position_end;
+
+        self.tokens[start].position.position_start..end
+    }
+
+    /// Make an expression that spans from the token at `start` to the previous token, and add it to the arena.
+    fn make_expression(&mut self, kind: ExpressionKind, start: usize) -> ExprId {
+        let span = self.span_from(start);
+
+        self.exprs.alloc(Expression::new(kind, span))
+    }
+
+    /// Make a statement that spans from the token at `start` to the previous token.
+    fn make_statement(&self, kind: StatementKind, start: usize) -> Statement {
+        Statement::new(kind, self.span_from(start))
+    }
+
+    /// Make a binary expression that spans both of the operands.
+    fn make_binary(&mut self, lhs: ExprId, op: BinaryOp, rhs: ExprId) -> ExprId {
+        let span = self.exprs[lhs].span.start..self.exprs[rhs].span.end;
+
+        self.exprs.alloc(Expression::new(ExpressionKind::BinaryOp(lhs, op, rhs), span))
+    }
+
+    /// Parse a node nested in the one being parsed, reporting an error if the code is nested more than `MAX_NESTING`
+    /// times.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= MAX_NESTING {
+            self.too_deep = true;
+
+            return Err(self
+                .make_error("code is nested too deeply", codes::E0023, self.index, "the nesting limit is reached here")
+                .set_help(format!("code can be nested at most {} times, move the nested code to a function", MAX_NESTING))
+                .build());
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Skip tokens until the start of the next statement.
+    /// This is used to recover from an error so that the following errors can be reported too.
+    fn synchronize(&mut self) {
+        let start = self.index;
+
+        while !self.is_eof() {
+            match self.peek() {
+                TokenType::Semi | TokenType::CloseBrace => {
+                    self.advance();
+
+                    break;
+                }
+                TokenType::Keyword(Keyword::Fn)
+                | TokenType::Keyword(Keyword::Extern)
+                | TokenType::Keyword(Keyword::Export)
+                | TokenType::Keyword(Keyword::Test)
+                | TokenType::Keyword(Keyword::Var)
+                | TokenType::Keyword(Keyword::Return)
+                | TokenType::Keyword(Keyword::If)
+                | TokenType::Keyword(Keyword::For)
+                    if self.index != start =>
+                {
+                    break
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Mark the innermost variable with the given name as used.
+    fn mark_used(&mut self, name: &str) {
+        let local = self.locals.iter_mut().rev().flat_map(|block| block.iter_mut().rev()).find(|local| local.name == name);
+
+        if let Some(local) = local {
+            local.used = true;
         }
     }
 
     /// Advance to the next character.
     #[inline]
     fn advance(&mut self) {
-        self.index += 1;
+        if !self.is_eof() {
+            self.index += 1;
+        }
     }
 
     /// Expect a token.
-    fn expect(&mut self, token: TokenType) {
+    fn expect(&mut self, token: TokenType) -> ParseResult<()> {
         if *self.peek() == token {
             self.advance();
+
+            Ok(())
+        } else if token == TokenType::Semi && self.index > 0 {
+            // Point at the end of the previous token, that is where the semicolon is missing.
+            let previous = &self.tokens[self.index - 1].position;
+            let end = previous.position_end;
+
+            Err(self
+                .make_error(format!("expected `;`, found `{}`", self.peek()), codes::E0006, self.index - 1, "expected `;` after this")
+                .push_suggestion(Suggestion::new(end..end, ";", "add `;` here"))
+                .build())
         } else {
-            panic!("Expected {}", token)
+            Err(self.unexpected_token(&format!("`{}`", token)))
         }
     }
 
     /// Expect an identifier and return its value.
-    fn expect_identifier(&mut self) -> String {
+    fn expect_identifier(&mut self) -> ParseResult<String> {
         let id = if let TokenType::Identifier(id) = self.peek() {
             id.to_string()
         } else {
-            panic!("Expected an identifier.")
+            return Err(self.unexpected_token("an identifier"));
         };
 
         self.advance();
 
-        id
+        Ok(id)
+    }
+
+    /// Make an error for the current token when it is not what the parser expected.
+    fn unexpected_token(&self, expected: &str) -> Diagnostic {
+        self.make_error(format!("expected {}, found `{}`", expected, self.peek()), codes::E0006, self.index, format!("expected {}", expected))
+            .build()
+    }
+
+    /// Make an error with a message and code, annotating the token at `index` with a label.
+    fn make_error(&self, message: impl Into<String>, code: impl Into<String>, index: usize, label: impl Into<String>) -> DiagnosticBuilder {
+        self.make_diagnostic(Severity::Error, message, code, index, label)
+    }
+
+    /// Make a diagnostic with a severity, message and code, annotating the token at `index` with a label.
+    fn make_diagnostic(&self, severity: Severity, message: impl Into<String>, code: impl Into<String>, index: usize, label: impl Into<String>) -> DiagnosticBuilder {
+        let position = &self.tokens[index].position;
+        let kind = if severity == Severity::Warning { AnnotationType::Warning } else { AnnotationType::Error };
+
+        DiagnosticBuilder::new(severity, message)
+            .set_code(code)
+            .push_slice(Slice::new(self.file.clone()).push_annotation(SourceAnnotation::new(position.position_start..position.position_end, kind).set_label(label)))
+    }
+
+    /// Check if the parser has reached the EOF token.
+    #[inline]
+    fn is_eof(&self) -> bool {
+        self.tokens.get(self.index).map_or(true, |token| token.kind == TokenType::EOF)
     }
 
     /// Peek the current token type.
@@ -0,0 +1,419 @@
+//! This file contains all of the unit tests for the parser.
+
+use fluid_error::Suggestion;
+use fluid_lexer::Lexer;
+
+use crate::{dump_ast, parse_format_string, placeholder_count, Ast, Declaration, ExpressionKind, FormatPiece, Parser, StatementKind, MAX_NESTING};
+
+#[inline]
+fn parse(source: &str) -> Result<Ast, Vec<fluid_error::Diagnostic>> {
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+
+    Parser::new(&tokens, lexer.file()).run()
+}
+
+#[test]
+fn test_function() {
+    let source = "
+        function add(a: number, b: number) -> number {
+            return a + b;
+        }
+    ";
+
+    let ast = parse(source).unwrap();
+
+    assert_eq!(ast.statements.len(), 1);
+}
+
+#[test]
+fn test_missing_semicolon() {
+    let source = "var x: number = 1\nvar y: number = 2;";
+
+    let errors = parse(source).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].suggestions(), &[Suggestion::new(17..17, ";", "add `;` here")]);
+}
+
+#[test]
+fn test_error_recovery() {
+    let source = "
+        var x: number = ;
+        var y: number = 2;
+        var z: number = ;
+    ";
+
+    let errors = parse(source).unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn test_unused_variable() {
+    let source = "
+        function main() {
+            var used: number = 1;
+            var unused: number = used;
+            var _ignored: number = 2;
+        }
+    ";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+
+    let mut parser = Parser::new(&tokens, lexer.file());
+    parser.run().unwrap();
+
+    let warnings = parser.take_warnings();
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code(), Some("W0001"));
+}
+
+#[test]
+fn test_if_else() {
+    let source = "
+        if (true) {
+            return 1;
+        } else if (false) {
+            return 2;
+        } else {
+            return 3;
+        }
+    ";
+
+    let ast = parse(source).unwrap();
+
+    assert_eq!(ast.statements.len(), 1);
+
+    let elif = match ast.statements[0].kind {
+        StatementKind::If(_, _, Some(ref elif)) => elif,
+        _ => panic!("expected an if statement with an else branch"),
+    };
+
+    match elif.kind {
+        StatementKind::If(_, _, Some(ref otherwise)) => assert!(matches!(otherwise.kind, StatementKind::Block(..))),
+        _ => panic!("expected an else if branch"),
+    }
+}
+
+#[test]
+fn test_no_mangle() {
+    let source = "
+        extern {
+            function puts(s: string) -> number;
+        }
+
+        export function exported() {}
+
+        function internal() {}
+    ";
+
+    let ast = parse(source).unwrap();
+
+    let no_mangle = ast
+        .statements
+        .iter()
+        .flat_map(|statement| match statement.kind {
+            StatementKind::Declaration(ref declaration) => match **declaration {
+                Declaration::Extern(ref externs) => externs.iter().map(|prototype| (prototype.name.as_str(), prototype.no_mangle)).collect(),
+                Declaration::Function(ref function) => vec![(function.prototype.name.as_str(), function.prototype.no_mangle)],
+                _ => vec![],
+            },
+            _ => vec![],
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(no_mangle, vec![("puts", true), ("exported", true), ("internal", false)]);
+}
+
+#[test]
+fn test_dump_ast() {
+    let source = "extern { function puts(s: string) -> number; }
+function main() -> number {
+    var x: number = -(1 + 2);
+    if (x == 3) { puts(\"three\"); }
+    return x;
+}";
+
+    let expected = "Extern 0..46
+  Prototype puts(s: string) -> number 18..22
+Function main() -> number 47..155
+  Block 73..155
+    VarDef x: number 79..104
+      Unary - 95..103
+        Paren 96..103
+          BinaryOp + 97..102
+            Literal Number(1) 97..98
+            Literal Number(2) 101..102
+    If 109..139
+      BinaryOp == 113..119
+        VarRef x 113..114
+        Literal Number(3) 118..119
+      Block 121..139
+        Expression 123..137
+          Call puts 123..136
+            Literal String(\"three\") 128..135
+    Return 144..153
+      VarRef x 151..152
+";
+
+    assert_eq!(dump_ast(&parse(source).unwrap()), expected);
+}
+
+#[test]
+fn test_test_functions() {
+    let ast = parse("test function it_works() {} function helper() {}").unwrap();
+
+    let tests = ast
+        .statements
+        .iter()
+        .filter_map(|statement| match statement.kind {
+            StatementKind::Declaration(ref declaration) => match **declaration {
+                Declaration::Function(ref function) => Some((function.prototype.name.as_str(), function.prototype.test)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(tests, vec![("it_works", true), ("helper", false)]);
+}
+
+#[test]
+fn test_nesting_limit() {
+    // The function, the variable and its value are nested 3 times.
+    let depth = MAX_NESTING - 3;
+    let source = format!("function main() {{ var x: number = {}1{}; }}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse(&source).is_ok());
+
+    let source = format!("function main() {{ var x: number = {}1{}; }}", "(".repeat(depth + 1), ")".repeat(depth + 1));
+    let errors = parse(&source).unwrap_err();
+
+    assert_eq!(errors[0].code(), Some("E0023"));
+    assert_eq!(errors[0].message(), "code is nested too deeply");
+
+    // The deeply nested code is reported instead of overflowing the stack.
+    for source in &[
+        format!("var x: number = {}1{};", "(".repeat(10_000), ")".repeat(10_000)),
+        format!("var x: number = {}1;", "-".repeat(10_000)),
+        format!("function main() {{ {}{} }}", "{".repeat(10_000), "}".repeat(10_000)),
+        format!("function main() {{ {} {{}} }}", "if (true) {} else ".repeat(10_000)),
+    ] {
+        let errors = parse(source).unwrap_err();
+
+        assert!(errors.iter().any(|error| error.code() == Some("E0023")));
+    }
+}
+
+#[test]
+fn test_unsupported_syntax() {
+    // The syntax that the compiler does not support yet is an error, the parser must not panic on it.
+    let errors = parse("function f(x: boolean) {}").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0021"));
+
+    let errors = parse("function f(x: 1) {}").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0006"));
+
+    let errors = parse("function f(x: (number, float)) {}").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0016"));
+
+    let errors = parse("function f() {\n    for () {\n        print(1);\n    }\n}").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0006"));
+}
+
+#[test]
+fn test_for_loop() {
+    let source = "function f(s: string) {\n    for (c in s) {\n        print(c);\n    }\n    var first: char = s[0];\n}";
+
+    let expected = "\
+Function f(s: string) -> void 0..96
+  Block 22..96
+    For c 28..66
+      VarRef s 38..39
+      Block 41..66
+        Expression 51..60
+          Call print 51..59
+            VarRef c 57..58
+    VarDef first: char 71..94
+      Index 89..93
+        VarRef s 89..90
+        Literal Number(0) 91..92
+";
+
+    let ast = parse(source).unwrap();
+
+    assert_eq!(dump_ast(&ast), expected);
+}
+
+#[test]
+fn test_bool_type() {
+    let source = "function f(done: bool) -> bool {\n    var flag: bool = true;\n    return flag;\n}";
+
+    let expected = "\
+Function f(done: bool) -> bool 0..78
+  Block 31..78
+    VarDef flag: bool 37..59
+      Literal Bool(true) 54..58
+    Return 64..76
+      VarRef flag 71..75
+";
+
+    assert_eq!(dump_ast(&parse(source).unwrap()), expected);
+}
+
+#[test]
+fn test_if_expression() {
+    let ast = parse("var x: number = if (a) { 1 } else if (b) { 2 } else { 3 };").unwrap();
+
+    // An `else if` is an if expression nested in the else branch.
+    let expected = "\
+VarDef x: number 0..58
+  If 16..57
+    VarRef a 20..21
+    Block 23..28
+      Literal Number(1) 25..26
+    If 34..57
+      VarRef b 38..39
+      Block 41..46
+        Literal Number(2) 43..44
+      Block 52..57
+        Literal Number(3) 54..55
+";
+
+    assert_eq!(dump_ast(&ast), expected);
+
+    // The value is missing if there is no `else` branch.
+    let errors = parse("var x: number = if (a) { 1 };").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0006"));
+}
+
+#[test]
+fn test_block_value() {
+    let source = "function double(x: number) -> number {\n    var y: number = { var z: number = x; z * 2 };\n    y\n}";
+
+    // The expression without a `;` at the end of a block is its value.
+    let expected = "\
+Function double(x: number) -> number 0..96
+  Block 37..96
+    VarDef y: number 43..88
+      Block 59..87
+        VarDef z: number 61..79
+          VarRef x 77..78
+        BinaryOp * 80..85
+          VarRef z 80..81
+          Literal Number(2) 84..85
+    VarRef y 93..94
+";
+
+    assert_eq!(dump_ast(&parse(source).unwrap()), expected);
+
+    let body_value = |source: &str, is_kind: fn(&ExpressionKind) -> bool| {
+        let ast = parse(source).unwrap();
+
+        let value = match ast.statements[0].kind {
+            StatementKind::Declaration(ref declaration) => match **declaration {
+                Declaration::Function(ref function) => match function.body.kind {
+                    StatementKind::Block(_, value) => value,
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        };
+
+        value.map(|value| is_kind(&ast.exprs[value].kind))
+    };
+
+    // An if statement at the end of a block is its value if all of its branches have values.
+    let source = "function sign(negative: bool) -> number {\n    if (negative) { -1 } else { 1 }\n}";
+    assert_eq!(body_value(source, |kind| matches!(kind, ExpressionKind::If(..))), Some(true));
+
+    // So is a nested block that ends with a value.
+    let source = "function one() -> number {\n    var x: number = 1;\n    { x }\n}";
+    assert_eq!(body_value(source, |kind| matches!(kind, ExpressionKind::Block(..))), Some(true));
+
+    // A nested block without a value is a statement.
+    assert_eq!(body_value("function f() {\n    { 1; }\n}", |_| true), None);
+
+    // A block expression must have a value.
+    let errors = parse("var x: number = { 1; };").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0006"));
+
+    // Only the last expression can be without a `;`.
+    let errors = parse("function f() { 1 2 }").unwrap_err();
+    assert_eq!(errors[0].code(), Some("E0006"));
+}
+
+#[test]
+fn test_coalesce() {
+    let ast = parse("var x: string = a ?? b || c ?? d;").unwrap();
+
+    // `??` binds looser than `||`, and the right operand is another `??`.
+    let expected = "\
+VarDef x: string 0..33
+  BinaryOp ?? 16..32
+    VarRef a 16..17
+    BinaryOp ?? 21..32
+      BinaryOp || 21..27
+        VarRef b 21..22
+        VarRef c 26..27
+      VarRef d 31..32
+";
+
+    assert_eq!(dump_ast(&ast), expected);
+}
+
+#[test]
+fn test_expression_arena() {
+    let ast = parse("var x: number = f(1, 2) + -3;").unwrap();
+
+    // The children are allocated before their parent, so the root of the expression is the last one.
+    assert_eq!(ast.exprs.len(), 6);
+
+    let (root, expression) = ast.exprs.iter().last().unwrap();
+
+    match ast.statements[0].kind {
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::VarDef(ref var_def) => assert_eq!(var_def.value, root),
+            _ => panic!("expected a variable definition"),
+        },
+        _ => panic!("expected a declaration"),
+    }
+
+    let (lhs, rhs) = match expression.kind {
+        ExpressionKind::BinaryOp(lhs, _, rhs) => (lhs, rhs),
+        _ => panic!("expected a binary expression"),
+    };
+
+    assert!(matches!(ast.exprs[lhs].kind, ExpressionKind::FunctionCall(ref name, ref args) if name == "f" && args.len() == 2));
+    assert!(matches!(ast.exprs[rhs].kind, ExpressionKind::Unary(..)));
+    assert_eq!(ast.exprs[rhs].span, 26..28);
+}
+
+#[test]
+fn test_format_string() {
+    let pieces = parse_format_string("{} + {{{}}} = {}%").unwrap();
+
+    assert_eq!(
+        pieces,
+        vec![
+            FormatPiece::Placeholder,
+            FormatPiece::Text(String::from(" + {")),
+            FormatPiece::Placeholder,
+            FormatPiece::Text(String::from("} = ")),
+            FormatPiece::Placeholder,
+            FormatPiece::Text(String::from("%")),
+        ]
+    );
+    assert_eq!(placeholder_count(&pieces), 3);
+    assert_eq!(parse_format_string(""), Ok(vec![]));
+    assert_eq!(parse_format_string("{x}"), Err('{'));
+    assert_eq!(parse_format_string("{}}"), Err('}'));
+    assert_eq!(parse_format_string("{"), Err('{'));
+}
@@ -0,0 +1,179 @@
+//! The `fluid_snapshot` crate is the golden snapshot test harness of the front end.
+//!
+//! Every `.fluid` file of the `tests/ui` directory at the root of the repository is lexed, parsed and type checked,
+//! and its tokens, its AST and its diagnostics are compared with the snapshot files next to it:
+//!
+//! * `name.tokens`, the tokens as written by `--emit tokens`, if the file could be lexed.
+//! * `name.ast`, the checked AST as written by `--emit ast`, if the file could be parsed.
+//! * `name.diagnostics`, the errors and the warnings as written by `--error-format json`, if there are any.
+//!
+//! A regression of the parser or of a diagnostic is caught by adding a source file. The snapshots are written,
+//! or updated after an intended change, by running the tests with `FLUID_BLESS=1`:
+//!
+//! ```text
+//! cd packages/fluid_snapshot
+//! FLUID_BLESS=1 cargo test
+//! ```
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use fluid_error::SourceFile;
+use fluid_lexer::{dump_tokens, Lexer};
+use fluid_parser::{dump_ast, Parser};
+use fluid_typeck::TypeChecker;
+
+/// The kinds of snapshots, by the extension of their file.
+pub const EXTENSIONS: &[&str] = &["tokens", "ast", "diagnostics"];
+
+/// The name of the environment variable that writes the snapshots instead of comparing them.
+pub const BLESS_VAR: &str = "FLUID_BLESS";
+
+/// The snapshots of a source file, along with the extension of their file. A snapshot is missing if the phase
+/// that produces it did not run, or if there are no diagnostics.
+pub fn snapshots(file: Arc<SourceFile>) -> Vec<(&'static str, String)> {
+    let mut snapshots = vec![];
+    let mut diagnostics = vec![];
+
+    // The phases stop at the first one that fails, like in the compiler.
+    let mut lexer = Lexer::from_file(&file);
+    let result = lexer.run().map(|tokens| {
+        snapshots.push(("tokens", dump_tokens(&tokens, &file)));
+
+        let mut parser = Parser::new(&tokens, file.clone());
+        let result = parser.run();
+
+        diagnostics.extend(parser.take_warnings());
+        result
+    });
+
+    match result {
+        Ok(Ok(mut ast)) => {
+            let mut checker = TypeChecker::new(file.clone());
+
+            if let Err(errors) = checker.run(&mut ast) {
+                diagnostics.extend(errors);
+            }
+
+            diagnostics.extend(checker.take_warnings());
+            snapshots.push(("ast", dump_ast(&ast)));
+        }
+        Ok(Err(errors)) | Err(errors) => diagnostics.extend(errors),
+    }
+
+    if !diagnostics.is_empty() {
+        let lines = diagnostics.iter().map(|diagnostic| diagnostic.to_json() + "\n");
+
+        snapshots.push(("diagnostics", lines.collect()));
+    }
+
+    snapshots
+}
+
+/// A snapshot that differs from what the front end produced.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// The path of the snapshot file.
+    pub path: PathBuf,
+    /// The contents of the snapshot file, `None` if it does not exist.
+    pub expected: Option<String>,
+    /// What the front end produced, `None` if it produced nothing.
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (expected, actual) = match (&self.expected, &self.actual) {
+            (None, _) => return write!(f, "{}: the snapshot does not exist", self.path.display()),
+            (_, None) => return write!(f, "{}: the snapshot is not produced anymore", self.path.display()),
+            (Some(expected), Some(actual)) => (expected, actual),
+        };
+
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = actual.lines();
+        let mut line = 1;
+
+        // Only the first differing line is shown, the whole snapshot is written by blessing it.
+        loop {
+            match (expected_lines.next(), actual_lines.next()) {
+                (Some(expected), Some(actual)) if expected == actual => line += 1,
+                (expected, actual) => {
+                    writeln!(f, "{}:{}: the snapshot differs", self.path.display(), line)?;
+                    writeln!(f, "  expected: {}", expected.unwrap_or("<end of the snapshot>"))?;
+
+                    return write!(f, "  actual:   {}", actual.unwrap_or("<end of the output>"));
+                }
+            }
+        }
+    }
+}
+
+/// Compare the snapshots of the `.fluid` files in the directory and its subdirectories with their files, or write
+/// them if `bless` is true. The snapshot files that are not produced anymore are removed when blessing.
+/// Returns the mismatches, always empty when blessing.
+pub fn run(dir: &Path, bless: bool) -> io::Result<Vec<Mismatch>> {
+    let mut paths = vec![];
+    let mut mismatches = vec![];
+
+    collect_fluid_files(dir, &mut paths)?;
+
+    for path in paths {
+        // The name is relative to the directory, so the snapshots do not depend on where the repository is.
+        let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        let file = Arc::new(SourceFile::new(name, fs::read_to_string(&path)?.replace("\r\n", "\n")));
+        let snapshots = snapshots(file);
+
+        for &extension in EXTENSIONS {
+            let path = path.with_extension(extension);
+            let actual = snapshots.iter().find(|(kind, _)| *kind == extension).map(|(_, snapshot)| snapshot.clone());
+
+            if bless {
+                match &actual {
+                    Some(actual) => fs::write(&path, actual)?,
+                    None if path.exists() => fs::remove_file(&path)?,
+                    None => (),
+                }
+
+                continue;
+            }
+
+            let expected = if path.exists() { Some(fs::read_to_string(&path)?.replace("\r\n", "\n")) } else { None };
+
+            if expected != actual {
+                mismatches.push(Mismatch { path, expected, actual });
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Collect the path of the file, or the paths of the `.fluid` files in the directory and its subdirectories in the
+/// order of their paths.
+fn collect_fluid_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().map_or(false, |extension| extension == "fluid") {
+            collect_fluid_files(&entry, files)?;
+        }
+    }
+
+    Ok(())
+}
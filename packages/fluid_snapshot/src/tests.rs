@@ -0,0 +1,75 @@
+//! This file contains all of the unit tests for the snapshot harness, and the test of the `tests/ui` directory.
+
+use std::{env, fs, path::Path, sync::Arc};
+
+use fluid_error::SourceFile;
+
+use crate::{run, snapshots, Mismatch, BLESS_VAR};
+
+#[test]
+fn test_ui() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/ui");
+    let bless = env::var_os(BLESS_VAR).map_or(false, |value| value != "0");
+    let mismatches = run(&dir, bless).unwrap();
+
+    if !mismatches.is_empty() {
+        let messages = mismatches.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        panic!(
+            "{}\n\n{} snapshots differ, run the tests with `{}=1` to update them",
+            messages.join("\n\n"),
+            mismatches.len(),
+            BLESS_VAR
+        );
+    }
+}
+
+#[test]
+fn test_snapshots() {
+    let kinds = |source: &str| snapshots(Arc::new(SourceFile::new("<test>", source))).into_iter().map(|(kind, _)| kind).collect::<Vec<_>>();
+
+    assert_eq!(kinds("function main() {}"), vec!["tokens", "ast"]);
+    assert_eq!(kinds("var x = $;"), vec!["diagnostics"]);
+    assert_eq!(kinds("var x = 1"), vec!["tokens", "diagnostics"]);
+    assert_eq!(kinds("var x: number = \"a\";"), vec!["tokens", "ast", "diagnostics"]);
+}
+
+#[test]
+fn test_run() {
+    let dir = env::temp_dir().join("fluid_snapshot_test_run");
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.fluid"), "function main() {}").unwrap();
+    fs::write(dir.join("a.diagnostics"), "stale").unwrap();
+
+    // The snapshots are missing until they are blessed.
+    assert_eq!(run(&dir, false).unwrap().len(), 3);
+    assert!(run(&dir, true).unwrap().is_empty());
+    assert!(!dir.join("a.diagnostics").exists());
+    assert!(run(&dir, false).unwrap().is_empty());
+
+    fs::write(dir.join("a.fluid"), "function main() {}\nfunction other() {}").unwrap();
+
+    let mismatches = run(&dir, false).unwrap();
+    let message = mismatches[0].to_string();
+
+    assert_eq!(
+        mismatches.iter().map(|mismatch| mismatch.path.clone()).collect::<Vec<_>>(),
+        vec![dir.join("a.tokens"), dir.join("a.ast")]
+    );
+    assert!(message.starts_with(&format!("{}:7: the snapshot differs", dir.join("a.tokens").display())));
+    assert!(message.contains("  expected: 1:19"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_mismatch() {
+    let mismatch = Mismatch {
+        path: "a.ast".into(),
+        expected: Some("a\nb\n".to_owned()),
+        actual: Some("a\n".to_owned()),
+    };
+
+    assert_eq!(mismatch.to_string(), "a.ast:2: the snapshot differs\n  expected: b\n  actual:   <end of the output>");
+}
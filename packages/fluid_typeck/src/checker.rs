@@ -0,0 +1,996 @@
+//! This file contains the type checker implementation, the `TypeChecker` interface.
+
+use std::{fmt::Display, ops::Range, sync::Arc};
+
+use fluid_error::{codes, AnnotationType, Diagnostic, DiagnosticBuilder, Severity, Slice, SourceAnnotation, SourceFile};
+use fluid_parser::{
+    parse_format_string, placeholder_count, Ast, BinaryOp, Builtin, Declaration, ExprArena, ExprId, Expression, ExpressionKind, Function, Literal, Prototype, Statement, StatementKind, Type, UnaryOp,
+};
+
+use crate::{report::*, resolve::Resolver, scope::*};
+
+/// Contains the internal state while checking a Fluid file.
+/// The names are resolved before the types are checked.
+///
+/// The scopes are kept between the calls to `run`, so the REPL can check every input
+/// against the declarations of the previous inputs.
+#[derive(Debug)]
+pub struct TypeChecker {
+    /// The file we are checking.
+    file: Arc<SourceFile>,
+    /// The name resolution pass.
+    resolver: Resolver,
+    /// The variables and functions that are in scope.
+    scopes: Scopes,
+    /// The return types of the functions that are being checked along with the byte ranges of their
+    /// annotations, the innermost function is the last one.
+    return_types: Vec<(Type, Option<Range<usize>>)>,
+    /// The errors found while checking.
+    errors: Vec<Diagnostic>,
+    /// The warnings found while checking.
+    warnings: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    /// Create a new instance of the type checker.
+    pub fn new(file: Arc<SourceFile>) -> Self {
+        let resolver = Resolver::new(file.clone());
+        let scopes = Scopes::new();
+        let return_types = vec![];
+        let errors = vec![];
+        let warnings = vec![];
+
+        Self {
+            file,
+            resolver,
+            scopes,
+            return_types,
+            errors,
+            warnings,
+        }
+    }
+
+    /// Set the file that the following spans point into. Used by the REPL, where every input is a new file.
+    pub fn set_file(&mut self, file: Arc<SourceFile>) {
+        self.resolver.set_file(file.clone());
+        self.file = file;
+    }
+
+    /// Take the warnings found while resolving the names and checking.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        let mut warnings = self.resolver.take_warnings();
+        warnings.append(&mut self.warnings);
+
+        warnings
+    }
+
+    /// Forget all of the declarations.
+    pub fn reset(&mut self) {
+        self.resolver.reset();
+        self.scopes = Scopes::new();
+    }
+
+    /// Resolve the names of the program, then check it.
+    /// The code generator must only run on the program if there are no errors.
+    pub fn run(&mut self, ast: &mut Ast) -> Result<(), Vec<Diagnostic>> {
+        let mut errors = self.resolver.run(ast).err().unwrap_or_default();
+
+        for statement in &ast.statements {
+            self.check_statement(&ast.exprs, statement);
+        }
+
+        errors.append(&mut self.errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Resolve the names of a single expression, then infer its type without checking it against any other type.
+    /// Returns `None` if the type can not be known without an error, like the type of `null`.
+    pub fn infer_expression(&mut self, exprs: &mut ExprArena, expression: ExprId) -> Result<Option<Type>, Vec<Diagnostic>> {
+        let mut errors = self.resolver.run_expression(exprs, expression).err().unwrap_or_default();
+        let kind = if Self::is_null(exprs, expression) { None } else { self.infer(exprs, expression) };
+
+        errors.append(&mut self.errors);
+
+        if errors.is_empty() {
+            Ok(kind)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check a statement.
+    /// Returns true if the statement always returns, so the statements after it are unreachable.
+    fn check_statement(&mut self, exprs: &ExprArena, statement: &Statement) -> bool {
+        match statement.kind {
+            StatementKind::Expression(expression) => {
+                self.infer(exprs, expression);
+
+                false
+            }
+            StatementKind::Return(expression) => {
+                self.check_return(exprs, expression, statement.span.clone());
+
+                true
+            }
+            StatementKind::If(condition, ref body, ref elif) => {
+                self.expect_type(exprs, condition, Type::Bool, None);
+
+                let body_returns = self.check_statement(exprs, body);
+                let elif_returns = elif.as_ref().map_or(false, |elif| self.check_statement(exprs, elif));
+
+                body_returns && elif_returns
+            }
+            StatementKind::For(ref for_loop) => {
+                self.expect_type(exprs, for_loop.iterable, Type::String, None);
+
+                self.scopes.push();
+                self.scopes.insert_variable(for_loop.name.clone(), Variable { kind: Type::Char });
+                self.check_statement(exprs, &for_loop.body);
+                self.scopes.pop();
+
+                // The body is not run if the string is empty.
+                false
+            }
+            StatementKind::Block(ref block, value) => {
+                self.scopes.push();
+
+                let returns = self.check_block(exprs, block, value);
+
+                if let Some(value) = value {
+                    self.check_unused_value(exprs, value);
+                }
+
+                self.scopes.pop();
+
+                returns
+            }
+            StatementKind::Declaration(ref declaration) => {
+                self.check_declaration(exprs, declaration);
+
+                false
+            }
+        }
+    }
+
+    /// Check the statements of a block, warning about the statements and the value after a statement that always
+    /// returns. The value at the end of the block is checked by the caller, which knows how it is used.
+    /// Returns true if the block always returns.
+    fn check_block(&mut self, exprs: &ExprArena, block: &[Statement], value: Option<ExprId>) -> bool {
+        let mut returning = None;
+
+        for (index, statement) in block.iter().enumerate() {
+            if self.check_statement(exprs, statement) && returning.is_none() {
+                returning = Some(index);
+            }
+        }
+
+        let index = match returning {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let value_span = value.map(|value| exprs[value].span.clone());
+        let first = block.get(index + 1).map(|statement| statement.span.start).or_else(|| value_span.as_ref().map(|span| span.start));
+        let last = value_span.map(|span| span.end).or_else(|| block.last().map(|statement| statement.span.end));
+
+        if let (Some(first), Some(last)) = (first, last) {
+            let secondary = SourceAnnotation::new(block[index].span.clone(), AnnotationType::Info).set_label("any code following this statement is unreachable");
+
+            let warning = DiagnosticBuilder::new(Severity::Warning, "unreachable statement")
+                .set_code(codes::W0002)
+                .push_slice(
+                    Slice::new(self.file.clone())
+                        .push_annotation(SourceAnnotation::new(first..last, AnnotationType::Warning).set_label("unreachable statement"))
+                        .push_annotation(secondary),
+                )
+                .build();
+
+            self.warnings.push(warning);
+        }
+
+        true
+    }
+
+    /// Check the value at the end of a block whose value is not used, which must be `void` like the value of a
+    /// statement.
+    fn check_unused_value(&mut self, exprs: &ExprArena, value: ExprId) {
+        let kind = match self.infer(exprs, value) {
+            Some(kind) => kind,
+            None => return,
+        };
+
+        if kind != Type::Void {
+            let error = self
+                .make_mismatch(exprs[value].span.clone(), Type::Void, kind, None)
+                .set_help("add a `;` after the value to ignore it, or `return` it")
+                .build();
+
+            self.errors.push(error);
+        }
+    }
+
+    /// Check that a return statement matches the return type of the function.
+    fn check_return(&mut self, exprs: &ExprArena, expression: Option<ExprId>, span: Range<usize>) {
+        let (return_type, return_type_span) = match self.return_types.last() {
+            Some(return_type) => return_type.clone(),
+            None => {
+                if let Some(expression) = expression {
+                    self.infer(exprs, expression);
+                }
+
+                return;
+            }
+        };
+
+        let origin = return_type_span.map(|span| Origin {
+            file: self.file.clone(),
+            span,
+            label: "expected because of this return type".into(),
+        });
+
+        match expression {
+            Some(expression) => self.expect_type(exprs, expression, return_type, origin),
+            None if return_type != Type::Void => self.report_mismatch(span, return_type, Type::Void, origin),
+            None => (),
+        }
+    }
+
+    /// Check a declaration.
+    fn check_declaration(&mut self, exprs: &ExprArena, declaration: &Declaration) {
+        match declaration {
+            Declaration::Function(function) => self.check_function(exprs, function),
+            Declaration::Extern(externs) => {
+                for prototype in externs {
+                    self.declare_prototype(prototype);
+                }
+            }
+            Declaration::VarDef(var_def) => {
+                let origin = Origin {
+                    file: self.file.clone(),
+                    span: var_def.type_span.clone(),
+                    label: "expected due to this".into(),
+                };
+
+                self.expect_type(exprs, var_def.value, var_def.typee, Some(origin));
+                self.scopes.insert_variable(var_def.name.clone(), Variable { kind: var_def.typee });
+            }
+        }
+    }
+
+    /// Check a function definition.
+    /// The function is declared before its body is checked, so that it can call itself.
+    fn check_function(&mut self, exprs: &ExprArena, function: &Function) {
+        let prototype = &function.prototype;
+
+        if prototype.test && (!prototype.args.is_empty() || prototype.return_type != Type::Void) {
+            let error = self
+                .make_error("a test function can not take arguments or return a value", codes::E0019, prototype.name_span.clone(), "this test")
+                .set_help("remove the arguments and the return type, and use `assert` to check the results")
+                .build();
+
+            self.errors.push(error);
+        }
+
+        self.declare_prototype(&function.prototype);
+
+        self.scopes.push();
+        self.return_types.push((function.prototype.return_type, function.prototype.return_type_span.clone()));
+
+        for arg in function.prototype.args.iter() {
+            self.scopes.insert_variable(arg.name.clone(), Variable { kind: arg.typee });
+        }
+
+        match function.body.kind {
+            // The value at the end of the body is returned.
+            StatementKind::Block(ref block, Some(value)) => {
+                self.scopes.push();
+                self.check_block(exprs, block, Some(value));
+                self.check_return(exprs, Some(value), exprs[value].span.clone());
+                self.scopes.pop();
+            }
+            _ => {
+                let returns = self.check_statement(exprs, &function.body);
+
+                if !returns && prototype.return_type != Type::Void {
+                    self.report_missing_return(prototype, function.body.span.clone());
+                }
+            }
+        }
+
+        self.return_types.pop();
+        self.scopes.pop();
+    }
+
+    /// Report a function with a return type whose body can reach its closing brace without returning a value.
+    fn report_missing_return(&mut self, prototype: &Prototype, body_span: Range<usize>) {
+        let origin = prototype.return_type_span.clone().map(|span| Origin {
+            file: self.file.clone(),
+            span,
+            label: format!("expected `{}` because of this return type", prototype.return_type),
+        });
+
+        let error = make_diagnostic(
+            Severity::Error,
+            "function may not return a value",
+            codes::E0022,
+            &self.file,
+            body_span.end - 1..body_span.end,
+            "the function can reach its end here",
+            origin,
+        )
+        .set_help("add a `return` at the end of the body, or end it with a value without a `;`")
+        .build();
+
+        self.errors.push(error);
+    }
+
+    /// Declare a function in the current scope.
+    fn declare_prototype(&mut self, prototype: &Prototype) {
+        let signature = Signature {
+            file: self.file.clone(),
+            args: prototype.args.iter().map(|arg| arg.typee).collect(),
+            arg_spans: prototype.args.iter().map(|arg| arg.type_span.clone()).collect(),
+            return_type: prototype.return_type,
+        };
+
+        self.scopes.insert_function(prototype.name.clone(), signature);
+    }
+
+    /// Infer the type of an expression.
+    /// Returns `None` if the type can not be known, either because of an error that was already
+    /// reported (by this pass or by the name resolution) or because the expression has no type yet.
+    /// No more errors are reported about it.
+    pub fn infer(&mut self, exprs: &ExprArena, id: ExprId) -> Option<Type> {
+        let expression = &exprs[id];
+
+        match expression.kind {
+            ExpressionKind::Literal(Literal::Null) => {
+                self.report_untyped_null(expression.span.clone());
+
+                None
+            }
+            ExpressionKind::Literal(ref literal) => Self::infer_literal(literal),
+            ExpressionKind::VarRef(ref name) => self.variable_type(name),
+            ExpressionKind::VarAssign(ref name, value) => {
+                let kind = self.variable_type(name);
+
+                match kind {
+                    Some(kind) => self.expect_type(exprs, value, kind, None),
+                    None => {
+                        self.infer(exprs, value);
+                    }
+                }
+
+                kind
+            }
+            ExpressionKind::FunctionCall(ref name, ref args) => self.infer_call(exprs, expression, name, args),
+            ExpressionKind::BinaryOp(lhs, ref op, rhs) => self.infer_binary(exprs, expression, lhs, op, rhs),
+            ExpressionKind::Unary(ref op, rhs) => self.infer_unary(exprs, expression, op, rhs),
+            ExpressionKind::Paren(inner) => self.infer(exprs, inner),
+            ExpressionKind::If(condition, then, otherwise) => self.infer_if(exprs, condition, then, otherwise),
+            ExpressionKind::Block(ref block, value) => {
+                self.scopes.push();
+                self.check_block(exprs, block, Some(value));
+
+                let kind = self.infer(exprs, value);
+
+                self.scopes.pop();
+
+                kind
+            }
+            ExpressionKind::Index(base, index) => {
+                self.expect_type(exprs, base, Type::String, None);
+                self.expect_type(exprs, index, Type::Number, None);
+
+                Some(Type::Char)
+            }
+        }
+    }
+
+    /// Infer the type of a function call and check the types of its arguments.
+    fn infer_call(&mut self, exprs: &ExprArena, expression: &Expression, name: &str, args: &[ExprId]) -> Option<Type> {
+        let signature = match self.scopes.get_function(name) {
+            Some(signature) => signature.clone(),
+            None => {
+                if let Some(builtin) = Builtin::from_name(name) {
+                    return self.infer_builtin_call(exprs, expression, builtin, args);
+                }
+
+                // The name resolution reported that the function is not in scope.
+                for &arg in args {
+                    self.infer(exprs, arg);
+                }
+
+                return None;
+            }
+        };
+
+        if args.len() != signature.args.len() {
+            self.report_arity(expression, signature.args.len(), args.len());
+        }
+
+        for (index, &arg) in args.iter().enumerate() {
+            match signature.args.get(index) {
+                Some(&kind) => {
+                    let origin = Origin {
+                        file: signature.file.clone(),
+                        span: signature.arg_spans[index].clone(),
+                        label: "parameter declared here".into(),
+                    };
+
+                    self.expect_type(exprs, arg, kind, Some(origin));
+                }
+                None => {
+                    self.infer(exprs, arg);
+                }
+            }
+        }
+
+        Some(signature.return_type)
+    }
+
+    /// Infer the type of a call to a builtin and check its arguments.
+    /// `print` and `println` take any value that can be printed, so they have no signature of their own.
+    fn infer_builtin_call(&mut self, exprs: &ExprArena, expression: &Expression, builtin: Builtin, args: &[ExprId]) -> Option<Type> {
+        match builtin {
+            Builtin::Print | Builtin::Println => {
+                if args.len() != 1 {
+                    self.report_arity(expression, 1, args.len());
+                }
+
+                for &arg in args {
+                    self.check_printable(exprs, builtin, arg);
+                }
+
+                Some(Type::Void)
+            }
+            Builtin::Input => {
+                if !args.is_empty() {
+                    self.report_arity(expression, 0, args.len());
+                }
+
+                for &arg in args {
+                    self.infer(exprs, arg);
+                }
+
+                Some(Type::String)
+            }
+            Builtin::Assert => {
+                if args.len() != 1 {
+                    self.report_arity(expression, 1, args.len());
+                }
+
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::Bool, None);
+                }
+
+                Some(Type::Void)
+            }
+            Builtin::ReadFile | Builtin::Env => {
+                if args.len() != 1 {
+                    self.report_arity(expression, 1, args.len());
+                }
+
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::String, None);
+                }
+
+                Some(Type::String)
+            }
+            Builtin::WriteFile | Builtin::AppendFile | Builtin::SetEnv => {
+                if args.len() != 2 {
+                    self.report_arity(expression, 2, args.len());
+                }
+
+                // Both the path and the contents, or the name and the value, are strings.
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::String, None);
+                }
+
+                Some(if builtin == Builtin::SetEnv { Type::Void } else { Type::Bool })
+            }
+            Builtin::Sqrt | Builtin::Pow | Builtin::Floor | Builtin::Ceil | Builtin::Sin | Builtin::Cos => {
+                let arity = if builtin == Builtin::Pow { 2 } else { 1 };
+
+                if args.len() != arity {
+                    self.report_arity(expression, arity, args.len());
+                }
+
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::Float, None);
+                }
+
+                Some(Type::Float)
+            }
+            Builtin::Abs | Builtin::Min | Builtin::Max => {
+                let arity = if builtin == Builtin::Abs { 1 } else { 2 };
+
+                if args.len() != arity {
+                    self.report_arity(expression, arity, args.len());
+                }
+
+                self.infer_numeric_args(exprs, args)
+            }
+            Builtin::Format => {
+                let (&format, values) = match args.split_first() {
+                    Some(split) => split,
+                    None => {
+                        self.report_arity(expression, 1, 0);
+
+                        return Some(Type::String);
+                    }
+                };
+
+                self.check_format_string(exprs, format, values.len());
+
+                // The values are formatted like `print` prints them.
+                for &value in values {
+                    self.check_printable(exprs, builtin, value);
+                }
+
+                Some(Type::String)
+            }
+            Builtin::Random => {
+                if !args.is_empty() {
+                    self.report_arity(expression, 0, args.len());
+                }
+
+                for &arg in args {
+                    self.infer(exprs, arg);
+                }
+
+                Some(Type::Float)
+            }
+            Builtin::RandomRange | Builtin::Seed => {
+                let arity = if builtin == Builtin::RandomRange { 2 } else { 1 };
+
+                if args.len() != arity {
+                    self.report_arity(expression, arity, args.len());
+                }
+
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::Number, None);
+                }
+
+                Some(if builtin == Builtin::Seed { Type::Void } else { Type::Number })
+            }
+            Builtin::NowMillis => {
+                if !args.is_empty() {
+                    self.report_arity(expression, 0, args.len());
+                }
+
+                for &arg in args {
+                    self.infer(exprs, arg);
+                }
+
+                Some(Type::Number)
+            }
+            Builtin::Sleep => {
+                if args.len() != 1 {
+                    self.report_arity(expression, 1, args.len());
+                }
+
+                for &arg in args {
+                    self.expect_type(exprs, arg, Type::Number, None);
+                }
+
+                Some(Type::Void)
+            }
+        }
+    }
+
+    /// Check that the argument of `print`, `println` or `format` has a value that can be printed.
+    fn check_printable(&mut self, exprs: &ExprArena, builtin: Builtin, arg: ExprId) {
+        if let Some(Type::Void) = self.infer(exprs, arg) {
+            let (verb, participle) = if builtin == Builtin::Format { ("format", "formatted") } else { ("print", "printed") };
+
+            let error = self
+                .make_error(
+                    format!("`{}` can not {} a `void` value", builtin.name(), verb),
+                    codes::E0017,
+                    exprs[arg].span.clone(),
+                    "this has no value",
+                )
+                .set_help(format!("only `number`, `float`, `string`, `bool` and `char` values can be {}", participle))
+                .build();
+
+            self.errors.push(error);
+        }
+    }
+
+    /// Check the format string of `format`, a string literal with a `{}` placeholder for every one of the values.
+    /// The placeholders are replaced at compile time, so the format string can not be computed.
+    fn check_format_string(&mut self, exprs: &ExprArena, format: ExprId, values: usize) {
+        self.expect_type(exprs, format, Type::String, None);
+
+        let span = exprs[format].span.clone();
+
+        let text = match exprs[format].kind {
+            ExpressionKind::Literal(Literal::String(ref text)) => text,
+            _ => {
+                let error = self
+                    .make_error("format argument must be a string literal", codes::E0020, span, "not a string literal")
+                    .set_help("the placeholders are checked at compile time, use `format(\"{}\", value)` to format a string value")
+                    .build();
+
+                self.errors.push(error);
+
+                return;
+            }
+        };
+
+        let placeholders = match parse_format_string(text) {
+            Ok(pieces) => placeholder_count(&pieces),
+            Err(brace) => {
+                let error = self
+                    .make_error(format!("invalid format string: unmatched `{}`", brace), codes::E0020, span, "in this format string")
+                    .set_help("a placeholder is `{}`, and `{{` and `}}` are the escapes of the braces")
+                    .build();
+
+                self.errors.push(error);
+
+                return;
+            }
+        };
+
+        if placeholders != values {
+            let plural = |count: usize| if count == 1 { "placeholder" } else { "placeholders" };
+
+            let error = self
+                .make_error(
+                    format!(
+                        "the format string has {} {} but {} {} supplied",
+                        placeholders,
+                        plural(placeholders),
+                        values,
+                        if values == 1 { "value was" } else { "values were" }
+                    ),
+                    codes::E0020,
+                    span,
+                    format!("{} {}", placeholders, plural(placeholders)),
+                )
+                .set_help("every `{}` placeholder is replaced by the next value")
+                .build();
+
+            self.errors.push(error);
+        }
+    }
+
+    /// Infer the type of the arguments of `abs`, `min` or `max`, which are all numbers or all floats.
+    /// The type of the first argument is the one of the others and of the result.
+    fn infer_numeric_args(&mut self, exprs: &ExprArena, args: &[ExprId]) -> Option<Type> {
+        let (&first, rest) = args.split_first()?;
+
+        let kind = match self.infer(exprs, first) {
+            Some(kind @ Type::Number) | Some(kind @ Type::Float) => Some(kind),
+            Some(found) => {
+                let error = make_diagnostic(
+                    Severity::Error,
+                    "mismatched types",
+                    codes::E0009,
+                    &self.file,
+                    exprs[first].span.clone(),
+                    format!("expected `number` or `float`, found `{}`", found),
+                    None,
+                )
+                .build();
+
+                self.errors.push(error);
+
+                None
+            }
+            None => None,
+        };
+
+        for &arg in rest {
+            match kind {
+                Some(kind) => {
+                    let origin = Origin {
+                        file: self.file.clone(),
+                        span: exprs[first].span.clone(),
+                        label: "expected because of the type of this".into(),
+                    };
+
+                    self.expect_type(exprs, arg, kind, Some(origin));
+                }
+                None => {
+                    self.infer(exprs, arg);
+                }
+            }
+        }
+
+        kind
+    }
+
+    /// Report a call with the wrong number of arguments.
+    fn report_arity(&mut self, expression: &Expression, expected: usize, found: usize) {
+        let plural = |count: usize| if count == 1 { "argument" } else { "arguments" };
+
+        let error = self
+            .make_error(
+                format!(
+                    "this function takes {} {} but {} {} supplied",
+                    expected,
+                    plural(expected),
+                    found,
+                    if found == 1 { "argument was" } else { "arguments were" }
+                ),
+                codes::E0012,
+                expression.span.clone(),
+                format!("expected {} {}", expected, plural(expected)),
+            )
+            .build();
+
+        self.errors.push(error);
+    }
+
+    /// Find the type of a variable.
+    /// The name resolution reported the variables that are not in scope.
+    fn variable_type(&self, name: &str) -> Option<Type> {
+        self.scopes.get_variable(name).map(|variable| variable.kind)
+    }
+
+    /// Check that the expression has the expected type.
+    /// The origin is pointed at as the reason of the expected type.
+    fn expect_type(&mut self, exprs: &ExprArena, id: ExprId, expected: Type, origin: Option<Origin>) {
+        let expression = &exprs[id];
+
+        // The value of a block is checked against the expected type, so that it can be `null`.
+        if let ExpressionKind::Block(ref block, value) = expression.kind {
+            self.scopes.push();
+            self.check_block(exprs, block, Some(value));
+            self.expect_type(exprs, value, expected, origin);
+            self.scopes.pop();
+
+            return;
+        }
+
+        // `null` has no type of its own, it is a value of every nullable type.
+        if Self::is_null(exprs, id) {
+            if !expected.is_nullable() {
+                let error = self
+                    .make_mismatch(expression.span.clone(), expected, "null", origin)
+                    .set_note("only `string` values can be `null`")
+                    .build();
+
+                self.errors.push(error);
+            }
+
+            return;
+        }
+
+        let found = match self.infer(exprs, id) {
+            Some(found) => found,
+            None => return,
+        };
+
+        if found != expected {
+            self.report_mismatch(expression.span.clone(), expected, found, origin);
+        }
+    }
+
+    /// Report that the code at the span has the type `found` instead of `expected`.
+    fn report_mismatch(&mut self, span: Range<usize>, expected: Type, found: Type, origin: Option<Origin>) {
+        let error = self.make_mismatch(span, expected, found, origin).build();
+
+        self.errors.push(error);
+    }
+
+    /// Make the error reported by `report_mismatch`, `found` is either a type or `null`.
+    fn make_mismatch(&self, span: Range<usize>, expected: Type, found: impl Display, origin: Option<Origin>) -> DiagnosticBuilder {
+        make_diagnostic(
+            Severity::Error,
+            "mismatched types",
+            codes::E0009,
+            &self.file,
+            span,
+            format!("expected `{}`, found `{}`", expected, found),
+            origin,
+        )
+    }
+
+    /// Report a `null` that is not used where a nullable type is expected, so its type is unknown.
+    fn report_untyped_null(&mut self, span: Range<usize>) {
+        let error = self
+            .make_error("cannot infer the type of `null`", codes::E0015, span, "the type of this `null` is unknown")
+            .set_help("`null` can only be used where a `string` is expected, like in `var name: string = null;`")
+            .build();
+
+        self.errors.push(error);
+    }
+
+    /// Returns true if the expression is the literal `null`, possibly in parentheses.
+    fn is_null(exprs: &ExprArena, id: ExprId) -> bool {
+        match exprs[id].kind {
+            ExpressionKind::Literal(Literal::Null) => true,
+            ExpressionKind::Paren(inner) => Self::is_null(exprs, inner),
+            _ => false,
+        }
+    }
+
+    /// Returns the value at the end of a block expression, or the expression itself if it is not a block.
+    fn block_value(exprs: &ExprArena, id: ExprId) -> ExprId {
+        match exprs[id].kind {
+            ExpressionKind::Block(_, value) => Self::block_value(exprs, value),
+            _ => id,
+        }
+    }
+
+    /// Infer the type of a literal.
+    fn infer_literal(literal: &Literal) -> Option<Type> {
+        match literal {
+            Literal::Bool(_) => Some(Type::Bool),
+            Literal::Number(_) => Some(Type::Number),
+            Literal::Float(_) => Some(Type::Float),
+            Literal::String(_) => Some(Type::String),
+            Literal::Char(_) => Some(Type::Char),
+            // `null` is handled by `infer` and `expect_type`.
+            Literal::Null => None,
+        }
+    }
+
+    /// Infer the type of a binary expression and check the types of its operands.
+    fn infer_binary(&mut self, exprs: &ExprArena, expression: &Expression, lhs: ExprId, op: &BinaryOp, rhs: ExprId) -> Option<Type> {
+        if let BinaryOp::Coalesce = op {
+            return self.infer_coalesce(exprs, lhs, rhs);
+        }
+
+        let divisor = rhs;
+
+        let lhs = self.infer(exprs, lhs)?;
+        let rhs = self.infer(exprs, rhs)?;
+
+        let is_numeric = lhs == rhs && matches!(lhs, Type::Number | Type::Float);
+
+        let kind = match op {
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Mul | BinaryOp::Div if is_numeric => Some(lhs),
+            BinaryOp::Lesser | BinaryOp::Greater if is_numeric => Some(Type::Bool),
+            BinaryOp::EqEq if lhs == rhs && lhs != Type::Void => Some(Type::Bool),
+            BinaryOp::And | BinaryOp::Or if lhs == Type::Bool && rhs == Type::Bool => Some(Type::Bool),
+            _ => None,
+        };
+
+        if kind.is_none() {
+            let error = self
+                .make_error(
+                    format!("cannot apply binary operator `{}` to types `{}` and `{}`", op, lhs, rhs),
+                    codes::E0008,
+                    expression.span.clone(),
+                    format!("`{}` and `{}`", lhs, rhs),
+                )
+                .build();
+
+            self.errors.push(error);
+        }
+
+        // The division by a zero variable traps at runtime, but dividing by a literal zero is always a mistake.
+        if matches!(op, BinaryOp::Div) && kind == Some(Type::Number) && Self::is_zero(exprs, divisor) {
+            let error = self
+                .make_error("attempt to divide by zero", codes::E0014, exprs[divisor].span.clone(), "dividing by zero")
+                .set_note("an integer division by zero aborts the program")
+                .build();
+
+            self.errors.push(error);
+        }
+
+        kind
+    }
+
+    /// Infer the type of a `??` expression, the type of its left operand, which must be nullable.
+    /// The right operand must have the same type, it is the value of the expression when the left operand is `null`.
+    fn infer_coalesce(&mut self, exprs: &ExprArena, lhs: ExprId, rhs: ExprId) -> Option<Type> {
+        // `null ?? value` is always the value, so the type of the `null` comes from it.
+        if Self::is_null(exprs, lhs) {
+            let kind = self.infer(exprs, rhs)?;
+
+            self.expect_type(exprs, lhs, kind, None);
+
+            return Some(kind);
+        }
+
+        let kind = self.infer(exprs, lhs)?;
+
+        if !kind.is_nullable() {
+            let error = self
+                .make_error(
+                    format!("cannot apply binary operator `??` to type `{}`", kind),
+                    codes::E0008,
+                    exprs[lhs].span.clone(),
+                    format!("`{}` values are never `null`", kind),
+                )
+                .set_note("only `string` values can be `null`")
+                .build();
+
+            self.errors.push(error);
+        }
+
+        let origin = Origin {
+            file: self.file.clone(),
+            span: exprs[lhs].span.clone(),
+            label: "expected because of the type of this".into(),
+        };
+
+        self.expect_type(exprs, rhs, kind, Some(origin));
+
+        Some(kind)
+    }
+
+    /// Infer the type of an if expression, the type of its branches. The condition must be a `bool`, and the branches
+    /// must have the same type.
+    fn infer_if(&mut self, exprs: &ExprArena, condition: ExprId, then: ExprId, otherwise: ExprId) -> Option<Type> {
+        self.expect_type(exprs, condition, Type::Bool, None);
+
+        // A `null` branch has the type of the other branch.
+        if Self::is_null(exprs, Self::block_value(exprs, then)) {
+            let kind = self.infer(exprs, otherwise)?;
+
+            self.expect_type(exprs, then, kind, None);
+
+            return Some(kind);
+        }
+
+        let kind = match self.infer(exprs, then) {
+            Some(kind) => kind,
+            None => {
+                self.infer(exprs, otherwise);
+
+                return None;
+            }
+        };
+
+        let origin = Origin {
+            file: self.file.clone(),
+            span: exprs[then].span.clone(),
+            label: "expected because of the type of this branch".into(),
+        };
+
+        self.expect_type(exprs, otherwise, kind, Some(origin));
+
+        Some(kind)
+    }
+
+    /// Returns true if the expression is the literal `0`, possibly in parentheses.
+    fn is_zero(exprs: &ExprArena, id: ExprId) -> bool {
+        match exprs[id].kind {
+            ExpressionKind::Literal(Literal::Number(number)) => number == 0,
+            ExpressionKind::Paren(inner) => Self::is_zero(exprs, inner),
+            _ => false,
+        }
+    }
+
+    /// Infer the type of a unary expression and check the type of its operand.
+    fn infer_unary(&mut self, exprs: &ExprArena, expression: &Expression, op: &UnaryOp, rhs: ExprId) -> Option<Type> {
+        let rhs = self.infer(exprs, rhs)?;
+
+        let kind = match op {
+            UnaryOp::Neg | UnaryOp::Plus if matches!(rhs, Type::Number | Type::Float) => Some(rhs),
+            UnaryOp::Not if rhs == Type::Bool => Some(Type::Bool),
+            _ => None,
+        };
+
+        if kind.is_none() {
+            let error = self
+                .make_error(
+                    format!("cannot apply unary operator `{}` to type `{}`", op, rhs),
+                    codes::E0008,
+                    expression.span.clone(),
+                    format!("`{}`", rhs),
+                )
+                .build();
+
+            self.errors.push(error);
+        }
+
+        kind
+    }
+
+    /// Make an error with a message and code, annotating the span with a label.
+    fn make_error(&self, message: impl Into<String>, code: impl Into<String>, span: Range<usize>, label: impl Into<String>) -> DiagnosticBuilder {
+        make_diagnostic(Severity::Error, message, code, &self.file, span, label, None)
+    }
+}
@@ -0,0 +1,16 @@
+//! The `fluid_typeck` crate contains the semantic analysis pass of Fluid.
+//! It walks the AST produced by `fluid_parser`, resolves the names and checks the types of every expression,
+//! so that the code generator only ever sees a valid program.
+
+#![deny(unsafe_code, trivial_numeric_casts, unused_extern_crates, unstable_features)]
+
+mod checker;
+mod report;
+mod resolve;
+mod scope;
+
+#[cfg(test)]
+mod tests;
+
+pub use checker::*;
+pub use resolve::*;
@@ -0,0 +1,94 @@
+//! This file contains the helpers shared by the passes to build their diagnostics.
+
+use std::{ops::Range, sync::Arc};
+
+use fluid_error::{AnnotationType, DiagnosticBuilder, Severity, Slice, SourceAnnotation, SourceFile};
+
+/// The code that explains a diagnostic, like the type annotation of a variable or the previous definition of a name.
+pub(crate) struct Origin {
+    /// The file of the code.
+    pub(crate) file: Arc<SourceFile>,
+    /// The byte range of the code.
+    pub(crate) span: Range<usize>,
+    /// The label shown on the code.
+    pub(crate) label: String,
+}
+
+/// Make a diagnostic annotating the span in the file with a label, and the origin if there is one.
+/// The origin is shown in the same snippet if it is in the same file, and in its own snippet otherwise.
+pub(crate) fn make_diagnostic(
+    severity: Severity,
+    message: impl Into<String>,
+    code: impl Into<String>,
+    file: &Arc<SourceFile>,
+    span: Range<usize>,
+    label: impl Into<String>,
+    origin: Option<Origin>,
+) -> DiagnosticBuilder {
+    let primary = SourceAnnotation::new(span, severity.annotation_type()).set_label(label);
+
+    let mut slice = Slice::new(file.clone()).push_annotation(primary);
+    let mut origin_slice = None;
+
+    if let Some(origin) = origin {
+        let secondary = SourceAnnotation::new(origin.span, AnnotationType::Info).set_label(origin.label);
+
+        if Arc::ptr_eq(&origin.file, file) {
+            slice = slice.push_annotation(secondary);
+        } else {
+            origin_slice = Some(Slice::new(origin.file).push_annotation(secondary));
+        }
+    }
+
+    let mut diagnostic = DiagnosticBuilder::new(severity, message).set_code(code).push_slice(slice);
+
+    if let Some(origin_slice) = origin_slice {
+        diagnostic = diagnostic.push_slice(origin_slice);
+    }
+
+    diagnostic
+}
+
+/// Find the candidate that is the closest to a misspelled name, to suggest it in a diagnostic.
+/// Only the candidates within a third of the length of the name are close enough, and the ties are broken
+/// alphabetically so that the suggestion doesn't depend on the order of the candidates.
+pub(crate) fn find_similar_name<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+
+    candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The edit distance between two strings, which is the number of characters to insert, remove or replace, or
+/// of adjacent characters to swap, to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    // The distances between the prefixes of the strings, `distances[i][j]` is for the first `i` characters of `a`
+    // and the first `j` characters of `b`.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            distances[i][j] = if i == 0 || j == 0 {
+                i + j
+            } else {
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                let mut distance = (distances[i - 1][j - 1] + cost).min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    distance = distance.min(distances[i - 2][j - 2] + 1);
+                }
+
+                distance
+            };
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
@@ -0,0 +1,384 @@
+//! This file contains the name resolution pass, the `Resolver` interface.
+
+use std::{collections::HashMap, mem, ops::Range, sync::Arc};
+
+use fluid_error::{codes, Diagnostic, Severity, SourceFile};
+use fluid_lexer::Keyword;
+use fluid_parser::{Ast, Builtin, Declaration, DefKind, DefSite, ExprArena, ExprId, ExpressionKind, Prototype, Statement, StatementKind};
+
+use crate::report::*;
+
+/// A name that is in scope.
+#[derive(Debug, Clone)]
+struct Definition {
+    /// The file the name is declared in.
+    file: Arc<SourceFile>,
+    /// The declaration of the name.
+    site: DefSite,
+}
+
+/// A single scope. Values and functions have separate namespaces.
+#[derive(Debug, Default)]
+struct Scope {
+    /// The variables and parameters declared in the scope.
+    values: HashMap<String, Definition>,
+    /// The functions declared in the scope.
+    functions: HashMap<String, Definition>,
+}
+
+/// Resolves every variable reference, assignment and function call to its declaration, and records
+/// the declaration in the `def_site` of the expression.
+///
+/// Declaring a name twice in the same scope is a redefinition, which is an error. Declaring it again in
+/// an inner scope shadows the outer declaration, which is allowed, but shadowing a parameter is warned about.
+///
+/// Like the type checker, the scopes are kept between the calls to `run`.
+#[derive(Debug)]
+pub struct Resolver {
+    /// The file we are resolving.
+    file: Arc<SourceFile>,
+    /// All of the scopes, the first one is the global scope and is never popped.
+    scopes: Vec<Scope>,
+    /// The errors found while resolving.
+    errors: Vec<Diagnostic>,
+    /// The warnings found while resolving.
+    warnings: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    /// Create a new instance of the resolver.
+    pub fn new(file: Arc<SourceFile>) -> Self {
+        let scopes = vec![Scope::default()];
+        let errors = vec![];
+        let warnings = vec![];
+
+        Self { file, scopes, errors, warnings }
+    }
+
+    /// Set the file that the following spans point into. Used by the REPL, where every input is a new file.
+    pub fn set_file(&mut self, file: Arc<SourceFile>) {
+        self.file = file;
+    }
+
+    /// Take the warnings found while resolving.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Forget all of the declarations.
+    pub fn reset(&mut self) {
+        self.scopes = vec![Scope::default()];
+    }
+
+    /// Resolve the names of the program.
+    pub fn run(&mut self, ast: &mut Ast) -> Result<(), Vec<Diagnostic>> {
+        for statement in &ast.statements {
+            self.resolve_statement(&mut ast.exprs, statement);
+        }
+
+        let errors = mem::take(&mut self.errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Resolve the names of a single expression, against the declarations of the previous calls to `run`.
+    pub fn run_expression(&mut self, exprs: &mut ExprArena, expression: ExprId) -> Result<(), Vec<Diagnostic>> {
+        self.resolve_expression(exprs, expression);
+
+        let errors = mem::take(&mut self.errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Resolve the names in a statement. Only its expressions are changed, they are in the arena.
+    fn resolve_statement(&mut self, exprs: &mut ExprArena, statement: &Statement) {
+        match statement.kind {
+            StatementKind::Expression(expression) => self.resolve_expression(exprs, expression),
+            StatementKind::Return(expression) => {
+                if let Some(expression) = expression {
+                    self.resolve_expression(exprs, expression);
+                }
+            }
+            StatementKind::If(condition, ref body, ref elif) => {
+                self.resolve_expression(exprs, condition);
+                self.resolve_statement(exprs, body);
+
+                if let Some(elif) = elif {
+                    self.resolve_statement(exprs, elif);
+                }
+            }
+            StatementKind::For(ref for_loop) => {
+                self.resolve_expression(exprs, for_loop.iterable);
+
+                // The variable of the character is in the scope of the body.
+                self.scopes.push(Scope::default());
+
+                let site = DefSite {
+                    kind: DefKind::Variable,
+                    span: for_loop.name_span.clone(),
+                };
+
+                self.declare_value(&for_loop.name, site);
+                self.resolve_statement(exprs, &for_loop.body);
+
+                self.scopes.pop();
+            }
+            StatementKind::Block(ref block, value) => self.resolve_block(exprs, block, value),
+            StatementKind::Declaration(ref declaration) => self.resolve_declaration(exprs, declaration),
+        }
+    }
+
+    /// Resolve the names in the statements and the value of a block, in a new scope.
+    fn resolve_block(&mut self, exprs: &mut ExprArena, block: &[Statement], value: Option<ExprId>) {
+        self.scopes.push(Scope::default());
+
+        for statement in block {
+            self.resolve_statement(exprs, statement);
+        }
+
+        if let Some(value) = value {
+            self.resolve_expression(exprs, value);
+        }
+
+        self.scopes.pop();
+    }
+
+    /// Resolve the names in a declaration and declare its names.
+    /// A function is declared before its body is resolved, so that it can call itself.
+    fn resolve_declaration(&mut self, exprs: &mut ExprArena, declaration: &Declaration) {
+        match declaration {
+            Declaration::Function(function) => {
+                self.declare_function(&function.prototype);
+
+                self.scopes.push(Scope::default());
+
+                for arg in function.prototype.args.iter() {
+                    let site = DefSite {
+                        kind: DefKind::Parameter,
+                        span: arg.name_span.clone(),
+                    };
+
+                    self.declare_value(&arg.name, site);
+                }
+
+                self.resolve_statement(exprs, &function.body);
+
+                self.scopes.pop();
+            }
+            Declaration::Extern(externs) => {
+                for prototype in externs.iter() {
+                    self.declare_function(prototype);
+                }
+            }
+            Declaration::VarDef(var_def) => {
+                self.resolve_expression(exprs, var_def.value);
+
+                let site = DefSite {
+                    kind: DefKind::Variable,
+                    span: var_def.name_span.clone(),
+                };
+
+                self.declare_value(&var_def.name, site);
+            }
+        }
+    }
+
+    /// Resolve the names in an expression.
+    /// The name of the expression is resolved before the names in its operands.
+    fn resolve_expression(&mut self, exprs: &mut ExprArena, id: ExprId) {
+        let expression = &exprs[id];
+        let span = expression.span.clone();
+
+        let def_site = match expression.kind {
+            ExpressionKind::VarRef(ref name) => self.resolve_value(name, span),
+            ExpressionKind::VarAssign(ref name, _) => self.resolve_value(name, span.start..span.start + name.len()),
+            ExpressionKind::FunctionCall(ref name, _) => self.resolve_function(name, span.start..span.start + name.len()),
+            _ => None,
+        };
+
+        exprs[id].def_site = def_site;
+
+        match exprs[id].kind {
+            ExpressionKind::FunctionCall(_, ref args) => {
+                for arg in args.clone() {
+                    self.resolve_expression(exprs, arg);
+                }
+            }
+            ExpressionKind::BinaryOp(lhs, _, rhs) | ExpressionKind::Index(lhs, rhs) => {
+                self.resolve_expression(exprs, lhs);
+                self.resolve_expression(exprs, rhs);
+            }
+            ExpressionKind::VarAssign(_, value) => self.resolve_expression(exprs, value),
+            ExpressionKind::Unary(_, rhs) => self.resolve_expression(exprs, rhs),
+            ExpressionKind::Paren(inner) => self.resolve_expression(exprs, inner),
+            ExpressionKind::If(condition, then, otherwise) => {
+                self.resolve_expression(exprs, condition);
+                self.resolve_expression(exprs, then);
+                self.resolve_expression(exprs, otherwise);
+            }
+            ExpressionKind::Block(ref block, value) => {
+                // The statements are in the expression, which is borrowed from the arena that they change.
+                let block = block.clone();
+
+                self.resolve_block(exprs, &block, Some(value));
+            }
+            ExpressionKind::VarRef(_) | ExpressionKind::Literal(_) => (),
+        }
+    }
+
+    /// Find the declaration of a variable, reporting an error at the span if it is not in scope.
+    fn resolve_value(&mut self, name: &str, span: Range<usize>) -> Option<DefSite> {
+        let site = self.scopes.iter().rev().find_map(|scope| scope.values.get(name)).map(|definition| definition.site.clone());
+
+        if site.is_none() {
+            let similar = self.find_similar(name, false);
+
+            self.report_not_found(format!("cannot find value `{}` in this scope", name), codes::E0010, span, similar);
+        }
+
+        site
+    }
+
+    /// Find the declaration of a function, reporting an error at the span if it is not in scope.
+    /// The builtins have no declaration, but they are in scope.
+    fn resolve_function(&mut self, name: &str, span: Range<usize>) -> Option<DefSite> {
+        let site = self.scopes.iter().rev().find_map(|scope| scope.functions.get(name)).map(|definition| definition.site.clone());
+
+        if site.is_none() && Builtin::from_name(name).is_none() {
+            let similar = self.find_similar(name, true);
+
+            self.report_not_found(format!("cannot find function `{}` in this scope", name), codes::E0011, span, similar);
+        }
+
+        site
+    }
+
+    /// Find the name in scope that is the closest to a name that is not, among the values or the functions
+    /// and the builtins. The keywords are candidates too, since a misspelled keyword is read as a name.
+    fn find_similar(&self, name: &str, function: bool) -> Option<String> {
+        let keywords = Keyword::ALL.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let keywords = keywords.iter().map(String::as_str);
+
+        let similar = if function {
+            let functions = self.scopes.iter().flat_map(|scope| scope.functions.keys()).map(String::as_str);
+            let builtins = Builtin::ALL.iter().map(|builtin| builtin.name());
+
+            find_similar_name(name, functions.chain(builtins).chain(keywords))
+        } else {
+            let values = self.scopes.iter().flat_map(|scope| scope.values.keys()).map(String::as_str);
+
+            find_similar_name(name, values.chain(keywords))
+        };
+
+        similar.map(str::to_owned)
+    }
+
+    /// Declare a variable or a parameter in the innermost scope.
+    fn declare_value(&mut self, name: &str, site: DefSite) {
+        if let Some(previous) = self.scopes.last().unwrap().values.get(name) {
+            let previous = previous.clone();
+            self.report_redefinition(name, site.span, previous);
+
+            return;
+        }
+
+        let shadowed = self.scopes.iter().rev().find_map(|scope| scope.values.get(name));
+
+        if let Some(shadowed) = shadowed.filter(|shadowed| shadowed.site.kind == DefKind::Parameter) {
+            let shadowed = shadowed.clone();
+
+            self.report_shadowed_parameter(name, site.span.clone(), shadowed);
+        }
+
+        let definition = Definition { file: self.file.clone(), site };
+
+        self.scopes.last_mut().unwrap().values.insert(name.to_owned(), definition);
+    }
+
+    /// Declare a function in the innermost scope.
+    fn declare_function(&mut self, prototype: &Prototype) {
+        if let Some(previous) = self.scopes.last().unwrap().functions.get(&prototype.name) {
+            let previous = previous.clone();
+            self.report_redefinition(&prototype.name, prototype.name_span.clone(), previous);
+
+            return;
+        }
+
+        let definition = Definition {
+            file: self.file.clone(),
+            site: DefSite {
+                kind: DefKind::Function,
+                span: prototype.name_span.clone(),
+            },
+        };
+
+        self.scopes.last_mut().unwrap().functions.insert(prototype.name.clone(), definition);
+    }
+
+    /// Report that a name is not in scope, suggesting the similar name if there is one.
+    fn report_not_found(&mut self, message: String, code: &str, span: Range<usize>, similar: Option<String>) {
+        let mut error = make_diagnostic(Severity::Error, message, code, &self.file, span, "not found in this scope", None);
+
+        if let Some(similar) = similar {
+            error = error.set_help(format!("did you mean `{}`?", similar));
+        }
+
+        let error = error.build();
+
+        self.errors.push(error);
+    }
+
+    /// Report that the name at the span was already declared in the same scope.
+    fn report_redefinition(&mut self, name: &str, span: Range<usize>, previous: Definition) {
+        let origin = Origin {
+            file: previous.file,
+            span: previous.site.span,
+            label: format!("previous definition of the {} `{}` here", previous.site.kind, name),
+        };
+
+        let error = make_diagnostic(
+            Severity::Error,
+            format!("the name `{}` is defined multiple times", name),
+            codes::E0013,
+            &self.file,
+            span,
+            format!("`{}` redefined here", name),
+            Some(origin),
+        )
+        .set_note(format!("`{}` must be defined only once in this scope", name))
+        .build();
+
+        self.errors.push(error);
+    }
+
+    /// Report that the variable at the span shadows a parameter.
+    fn report_shadowed_parameter(&mut self, name: &str, span: Range<usize>, parameter: Definition) {
+        let origin = Origin {
+            file: parameter.file,
+            span: parameter.site.span,
+            label: format!("parameter `{}` declared here", name),
+        };
+
+        let warning = make_diagnostic(
+            Severity::Warning,
+            format!("`{}` shadows a parameter", name),
+            codes::W0003,
+            &self.file,
+            span,
+            format!("shadows the parameter `{}`", name),
+            Some(origin),
+        )
+        .set_help("rename the variable, or assign to the parameter if it is meant to be changed")
+        .build();
+
+        self.warnings.push(warning);
+    }
+}
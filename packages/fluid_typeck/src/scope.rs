@@ -0,0 +1,90 @@
+//! This file contains the scoped symbol tables used by the type checker.
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use fluid_error::SourceFile;
+use fluid_parser::Type;
+
+/// A variable that is in scope.
+#[derive(Debug, Clone)]
+pub(crate) struct Variable {
+    /// The type of the variable.
+    pub(crate) kind: Type,
+}
+
+/// The signature of a function that is in scope.
+#[derive(Debug, Clone)]
+pub(crate) struct Signature {
+    /// The file the function is declared in.
+    pub(crate) file: Arc<SourceFile>,
+    /// The types of the arguments.
+    pub(crate) args: Vec<Type>,
+    /// The byte ranges of the type annotations of the arguments.
+    pub(crate) arg_spans: Vec<Range<usize>>,
+    /// The return type.
+    pub(crate) return_type: Type,
+}
+
+/// A single scope, like a block or the arguments of a function.
+#[derive(Debug, Default)]
+struct Scope {
+    /// The variables declared in the scope.
+    variables: HashMap<String, Variable>,
+    /// The functions declared in the scope.
+    functions: HashMap<String, Signature>,
+}
+
+/// The stack of scopes. The first scope is the global scope and is never popped.
+#[derive(Debug)]
+pub(crate) struct Scopes {
+    /// All of the scopes, the innermost scope is the last one.
+    scopes: Vec<Scope>,
+}
+
+impl Scopes {
+    /// Create the scopes with only the global scope.
+    pub(crate) fn new() -> Self {
+        Self { scopes: vec![Scope::default()] }
+    }
+
+    /// Enter a new scope.
+    pub(crate) fn push(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    /// Leave the innermost scope.
+    pub(crate) fn pop(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Insert a variable in the innermost scope.
+    /// If the innermost scope already has a variable with the same name, the first one is kept,
+    /// the name resolution reports the redefinition.
+    pub(crate) fn insert_variable(&mut self, name: String, variable: Variable) {
+        self.innermost().variables.entry(name).or_insert(variable);
+    }
+
+    /// Insert a function in the innermost scope.
+    /// If the innermost scope already has a function with the same name, the first one is kept,
+    /// the name resolution reports the redefinition.
+    pub(crate) fn insert_function(&mut self, name: String, signature: Signature) {
+        self.innermost().functions.entry(name).or_insert(signature);
+    }
+
+    /// Find a variable, starting from the innermost scope.
+    pub(crate) fn get_variable(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.variables.get(name))
+    }
+
+    /// Find a function, starting from the innermost scope.
+    pub(crate) fn get_function(&self, name: &str) -> Option<&Signature> {
+        self.scopes.iter().rev().find_map(|scope| scope.functions.get(name))
+    }
+
+    /// Returns the innermost scope.
+    fn innermost(&mut self) -> &mut Scope {
+        self.scopes.last_mut().unwrap()
+    }
+}
@@ -0,0 +1,754 @@
+//! This file contains all of the unit tests for the type checker.
+
+use std::sync::Arc;
+
+use fluid_error::{Diagnostic, SourceFile};
+use fluid_lexer::Lexer;
+use fluid_parser::{Declaration, DefKind, DefSite, ExpressionKind, Parser, StatementKind, Type};
+
+use crate::TypeChecker;
+
+#[inline]
+fn check(source: &str) -> Result<(), Vec<Diagnostic>> {
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+
+    let mut parser = Parser::new(&tokens, lexer.file());
+    let mut ast = parser.run().unwrap();
+
+    TypeChecker::new(lexer.file()).run(&mut ast)
+}
+
+#[test]
+fn test_valid_program() {
+    let source = "
+        function add(a: number, b: number) -> number {
+            return a + b;
+        }
+
+        function main() {
+            var x: number = add(1, 2) * 3;
+
+            if (x > 2 && !(x == 4)) {
+                x = -x;
+            }
+        }
+    ";
+
+    assert!(check(source).is_ok());
+}
+
+#[test]
+fn test_invalid_operands() {
+    let source = "
+        function main() {
+            var x: number = 1 + \"2\";
+            var y: number = -true;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| error.code() == Some("E0008")));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 55..62)));
+}
+
+#[test]
+fn test_mismatched_types() {
+    let source = "
+        function square(x: float) -> float {
+            return x * x;
+        }
+
+        function main() -> number {
+            var x: number = \"hi\";
+            var y: float = square(1);
+
+            if (x) {
+                x = 1.5;
+            }
+
+            return 2.5;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 5);
+    assert!(errors.iter().all(|error| error.code() == Some("E0009")));
+}
+
+#[test]
+fn test_undefined_names() {
+    let source = "
+        function main() {
+            {
+                var x: number = 1;
+            }
+
+            x = y + 1;
+            undefined(x);
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0010", "E0010", "E0011", "E0010"]);
+}
+
+#[test]
+fn test_similar_names() {
+    let source = "
+        function double(value: number) -> number {
+            return valeu * 2;
+        }
+
+        function main() {
+            pirntln(dubble(1));
+            println(ture);
+            undefined(1);
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let rendered = errors.iter().map(Diagnostic::render_plain).collect::<Vec<_>>();
+
+    assert_eq!(rendered.len(), 5);
+    assert!(rendered[0].contains("did you mean `value`?"));
+    assert!(rendered[1].contains("did you mean `println`?"));
+    assert!(rendered[2].contains("did you mean `double`?"));
+    assert!(rendered[3].contains("did you mean `true`?"));
+    assert!(!rendered[4].contains("did you mean"));
+}
+
+#[test]
+fn test_call_arity() {
+    let source = "
+        function add(a: number, b: number) -> number {
+            return a + b;
+        }
+
+        function main() {
+            add(1);
+            add(1, 2, 3);
+            add(1, 2);
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| error.code() == Some("E0012")));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 131..137)));
+}
+
+#[test]
+fn test_return_types() {
+    let source = "
+        function one() -> number {
+            return;
+        }
+
+        function nothing() {
+            return 1;
+        }
+
+        function early() {
+            return;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| error.code() == Some("E0009")));
+}
+
+#[test]
+fn test_unreachable_code() {
+    let source = "
+        function answer(x: number) -> number {
+            return 42;
+
+            x = 2;
+            return x;
+        }
+    ";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+    let mut ast = Parser::new(&tokens, lexer.file()).run().unwrap();
+
+    let mut checker = TypeChecker::new(lexer.file());
+    checker.run(&mut ast).unwrap();
+
+    let warnings = checker.take_warnings();
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code(), Some("W0002"));
+    assert_eq!(warnings[0].primary_span(), Some(("<test>", 84..112)));
+}
+
+#[test]
+fn test_missing_return() {
+    let source = "
+        function sign(x: number) -> number {
+            if (x < 0) {
+                return -1;
+            }
+        }
+
+        function abs(x: number) -> number {
+            if (x < 0) {
+                return -x;
+            } else {
+                return x;
+            }
+        }
+
+        function nothing() {
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    // Only the function that can reach its closing brace without returning is an error.
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0022"));
+    assert_eq!(errors[0].message(), "function may not return a value");
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 120..121)));
+    assert!(errors[0].render_plain().contains("expected `number` because of this return type"));
+}
+
+#[test]
+fn test_implicit_return() {
+    let source = "
+        function add(a: number, b: number) -> number {
+            a + b
+        }
+
+        function half(x: float) -> number {
+            x / 2.0
+        }
+
+        function early() -> number {
+            return 1;
+            2
+        }
+    ";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+    let mut ast = Parser::new(&tokens, lexer.file()).run().unwrap();
+
+    let mut checker = TypeChecker::new(lexer.file());
+    let errors = checker.run(&mut ast).unwrap_err();
+
+    // The value is checked against the return type, like the value of a `return` statement.
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0009"));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 141..148)));
+
+    // The value after a `return` is never returned.
+    let warnings = checker.take_warnings();
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code(), Some("W0002"));
+    assert_eq!(warnings[0].primary_span(), Some(("<test>", 231..232)));
+}
+
+#[test]
+fn test_redefinitions() {
+    let source = "
+        function add(a: number, a: number) -> number {
+            var b: number = a;
+            var b: number = 1;
+
+            return b;
+        }
+
+        function add() {}
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 3);
+    assert!(errors.iter().all(|error| error.code() == Some("E0013")));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 33..34)));
+    assert_eq!(errors[2].primary_span(), Some(("<test>", 169..172)));
+}
+
+#[test]
+fn test_shadowing() {
+    let source = "
+        function area(width: number) -> number {
+            {
+                var width: number = 2;
+            }
+
+            var width: number = width * 2;
+
+            return width;
+        }
+    ";
+
+    let filename = "<test>";
+
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.run().unwrap();
+    let mut ast = Parser::new(&tokens, lexer.file()).run().unwrap();
+
+    let mut checker = TypeChecker::new(lexer.file());
+    checker.run(&mut ast).unwrap();
+
+    let warnings = checker.take_warnings();
+
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings.iter().all(|warning| warning.code() == Some("W0003")));
+
+    // The value of the variable refers to the parameter, and the return to the variable.
+    let body = match ast.statements[0].kind {
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::Function(ref function) => match function.body.kind {
+                StatementKind::Block(ref body, _) => body,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+
+    let value = match body[1].kind {
+        StatementKind::Declaration(ref declaration) => match **declaration {
+            Declaration::VarDef(ref var_def) => match ast.exprs[var_def.value].kind {
+                ExpressionKind::BinaryOp(lhs, _, _) => &ast.exprs[lhs],
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+
+    let returned = match body[2].kind {
+        StatementKind::Return(Some(returned)) => &ast.exprs[returned],
+        _ => unreachable!(),
+    };
+
+    assert_eq!(
+        value.def_site,
+        Some(DefSite {
+            kind: DefKind::Parameter,
+            span: 23..28
+        })
+    );
+    assert_eq!(
+        returned.def_site,
+        Some(DefSite {
+            kind: DefKind::Variable,
+            span: 134..139
+        })
+    );
+}
+
+#[test]
+fn test_division_by_zero() {
+    let source = "
+        function main(x: number, y: float) {
+            var a: number = x / (0);
+            var b: number = x / x;
+            var c: float = y / 0.0;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), Some("E0014"));
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 78..81)));
+}
+
+#[test]
+fn test_null() {
+    let source = "
+        function name() -> string {
+            return null;
+        }
+
+        function main() {
+            var a: string = (null);
+            var b: number = null;
+            null;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0015"]);
+}
+
+#[test]
+fn test_coalesce() {
+    let source = "
+        function name(first: string, last: string) -> string {
+            return first ?? last ?? null;
+        }
+
+        function main() {
+            var a: string = null ?? name(null, null);
+            var b: number = 1 ?? 2;
+            var c: string = name(null, null) ?? 3;
+            var d: number = null ?? 4;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0008", "E0009", "E0009"]);
+    assert_eq!(errors[0].message(), "cannot apply binary operator `??` to type `number`");
+}
+
+#[test]
+fn test_builtins() {
+    let source = "
+        function nothing() {}
+
+        function main() {
+            var name: string = input();
+
+            print(name);
+            println(1.5);
+            println(1, 2);
+            println(nothing());
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0012", "E0017"]);
+
+    let source = "
+        function print(value: number) -> number {
+            return value;
+        }
+
+        function main() {
+            var x: number = print(1);
+        }
+    ";
+
+    assert!(check(source).is_ok());
+}
+
+#[test]
+fn test_file_builtins() {
+    let source = "
+        function main(path: string) {
+            var contents: string = read_file(path) ?? \"\";
+
+            assert(write_file(path, contents));
+            assert(append_file(path, null));
+            var x: number = read_file(path);
+            write_file(path, 1);
+            append_file(path);
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0012"]);
+
+    // The error points at the argument of the wrong type.
+    assert_eq!(errors[1].message(), "mismatched types");
+    assert_eq!(errors[1].primary_span(), Some(("<test>", 265..266)));
+    assert!(errors[1].render_plain().contains("expected `string`, found `number`"));
+    assert_eq!(errors[2].message(), "this function takes 2 arguments but 1 argument was supplied");
+    assert_eq!(errors[2].primary_span(), Some(("<test>", 281..298)));
+}
+
+#[test]
+fn test_math_builtins() {
+    let source = "
+        function main(x: float, n: number) {
+            var a: float = sqrt(pow(x, 2.0)) + floor(x);
+            var b: float = ceil(x) * sin(x);
+            var c: float = cos(x) * min(x, abs(x));
+            var d: number = abs(n) + min(n, max(2, n));
+            var e: float = sqrt(n);
+            var f: number = max(n, 1.5);
+            var g: number = abs(\"a\");
+            var h: float = pow(x);
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0009", "E0012"]);
+
+    // The numbers are not converted to floats.
+    assert_eq!(errors[0].message(), "mismatched types");
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 288..289)));
+    assert!(errors[0].render_plain().contains("expected `float`, found `number`"));
+    assert_eq!(errors[3].message(), "this function takes 2 arguments but 1 argument was supplied");
+    assert_eq!(errors[3].primary_span(), Some(("<test>", 398..404)));
+}
+
+#[test]
+fn test_format() {
+    let source = "
+        function nothing() {}
+
+        function main(name: string, format_string: string) {
+            var a: string = format(\"{} is {}, {{escaped}}\", name, 1.5);
+            var b: string = format(\"no placeholders\");
+            var c: string = format(format_string, name);
+            var d: string = format(\"{} and {}\", name);
+            var e: string = format(\"{\", name);
+            var f: string = format(\"{}\", nothing());
+            var g: number = format(\"{}\", 1);
+            var h: string = format();
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0020", "E0020", "E0020", "E0017", "E0009", "E0012"]);
+    assert_eq!(errors[1].message(), "the format string has 2 placeholders but 1 value was supplied");
+    assert_eq!(errors[2].message(), "invalid format string: unmatched `{`");
+}
+
+#[test]
+fn test_random_builtins() {
+    let source = "
+        function main() {
+            seed(42);
+
+            var a: float = random();
+            var b: number = random_range(1, 7);
+            var c: number = random();
+            var d: number = random_range(1.5, 7);
+            var e: float = random(1);
+            seed();
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0012", "E0012"]);
+
+    // The error points at the argument of the wrong type.
+    assert_eq!(errors[1].message(), "mismatched types");
+    assert_eq!(errors[1].primary_span(), Some(("<test>", 214..217)));
+    assert!(errors[1].render_plain().contains("expected `number`, found `float`"));
+    assert_eq!(errors[2].message(), "this function takes 0 arguments but 1 argument was supplied");
+    assert_eq!(errors[2].primary_span(), Some(("<test>", 250..259)));
+}
+
+#[test]
+fn test_time_builtins() {
+    let source = "
+        function main() {
+            var start: number = now_millis();
+
+            sleep(10);
+            sleep(now_millis() - start);
+            sleep(1.5);
+            var x: float = now_millis();
+            sleep();
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0012"]);
+
+    assert_eq!(errors[0].message(), "mismatched types");
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 156..159)));
+    assert!(errors[0].render_plain().contains("expected `number`, found `float`"));
+    assert_eq!(errors[2].message(), "this function takes 1 argument but 0 arguments were supplied");
+    assert_eq!(errors[2].primary_span(), Some(("<test>", 215..222)));
+}
+
+#[test]
+fn test_env_builtins() {
+    let source = "
+        function main() {
+            var home: string = env(\"HOME\");
+
+            set_env(\"FLUID\", home);
+            set_env(\"FLUID\", null);
+            set_env(\"FLUID\", 1);
+            var x: number = env(\"HOME\");
+            var y: string = set_env(\"FLUID\", \"1\");
+            env();
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0009", "E0012"]);
+
+    // The error points at the value, the second argument of `set_env`.
+    assert_eq!(errors[0].message(), "mismatched types");
+    assert_eq!(errors[0].primary_span(), Some(("<test>", 173..174)));
+    assert!(errors[0].render_plain().contains("expected `string`, found `number`"));
+    assert_eq!(errors[3].message(), "this function takes 1 argument but 0 arguments were supplied");
+    assert_eq!(errors[3].primary_span(), Some(("<test>", 281..286)));
+}
+
+#[test]
+fn test_if_expression() {
+    let source = "
+        function main() {
+            var flag: bool = true;
+            var x: number = if (flag) { 1 } else if (false) { 2 } else { 3 };
+            var name: string = if (flag) { null } else { \"fluid\" };
+
+            var y: number = if (flag) { 1 } else { 2.5 };
+            var z: number = if (1) { 1 } else { 2 };
+            var w: string = if (flag) { 1 } else { 2 };
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0009", "E0009", "E0009"]);
+}
+
+#[test]
+fn test_string_iteration() {
+    let source = "
+        function count(s: string) -> number {
+            var first: char = s[0];
+            var last: char = \"fluid\"[4];
+
+            for (c in s) {
+                println(c);
+            }
+
+            for (c in 42) {}
+            var letter: number = s[0];
+            var byte: char = s[true];
+            println(c);
+
+            return 0;
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0010", "E0009", "E0009", "E0009"]);
+}
+
+#[test]
+fn test_block_values() {
+    let source = "
+        function double(x: number) -> number {
+            x * 2
+        }
+
+        function name(anonymous: bool) -> string {
+            if (anonymous) { null } else { \"fluid\" }
+        }
+
+        function area(side: number) -> number {
+            var squared: number = {
+                var half: number = double(side);
+                half * half
+            };
+
+            squared
+        }
+
+        function log(message: string) {
+            println(message)
+        }
+
+        function wrong() -> number {
+            2.5
+        }
+
+        function ignored(x: number) {
+            if (true) {
+                x
+            }
+        }
+
+        function scoped() -> number {
+            var x: number = { var inner: number = 1; inner };
+            inner
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0010", "E0009", "E0009"]);
+}
+
+#[test]
+fn test_test_functions() {
+    let source = "
+        test function adds() {
+            assert(1 + 1 == 2);
+        }
+
+        test function takes(a: number) {}
+
+        test function returns() -> number {
+            return 1;
+        }
+
+        test function asserts() {
+            assert(1);
+            assert();
+        }
+    ";
+
+    let errors = check(source).unwrap_err();
+    let codes = errors.iter().map(|error| error.code().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(codes, vec!["E0019", "E0019", "E0009", "E0012"]);
+}
+
+#[test]
+fn test_infer_expression() {
+    let mut checker = TypeChecker::new(Arc::new(SourceFile::new("<test>", "")));
+    let mut infer = |source: &str| {
+        let mut lexer = Lexer::new(source, "<test>");
+        let tokens = lexer.run().unwrap();
+        let (expression, mut exprs) = Parser::new(&tokens, lexer.file()).run_expression().unwrap();
+
+        checker.set_file(lexer.file());
+        checker
+            .infer_expression(&mut exprs, expression)
+            .map_err(|errors| errors.iter().map(|error| error.code().unwrap().to_owned()).collect::<Vec<_>>())
+    };
+
+    assert_eq!(infer("1 + 2 * 3"), Ok(Some(Type::Number)));
+    assert_eq!(infer("1.5 == 2.5;"), Ok(Some(Type::Bool)));
+    assert_eq!(infer("input()"), Ok(Some(Type::String)));
+    assert_eq!(infer("null"), Ok(None));
+    assert_eq!(infer("x + 1"), Err(vec!["E0010".to_owned()]));
+    assert_eq!(infer("1 + true"), Err(vec!["E0008".to_owned()]));
+    assert_eq!(infer("-+1.5"), Ok(Some(Type::Float)));
+    assert_eq!(infer("+true"), Err(vec!["E0008".to_owned()]));
+}
@@ -0,0 +1,294 @@
+//! The completion, the hints and the highlighting of the REPL.
+
+use std::borrow::Cow;
+
+use ansi_term::{Colour, Style};
+use fluid_codegen::FunctionSignature;
+use fluid_lexer::{Keyword, Lexer, Token, TokenType};
+use fluid_parser::{Builtin, Type};
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::{Hint, Hinter},
+    validate::Validator,
+    Context, Helper,
+};
+
+/// Completes the keywords, the builtins and the functions of the REPL with Tab, and hints the signature of a function
+/// once its name is followed by `(`.
+#[derive(Debug)]
+pub struct ReplHelper {
+    /// The functions defined in the REPL so far.
+    functions: Vec<FunctionSignature>,
+    /// Whether the input and the hints are colored.
+    color: bool,
+}
+
+impl ReplHelper {
+    /// Create a helper without any functions.
+    pub fn new(color: bool) -> Self {
+        Self { functions: vec![], color }
+    }
+
+    /// Set the functions defined in the REPL, they are given by the code generator after every input.
+    pub fn set_functions(&mut self, functions: Vec<FunctionSignature>) {
+        self.functions = functions;
+    }
+
+    /// Get the signature of the function or the builtin with the name. The functions shadow the builtins.
+    fn signature(&self, name: &str) -> Option<FunctionSignature> {
+        if let Some(function) = self.functions.iter().find(|function| function.name == name) {
+            return Some(function.clone());
+        }
+
+        let (args, return_type) = match Builtin::from_name(name)? {
+            // `print` and `println` take any value that can be printed, so a `string` is only an example.
+            Builtin::Print | Builtin::Println => (vec![Type::String], Type::Void),
+            Builtin::Input => (vec![], Type::String),
+            Builtin::Assert => (vec![Type::Bool], Type::Void),
+            Builtin::ReadFile | Builtin::Env => (vec![Type::String], Type::String),
+            Builtin::SetEnv => (vec![Type::String, Type::String], Type::Void),
+            Builtin::WriteFile | Builtin::AppendFile => (vec![Type::String, Type::String], Type::Bool),
+            Builtin::Sqrt | Builtin::Floor | Builtin::Ceil | Builtin::Sin | Builtin::Cos => (vec![Type::Float], Type::Float),
+            Builtin::Pow => (vec![Type::Float, Type::Float], Type::Float),
+            // `abs`, `min` and `max` take numbers or floats, so a `float` is only an example.
+            Builtin::Abs => (vec![Type::Float], Type::Float),
+            Builtin::Min | Builtin::Max => (vec![Type::Float, Type::Float], Type::Float),
+            // `format` takes any number of values after the format string.
+            Builtin::Format => (vec![Type::String], Type::String),
+            Builtin::Random => (vec![], Type::Float),
+            Builtin::RandomRange => (vec![Type::Number, Type::Number], Type::Number),
+            Builtin::Seed | Builtin::Sleep => (vec![Type::Number], Type::Void),
+            Builtin::NowMillis => (vec![], Type::Number),
+        };
+
+        Some(FunctionSignature {
+            name: name.to_owned(),
+            args,
+            return_type,
+        })
+    }
+
+    /// Get the candidates that start with the word, the keywords first, then the functions and the builtins.
+    fn candidates(&self, word: &str) -> Vec<Pair> {
+        let keywords = Keyword::ALL.iter().map(|keyword| keyword.to_string()).map(|keyword| Pair {
+            display: keyword.clone(),
+            replacement: keyword,
+        });
+
+        let mut names = self.functions.iter().map(|function| function.name.clone()).collect::<Vec<_>>();
+
+        names.extend(Builtin::ALL.iter().map(|builtin| builtin.name().to_owned()));
+        names.sort();
+        names.dedup();
+
+        // The functions are displayed with their signature, and only their name is inserted.
+        let functions = names.into_iter().map(|name| Pair {
+            display: self.signature(&name).map_or_else(|| name.clone(), |signature| signature.to_string()),
+            replacement: name,
+        });
+
+        keywords.chain(functions).filter(|pair| pair.replacement.starts_with(word)).collect()
+    }
+}
+
+/// A hint of the REPL. The rest of a name can be inserted with the right arrow, a signature is only displayed.
+#[derive(Debug)]
+pub struct ReplHint {
+    text: String,
+    complete: bool,
+}
+
+impl Hint for ReplHint {
+    fn display(&self) -> &str {
+        &self.text
+    }
+
+    fn completion(&self) -> Option<&str> {
+        if self.complete {
+            Some(&self.text)
+        } else {
+            None
+        }
+    }
+}
+
+/// Get the byte index of the start of the identifier that ends at the position.
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, char)| char.is_alphanumeric() || *char == '_')
+        .last()
+        .map_or(pos, |(index, _)| index)
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = word_start(line, pos);
+
+        Ok((start, self.candidates(&line[start..pos])))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = ReplHint;
+
+    /// Hint the arguments and the return type of a function right after `(`, and the rest of the only candidate
+    /// that starts with the word otherwise. The hints are only shown at the end of the line.
+    fn hint(&self, line: &str, pos: usize, _: &Context<'_>) -> Option<ReplHint> {
+        if pos != line.len() {
+            return None;
+        }
+
+        if let Some(before) = line.strip_suffix('(') {
+            let signature = self.signature(&before[word_start(before, before.len())..])?;
+            let args = signature.args.iter().map(Type::to_string).collect::<Vec<_>>();
+            let return_type = if signature.return_type == Type::Void {
+                String::new()
+            } else {
+                format!(" -> {}", signature.return_type)
+            };
+
+            return Some(ReplHint {
+                text: format!("{}){}", args.join(", "), return_type),
+                complete: false,
+            });
+        }
+
+        let word = &line[word_start(line, pos)..pos];
+
+        if word.is_empty() {
+            return None;
+        }
+
+        match &self.candidates(word)[..] {
+            [candidate] if candidate.replacement != word => Some(ReplHint {
+                text: candidate.replacement[word.len()..].to_owned(),
+                complete: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Get the style of a kind of token, or `None` if it is not highlighted.
+fn token_style(kind: &TokenType) -> Option<Style> {
+    match kind {
+        TokenType::Keyword(Keyword::True) | TokenType::Keyword(Keyword::False) | TokenType::Keyword(Keyword::Null) => Some(Colour::Yellow.normal()),
+        TokenType::Keyword(_) => Some(Colour::Purple.bold()),
+        TokenType::Number(_) | TokenType::Float(_) => Some(Colour::Yellow.normal()),
+        TokenType::String(_) | TokenType::Char(_) => Some(Colour::Green.normal()),
+        TokenType::Comment(_) => Some(Style::new().dimmed()),
+        TokenType::Unknown(_) => Some(Colour::Red.normal()),
+        _ => None,
+    }
+}
+
+/// Get the closing bracket of an opening bracket.
+fn closing_bracket(kind: &TokenType) -> Option<TokenType> {
+    match kind {
+        TokenType::OpenParen => Some(TokenType::CloseParen),
+        TokenType::OpenBrace => Some(TokenType::CloseBrace),
+        TokenType::OpenBrac => Some(TokenType::CloseBrac),
+        _ => None,
+    }
+}
+
+/// Get the opening bracket of a closing bracket.
+fn opening_bracket(kind: &TokenType) -> Option<TokenType> {
+    match kind {
+        TokenType::CloseParen => Some(TokenType::OpenParen),
+        TokenType::CloseBrace => Some(TokenType::OpenBrace),
+        TokenType::CloseBrac => Some(TokenType::OpenBrac),
+        _ => None,
+    }
+}
+
+/// Get the indices of the bracket at the cursor, or right before it, and of the bracket it matches.
+/// The brackets are tokens, so the ones in strings and comments are not matched.
+fn matching_brackets(tokens: &[Token], pos: usize) -> Option<(usize, usize)> {
+    let is_bracket = |token: &Token| closing_bracket(&token.kind).is_some() || opening_bracket(&token.kind).is_some();
+    let index = tokens
+        .iter()
+        .position(|token| token.position.position_start == pos && is_bracket(token))
+        .or_else(|| tokens.iter().position(|token| token.position.position_end == pos && is_bracket(token)))?;
+
+    let bracket = &tokens[index].kind;
+    let mut depth = 0;
+
+    // Search forward from an opening bracket and backward from a closing one, skipping the nested pairs.
+    let (matching, candidates): (TokenType, Box<dyn Iterator<Item = usize>>) = match closing_bracket(bracket) {
+        Some(closing) => (closing, Box::new(index + 1..tokens.len())),
+        None => (opening_bracket(bracket)?, Box::new((0..index).rev())),
+    };
+
+    for candidate in candidates {
+        if tokens[candidate].kind == *bracket {
+            depth += 1;
+        } else if tokens[candidate].kind == matching {
+            if depth == 0 {
+                return Some((index, candidate));
+            }
+
+            depth -= 1;
+        }
+    }
+
+    None
+}
+
+impl Highlighter for ReplHelper {
+    /// Color the keywords, the literals and the comments with the tokens of the lexer, and emphasize the bracket at
+    /// the cursor and the bracket it matches. The text that is not a token, like an unterminated string, is kept as is.
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if !self.color {
+            return Cow::Borrowed(line);
+        }
+
+        let (tokens, _) = Lexer::new(line, "<stdin>").set_preserve_comments(true).tokenize();
+        let brackets = matching_brackets(&tokens, pos);
+
+        let mut output = String::with_capacity(line.len());
+        let mut end = 0;
+
+        for (index, token) in tokens.iter().enumerate() {
+            let style = match brackets {
+                Some((bracket, matching)) if index == bracket || index == matching => Colour::Blue.bold(),
+                _ => match token_style(&token.kind) {
+                    Some(style) => style,
+                    None => continue,
+                },
+            };
+
+            let span = token.position.position_start..token.position.position_end;
+
+            output.push_str(&line[end..span.start]);
+            output.push_str(&style.paint(&line[span.clone()]).to_string());
+            end = span.end;
+        }
+
+        output.push_str(&line[end..]);
+
+        Cow::Owned(output)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if !self.color {
+            return Cow::Borrowed(hint);
+        }
+
+        Cow::Owned(Style::new().dimmed().paint(hint).to_string())
+    }
+
+    /// The line is highlighted again when the cursor moves, so the emphasized brackets follow it.
+    fn highlight_char(&self, line: &str, _: usize) -> bool {
+        self.color && !line.is_empty()
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
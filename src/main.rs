@@ -1,173 +1,1025 @@
-use fluid_codegen::{CodeGen, CodeGenType};
-use fluid_lexer::Lexer;
-use fluid_parser::Parser;
+use fluid_codegen::{available_targets, host_cpu, host_features, host_target, CodeGenType, CodeModel, OptLevel, RelocModel, TargetOptions};
+use fluid_driver::{collect_fluid_files, profile, read_files, timings, CompileError, Emit, Options, Session};
+use fluid_error::{codes, CollectingEmitter, ColorChoice, DiagnosticHandler, Emitter, HumanEmitter, JsonEmitter, Level, LintLevels, SourceFile};
+use fluid_lexer::{dump_tokens, Lexer};
+use fluid_mangle::{demangle_text, Mangling};
+use fluid_parser::{dump_ast, Parser};
 
-use ansi_term::Colour;
+use ansi_term::{Colour, Style};
 use rustyline::Editor;
 use structopt::StructOpt;
 
-use std::{error::Error, fs::File, io::Read, path::Path, process};
+use crate::{completion::ReplHelper, manifest::Manifest};
+
+mod completion;
+mod manifest;
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, BufRead},
+    iter,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The interval between two checks of the modification time of a watched file.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
 const HELP: &str = "At the prompt you can type Fluid Code or type repl commands preceded by a `.`
 
     .reset => Reset the codegen context.
+    .type <expression> => Print the type of the expression without running it.
+    .ir [function] => Print the LLVM IR of the code compiled so far, or only the one of the function.
+    .history [clear] => Print the path of the history file, or clear the history.
 
 For more information about fluid commands `fluid --help`";
 
 #[derive(Debug, StructOpt)]
 enum Command {
     Run {
-        path: String,
+        /// The files of the program, or directories of `.fluid` files. A file can call the functions of the files
+        /// before it, the files of a directory are in the order of their paths. The entry point of the project of the
+        /// `fluid.toml` manifest of the current directory or of one of its parents is compiled if there are none.
+        paths: Vec<String>,
+
+        /// Compile every function the first time it is called, instead of the whole program before running it.
+        #[structopt(long)]
+        lazy_jit: bool,
+
+        /// The optimization level, `0`, `1`, `2` or `3`. The level of the manifest is used by default.
+        #[structopt(long, short = "O")]
+        opt_level: Option<OptLevel>,
+
+        /// Run the program again every time one of its files is saved.
+        #[structopt(long)]
+        watch: bool,
+
+        /// A library to link the program with, like `-l m` for the math library. Added to the libraries of the
+        /// manifest.
+        #[structopt(short = "l", long = "library", number_of_values = 1)]
+        libraries: Vec<String>,
+
+        /// A directory to search for the libraries in, before the ones of the system. Added to the library paths of
+        /// the manifest.
+        #[structopt(short = "L", long = "library-path", number_of_values = 1, parse(from_os_str))]
+        library_paths: Vec<PathBuf>,
+
+        /// The arguments of the program, after `--`, like `fluid run app.fluid -- --verbose`.
+        #[structopt(last = true)]
+        args: Vec<String>,
+    },
+    /// Check a program for errors without generating code.
+    Check {
+        /// The files of the program, or directories of `.fluid` files. A file can call the functions of the files
+        /// before it, the files of a directory are in the order of their paths. The entry point of the project of the
+        /// `fluid.toml` manifest of the current directory or of one of its parents is compiled if there are none.
+        paths: Vec<String>,
+
+        /// Check the program again every time one of its files is saved.
+        #[structopt(long)]
+        watch: bool,
     },
     Build {
+        /// The files of the program, or directories of `.fluid` files. A file can call the functions of the files
+        /// before it, the files of a directory are in the order of their paths. The entry point of the project of the
+        /// `fluid.toml` manifest of the current directory or of one of its parents is compiled if there are none.
+        paths: Vec<String>,
+
+        /// The comma separated artifacts to emit next to the file, `tokens`, `ast`, `llvm-ir`, `bc`, `asm`, `obj`,
+        /// `exe` or `c-header`. They are all produced by a single compilation.
+        #[structopt(long, use_delimiter = true, default_value = "exe")]
+        emit: Vec<Emit>,
+
+        /// The target triple to compile for, the machine the compiler runs on by default.
+        #[structopt(long)]
+        target: Option<String>,
+
+        /// The CPU to compile for, `native` for the CPU of the machine the compiler runs on.
+        #[structopt(long)]
+        cpu: Option<String>,
+
+        /// The comma separated CPU features to enable or disable, like `+avx2,-sse4.1`.
+        #[structopt(long)]
+        features: Option<String>,
+
+        /// The relocation model, `default`, `pic` or `static`.
+        #[structopt(long, default_value = "default")]
+        reloc_model: RelocModel,
+
+        /// The code model, `default`, `tiny`, `small`, `kernel`, `medium` or `large`.
+        #[structopt(long, default_value = "default")]
+        code_model: CodeModel,
+
+        /// The optimization level, `0`, `1`, `2` or `3`. The level of the manifest is used by default.
+        #[structopt(long, short = "O")]
+        opt_level: Option<OptLevel>,
+
+        /// The mangling scheme of the function symbols, `fluid` or `itanium`.
+        /// The `itanium` scheme is the one of C++, so the symbols can be demangled by the C++ tools.
+        #[structopt(long, default_value = "fluid")]
+        mangling: Mangling,
+
+        /// A library to link the program with, like `-l m` for the math library. Added to the libraries of the
+        /// manifest.
+        #[structopt(short = "l", long = "library", number_of_values = 1)]
+        libraries: Vec<String>,
+
+        /// A directory to search for the libraries in, before the ones of the system. Added to the library paths of
+        /// the manifest.
+        #[structopt(short = "L", long = "library-path", number_of_values = 1, parse(from_os_str))]
+        library_paths: Vec<PathBuf>,
+    },
+    /// Run the test functions of a program. Every test runs in its own process, so that a failed assertion or a crash
+    /// only fails that test, and the output of a test is only printed if it fails.
+    Test {
+        /// The files of the program, or directories of `.fluid` files. A file can call the functions of the files
+        /// before it, the files of a directory are in the order of their paths. The entry point of the project of the
+        /// `fluid.toml` manifest of the current directory or of one of its parents is compiled if there are none.
+        paths: Vec<String>,
+
+        /// Only run the tests whose name contains this text.
+        #[structopt(long)]
+        filter: Option<String>,
+
+        /// Run the test with this name in this process, the way every test is run.
+        #[structopt(long, hidden = true)]
+        exact: Option<String>,
+    },
+    /// Print the long-form explanation of a diagnostic code.
+    Explain { code: String },
+    /// Demangle the symbols into Fluid signatures. The symbols are read from the standard input if there are none,
+    /// and the symbols in the text of the input are demangled, like in the output of a linker.
+    Demangle { symbols: Vec<String> },
+    /// Format a Fluid file, or all of the Fluid files of a directory, in the canonical style.
+    Fmt {
         path: String,
 
-        #[structopt(long, short)]
-        emit_llvm: bool,
+        /// Do not write the files, exit with an error if any of them is not formatted.
+        #[structopt(long)]
+        check: bool,
     },
+    /// Print the tokens of a file, with their kinds, byte ranges, lines and columns.
+    /// The tokens are printed even if the file has lexer errors, which are reported after them.
+    Tokens { path: String },
+    /// Print the AST of a file as a tree, with the byte ranges of its nodes.
+    Ast {
+        path: String,
+
+        /// Print the AST as JSON instead, for the tools that consume it.
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Create a project in a new directory, with a `fluid.toml` manifest and a `src/main.fluid` entry point.
+    New { name: String },
+    /// Create a project in the current directory, named after it.
+    Init,
+    /// Start the language server, which speaks the Language Server Protocol over the standard input and output.
+    Lsp,
+}
+
+/// The format of the diagnostics printed by the compiler.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MessageFormat {
+    /// Human readable diagnostics.
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("invalid message format `{}`, expected `human` or `json`", format)),
+        }
+    }
+}
+
+/// The information about the compiler printed by `--print`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Print {
+    /// The target triple of the machine the compiler runs on, which code is generated for by default.
+    TargetTriple,
+    /// The configuration of the default target, one `key="value"` per line.
+    Cfg,
+    /// The version of the compiler along with its default target, the supported targets and the features of the
+    /// host CPU, as a JSON object.
+    VersionJson,
+}
+
+impl FromStr for Print {
+    type Err = String;
+
+    fn from_str(print: &str) -> Result<Self, Self::Err> {
+        match print {
+            "target-triple" => Ok(Print::TargetTriple),
+            "cfg" => Ok(Print::Cfg),
+            "version-json" => Ok(Print::VersionJson),
+            _ => Err(format!("invalid print request `{}`, expected `target-triple`, `cfg` or `version-json`", print)),
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
 struct CLI {
     #[structopt(subcommand)]
     command: Option<Command>,
+
+    /// Allow the warning with the given code.
+    #[structopt(short = "A", long = "allow", global = true, number_of_values = 1)]
+    allow: Vec<String>,
+
+    /// Report the warning with the given code as a warning.
+    #[structopt(short = "W", long = "warn", global = true, number_of_values = 1)]
+    warn: Vec<String>,
+
+    /// Report the warning with the given code as an error.
+    #[structopt(short = "D", long = "deny", global = true, number_of_values = 1)]
+    deny: Vec<String>,
+
+    /// The format of the diagnostics, `human` or `json`.
+    #[structopt(long, global = true, default_value = "human")]
+    message_format: MessageFormat,
+
+    /// Color the output and the diagnostics, `auto`, `always` or `never`. With `auto`, the output is colored if it
+    /// is a terminal and the `NO_COLOR` environment variable is not set.
+    #[structopt(long, global = true, default_value = "auto")]
+    color: ColorChoice,
+
+    /// The number of errors to print before the rest are suppressed, `0` for no limit.
+    #[structopt(long, global = true, default_value = "20")]
+    error_limit: usize,
+
+    /// Print the targets that code can be generated for.
+    #[structopt(long)]
+    print_targets: bool,
+
+    /// Print information about the compiler for build systems and editors and exit, `target-triple`, `cfg` or
+    /// `version-json`. Can be given several times.
+    #[structopt(long, number_of_values = 1)]
+    print: Vec<Print>,
+
+    /// Print the time spent in each phase of the compiler, like lexing, type checking and the passes of LLVM.
+    #[structopt(short = "v", long = "timings", alias = "time-passes", global = true)]
+    timings: bool,
+
+    /// Write the time spent in each phase of the compiler and in the code generation of each function, and the memory
+    /// used by the compiler, to the file. It is a Chrome trace, or folded stacks if the file ends with `.folded`.
+    #[structopt(long, global = true, parse(from_os_str))]
+    self_profile: Option<PathBuf>,
+
+    /// The file of the history of the REPL, `fluid/history.txt` in the data directory of the user by default.
+    #[structopt(long, parse(from_os_str))]
+    history: Option<PathBuf>,
+}
+
+impl CLI {
+    /// Collect the lint levels from the `-A`, `-W` and `-D` flags.
+    fn lint_levels(&self) -> LintLevels {
+        let mut levels = LintLevels::new();
+
+        for (codes, level) in [(&self.allow, Level::Allow), (&self.warn, Level::Warn), (&self.deny, Level::Deny)].iter() {
+            for code in codes.iter() {
+                levels.set_level(code, *level);
+            }
+        }
+
+        levels
+    }
+
+    /// Create the emitter for the requested message format.
+    fn emitter(&self) -> Box<dyn Emitter> {
+        match self.message_format {
+            MessageFormat::Human => Box::new(HumanEmitter::new(self.color)),
+            MessageFormat::Json => Box::new(JsonEmitter),
+        }
+    }
+
+    /// Create the diagnostic handler shared by all of the phases of the compiler.
+    fn diagnostic_handler(&self) -> DiagnosticHandler {
+        DiagnosticHandler::new(self.emitter()).set_lint_levels(self.lint_levels()).set_error_limit(self.error_limit)
+    }
+}
+
+/// Whether the output of the CLI is colored, decided by `--color` before anything is printed.
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Get the style if the output is colored, and the plain style otherwise.
+fn style(style: Style) -> Style {
+    if COLOR.load(Ordering::Relaxed) {
+        style
+    } else {
+        Style::new()
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = CLI::from_args();
 
+    COLOR.store(args.color.should_color(), Ordering::Relaxed);
+
+    if args.timings {
+        timings::enable();
+    }
+
+    if let Some(path) = args.self_profile.clone() {
+        profile::enable(path);
+    }
+
+    let mut handler = args.diagnostic_handler();
+
+    if args.print_targets {
+        print_targets();
+
+        return Ok(());
+    }
+
+    if !args.print.is_empty() {
+        for print in args.print.iter() {
+            print_info(*print)?;
+        }
+
+        return Ok(());
+    }
+
     match args.command {
         Some(command) => match command {
-            Command::Run { path } => run_file(path)?,
-            Command::Build { path, emit_llvm } => build_file(path, emit_llvm)?,
+            Command::Run { paths, watch: true, .. } | Command::Check { paths, watch: true } => watch(&project(paths)?.0)?,
+            Command::Run {
+                paths,
+                lazy_jit,
+                opt_level,
+                args,
+                libraries,
+                library_paths,
+                ..
+            } => {
+                let (paths, manifest) = project(paths)?;
+                let (libraries, library_paths) = libraries_of(libraries, library_paths, manifest.as_ref());
+
+                let options = Options {
+                    target: TargetOptions {
+                        opt_level: opt_level_of(opt_level, manifest.as_ref())?,
+                        ..TargetOptions::default()
+                    },
+                    libraries,
+                    library_paths,
+                    ..Options::default()
+                };
+
+                process::exit(run_files(&paths, lazy_jit, options, args, handler)?)
+            }
+            Command::Check { paths, .. } => check(&project(paths)?.0, handler)?,
+            Command::Build {
+                paths,
+                emit,
+                target,
+                cpu,
+                features,
+                reloc_model,
+                code_model,
+                opt_level,
+                mangling,
+                libraries,
+                library_paths,
+            } => {
+                let (paths, manifest) = project(paths)?;
+                let (libraries, library_paths) = libraries_of(libraries, library_paths, manifest.as_ref());
+
+                let options = Options {
+                    target: TargetOptions {
+                        triple: target,
+                        cpu,
+                        features,
+                        reloc_model,
+                        code_model,
+                        opt_level: opt_level_of(opt_level, manifest.as_ref())?,
+                    },
+                    mangling,
+                    emit,
+                    libraries,
+                    library_paths,
+                };
+
+                build_files(&paths, manifest.as_ref(), options, handler)?
+            }
+            Command::Test { paths, exact: Some(name), .. } => run_test(&project(paths)?.0, &name)?,
+            Command::Test { paths, filter, .. } => test_files(&project(paths)?.0, filter.as_deref(), handler)?,
+            Command::Explain { code } => explain(&code),
+            Command::Demangle { symbols } => demangle(&symbols)?,
+            Command::Fmt { path, check } => fmt(Path::new(&path), check, &mut handler)?,
+            Command::Tokens { path } => tokens(&path, &mut handler)?,
+            Command::Ast { path, json } => ast(&path, json, &mut handler)?,
+            Command::New { name } => new_project(&name)?,
+            Command::Init => init_project()?,
+            Command::Lsp => process::exit(fluid_lsp::run()?),
         },
-        None => repl()?,
+        None => repl(args.history.or_else(default_history_path), handler)?,
     }
 
     Ok(())
 }
 
-fn run_file(path: String) -> Result<(), Box<dyn Error>> {
-    let mut file = File::open(&path)?;
-    let mut contents = String::new();
+/// Get the paths to compile, the entry point of the project of the current directory if there are none along with
+/// its manifest.
+fn project(paths: Vec<String>) -> Result<(Vec<String>, Option<Manifest>), Box<dyn Error>> {
+    if !paths.is_empty() {
+        return Ok((paths, None));
+    }
 
-    file.read_to_string(&mut contents)?;
+    let manifest = Manifest::find()?;
 
-    let mut codegen = CodeGen::new(&path, CodeGenType::JIT { run_main: true });
+    Ok((vec![manifest.entry_path().to_string_lossy().into_owned()], Some(manifest)))
+}
 
-    let mut lexer = Lexer::new(contents, path);
-    let tokens = match lexer.run() {
-        Ok(tokens) => tokens,
-        Err(errors) => {
-            for err in errors {
-                println!("{}", err);
+/// Get the optimization level of the flag, of the manifest if the flag is not given.
+fn opt_level_of(flag: Option<OptLevel>, manifest: Option<&Manifest>) -> Result<OptLevel, Box<dyn Error>> {
+    match (flag, manifest) {
+        (Some(opt_level), _) => Ok(opt_level),
+        (None, Some(manifest)) => Ok(manifest.opt_level()?),
+        (None, None) => Ok(OptLevel::default()),
+    }
+}
+
+/// Get the libraries and the library paths of the manifest followed by the ones of the flags.
+fn libraries_of(libraries: Vec<String>, library_paths: Vec<PathBuf>, manifest: Option<&Manifest>) -> (Vec<String>, Vec<PathBuf>) {
+    match manifest {
+        Some(manifest) => (
+            manifest.libraries.iter().cloned().chain(libraries).collect(),
+            manifest.library_paths().into_iter().chain(library_paths).collect(),
+        ),
+        None => (libraries, library_paths),
+    }
+}
+
+fn new_project(name: &str) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(name);
+
+    if dir.exists() {
+        return Err(format!("`{}` already exists", dir.display()).into());
+    }
+
+    // The name of the project is the last component of the path, so `fluid new projects/hello` is named `hello`.
+    let name = dir.file_name().map_or_else(|| name.to_owned(), |name| name.to_string_lossy().into_owned());
+
+    manifest::scaffold(dir, &name)?;
+
+    println!("Created the project `{}` in `{}`", name, dir.display());
+
+    Ok(())
+}
+
+fn init_project() -> Result<(), Box<dyn Error>> {
+    let dir = env::current_dir()?;
+    let name = dir.file_name().map_or_else(|| "main".to_owned(), |name| name.to_string_lossy().into_owned());
+
+    manifest::scaffold(&dir, &name)?;
+
+    println!("Created the project `{}`", name);
+
+    Ok(())
+}
+
+fn print_targets() {
+    println!("host: {}", host_target());
+    println!();
+    println!("registered targets:");
+
+    for (name, description) in available_targets() {
+        println!("    {:<12} - {}", name, description);
+    }
+}
+
+/// Print information about the compiler in a format that is easy to parse.
+fn print_info(print: Print) -> Result<(), Box<dyn Error>> {
+    match print {
+        Print::TargetTriple => println!("{}", host_target()),
+        Print::Cfg => {
+            let triple = host_target();
+            let mut components = triple.split('-');
+
+            println!("target_triple=\"{}\"", triple);
+
+            // The components of a triple are the architecture, the vendor, the operating system and the environment.
+            for key in ["target_arch", "target_vendor", "target_os", "target_env"].iter() {
+                if let Some(component) = components.next() {
+                    println!("{}=\"{}\"", key, component);
+                }
             }
 
+            println!("target_cpu=\"{}\"", host_cpu());
+
+            for feature in host_features() {
+                println!("target_feature=\"{}\"", feature);
+            }
+        }
+        Print::VersionJson => {
+            let targets = available_targets()
+                .into_iter()
+                .map(|(name, description)| serde_json::json!({ "name": name, "description": description }))
+                .collect::<Vec<_>>();
+
+            let version = serde_json::json!({
+                "name": "fluid",
+                "version": VERSION,
+                "host": host_target(),
+                "targets": targets,
+                "cpu": host_cpu(),
+                "features": host_features(),
+            });
+
+            println!("{}", serde_json::to_string(&version)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn explain(code: &str) {
+    match codes::explain(code) {
+        Some(explanation) => println!("{}", explanation),
+        None => {
+            eprintln!("{}: no extended information for `{}`", style(Colour::Red.bold()).paint("error"), code);
+
             process::exit(1);
         }
-    };
+    }
+}
+
+fn demangle(symbols: &[String]) -> Result<(), Box<dyn Error>> {
+    if symbols.is_empty() {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            println!("{}", demangle_text(&line?));
+        }
+    } else {
+        // A symbol that is not a Fluid symbol is printed as it is, like `c++filt` does.
+        for symbol in symbols {
+            match fluid_mangle::demangle(symbol) {
+                Some(demangled) => println!("{}", demangled),
+                None => println!("{}", symbol),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn fmt(path: &Path, check: bool, handler: &mut DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let mut files = vec![];
+
+    collect_fluid_files(path, &mut files)?;
+
+    let mut unformatted = false;
+
+    for path in files {
+        let contents = fs::read_to_string(&path)?;
+        let file = Arc::new(SourceFile::new(path.to_string_lossy(), contents.as_str()));
+
+        let formatted = match fluid_fmt::format(file) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                handler.extend(errors);
+
+                continue;
+            }
+        };
+
+        if formatted == contents {
+            continue;
+        }
+
+        if check {
+            println!("{} is not formatted", path.display());
+
+            unformatted = true;
+        } else {
+            fs::write(&path, formatted)?;
+        }
+    }
 
-    let parser = Parser::new(tokens);
+    if handler.has_errors() {
+        abort(handler);
+    }
 
-    codegen.run(parser);
-    codegen.free();
+    if unformatted {
+        process::exit(1);
+    }
 
     Ok(())
 }
 
-fn build_file(path: String, emit_llvm: bool) -> Result<(), Box<dyn Error>> {
-    let mut file = File::open(&path)?;
-    let mut contents = String::new();
+fn tokens(path: &str, handler: &mut DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let file = Arc::new(SourceFile::new(path, fs::read_to_string(path)?));
+    let (tokens, errors) = Lexer::from_file(&file).tokenize();
+
+    print!("{}", dump_tokens(&tokens, &file));
+
+    handler.extend(errors);
+
+    if handler.has_errors() {
+        abort(handler);
+    }
+
+    Ok(())
+}
 
-    file.read_to_string(&mut contents)?;
+/// Print the AST of a file as it was parsed, the names are not resolved.
+fn ast(path: &str, json: bool, handler: &mut DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let file = Arc::new(SourceFile::new(path, fs::read_to_string(path)?));
 
-    let mut lexer = Lexer::new(&contents, &path);
-    let tokens = match lexer.run() {
+    let tokens = match Lexer::from_file(&file).run() {
         Ok(tokens) => tokens,
         Err(errors) => {
-            for err in errors {
-                println!("{}", err);
-            }
+            handler.extend(errors);
 
-            process::exit(1);
+            abort(handler);
         }
     };
 
-    let parser = Parser::new(tokens);
+    let mut parser = Parser::new(&tokens, file);
+    let result = parser.run();
+
+    handler.extend(parser.take_warnings());
 
-    if emit_llvm {
-        let mut codegen = CodeGen::new(&path, CodeGenType::JIT { run_main: false });
+    let ast = match result {
+        Ok(ast) => ast,
+        Err(errors) => {
+            handler.extend(errors);
+
+            abort(handler);
+        }
+    };
 
-        codegen.run(parser);
-        codegen.emit_llvm(&path);
-        codegen.free();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&ast)?);
     } else {
-        let mut codegen = CodeGen::new(&path, CodeGenType::JIT { run_main: false });
-        let path = Path::new(&path);
+        print!("{}", dump_ast(&ast));
+    }
+
+    handler.print_summary();
+
+    Ok(())
+}
+
+/// Print the time spent in each phase and write the self-profile, if they were requested.
+fn print_timings() {
+    timings::print();
+
+    if let Err(error) = profile::write() {
+        eprintln!("{}: could not write the self-profile: {}", style(Colour::Red.bold()).paint("error"), error);
+    }
+}
+
+/// Print the summary of the diagnostics and exit.
+fn abort(handler: &mut DiagnosticHandler) -> ! {
+    handler.print_summary();
+    print_timings();
+
+    process::exit(1);
+}
+
+/// Get the value of the result, or exit if the program has errors, which were emitted to the handler of the session.
+fn or_abort<T>(result: Result<T, CompileError>, session: &mut Session) -> Result<T, Box<dyn Error>> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(CompileError::Diagnostics) => abort(session.handler_mut()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn check(paths: &[String], handler: DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let mut session = Session::new(handler, Options::default());
+
+    or_abort(session.check_files(read_files(paths)?), &mut session)?;
+
+    session.handler_mut().print_summary();
+    print_timings();
+
+    Ok(())
+}
+
+/// Get the modification time of a file, `None` if it can not be read.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Get the modification times of the files of the paths. A file added to or removed from a directory changes them.
+fn modified_times(paths: &[String]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = vec![];
+
+    for path in paths {
+        // A path that can not be read is kept, so that the program is compiled again when it can be.
+        if collect_fluid_files(Path::new(path), &mut files).is_err() {
+            files.push(PathBuf::from(path));
+        }
+    }
+
+    files.into_iter().map(|file| (file.clone(), modified_time(&file))).collect()
+}
+
+/// Run the command again, without `--watch`, in a child process every time one of the files is saved, so that the
+/// diagnostics are always the ones of the saved files. A run that has not finished when a file is saved is killed.
+fn watch(paths: &[String]) -> Result<(), Box<dyn Error>> {
+    let executable = env::current_exe()?;
+    // The arguments of the program after `--` are kept, even `--watch`.
+    let mut args = env::args_os().skip(1).collect::<Vec<_>>();
+    let end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+
+    if let Some(index) = args[..end].iter().position(|arg| arg == "--watch") {
+        args.remove(index);
+    }
+
+    let mut modified = modified_times(paths);
+
+    loop {
+        eprintln!("{}", style(Colour::Cyan.normal()).paint(format!("[compiling `{}`]", paths.join("`, `"))));
 
-        codegen.run(parser);
+        let mut child = process::Command::new(&executable).args(&args).spawn()?;
+        let mut finished = false;
 
-        if let Some(parent) = path.parent() {
-            let file_name = path.file_name().unwrap().to_string_lossy().replace(".fluid", ".obj");
+        // The file is only compiled again when it changes, a finished run is kept until then.
+        while modified_times(paths) == modified {
+            thread::sleep(WATCH_INTERVAL);
 
-            let out = parent.join(file_name);
-            codegen.emit_object(&out);
+            if !finished {
+                if let Some(status) = child.try_wait()? {
+                    eprintln!("{}", style(Colour::Cyan.normal()).paint(format!("[finished with {}, waiting for changes]", status)));
+
+                    finished = true;
+                }
+            }
+        }
+
+        modified = modified_times(paths);
+
+        if !finished {
+            // The run may have finished since it was last waited for, then there is nothing to kill.
+            let _ = child.kill();
+            child.wait()?;
+        }
+    }
+}
+
+/// Get the path the artifacts of a build are named after: the file if there is one, the directory joined with its
+/// own name if there is one, and the first file otherwise.
+fn output_stem(paths: &[String], files: &[Arc<SourceFile>]) -> PathBuf {
+    let path = Path::new(&paths[0]);
+
+    match path.file_name() {
+        Some(name) if paths.len() == 1 && path.is_dir() => path.join(name),
+        _ => PathBuf::from(files[0].name()),
+    }
+}
+
+/// Compile the program just in time and run it. Returns the exit code of the program, the compiler is disposed of
+/// before it exits.
+fn run_files(paths: &[String], lazy_jit: bool, options: Options, args: Vec<String>, handler: DiagnosticHandler) -> Result<i32, Box<dyn Error>> {
+    let files = read_files(paths)?;
+
+    let mut session = Session::new(handler, options);
+    let mut codegen = session.codegen(files[0].name(), CodeGenType::JIT { lazy: lazy_jit })?;
+
+    // The libraries are loaded before the code is generated, so that the JIT resolves the extern functions to them.
+    session.load_libraries()?;
+
+    // The program is named after its first file, like an executable built from it.
+    codegen.set_args(iter::once(files[0].name().to_owned()).chain(args).collect());
+
+    let modules = or_abort(session.check_files(files), &mut session)?;
+
+    // The warnings are summarized before the program runs, the summary of the codegen errors follows them.
+    session.handler_mut().print_summary();
+    session.handler_mut().reset();
+
+    or_abort(session.generate(&mut codegen, modules), &mut session)?;
+
+    // The program is compiled to machine code while it runs, so it is not timed.
+    print_timings();
+
+    Ok(codegen.run_main()?)
+}
+
+/// Run one test of the program in this process. The diagnostics are not printed, since the process that runs all of
+/// the tests already reported them.
+fn run_test(paths: &[String], name: &str) -> Result<(), Box<dyn Error>> {
+    let mut session = Session::new(DiagnosticHandler::new(Box::new(CollectingEmitter::default())), Options::default());
+
+    match session.compile(read_files(paths)?, CodeGenType::Test) {
+        Ok(codegen) => Ok(codegen.run_test(name)?),
+        Err(CompileError::Diagnostics) => Err("the program has errors".into()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Run every test of the program whose name contains the filter in a child process, then print a summary along with
+/// the output of the tests that failed. Exits with an error if any test failed.
+fn test_files(paths: &[String], filter: Option<&str>, handler: DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let mut session = Session::new(handler, Options::default());
+    let codegen = or_abort(session.compile(read_files(paths)?, CodeGenType::Test), &mut session)?;
+
+    session.handler_mut().print_summary();
+    print_timings();
+    let all_tests = codegen.tests();
+    let tests = all_tests.iter().filter(|name| filter.map_or(true, |filter| name.contains(filter))).collect::<Vec<_>>();
+
+    let executable = env::current_exe()?;
+    let mut failures = vec![];
+
+    println!("running {} {}", tests.len(), if tests.len() == 1 { "test" } else { "tests" });
+
+    for name in tests.iter() {
+        let output = process::Command::new(&executable).arg("test").args(paths).arg("--exact").arg(name).output()?;
+
+        if output.status.success() {
+            println!("test {} ... {}", name, style(Colour::Green.normal()).paint("ok"));
         } else {
-            let file_name = path.file_name().unwrap().to_string_lossy().replace(".fluid", ".obj");
+            println!("test {} ... {}", name, style(Colour::Red.normal()).paint("FAILED"));
+            failures.push((name, output));
+        }
+    }
+
+    if !failures.is_empty() {
+        println!();
+        println!("failures:");
 
-            let out = Path::new(&file_name);
-            codegen.emit_object(&out);
+        for (name, output) in failures.iter() {
+            println!();
+            println!("---- {} ----", name);
+            print!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            println!("{}", output.status);
         }
+    }
+
+    let result = if failures.is_empty() {
+        style(Colour::Green.normal()).paint("ok")
+    } else {
+        style(Colour::Red.normal()).paint("FAILED")
+    };
+
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed; {} filtered out",
+        result,
+        tests.len() - failures.len(),
+        failures.len(),
+        all_tests.len() - tests.len()
+    );
 
-        codegen.free();
+    if !failures.is_empty() {
+        process::exit(1);
     }
 
     Ok(())
 }
 
-fn repl() -> Result<(), Box<dyn Error>> {
-    println!("{}", Colour::Yellow.paint(format!("Fluid v{}", VERSION)));
-    println!("{}", Colour::Green.paint("Type help for more information."));
+fn build_files(paths: &[String], manifest: Option<&Manifest>, options: Options, handler: DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    let files = read_files(paths)?;
+
+    // The artifacts of a project are in its output directory.
+    let stem = match manifest {
+        Some(manifest) => {
+            eprintln!("{}", style(Colour::Cyan.normal()).paint(format!("[building `{}` v{}]", manifest.name, manifest.version)));
+            fs::create_dir_all(manifest.root.join(&manifest.output_dir))?;
+
+            manifest.output_stem()
+        }
+        None => output_stem(paths, &files),
+    };
+
+    let mut session = Session::new(handler, options);
+    let modules = or_abort(session.check_files(files), &mut session)?;
+
+    session.emit_header(&modules, &stem)?;
+
+    // The tokens and the AST were written while checking, the code is only generated if an artifact needs it.
+    if session.options().emit.iter().any(|emit| emit.needs_codegen()) {
+        let mut codegen = session.codegen(&stem.to_string_lossy(), CodeGenType::Object)?;
+
+        or_abort(session.generate(&mut codegen, modules), &mut session)?;
+        session.emit(&mut codegen, &stem)?;
+    }
+
+    session.handler_mut().print_summary();
+    print_timings();
+
+    Ok(())
+}
+
+/// Print the type of an expression of the REPL, inferred from the declarations of the previous inputs. The expression
+/// is not run.
+fn print_type(source: &str, session: &mut Session) {
+    match session.infer_type(Arc::new(SourceFile::new("<stdin>", source))) {
+        Ok(Some(kind)) => println!("{}", kind),
+        Ok(None) => println!("`null` has no type of its own, it is a value of every nullable type"),
+        Err(_) => {
+            session.handler_mut().print_summary();
+            session.handler_mut().reset();
+        }
+    }
+}
+
+/// Get the default path of the history of the REPL, in the data directory of the user: `$XDG_DATA_HOME` or
+/// `~/.local/share` on Linux, `~/Library/Application Support` on macOS and `%APPDATA%` on Windows.
+fn default_history_path() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+
+    let data_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        // A relative `$XDG_DATA_HOME` is invalid, and must be ignored.
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| home().map(|home| home.join(".local").join("share")))
+    };
+
+    data_dir.map(|data_dir| data_dir.join("fluid").join("history.txt"))
+}
+
+/// Run the REPL. The history is loaded from the file and saved to it on exit, it is not kept if there is no file.
+fn repl(history: Option<PathBuf>, handler: DiagnosticHandler) -> Result<(), Box<dyn Error>> {
+    println!("{}", style(Colour::Yellow.normal()).paint(format!("Fluid v{}", VERSION)));
+    println!("{}", style(Colour::Green.normal()).paint("Type help for more information."));
 
     // Init repl editor
-    let mut rl = Editor::<()>::new();
-    rl.load_history("./history.txt").unwrap_or(());
+    let mut rl = Editor::<ReplHelper>::new();
+    rl.set_helper(Some(ReplHelper::new(COLOR.load(Ordering::Relaxed))));
+
+    if let Some(history) = &history {
+        // There is no history the first time the REPL is run.
+        rl.load_history(history).unwrap_or(());
+    }
 
-    // Create codegen context
-    let mut codegen = CodeGen::new("__repl__", CodeGenType::Repl);
+    // The session keeps the declarations of the previous inputs.
+    let mut session = Session::new(handler, Options::default());
+    let mut codegen = session.codegen("__repl__", CodeGenType::Repl)?;
 
     loop {
         let readline = rl.readline(">>> ");
 
         match readline {
             Ok(code) => {
-                if code.starts_with(".") {
-                    let command = &code.as_str()[1..];
+                if let Some(command) = code.strip_prefix('.') {
+                    let mut parts = command.trim().splitn(2, char::is_whitespace);
+                    let command = parts.next().unwrap_or_default();
 
                     match command {
-                        "reset" => codegen.reset(),
-                        _ => println!("{}: Invalid repl command `{}`", Colour::Red.bold().paint("error"), command),
+                        "reset" => {
+                            codegen.reset();
+                            session.reset();
+                        }
+                        "type" => print_type(parts.next().unwrap_or_default(), &mut session),
+                        "history" => match (parts.next().map(str::trim), &history) {
+                            (Some("clear"), _) => rl.clear_history(),
+                            (Some(argument), _) => println!("{}: Invalid argument `{}` of `.history`", style(Colour::Red.bold()).paint("error"), argument),
+                            (None, Some(history)) => println!("{}", history.display()),
+                            (None, None) => println!("The history is not saved, there is no data directory"),
+                        },
+                        "ir" | "dump" => match codegen.print_ir(parts.next().map(str::trim)) {
+                            Ok(ir) => print!("{}", ir),
+                            Err(error) => println!("{}: {}", style(Colour::Red.bold()).paint("error"), error),
+                        },
+                        _ => println!("{}: Invalid repl command `{}`", style(Colour::Red.bold()).paint("error"), command),
                     }
                 } else {
                     match code.as_str() {
-                        "help" => println!("{}", Colour::Yellow.paint(HELP)),
+                        "help" => println!("{}", style(Colour::Yellow.normal()).paint(HELP)),
                         _ => {
-                            let mut lexer = Lexer::new(&code, "<stdin>");
-                            let tokens = match lexer.run() {
-                                Ok(tokens) => tokens,
-                                Err(errors) => {
-                                    for err in errors {
-                                        println!("{}", err);
-                                    }
-
-                                    continue;
+                            let file = Arc::new(SourceFile::new("<stdin>", code.as_str()));
+
+                            // The errors were emitted to the handler, the next input can be compiled anyway.
+                            match session.check_file(file.clone()) {
+                                Ok(ast) => {
+                                    let _ = session.generate(&mut codegen, vec![(file, ast)]);
                                 }
-                            };
+                                Err(CompileError::Diagnostics) => (),
+                                Err(error) => return Err(error.into()),
+                            }
 
-                            let parser = Parser::new(tokens);
+                            // The functions that were defined are completed from now on.
+                            if let Some(helper) = rl.helper_mut() {
+                                helper.set_functions(codegen.functions());
+                            }
 
-                            codegen.run(parser);
+                            session.handler_mut().print_summary();
+                            session.handler_mut().reset();
                         }
                     }
                 }
@@ -178,10 +1030,14 @@ fn repl() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    codegen.free();
+    // Save the editor history.
+    if let Some(history) = &history {
+        if let Some(parent) = history.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-    // Save the editor histroy.
-    rl.save_history("./history.txt")?;
+        rl.save_history(history)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,138 @@
+//! The `fluid.toml` manifest of a project, and the scaffolding of new projects.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use fluid_codegen::OptLevel;
+use serde::Deserialize;
+
+/// The file name of the manifest.
+pub const MANIFEST_NAME: &str = "fluid.toml";
+
+/// The source of the entry point of a new project.
+const MAIN_SOURCE: &str = "function main() -> number {
+    println(\"Hello, world!\");
+
+    return 0;
+}
+";
+
+/// The manifest of a project. The paths are relative to the directory of the manifest.
+///
+/// ```toml
+/// name = "hello"
+/// version = "0.1.0"
+/// entry = "src/main.fluid"
+/// output-dir = "build"
+/// opt-level = 0
+/// libraries = ["m"]
+/// library-paths = ["lib"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// The name of the project, the executable is named after it.
+    pub name: String,
+    /// The version of the project.
+    pub version: String,
+    /// The file of the `main` function.
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    /// The directory of the artifacts of the builds.
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    /// The optimization level, from `0` to `3`.
+    #[serde(default)]
+    pub opt_level: u8,
+    /// The libraries to link the program with and to load into the JIT, like `m` for the math library.
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// The directories to search for the libraries in, before the ones of the system.
+    #[serde(default)]
+    pub library_paths: Vec<PathBuf>,
+    /// The directory of the manifest.
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("src/main.fluid")
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("build")
+}
+
+impl Manifest {
+    /// Find the manifest of the project of the current directory, in it or in one of its parents, and load it.
+    pub fn find() -> Result<Self, String> {
+        let current_dir = env::current_dir().map_err(|error| format!("could not get the current directory: {}", error))?;
+
+        match current_dir.ancestors().map(|dir| dir.join(MANIFEST_NAME)).find(|path| path.is_file()) {
+            Some(path) => Self::load(&path),
+            None => Err(format!(
+                "could not find `{}` in `{}` or any of its parents, give the files to compile instead",
+                MANIFEST_NAME,
+                current_dir.display()
+            )),
+        }
+    }
+
+    /// Load the manifest at the path.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|error| format!("could not read `{}`: {}", path.display(), error))?;
+        let mut manifest: Manifest = toml::from_str(&source).map_err(|error| format!("invalid manifest `{}`: {}", path.display(), error))?;
+
+        // Check the optimization level now, so the error points at the manifest.
+        manifest.opt_level()?;
+        manifest.root = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+
+        Ok(manifest)
+    }
+
+    /// The path of the entry point.
+    pub fn entry_path(&self) -> PathBuf {
+        self.root.join(&self.entry)
+    }
+
+    /// The path the artifacts of the builds are named after, the name of the project in the output directory.
+    pub fn output_stem(&self) -> PathBuf {
+        self.root.join(&self.output_dir).join(&self.name)
+    }
+
+    /// The paths of the directories to search for the libraries in.
+    pub fn library_paths(&self) -> Vec<PathBuf> {
+        self.library_paths.iter().map(|path| self.root.join(path)).collect()
+    }
+
+    /// The optimization level.
+    pub fn opt_level(&self) -> Result<OptLevel, String> {
+        self.opt_level.to_string().parse().map_err(|error| format!("invalid manifest: {}", error))
+    }
+}
+
+/// Create the manifest and the entry point of a new project in the directory, which may already exist.
+/// The files that already exist are kept, except the manifest, which must not exist.
+pub fn scaffold(dir: &Path, name: &str) -> Result<(), String> {
+    let manifest_path = dir.join(MANIFEST_NAME);
+
+    if manifest_path.exists() {
+        return Err(format!("`{}` already exists", manifest_path.display()));
+    }
+
+    let manifest = format!("name = \"{}\"\nversion = \"0.1.0\"\nentry = \"src/main.fluid\"\noutput-dir = \"build\"\nopt-level = 0\n", name);
+    let main_path = dir.join(default_entry());
+
+    let write = |path: &Path, contents: &str| fs::write(path, contents).map_err(|error| format!("could not write `{}`: {}", path.display(), error));
+
+    fs::create_dir_all(dir.join("src")).map_err(|error| format!("could not create `{}`: {}", dir.display(), error))?;
+    write(&manifest_path, &manifest)?;
+
+    if !main_path.exists() {
+        write(&main_path, MAIN_SOURCE)?;
+    }
+
+    Ok(())
+}